@@ -0,0 +1,331 @@
+//! ANSI escape handling for action output.
+//!
+//! Build tools color their output with SGR escape codes. Runs can either pass
+//! output through untouched, strip the escapes, or parse SGR codes into styled
+//! spans for the frontend to render. Output arrives in fixed-size reads, so the
+//! parser carries both the current style and any escape sequence split across
+//! a chunk boundary over to the next chunk.
+
+use serde::{Deserialize, Serialize};
+
+/// Longest incomplete escape sequence held over between chunks. Anything
+/// longer is malformed (or a runaway OSC) and is dropped.
+const MAX_PENDING_ESCAPE: usize = 4096;
+
+const ESC: char = '\x1b';
+const BEL: char = '\x07';
+
+/// How escape codes in a run's output are delivered to the frontend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AnsiMode {
+    /// Emit output as the process wrote it
+    #[default]
+    Raw,
+    /// Remove escape sequences before emitting
+    Strip,
+    /// Emit the raw chunk plus the text split into styled spans
+    Spans,
+}
+
+/// A terminal color: one of the 256 palette entries (0-7 standard, 8-15
+/// bright) or a 24-bit color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AnsiColor {
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// Text attributes set by SGR codes. Unset attributes are omitted when
+/// serialized.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnsiStyle {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fg: Option<AnsiColor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bg: Option<AnsiColor>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub bold: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub dim: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub italic: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub underline: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub inverse: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// A run of text in a single style.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StyledSpan {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "is_plain")]
+    pub style: AnsiStyle,
+}
+
+fn is_plain(style: &AnsiStyle) -> bool {
+    *style == AnsiStyle::default()
+}
+
+/// Remove escape sequences from a complete piece of text.
+pub fn strip_ansi(text: &str) -> String {
+    AnsiParser::new().strip(text)
+}
+
+/// Streaming parser for one output stream.
+#[derive(Debug, Default)]
+pub struct AnsiParser {
+    style: AnsiStyle,
+    /// Start of an escape sequence cut off by the end of the last chunk
+    pending: String,
+}
+
+/// What an escape sequence at the start of some input turned out to be.
+enum Escape {
+    /// Needs more input to tell
+    Incomplete,
+    /// A sequence of this many bytes; `Some` params for SGR
+    Complete(usize, Option<Vec<u16>>),
+}
+
+impl AnsiParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Split a chunk into styled spans, dropping escape sequences. Adjacent
+    /// text in the same style is merged and empty spans are skipped.
+    pub fn push(&mut self, chunk: &str) -> Vec<StyledSpan> {
+        let input = std::mem::take(&mut self.pending) + chunk;
+        let mut spans = Vec::new();
+        let mut text_start = 0;
+        let mut i = 0;
+        while let Some(offset) = input[i..].find(ESC) {
+            let esc = i + offset;
+            self.push_text(&input[text_start..esc], &mut spans);
+            match parse_escape(&input[esc..]) {
+                Escape::Incomplete => {
+                    if input.len() - esc <= MAX_PENDING_ESCAPE {
+                        self.pending = input[esc..].to_string();
+                    }
+                    return spans;
+                }
+                Escape::Complete(len, sgr) => {
+                    if let Some(params) = sgr {
+                        self.apply_sgr(&params);
+                    }
+                    i = esc + len;
+                    text_start = i;
+                }
+            }
+        }
+        self.push_text(&input[text_start..], &mut spans);
+        spans
+    }
+
+    /// Like [`AnsiParser::push`], keeping only the text.
+    pub fn strip(&mut self, chunk: &str) -> String {
+        self.push(chunk).into_iter().map(|span| span.text).collect()
+    }
+
+    fn push_text(&self, text: &str, spans: &mut Vec<StyledSpan>) {
+        if text.is_empty() {
+            return;
+        }
+        match spans.last_mut() {
+            Some(last) if last.style == self.style => last.text.push_str(text),
+            _ => spans.push(StyledSpan {
+                text: text.to_string(),
+                style: self.style,
+            }),
+        }
+    }
+
+    fn apply_sgr(&mut self, params: &[u16]) {
+        let style = &mut self.style;
+        // `ESC[m` is the same as `ESC[0m`
+        if params.is_empty() {
+            *style = AnsiStyle::default();
+            return;
+        }
+        let mut params = params.iter().copied();
+        while let Some(code) = params.next() {
+            match code {
+                0 => *style = AnsiStyle::default(),
+                1 => style.bold = true,
+                2 => style.dim = true,
+                3 => style.italic = true,
+                4 => style.underline = true,
+                7 => style.inverse = true,
+                22 => {
+                    style.bold = false;
+                    style.dim = false;
+                }
+                23 => style.italic = false,
+                24 => style.underline = false,
+                27 => style.inverse = false,
+                30..=37 => style.fg = Some(AnsiColor::Indexed((code - 30) as u8)),
+                38 => style.fg = extended_color(&mut params),
+                39 => style.fg = None,
+                40..=47 => style.bg = Some(AnsiColor::Indexed((code - 40) as u8)),
+                48 => style.bg = extended_color(&mut params),
+                49 => style.bg = None,
+                90..=97 => style.fg = Some(AnsiColor::Indexed((code - 90 + 8) as u8)),
+                100..=107 => style.bg = Some(AnsiColor::Indexed((code - 100 + 8) as u8)),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The color after a `38` or `48`: `5;n` for a palette index or `2;r;g;b`.
+fn extended_color(params: &mut impl Iterator<Item = u16>) -> Option<AnsiColor> {
+    let mut next = || params.next().map(|v| v.min(255) as u8);
+    match next() {
+        Some(5) => next().map(AnsiColor::Indexed),
+        Some(2) => Some(AnsiColor::Rgb(next()?, next()?, next()?)),
+        _ => None,
+    }
+}
+
+/// Parse the escape sequence at the start of `input`, which begins with ESC.
+///
+/// Handles CSI (`ESC [ ... final`), OSC (`ESC ] ... BEL` or `ESC ] ... ESC \`)
+/// and the other short `ESC [intermediates] final` forms.
+fn parse_escape(input: &str) -> Escape {
+    let bytes = input.as_bytes();
+    match bytes.get(1) {
+        None => Escape::Incomplete,
+        Some(b'[') => {
+            // Parameter bytes, then intermediate bytes, then one final byte
+            let mut end = 2;
+            while let Some(&b) = bytes.get(end) {
+                match b {
+                    0x20..=0x3f => end += 1,
+                    0x40..=0x7e => {
+                        let sgr = (b == b'm').then(|| parse_params(&input[2..end]));
+                        return Escape::Complete(end + 1, sgr);
+                    }
+                    // Not a valid CSI; drop what we've seen and carry on
+                    _ => return Escape::Complete(end, None),
+                }
+            }
+            Escape::Incomplete
+        }
+        Some(b']') => {
+            let body = &input[2..];
+            match (body.find(BEL), body.find("\x1b\\")) {
+                (Some(bel), Some(st)) if st < bel => Escape::Complete(2 + st + 2, None),
+                (Some(bel), _) => Escape::Complete(2 + bel + 1, None),
+                (None, Some(st)) => Escape::Complete(2 + st + 2, None),
+                (None, None) => Escape::Incomplete,
+            }
+        }
+        Some(_) => {
+            let mut end = 1;
+            while let Some(0x20..=0x2f) = bytes.get(end) {
+                end += 1;
+            }
+            match bytes.get(end) {
+                None => Escape::Incomplete,
+                Some(0x30..=0x7e) => Escape::Complete(end + 1, None),
+                // Lone ESC before ordinary text
+                Some(_) => Escape::Complete(end, None),
+            }
+        }
+    }
+}
+
+/// SGR parameters; missing or non-numeric ones count as 0.
+fn parse_params(params: &str) -> Vec<u16> {
+    if params.is_empty() {
+        return Vec::new();
+    }
+    params.split(';').map(|p| p.parse().unwrap_or(0)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi() {
+        let sample = "\x1b[0m\x1b[1m\x1b[38;5;9merror[E0308]\x1b[0m\x1b[0m\x1b[1m: mismatched types\x1b[0m\n\
+                      \x1b]8;;file:///src/main.rs\x1b\\src/main.rs\x1b]8;;\x1b\\:4:5\n\
+                      \x1b[2K\x1b[1G\x1b(Bdone\n";
+        assert_eq!(
+            strip_ansi(sample),
+            "error[E0308]: mismatched types\nsrc/main.rs:4:5\ndone\n"
+        );
+        assert_eq!(strip_ansi("plain text"), "plain text");
+    }
+
+    #[test]
+    fn test_escape_split_across_chunks() {
+        let mut parser = AnsiParser::new();
+        assert_eq!(parser.strip("ok \x1b[3"), "ok ");
+        assert_eq!(parser.strip("2mgreen\x1b"), "green");
+        assert_eq!(parser.strip("[0m plain"), " plain");
+    }
+
+    #[test]
+    fn test_parse_colored_line_into_spans() {
+        let mut parser = AnsiParser::new();
+        let spans = parser
+            .push("\x1b[1;31merror\x1b[0m: expected \x1b[38;2;0;128;255m`u32`\x1b[39m, found");
+        assert_eq!(
+            spans,
+            vec![
+                StyledSpan {
+                    text: "error".to_string(),
+                    style: AnsiStyle {
+                        fg: Some(AnsiColor::Indexed(1)),
+                        bold: true,
+                        ..AnsiStyle::default()
+                    },
+                },
+                StyledSpan {
+                    text: ": expected ".to_string(),
+                    style: AnsiStyle::default(),
+                },
+                StyledSpan {
+                    text: "`u32`".to_string(),
+                    style: AnsiStyle {
+                        fg: Some(AnsiColor::Rgb(0, 128, 255)),
+                        ..AnsiStyle::default()
+                    },
+                },
+                StyledSpan {
+                    text: ", found".to_string(),
+                    style: AnsiStyle::default(),
+                },
+            ]
+        );
+
+        // Style carries over to the next chunk until reset
+        let spans = parser.push("\x1b[92;4mok");
+        assert_eq!(
+            spans[0].style,
+            AnsiStyle {
+                fg: Some(AnsiColor::Indexed(10)),
+                underline: true,
+                ..AnsiStyle::default()
+            }
+        );
+        assert_eq!(parser.push(" still")[0].style, spans[0].style);
+
+        let json = serde_json::to_value(&spans[0]).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "text": "ok", "style": { "fg": { "indexed": 10 }, "underline": true } })
+        );
+    }
+}
@@ -6,10 +6,60 @@
 
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
-
-use crate::ai::{find_acp_agent, run_acp_prompt_raw};
-use crate::store::ActionType;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::ai::budget::{self, PromptSection};
+use crate::ai::config::AgentsConfig;
+use crate::ai::{find_acp_agent_for_repo, run_acp_prompt_raw_cancellable, CancellationHandle};
+use crate::git;
+use crate::logging;
+use crate::repo_config::{ActionScanConfig, RepoConfig};
+use crate::store::{ActionType, ProjectAction};
+
+/// Build/config files whose contents are sent to action detection.
+/// Repos can adjust this list via `[actions]` in `.staged.toml`.
+const CONFIG_FILES: &[&str] = &[
+    "package.json",
+    "justfile",
+    "Justfile",
+    "Makefile",
+    "makefile",
+    "Cargo.toml",
+    "pyproject.toml",
+    "setup.py",
+    "tsconfig.json",
+    ".eslintrc.json",
+    ".eslintrc.js",
+    "eslint.config.js",
+    ".prettierrc",
+    ".prettierrc.json",
+];
+
+/// Per-file cap on build file contents sent to detection.
+const MAX_FILE_TOKENS: usize = 1000;
+
+/// How much a build file is worth keeping when the prompt must shrink.
+/// Task runners list the commands directly; lockfiles say almost nothing.
+fn config_file_priority(name: &str) -> u8 {
+    let lower = name.to_ascii_lowercase();
+    if lower.ends_with(".lock") || lower.contains("-lock.") || lower.contains("lock.y") {
+        0
+    } else if matches!(
+        lower.as_str(),
+        "justfile" | "makefile" | "taskfile.yml" | "taskfile.yaml"
+    ) {
+        3
+    } else if matches!(
+        lower.as_str(),
+        "package.json" | "cargo.toml" | "pyproject.toml" | "setup.py"
+    ) {
+        2
+    } else {
+        1
+    }
+}
 
 /// A suggested action that was detected
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,6 +72,121 @@ pub struct SuggestedAction {
     pub source: String, // e.g., "justfile", "Makefile", "package.json"
 }
 
+impl SuggestedAction {
+    /// Find a detected action by name.
+    pub fn find<'a>(actions: &'a [SuggestedAction], name: &str) -> Result<&'a SuggestedAction> {
+        actions
+            .iter()
+            .find(|a| a.name == name)
+            .ok_or_else(|| anyhow::anyhow!("No detected action named '{name}'"))
+    }
+
+    /// An unsaved project action that runs this suggestion.
+    pub fn to_project_action(&self, project_id: &str) -> ProjectAction {
+        ProjectAction::new(project_id, &self.name, &self.command, self.action_type, 0)
+            .with_auto_commit(self.auto_commit)
+    }
+}
+
+/// Returned when detection is cancelled before the agent responds.
+#[derive(Debug, thiserror::Error)]
+#[error("Action detection cancelled")]
+pub struct DetectionCancelled;
+
+/// Cancellation handles for in-flight detections, the last results of
+/// finished ones and which of those the user confirmed, keyed by project.
+#[derive(Default)]
+pub struct DetectionRegistry {
+    pending: Mutex<HashMap<String, Arc<CancellationHandle>>>,
+    detected: Mutex<HashMap<String, Vec<SuggestedAction>>>,
+    /// Names of detected actions the user confirmed, per key
+    confirmed: Mutex<HashMap<String, HashSet<String>>>,
+}
+
+impl DetectionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a new detection for `key`, cancelling any still running for it.
+    pub fn start(&self, key: &str) -> Arc<CancellationHandle> {
+        let handle = Arc::new(CancellationHandle::new());
+        let previous = self
+            .pending
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), handle.clone());
+        if let Some(previous) = previous {
+            previous.cancel();
+        }
+        handle
+    }
+
+    /// Forget a finished detection (unless a newer one replaced it).
+    pub fn finish(&self, key: &str, handle: &Arc<CancellationHandle>) {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.get(key).is_some_and(|h| Arc::ptr_eq(h, handle)) {
+            pending.remove(key);
+        }
+    }
+
+    /// Keep the actions detected for `key`, replacing earlier results and
+    /// any confirmations of them.
+    pub fn remember(&self, key: &str, actions: &[SuggestedAction]) {
+        self.detected
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), actions.to_vec());
+        self.confirmed.lock().unwrap().remove(key);
+    }
+
+    /// Record that the user confirmed running a detected action. Fails if
+    /// nothing by that name was detected for `key`.
+    pub fn confirm(&self, key: &str, name: &str) -> Result<()> {
+        let detected = self.detected(key).unwrap_or_default();
+        SuggestedAction::find(&detected, name)?;
+        self.confirmed
+            .lock()
+            .unwrap()
+            .entry(key.to_string())
+            .or_default()
+            .insert(name.to_string());
+        Ok(())
+    }
+
+    /// The detected action `name`, provided the user has confirmed it.
+    pub fn confirmed_action(&self, key: &str, name: &str) -> Result<SuggestedAction> {
+        let detected = self.detected(key).unwrap_or_default();
+        let action = SuggestedAction::find(&detected, name)?;
+        let confirmed = self
+            .confirmed
+            .lock()
+            .unwrap()
+            .get(key)
+            .is_some_and(|names| names.contains(name));
+        if !confirmed {
+            anyhow::bail!("Detected action '{name}' must be confirmed before it runs");
+        }
+        Ok(action.clone())
+    }
+
+    /// The actions last detected for `key`, if detection has run.
+    pub fn detected(&self, key: &str) -> Option<Vec<SuggestedAction>> {
+        self.detected.lock().unwrap().get(key).cloned()
+    }
+
+    /// Cancel the detection running for `key`. Returns false if there was none.
+    pub fn cancel(&self, key: &str) -> bool {
+        match self.pending.lock().unwrap().remove(key) {
+            Some(handle) => {
+                handle.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
 /// System prompt for AI action detection
 const DETECTION_PROMPT_TEMPLATE: &str = r#"You are analyzing a project directory to detect available actions (build, test, lint, format commands).
 
@@ -87,11 +252,19 @@ Return ONLY a JSON array with detected actions. Example:
   }
 ]"#;
 
-/// Detect actions from a project repository using AI
+/// Detect actions from a project repository using AI.
+///
+/// Triggering `cancellation` kills the agent process; the call then fails
+/// with [`DetectionCancelled`].
 pub async fn detect_actions(
     repo_path: &Path,
     subpath: Option<&str>,
+    cancellation: Arc<CancellationHandle>,
 ) -> Result<Vec<SuggestedAction>> {
+    if cancellation.is_cancelled() {
+        return Err(DetectionCancelled.into());
+    }
+
     let working_dir = if let Some(sp) = subpath {
         repo_path.join(sp)
     } else {
@@ -99,22 +272,25 @@ pub async fn detect_actions(
     };
 
     // Find an available ACP agent
-    let agent = find_acp_agent()
+    let agent = find_acp_agent_for_repo(repo_path)
         .ok_or_else(|| anyhow::anyhow!("No AI agent available (goose or claude-code-acp). Please install an ACP-compatible agent to use action detection."))?;
 
     // Collect information about the project
     let file_list = collect_file_list(&working_dir)?;
-    let file_contents = collect_relevant_files(&working_dir)?;
+    let scan_config = RepoConfig::load(repo_path).actions;
+    let file_contents = collect_config_sections(&working_dir, &scan_config);
 
-    // Build the prompt
-    let prompt = DETECTION_PROMPT_TEMPLATE
-        .replace("{file_list}", &file_list)
-        .replace("{file_contents}", &file_contents);
+    // Build the prompt, trimmed to the agent's context budget
+    let token_budget = AgentsConfig::load().prompt_token_budget(agent.name());
+    let prompt = build_discovery_prompt(&file_list, file_contents, token_budget);
 
     // Call AI to analyze and suggest actions
-    let response = run_acp_prompt_raw(&agent, &working_dir, &prompt)
-        .await
-        .map_err(|e| anyhow::anyhow!("AI detection failed: {}", e))?;
+    let response =
+        run_acp_prompt_raw_cancellable(&agent, &working_dir, &prompt, cancellation.clone()).await;
+    if cancellation.is_cancelled() {
+        return Err(DetectionCancelled.into());
+    }
+    let response = response.map_err(|e| anyhow::anyhow!("AI detection failed: {}", e))?;
 
     // Parse the JSON response
     parse_ai_response(&response)
@@ -148,47 +324,97 @@ fn collect_file_list(dir: &Path) -> Result<String> {
     Ok(files.join("\n"))
 }
 
-/// Collect contents of relevant build/config files
-fn collect_relevant_files(dir: &Path) -> Result<String> {
-    let relevant_files = [
-        "package.json",
-        "justfile",
-        "Justfile",
-        "Makefile",
-        "makefile",
-        "Cargo.toml",
-        "pyproject.toml",
-        "setup.py",
-        "tsconfig.json",
-        ".eslintrc.json",
-        ".eslintrc.js",
-        "eslint.config.js",
-        ".prettierrc",
-        ".prettierrc.json",
-    ];
-
-    let mut contents = Vec::new();
-
-    for file_name in &relevant_files {
-        let file_path = dir.join(file_name);
-        if file_path.exists() {
-            if let Ok(content) = std::fs::read_to_string(&file_path) {
-                // Limit file size to avoid token overflow
-                let truncated = if content.len() > 4000 {
-                    format!("{}... (truncated)", &content[..4000])
-                } else {
-                    content
-                };
-                contents.push(format!("=== {} ===\n{}\n", file_name, truncated));
-            }
-        }
+/// Read the relevant build/config files present in `dir`, each capped at
+/// `MAX_FILE_TOKENS` and tagged with its priority.
+fn collect_config_sections(dir: &Path, scan_config: &ActionScanConfig) -> Vec<PromptSection> {
+    let mut sections = Vec::new();
+    for file_name in scan_config.apply(CONFIG_FILES) {
+        let Some(path) = resolve_config_file(dir, &file_name) else {
+            continue;
+        };
+        let Ok(content) = std::fs::read_to_string(path) else {
+            continue;
+        };
+        let text = if budget::estimate_tokens(&content) > MAX_FILE_TOKENS {
+            format!(
+                "{}... (truncated)",
+                budget::truncate_to_tokens(&content, MAX_FILE_TOKENS)
+            )
+        } else {
+            content
+        };
+        let priority = config_file_priority(&file_name);
+        sections.push(PromptSection::new(file_name, text, priority));
     }
+    sections
+}
 
-    if contents.is_empty() {
-        Ok("No relevant build files found.".to_string())
-    } else {
-        Ok(contents.join("\n"))
+/// Resolve a build file name to an existing file inside `dir`.
+///
+/// Names can come from a cloned repo's `.staged.toml`, and whatever they
+/// point at is sent to the agent, so absolute paths, `..` escapes and
+/// symlinks leading out of the repo are refused.
+fn resolve_config_file(dir: &Path, file_name: &str) -> Option<PathBuf> {
+    let refuse = |reason: &str| {
+        log::warn!(
+            target: logging::ACTIONS,
+            "Ignoring build file {file_name:?} from repo config: {reason}"
+        );
+        None
+    };
+    if Path::new(file_name).is_absolute() || file_name.starts_with(['/', '\\']) {
+        return refuse("absolute paths aren't allowed");
+    }
+    let relative = match git::normalize_repo_path(dir, file_name) {
+        Ok(relative) => relative,
+        Err(e) => return refuse(&e.to_string()),
+    };
+    // Missing files are simply skipped
+    let resolved = dir.join(relative).canonicalize().ok()?;
+    let root = dir.canonicalize().ok()?;
+    if !resolved.starts_with(&root) {
+        return refuse("it resolves outside the repository");
     }
+    Some(resolved)
+}
+
+/// Format config file sections for the prompt.
+fn format_config_sections(sections: &[PromptSection]) -> String {
+    if sections.is_empty() {
+        return "No relevant build files found.".to_string();
+    }
+    sections
+        .iter()
+        .map(|s| format!("=== {} ===\n{}\n", s.label, s.text))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Build the detection prompt, trimming the least important build files so
+/// the whole prompt fits in `token_budget`.
+fn build_discovery_prompt(
+    file_list: &str,
+    mut sections: Vec<PromptSection>,
+    token_budget: usize,
+) -> String {
+    let build = |contents: &str| {
+        DETECTION_PROMPT_TEMPLATE
+            .replace("{file_list}", file_list)
+            .replace("{file_contents}", contents)
+    };
+    // Headers and separators count against the budget too
+    let overhead = budget::estimate_tokens(&build(""))
+        + sections
+            .iter()
+            .map(|s| budget::estimate_tokens(&format!("=== {} ===\n\n\n", s.label)))
+            .sum::<usize>();
+    if budget::fit_to_budget(&mut sections, token_budget.saturating_sub(overhead)) {
+        log::info!(
+            target: logging::ACTIONS,
+            "Trimmed build files to fit a {token_budget}-token prompt budget"
+        );
+    }
+    build(&format_config_sections(&sections))
 }
 
 /// Parse the AI response and extract suggested actions
@@ -238,6 +464,13 @@ fn extract_json_array(text: &str) -> Result<String> {
 mod tests {
     use super::*;
 
+    fn collect_relevant_files(dir: &Path, scan_config: &ActionScanConfig) -> Result<String> {
+        Ok(format_config_sections(&collect_config_sections(
+            dir,
+            scan_config,
+        )))
+    }
+
     #[test]
     fn test_extract_json_array() {
         let text = r#"Here are some actions:
@@ -257,4 +490,156 @@ That's all!"#;
         let result = extract_json_array(text);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_collect_respects_exclude() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("package.json"), "{}").unwrap();
+        std::fs::write(dir.path().join("Makefile"), "all:").unwrap();
+
+        let config = ActionScanConfig {
+            exclude: vec!["Makefile".to_string()],
+            ..Default::default()
+        };
+        let contents = collect_relevant_files(dir.path(), &config).unwrap();
+        assert!(contents.contains("=== package.json ==="));
+        assert!(!contents.contains("Makefile"));
+    }
+
+    #[test]
+    fn test_collect_respects_include() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("Taskfile.yml"), "version: '3'").unwrap();
+
+        let default = collect_relevant_files(dir.path(), &ActionScanConfig::default()).unwrap();
+        assert_eq!(default, "No relevant build files found.");
+
+        let config = ActionScanConfig {
+            include: vec!["Taskfile.yml".to_string()],
+            ..Default::default()
+        };
+        let contents = collect_relevant_files(dir.path(), &config).unwrap();
+        assert!(contents.contains("=== Taskfile.yml ===\nversion: '3'"));
+    }
+
+    #[test]
+    fn test_collect_refuses_includes_outside_repo() {
+        let outer = tempfile::tempdir().unwrap();
+        let repo = outer.path().join("repo");
+        std::fs::create_dir_all(repo.join("build")).unwrap();
+        std::fs::write(outer.path().join("secret"), "TOKEN=hunter2").unwrap();
+        std::fs::write(repo.join("build/tasks.yml"), "tasks: []").unwrap();
+
+        let mut include = vec![
+            "../secret".to_string(),
+            "build/../../secret".to_string(),
+            "/etc/passwd".to_string(),
+            outer.path().join("secret").to_string_lossy().to_string(),
+            "build/tasks.yml".to_string(),
+        ];
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(outer.path().join("secret"), repo.join("link.txt")).unwrap();
+            include.push("link.txt".to_string());
+        }
+        let config = ActionScanConfig {
+            include,
+            ..Default::default()
+        };
+        let contents = collect_relevant_files(&repo, &config).unwrap();
+        assert_eq!(contents, "=== build/tasks.yml ===\ntasks: []\n");
+    }
+
+    #[test]
+    fn test_discovery_prompt_fits_budget_and_keeps_task_runners() {
+        let justfile = "test:\n    cargo test\n\nfmt:\n    cargo fmt\n";
+        let sections = vec![
+            PromptSection::new(
+                "Cargo.lock",
+                "x = 1\n".repeat(800),
+                config_file_priority("Cargo.lock"),
+            ),
+            PromptSection::new("justfile", justfile, config_file_priority("justfile")),
+            PromptSection::new(
+                "package.json",
+                format!("{{\n{}}}\n", "  \"k\": \"v\",\n".repeat(300)),
+                config_file_priority("package.json"),
+            ),
+        ];
+        let untrimmed =
+            build_discovery_prompt("justfile\nCargo.lock\n", sections.clone(), usize::MAX);
+        let budget = budget::estimate_tokens(&untrimmed) - 1500;
+
+        let prompt = build_discovery_prompt("justfile\nCargo.lock\n", sections, budget);
+        assert!(budget::estimate_tokens(&prompt) <= budget);
+        assert!(prompt.contains(&format!("=== justfile ===\n{justfile}")));
+        // The lockfile goes before the manifest
+        assert!(prompt.contains("=== Cargo.lock ===\n(omitted to fit context budget)"));
+        assert!(prompt.contains("\"k\": \"v\""));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_detection_returns_cancelled() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = DetectionRegistry::new();
+        let handle = registry.start("project");
+        assert!(registry.cancel("project"));
+        assert!(!registry.cancel("project"));
+
+        let err = detect_actions(dir.path(), None, handle).await.unwrap_err();
+        assert!(err.downcast_ref::<DetectionCancelled>().is_some());
+    }
+
+    #[test]
+    fn test_restarting_detection_cancels_previous() {
+        let registry = DetectionRegistry::new();
+        let first = registry.start("project");
+        let second = registry.start("project");
+        assert!(first.is_cancelled());
+        assert!(!second.is_cancelled());
+
+        // A stale finish doesn't drop the newer detection
+        registry.finish("project", &first);
+        assert!(registry.cancel("project"));
+        assert!(second.is_cancelled());
+    }
+
+    #[test]
+    fn test_detected_action_runs_only_once_confirmed() {
+        let registry = DetectionRegistry::new();
+        let detect = |command: &str| {
+            registry.remember(
+                "project",
+                &[SuggestedAction {
+                    name: "Greet".to_string(),
+                    command: command.to_string(),
+                    action_type: ActionType::Run,
+                    auto_commit: false,
+                    source: "justfile".to_string(),
+                }],
+            );
+        };
+        detect("echo detected-ran");
+
+        // Nothing runs until the user confirms it
+        let err = registry.confirmed_action("project", "Greet").unwrap_err();
+        assert!(err.to_string().contains("must be confirmed"));
+        registry.confirm("project", "Greet").unwrap();
+        let action = registry
+            .confirmed_action("project", "Greet")
+            .unwrap()
+            .to_project_action("project");
+        assert_eq!(action.command, "echo detected-ran");
+        assert!(!action.auto_commit);
+
+        // Detecting again drops the confirmation
+        detect("echo changed");
+        assert!(registry.confirmed_action("project", "Greet").is_err());
+
+        // Only detected names can be confirmed
+        let err = registry.confirm("project", "rm -rf /").unwrap_err();
+        assert!(err.to_string().contains("No detected action named"));
+        assert!(registry.confirm("other", "Greet").is_err());
+        assert!(registry.confirmed_action("other", "Greet").is_err());
+    }
 }
@@ -0,0 +1,392 @@
+//! Compiler and linter diagnostics extracted from action output.
+//!
+//! Output arrives in arbitrary chunks, so each stream gets its own
+//! [`DiagnosticMatcher`] that buffers partial lines and recognizes a few
+//! common formats:
+//!
+//! - rustc/cargo: `error[E0308]: message` followed by ` --> file:line:col`
+//! - tsc: `file(line,col): error TS1234: message` or `file:line:col - error TS1234: message`
+//! - eslint (stylish): a file path line (ending in a lintable extension) followed by
+//!   `  line:col  error  message  rule`
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "error" => Some(Severity::Error),
+            "warning" => Some(Severity::Warning),
+            _ => None,
+        }
+    }
+}
+
+/// A file:line location reported by a tool, with its message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionDiagnostic {
+    pub file: String,
+    pub line: u32,
+    pub column: Option<u32>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Event emitted when a diagnostic is recognized in action output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionDiagnosticEvent {
+    pub execution_id: String,
+    pub stream: String, // "stdout" or "stderr"
+    pub diagnostic: ActionDiagnostic,
+}
+
+struct Patterns {
+    ansi: Regex,
+    rustc_header: Regex,
+    rustc_location: Regex,
+    tsc: Regex,
+    tsc_pretty: Regex,
+    eslint_file: Regex,
+    eslint_entry: Regex,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| Patterns {
+        ansi: Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").unwrap(),
+        rustc_header: Regex::new(r"^(error|warning)(?:\[\w+\])?: (.+)$").unwrap(),
+        rustc_location: Regex::new(r"^\s*--> (.+?):(\d+):(\d+)$").unwrap(),
+        tsc: Regex::new(r"^(.+?)\((\d+),(\d+)\): (error|warning) (TS\d+: .+)$").unwrap(),
+        tsc_pretty: Regex::new(r"^(.+?):(\d+):(\d+) - (error|warning) (TS\d+: .+)$").unwrap(),
+        eslint_file: Regex::new(r"^\S.*\.(?:[cm]?[jt]sx?|vue|svelte|astro|json|md)$").unwrap(),
+        eslint_entry: Regex::new(r"^\s+(\d+):(\d+)\s+(error|warning)\s+(.+?)(?:\s{2,}\S+)?$")
+            .unwrap(),
+    })
+}
+
+/// Line-buffered matcher for one output stream.
+#[derive(Debug, Default)]
+pub struct DiagnosticMatcher {
+    /// Incomplete trailing line from the last chunk
+    partial: String,
+    /// The partial line outgrew [`MAX_PARTIAL_LEN`] and is being skipped
+    skipping_line: bool,
+    /// The last chunk ended in `\r`, so a leading `\n` completes a `\r\n`
+    after_cr: bool,
+    /// rustc header (`error: ...`) awaiting its `-->` location line
+    pending_rustc: Option<(Severity, String)>,
+    /// Most recent file heading in eslint's stylish output
+    eslint_file: Option<String>,
+}
+
+/// Longest incomplete line buffered between chunks. A longer line (a
+/// minified bundle, a progress bar without breaks) is skipped.
+const MAX_PARTIAL_LEN: usize = 64 * 1024;
+
+impl DiagnosticMatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed a chunk of output, returning diagnostics completed by it. `\n`,
+    /// `\r\n` and a lone `\r` each end a line.
+    pub fn push(&mut self, chunk: &str) -> Vec<ActionDiagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut rest = chunk;
+        if self.after_cr && !rest.is_empty() {
+            self.after_cr = false;
+            rest = rest.strip_prefix('\n').unwrap_or(rest);
+        }
+
+        // Only the new chunk can hold the break that completes a line
+        while let Some(end) = rest.find(['\n', '\r']) {
+            let is_cr = rest.as_bytes()[end] == b'\r';
+            self.append_partial(&rest[..end]);
+            rest = &rest[end + 1..];
+            if is_cr {
+                if rest.is_empty() {
+                    self.after_cr = true;
+                } else {
+                    rest = rest.strip_prefix('\n').unwrap_or(rest);
+                }
+            }
+
+            let line = std::mem::take(&mut self.partial);
+            if !std::mem::take(&mut self.skipping_line) {
+                diagnostics.extend(self.match_line(&line));
+            }
+        }
+        self.append_partial(rest);
+        diagnostics
+    }
+
+    /// Flush a final line that wasn't newline-terminated.
+    pub fn finish(&mut self) -> Vec<ActionDiagnostic> {
+        let rest = std::mem::take(&mut self.partial);
+        self.after_cr = false;
+        if std::mem::take(&mut self.skipping_line) {
+            return Vec::new();
+        }
+        self.match_line(&rest).into_iter().collect()
+    }
+
+    fn append_partial(&mut self, text: &str) {
+        if self.skipping_line {
+            return;
+        }
+        if self.partial.len() + text.len() > MAX_PARTIAL_LEN {
+            self.partial.clear();
+            self.skipping_line = true;
+        } else {
+            self.partial.push_str(text);
+        }
+    }
+
+    fn match_line(&mut self, line: &str) -> Option<ActionDiagnostic> {
+        let p = patterns();
+        let line = p.ansi.replace_all(line.trim_end_matches('\r'), "");
+        let line = line.as_ref();
+
+        if let Some((severity, message)) = self.pending_rustc.take() {
+            if let Some(caps) = p.rustc_location.captures(line) {
+                return Some(ActionDiagnostic {
+                    file: caps[1].to_string(),
+                    line: caps[2].parse().ok()?,
+                    column: caps[3].parse().ok(),
+                    severity,
+                    message,
+                });
+            }
+        }
+
+        if let Some(caps) = p.rustc_header.captures(line) {
+            self.pending_rustc = Some((Severity::parse(&caps[1])?, caps[2].to_string()));
+            return None;
+        }
+
+        if let Some(caps) = p.tsc.captures(line).or_else(|| p.tsc_pretty.captures(line)) {
+            return Some(ActionDiagnostic {
+                file: caps[1].to_string(),
+                line: caps[2].parse().ok()?,
+                column: caps[3].parse().ok(),
+                severity: Severity::parse(&caps[4])?,
+                message: caps[5].to_string(),
+            });
+        }
+
+        if let Some(caps) = p.eslint_entry.captures(line) {
+            let file = self.eslint_file.clone()?;
+            return Some(ActionDiagnostic {
+                file,
+                line: caps[1].parse().ok()?,
+                column: caps[2].parse().ok(),
+                severity: Severity::parse(&caps[3])?,
+                message: caps[4].to_string(),
+            });
+        }
+
+        if p.eslint_file.is_match(line) {
+            self.eslint_file = Some(line.to_string());
+        } else if line.trim().is_empty() {
+            self.eslint_file = None;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(
+        file: &str,
+        line: u32,
+        column: u32,
+        severity: Severity,
+        message: &str,
+    ) -> ActionDiagnostic {
+        ActionDiagnostic {
+            file: file.to_string(),
+            line,
+            column: Some(column),
+            severity,
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_rustc_output() {
+        let output = "\
+   Compiling demo v0.1.0 (/tmp/demo)
+\x1b[0m\x1b[1m\x1b[38;5;9merror[E0308]\x1b[0m\x1b[1m: mismatched types\x1b[0m
+  --> src/main.rs:4:18
+   |
+4  |     let x: u32 = \"five\";
+   |            ---   ^^^^^^ expected `u32`, found `&str`
+
+warning: unused variable: `y`
+ --> src/lib.rs:2:9
+  |
+2 |     let y = 1;
+  |         ^ help: if this is intentional, prefix it with an underscore: `_y`
+
+warning: `demo` (bin \"demo\") generated 1 warning
+error: could not compile `demo` due to 1 previous error
+";
+        // Split mid-line to exercise buffering across chunks
+        let (first, second) = output.split_at(120);
+        let mut matcher = DiagnosticMatcher::new();
+        let mut found = matcher.push(first);
+        found.extend(matcher.push(second));
+        found.extend(matcher.finish());
+
+        assert_eq!(
+            found,
+            vec![
+                diagnostic("src/main.rs", 4, 18, Severity::Error, "mismatched types"),
+                diagnostic(
+                    "src/lib.rs",
+                    2,
+                    9,
+                    Severity::Warning,
+                    "unused variable: `y`"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tsc_output() {
+        let output = "\
+src/index.ts(3,7): error TS2322: Type 'string' is not assignable to type 'number'.
+src/util.ts:10:1 - error TS2304: Cannot find name 'foo'.
+
+10 foo();
+   ~~~
+
+Found 2 errors in 2 files.";
+        let mut matcher = DiagnosticMatcher::new();
+        let mut found = matcher.push(output);
+        found.extend(matcher.finish());
+
+        assert_eq!(
+            found,
+            vec![
+                diagnostic(
+                    "src/index.ts",
+                    3,
+                    7,
+                    Severity::Error,
+                    "TS2322: Type 'string' is not assignable to type 'number'."
+                ),
+                diagnostic(
+                    "src/util.ts",
+                    10,
+                    1,
+                    Severity::Error,
+                    "TS2304: Cannot find name 'foo'."
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_eslint_stylish_output() {
+        let output = "
+/repo/src/app.js
+  1:10  error    'foo' is defined but never used  no-unused-vars
+  2:1   warning  Unexpected console statement     no-console
+
+\u{2716} 2 problems (1 error, 1 warning)
+";
+        let mut matcher = DiagnosticMatcher::new();
+        let found = matcher.push(output);
+
+        assert_eq!(
+            found,
+            vec![
+                diagnostic(
+                    "/repo/src/app.js",
+                    1,
+                    10,
+                    Severity::Error,
+                    "'foo' is defined but never used"
+                ),
+                diagnostic(
+                    "/repo/src/app.js",
+                    2,
+                    1,
+                    Severity::Warning,
+                    "Unexpected console statement"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_eslint_entries_need_a_path_heading() {
+        let output = "
+> lint
+  1:10  error  'foo' is defined but never used  no-unused-vars
+
+src/app.ts:3:4
+  2:1  warning  Unexpected console statement  no-console
+";
+        let mut matcher = DiagnosticMatcher::new();
+        assert!(matcher.push(output).is_empty());
+    }
+
+    #[test]
+    fn test_line_breaks_across_chunks() {
+        let expected = vec![diagnostic(
+            "src/app.ts",
+            3,
+            5,
+            Severity::Error,
+            "TS2304: Cannot find name 'foo'.",
+        )];
+        let line = "src/app.ts(3,5): error TS2304: Cannot find name 'foo'.";
+
+        // A lone CR ends a line, and CRLF split between chunks is one break
+        let mut matcher = DiagnosticMatcher::new();
+        assert_eq!(matcher.push(&format!("{line}\r")), expected);
+        assert_eq!(matcher.push(&format!("\n{line}\r")), expected);
+        assert!(matcher.push("progress 50%\r").is_empty());
+        assert!(matcher.finish().is_empty());
+
+        // CRLF doesn't add a blank line that would drop an eslint heading
+        let mut matcher = DiagnosticMatcher::new();
+        let eslint = "src/app.ts\r\n  3:5  error  'foo' is not defined  no-undef\r\n";
+        assert_eq!(
+            matcher.push(eslint),
+            [diagnostic(
+                "src/app.ts",
+                3,
+                5,
+                Severity::Error,
+                "'foo' is not defined"
+            )]
+        );
+
+        // An overlong line is skipped without affecting the next one
+        let mut matcher = DiagnosticMatcher::new();
+        let filler = "x".repeat(MAX_PARTIAL_LEN / 2);
+        for _ in 0..3 {
+            assert!(matcher.push(&filler).is_empty());
+        }
+        assert!(matcher.partial.is_empty());
+        assert!(matcher.push(&format!("{line}\n")).is_empty());
+        assert_eq!(matcher.push(&format!("{line}\n")), expected);
+    }
+}
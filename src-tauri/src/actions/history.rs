@@ -0,0 +1,244 @@
+//! Persistent history of completed action runs.
+//!
+//! The runner only tracks actions while they are live. Once a run finishes it
+//! is recorded here (exit code, duration, tail of its output) so the UI can
+//! show things like "last run: passed 3m ago".
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use super::runner::{ActionStatus, OutputChunk};
+use crate::util::lock;
+
+/// Maximum number of bytes of output kept per run (the tail is kept).
+pub const MAX_OUTPUT_TAIL_BYTES: usize = 16 * 1024;
+
+/// A completed action run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionRun {
+    /// Execution ID assigned by the runner
+    pub id: String,
+    pub repo_path: String,
+    pub action_id: String,
+    pub action_name: String,
+    pub command: String,
+    pub status: ActionStatus,
+    pub exit_code: Option<i32>,
+    pub started_at: i64,
+    pub completed_at: i64,
+    pub duration_ms: i64,
+    /// Last `MAX_OUTPUT_TAIL_BYTES` of combined stdout/stderr
+    pub output_tail: String,
+}
+
+impl ActionRun {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        let status: String = row.get(5)?;
+        Ok(Self {
+            id: row.get(0)?,
+            repo_path: row.get(1)?,
+            action_id: row.get(2)?,
+            action_name: row.get(3)?,
+            command: row.get(4)?,
+            status: ActionStatus::parse(&status).unwrap_or(ActionStatus::Failed),
+            exit_code: row.get(6)?,
+            started_at: row.get(7)?,
+            completed_at: row.get(8)?,
+            duration_ms: row.get(9)?,
+            output_tail: row.get(10)?,
+        })
+    }
+}
+
+/// Join buffered output chunks and keep only the last `MAX_OUTPUT_TAIL_BYTES`.
+pub fn output_tail(chunks: &[OutputChunk]) -> String {
+    let output: String = chunks.iter().map(|c| c.chunk.as_str()).collect();
+    if output.len() <= MAX_OUTPUT_TAIL_BYTES {
+        return output;
+    }
+    let mut start = output.len() - MAX_OUTPUT_TAIL_BYTES;
+    while !output.is_char_boundary(start) {
+        start += 1;
+    }
+    output[start..].to_string()
+}
+
+/// SQLite-backed store of completed action runs
+pub struct ActionHistory {
+    conn: Mutex<Connection>,
+}
+
+impl ActionHistory {
+    /// Open or create the history database at the given path
+    pub fn open(db_path: PathBuf) -> Result<Self> {
+        if let Some(parent) = db_path.parent() {
+            std::fs::create_dir_all(parent).context("Cannot create directory")?;
+        }
+
+        let conn = Connection::open(&db_path).context("Failed to open action history")?;
+        conn.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS action_runs (
+                id TEXT PRIMARY KEY,
+                repo_path TEXT NOT NULL,
+                action_id TEXT NOT NULL,
+                action_name TEXT NOT NULL,
+                command TEXT NOT NULL,
+                status TEXT NOT NULL,
+                exit_code INTEGER,
+                started_at INTEGER NOT NULL,
+                completed_at INTEGER NOT NULL,
+                duration_ms INTEGER NOT NULL,
+                output_tail TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_action_runs_repo
+                ON action_runs(repo_path, command, completed_at DESC);
+            "#,
+        )?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Record a completed run. The output tail is capped before storing.
+    pub fn record_run(&self, run: &ActionRun) -> Result<()> {
+        let conn = lock(&self.conn);
+        let tail = if run.output_tail.len() > MAX_OUTPUT_TAIL_BYTES {
+            output_tail(&[OutputChunk {
+                chunk: run.output_tail.clone(),
+                stream: "stdout".to_string(),
+                timestamp: run.completed_at,
+            }])
+        } else {
+            run.output_tail.clone()
+        };
+        conn.execute(
+            "INSERT OR REPLACE INTO action_runs
+             (id, repo_path, action_id, action_name, command, status, exit_code,
+              started_at, completed_at, duration_ms, output_tail)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                run.id,
+                run.repo_path,
+                run.action_id,
+                run.action_name,
+                run.command,
+                run.status.as_str(),
+                run.exit_code,
+                run.started_at,
+                run.completed_at,
+                run.duration_ms,
+                tail,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Reclaim space left by deleted rows. Returns bytes freed, or `None` if
+    /// a transaction was in progress and compaction was skipped.
+    pub fn compact(&self) -> Result<Option<u64>> {
+        let conn = lock(&self.conn);
+        crate::util::sqlite::compact(&conn).context("Failed to compact action history")
+    }
+
+    /// List the most recent runs for a repository, newest first
+    pub fn list_action_runs(&self, repo_path: &str, limit: usize) -> Result<Vec<ActionRun>> {
+        let conn = lock(&self.conn);
+        let mut stmt = conn.prepare(
+            "SELECT id, repo_path, action_id, action_name, command, status, exit_code,
+                    started_at, completed_at, duration_ms, output_tail
+             FROM action_runs WHERE repo_path = ?1
+             ORDER BY completed_at DESC LIMIT ?2",
+        )?;
+        let runs = stmt
+            .query_map(params![repo_path, limit as i64], ActionRun::from_row)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(runs)
+    }
+
+    /// Get a single run by its execution ID
+    pub fn get_action_run(&self, id: &str) -> Result<Option<ActionRun>> {
+        let conn = lock(&self.conn);
+        let run = conn
+            .query_row(
+                "SELECT id, repo_path, action_id, action_name, command, status, exit_code,
+                        started_at, completed_at, duration_ms, output_tail
+                 FROM action_runs WHERE id = ?1",
+                params![id],
+                ActionRun::from_row,
+            )
+            .optional()?;
+        Ok(run)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn sample_run(id: &str, status: ActionStatus, exit_code: i32, completed_at: i64) -> ActionRun {
+        ActionRun {
+            id: id.to_string(),
+            repo_path: "/tmp/repo".to_string(),
+            action_id: "action-1".to_string(),
+            action_name: "Test".to_string(),
+            command: "cargo test".to_string(),
+            status,
+            exit_code: Some(exit_code),
+            started_at: completed_at - 1000,
+            completed_at,
+            duration_ms: 1000,
+            output_tail: "test result: ok".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_record_and_list_runs() {
+        let dir = tempdir().unwrap();
+        let history = ActionHistory::open(dir.path().join("history.db")).unwrap();
+
+        history
+            .record_run(&sample_run("run-1", ActionStatus::Completed, 0, 1000))
+            .unwrap();
+        history
+            .record_run(&sample_run("run-2", ActionStatus::Failed, 101, 2000))
+            .unwrap();
+
+        let runs = history.list_action_runs("/tmp/repo", 10).unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].id, "run-2");
+        assert!(matches!(runs[0].status, ActionStatus::Failed));
+        assert_eq!(runs[0].exit_code, Some(101));
+        assert!(matches!(runs[1].status, ActionStatus::Completed));
+
+        let limited = history.list_action_runs("/tmp/repo", 1).unwrap();
+        assert_eq!(limited.len(), 1);
+
+        let run = history.get_action_run("run-1").unwrap().unwrap();
+        assert_eq!(run.exit_code, Some(0));
+        assert_eq!(run.output_tail, "test result: ok");
+
+        assert!(history.list_action_runs("/other", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_output_is_capped() {
+        let dir = tempdir().unwrap();
+        let history = ActionHistory::open(dir.path().join("history.db")).unwrap();
+
+        let mut run = sample_run("run-1", ActionStatus::Completed, 0, 1000);
+        run.output_tail = format!("{}end", "x".repeat(MAX_OUTPUT_TAIL_BYTES));
+        history.record_run(&run).unwrap();
+
+        let stored = history.get_action_run("run-1").unwrap().unwrap();
+        assert_eq!(stored.output_tail.len(), MAX_OUTPUT_TAIL_BYTES);
+        assert!(stored.output_tail.ends_with("end"));
+    }
+}
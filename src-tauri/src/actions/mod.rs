@@ -1,5 +1,13 @@
+pub mod ansi;
 pub mod detector;
+pub mod diagnostics;
+pub mod history;
+pub mod output;
 pub mod runner;
 
-pub use detector::{detect_actions, SuggestedAction};
+pub use ansi::{strip_ansi, AnsiMode, StyledSpan};
+pub use detector::{detect_actions, DetectionCancelled, DetectionRegistry, SuggestedAction};
+pub use diagnostics::{ActionDiagnostic, ActionDiagnosticEvent, Severity};
+pub use history::{ActionHistory, ActionRun};
+pub use output::OutputSlice;
 pub use runner::{ActionOutputEvent, ActionRunner, ActionStatus, ActionStatusEvent};
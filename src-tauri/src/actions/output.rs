@@ -0,0 +1,154 @@
+//! Resumable action output.
+//!
+//! Streamed output is also appended to a bounded per-run log addressed by
+//! absolute byte offsets, so a frontend that reconnects mid-run (or after it
+//! finishes) can catch up from the last offset it saw.
+
+use base64::Engine;
+use serde::{Serialize, Serializer};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Max bytes retained per run; older output is dropped from the front.
+pub const MAX_OUTPUT_LOG_BYTES: usize = 1024 * 1024;
+
+/// Max runs whose output is retained; the oldest are evicted first.
+const MAX_RETAINED_RUNS: usize = 32;
+
+/// Output read from a run's log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputSlice {
+    /// Raw output bytes, sent as base64 since they needn't be UTF-8
+    #[serde(serialize_with = "serialize_base64")]
+    pub data: Vec<u8>,
+    /// Offset to pass on the next read
+    pub next_offset: u64,
+}
+
+fn serialize_base64<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&base64::engine::general_purpose::STANDARD.encode(bytes))
+}
+
+/// A ring buffer of one run's combined stdout/stderr bytes.
+#[derive(Debug)]
+pub struct OutputLog {
+    data: VecDeque<u8>,
+    /// Absolute offset of the first retained byte
+    start_offset: u64,
+    capacity: usize,
+}
+
+impl OutputLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            data: VecDeque::new(),
+            start_offset: 0,
+            capacity,
+        }
+    }
+
+    /// Append bytes, dropping the oldest if over capacity.
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.data.extend(bytes);
+        let overflow = self.data.len().saturating_sub(self.capacity);
+        if overflow > 0 {
+            self.data.drain(..overflow);
+            self.start_offset += overflow as u64;
+        }
+    }
+
+    /// Offset just past the last byte written.
+    pub fn end_offset(&self) -> u64 {
+        self.start_offset + self.data.len() as u64
+    }
+
+    /// Read everything from `offset` on. Offsets that have already been
+    /// dropped read from the oldest retained byte instead.
+    pub fn read_from(&self, offset: u64) -> OutputSlice {
+        let from = offset.clamp(self.start_offset, self.end_offset());
+        let skip = (from - self.start_offset) as usize;
+        OutputSlice {
+            data: self.data.iter().skip(skip).copied().collect(),
+            next_offset: self.end_offset(),
+        }
+    }
+}
+
+/// Output logs keyed by run (execution) id.
+#[derive(Default)]
+pub struct OutputLogs {
+    logs: HashMap<String, Arc<Mutex<OutputLog>>>,
+    /// Run ids in creation order, for eviction
+    order: VecDeque<String>,
+}
+
+impl OutputLogs {
+    /// Create the log for a new run, evicting the oldest runs if needed.
+    pub fn create(&mut self, run_id: &str) -> Arc<Mutex<OutputLog>> {
+        while self.order.len() >= MAX_RETAINED_RUNS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.logs.remove(&oldest);
+            }
+        }
+        let log = Arc::new(Mutex::new(OutputLog::new(MAX_OUTPUT_LOG_BYTES)));
+        self.logs.insert(run_id.to_string(), log.clone());
+        self.order.push_back(run_id.to_string());
+        log
+    }
+
+    pub fn get(&self, run_id: &str) -> Option<Arc<Mutex<OutputLog>>> {
+        self.logs.get(run_id).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_from_offset_and_tail() {
+        let mut log = OutputLog::new(16);
+        log.push(b"hello ");
+        let first = log.read_from(0);
+        assert_eq!(first.data, b"hello ");
+        assert_eq!(first.next_offset, 6);
+
+        // Catch up from where the last read left off
+        log.push(b"world\n");
+        let next = log.read_from(first.next_offset);
+        assert_eq!(next.data, b"world\n");
+        assert_eq!(next.next_offset, 12);
+
+        // Nothing new
+        assert!(log.read_from(12).data.is_empty());
+
+        // Overflow drops the head; a stale offset gets the retained tail
+        log.push(b"0123456789");
+        let tail = log.read_from(0);
+        assert_eq!(tail.data, b"world\n0123456789");
+        assert_eq!(tail.next_offset, 22);
+        assert_eq!(log.read_from(18).data, b"6789");
+    }
+
+    #[test]
+    fn test_slice_serializes_as_base64() {
+        let mut log = OutputLog::new(16);
+        log.push(b"caf\xe9\n");
+        let json = serde_json::to_value(log.read_from(0)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "data": "Y2Fm6Qo=", "nextOffset": 5 })
+        );
+    }
+
+    #[test]
+    fn test_oldest_runs_are_evicted() {
+        let mut logs = OutputLogs::default();
+        for i in 0..=MAX_RETAINED_RUNS {
+            logs.create(&format!("run-{i}"));
+        }
+        assert!(logs.get("run-0").is_none());
+        assert!(logs.get(&format!("run-{MAX_RETAINED_RUNS}")).is_some());
+    }
+}
@@ -1,13 +1,19 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
 use std::process::{Command, Stdio};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use tauri::{AppHandle, Emitter};
 
-use crate::store::Store;
+use super::ansi::{AnsiMode, AnsiParser, StyledSpan};
+use super::diagnostics::{ActionDiagnostic, ActionDiagnosticEvent, DiagnosticMatcher};
+use super::history::{self, ActionHistory, ActionRun};
+use super::output::{OutputLogs, OutputSlice};
+use crate::logging;
+use crate::store::{ProjectAction, Store};
 
 /// Event emitted when action output is produced
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,6 +22,9 @@ pub struct ActionOutputEvent {
     pub execution_id: String,
     pub chunk: String,
     pub stream: String, // "stdout" or "stderr"
+    /// The chunk's text split into styled spans, when run with `AnsiMode::Spans`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spans: Option<Vec<StyledSpan>>,
 }
 
 /// Event emitted when action status changes
@@ -41,6 +50,27 @@ pub enum ActionStatus {
     Stopped,
 }
 
+impl ActionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ActionStatus::Running => "running",
+            ActionStatus::Completed => "completed",
+            ActionStatus::Failed => "failed",
+            ActionStatus::Stopped => "stopped",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "running" => Some(ActionStatus::Running),
+            "completed" => Some(ActionStatus::Completed),
+            "failed" => Some(ActionStatus::Failed),
+            "stopped" => Some(ActionStatus::Stopped),
+            _ => None,
+        }
+    }
+}
+
 /// Represents a single output chunk with its metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OutputChunk {
@@ -49,6 +79,175 @@ pub struct OutputChunk {
     pub timestamp: i64,
 }
 
+/// Emit an event for each diagnostic recognized in a stream's output.
+fn emit_diagnostics(
+    app: &AppHandle,
+    execution_id: &str,
+    stream: &str,
+    diagnostics: Vec<ActionDiagnostic>,
+) {
+    for diagnostic in diagnostics {
+        let _ = app.emit(
+            "action_diagnostic",
+            ActionDiagnosticEvent {
+                execution_id: execution_id.to_string(),
+                stream: stream.to_string(),
+                diagnostic,
+            },
+        );
+    }
+}
+
+/// Apply `mode` to a raw output chunk, returning the chunk to emit and, in
+/// `Spans` mode, its styled spans.
+fn apply_ansi_mode(
+    mode: AnsiMode,
+    parser: &mut AnsiParser,
+    raw: String,
+) -> (String, Option<Vec<StyledSpan>>) {
+    match mode {
+        AnsiMode::Raw => (raw, None),
+        AnsiMode::Strip => (parser.strip(&raw), None),
+        AnsiMode::Spans => {
+            let spans = parser.push(&raw);
+            (raw, Some(spans))
+        }
+    }
+}
+
+/// Resolve the directory an action runs in: the worktree itself, or `subpath`
+/// within it. Rejects subpaths that escape the worktree (absolute paths, `..`,
+/// or symlinks pointing elsewhere).
+fn resolve_working_dir(worktree_path: &Path, subpath: Option<&str>) -> Result<PathBuf> {
+    let subpath = match subpath.map(str::trim) {
+        Some(s) if !s.is_empty() && s != "." => Path::new(s),
+        _ => return Ok(worktree_path.to_path_buf()),
+    };
+
+    if subpath
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        bail!(
+            "Subpath must be relative to the repository: {}",
+            subpath.display()
+        );
+    }
+
+    let root = worktree_path
+        .canonicalize()
+        .with_context(|| format!("Worktree not found: {}", worktree_path.display()))?;
+    let dir = root
+        .join(subpath)
+        .canonicalize()
+        .with_context(|| format!("Subpath not found: {}", subpath.display()))?;
+    if !dir.starts_with(&root) {
+        bail!("Subpath escapes the repository: {}", subpath.display());
+    }
+    if !dir.is_dir() {
+        bail!("Subpath is not a directory: {}", subpath.display());
+    }
+    Ok(dir)
+}
+
+/// Run a git command in `dir`, failing on a non-zero exit
+fn run_git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git").args(args).current_dir(dir).output()?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Run git with `input` on stdin, e.g. for `--pathspec-from-file=-`.
+fn run_git_with_input(dir: &Path, args: &[&str], input: &[u8]) -> Result<()> {
+    let mut child = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input)?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Paths with uncommitted changes in a worktree, untracked files included,
+/// as the raw bytes git reports them.
+fn dirty_paths(dir: &Path) -> Result<HashSet<Vec<u8>>> {
+    let output = Command::new("git")
+        .args(["status", "--porcelain", "-z", "--untracked-files=all"])
+        .current_dir(dir)
+        .output()?;
+    if !output.status.success() {
+        bail!(
+            "git status failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let mut paths = HashSet::new();
+    let mut fields = output.stdout.split(|&b| b == 0).filter(|f| !f.is_empty());
+    while let Some(entry) = fields.next() {
+        // "XY path"; renames and copies are followed by their original path
+        if let Some(path) = entry.get(3..) {
+            paths.insert(path.to_vec());
+        }
+        if matches!(entry.first(), Some(b'R' | b'C')) {
+            if let Some(original) = fields.next() {
+                paths.insert(original.to_vec());
+            }
+        }
+    }
+    Ok(paths)
+}
+
+/// Commit message for an auto-commit, e.g. "chore: ran cargo fmt"
+fn auto_commit_message(command: &str) -> String {
+    let summary = command.lines().map(str::trim).find(|l| !l.is_empty());
+    format!("chore: ran {}", summary.unwrap_or("action"))
+}
+
+/// Build the shell process used to run an action.
+///
+/// Uses interactive (-i) + login (-l) + stdin (-s) with stdin piping to ensure:
+/// 1. Interactive mode triggers directory-based hooks (like Hermit's chpwd/precmd)
+/// 2. Login shell loads the full environment
+/// 3. -s flag forces shell to read commands from stdin (critical for non-TTY context)
+/// 4. Stdin commands execute AFTER shell initialization and hook activation
+///
+/// `env` is applied on top of the minimal inherited environment.
+fn shell_command(shell: &str, working_dir: &Path, env: &HashMap<String, String>) -> Command {
+    let mut cmd = Command::new(shell);
+    cmd.current_dir(working_dir) // Start in target directory to trigger directory hooks
+        .env_clear() // Clear all inherited environment variables
+        .env("HOME", std::env::var("HOME").unwrap_or_default()) // Preserve HOME for shell profile loading
+        .env("USER", std::env::var("USER").unwrap_or_default()) // Preserve USER for shell profile loading
+        .env("SHELL", shell) // Preserve SHELL so it knows which shell it is
+        .envs(env)
+        .arg("-i") // Interactive shell to trigger hooks like chpwd for Hermit
+        .arg("-l") // Login shell to load profile
+        .arg("-s") // Force shell to read commands from stdin (required for non-TTY)
+        .stdin(Stdio::piped()) // Pipe stdin to send commands after initialization
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    cmd
+}
+
 /// Tracks a running action
 struct RunningActionState {
     execution_id: String,
@@ -64,6 +263,10 @@ struct RunningActionState {
 /// Manages action execution
 pub struct ActionRunner {
     running: Arc<Mutex<HashMap<String, RunningActionState>>>,
+    /// Resumable output by execution id, kept after runs finish
+    output_logs: Arc<Mutex<OutputLogs>>,
+    /// Where completed runs are recorded, if configured
+    history: Option<Arc<ActionHistory>>,
 }
 
 impl Default for ActionRunner {
@@ -76,10 +279,34 @@ impl ActionRunner {
     pub fn new() -> Self {
         Self {
             running: Arc::new(Mutex::new(HashMap::new())),
+            output_logs: Arc::new(Mutex::new(OutputLogs::default())),
+            history: None,
+        }
+    }
+
+    /// Create a runner that records completed runs in `history`
+    pub fn with_history(history: Arc<ActionHistory>) -> Self {
+        Self {
+            running: Arc::new(Mutex::new(HashMap::new())),
+            output_logs: Arc::new(Mutex::new(OutputLogs::default())),
+            history: Some(history),
         }
     }
 
-    /// Execute an action in the given worktree directory
+    /// Execute an action in the given worktree directory.
+    ///
+    /// When `subpath` is set (monorepo sub-packages), the command runs in that
+    /// directory relative to the worktree; it must stay within the worktree.
+    ///
+    /// `env` is merged over the action's saved default environment. Values may
+    /// contain secrets and are never logged or recorded in the run history.
+    ///
+    /// When `auto_commit` is set and the action is configured to auto-commit,
+    /// any changes it leaves behind are committed after a successful run.
+    ///
+    /// `ansi` controls how escape codes in the output are emitted; the
+    /// resumable output log always keeps the raw bytes.
+    #[allow(clippy::too_many_arguments)]
     pub fn run_action(
         &self,
         app: AppHandle,
@@ -87,14 +314,53 @@ impl ActionRunner {
         branch_id: String,
         action_id: String,
         worktree_path: String,
+        subpath: Option<String>,
+        env: HashMap<String, String>,
+        auto_commit: bool,
+        ansi: AnsiMode,
     ) -> Result<String> {
-        let execution_id = uuid::Uuid::new_v4().to_string();
-
         // Get the action from store
         let action = store
             .get_project_action(&action_id)?
             .context("Action not found")?;
 
+        self.run_project_action(
+            app,
+            store,
+            branch_id,
+            action,
+            worktree_path,
+            subpath,
+            env,
+            auto_commit,
+            ansi,
+        )
+    }
+
+    /// Like `run_action`, but for an action that may not be saved, such as
+    /// one just detected.
+    #[allow(clippy::too_many_arguments)]
+    pub fn run_project_action(
+        &self,
+        app: AppHandle,
+        store: Arc<Store>,
+        branch_id: String,
+        action: ProjectAction,
+        worktree_path: String,
+        subpath: Option<String>,
+        env: HashMap<String, String>,
+        auto_commit: bool,
+        ansi: AnsiMode,
+    ) -> Result<String> {
+        let execution_id = uuid::Uuid::new_v4().to_string();
+        let action_id = action.id.clone();
+
+        let working_dir = resolve_working_dir(Path::new(&worktree_path), subpath.as_deref())?;
+
+        // Saved defaults first, then per-run overrides
+        let mut merged_env = action.env.clone();
+        merged_env.extend(env);
+
         // Determine which shell to use
         let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string());
 
@@ -103,23 +369,25 @@ impl ActionRunner {
         // When using -c, the command runs immediately before hooks can activate Hermit.
         let commands = format!("{}\nexit\n", action.command);
 
-        // Use interactive (-i) + login (-l) + stdin (-s) with stdin piping to ensure:
-        // 1. Interactive mode triggers directory-based hooks (like Hermit's chpwd/precmd)
-        // 2. Login shell loads the full environment
-        // 3. -s flag forces shell to read commands from stdin (critical for non-TTY context)
-        // 4. Stdin commands execute AFTER shell initialization and hook activation
-        let mut child = Command::new(&shell)
-            .current_dir(&worktree_path) // Start in target directory to trigger directory hooks
-            .env_clear() // Clear all inherited environment variables
-            .env("HOME", std::env::var("HOME").unwrap_or_default()) // Preserve HOME for shell profile loading
-            .env("USER", std::env::var("USER").unwrap_or_default()) // Preserve USER for shell profile loading
-            .env("SHELL", &shell) // Preserve SHELL so it knows which shell it is
-            .arg("-i") // Interactive shell to trigger hooks like chpwd for Hermit
-            .arg("-l") // Login shell to load profile
-            .arg("-s") // Force shell to read commands from stdin (required for non-TTY)
-            .stdin(Stdio::piped()) // Pipe stdin to send commands after initialization
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
+        // Auto-commit requires both the action setting and the per-run opt-in.
+        // Only what the action changes is committed, so note what's already dirty.
+        let dirty_before = if action.auto_commit && auto_commit {
+            match dirty_paths(Path::new(&worktree_path)) {
+                Ok(paths) => Some(paths),
+                Err(e) => {
+                    log::error!(
+                        target: logging::ACTIONS,
+                        "Not auto-committing, cannot read worktree status: {}",
+                        e
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let mut child = shell_command(&shell, &working_dir, &merged_env)
             .spawn()
             .context("Failed to spawn action process")?;
 
@@ -131,12 +399,12 @@ impl ActionRunner {
             // Spawn a thread to write to stdin to avoid blocking
             thread::spawn(move || {
                 if let Err(e) = stdin.write_all(commands_clone.as_bytes()) {
-                    eprintln!("Failed to write to stdin: {}", e);
+                    log::error!(target: logging::ACTIONS, "Failed to write to stdin: {}", e);
                     return;
                 }
                 // Explicitly flush to ensure commands are sent
                 if let Err(e) = stdin.flush() {
-                    eprintln!("Failed to flush stdin: {}", e);
+                    log::error!(target: logging::ACTIONS, "Failed to flush stdin: {}", e);
                 }
                 // stdin is automatically closed when dropped
             });
@@ -144,6 +412,9 @@ impl ActionRunner {
 
         // Create output buffer
         let output_buffer = Arc::new(Mutex::new(Vec::new()));
+        let output_log = self.output_logs.lock().unwrap().create(&execution_id);
+
+        let started_at = crate::store::now_timestamp();
 
         // Record the running action
         {
@@ -155,7 +426,7 @@ impl ActionRunner {
                     action_id: action_id.clone(),
                     action_name: action.name.clone(),
                     branch_id: branch_id.clone(),
-                    started_at: crate::store::now_timestamp(),
+                    started_at,
                     child_pid: Some(child_pid),
                     output_buffer: output_buffer.clone(),
                 },
@@ -172,7 +443,7 @@ impl ActionRunner {
                 action_name: action.name.clone(),
                 status: ActionStatus::Running,
                 exit_code: None,
-                started_at: crate::store::now_timestamp(),
+                started_at,
                 completed_at: None,
             },
         );
@@ -181,15 +452,22 @@ impl ActionRunner {
         let exec_id = execution_id.clone();
         let app_clone = app.clone();
         let buffer_clone = output_buffer.clone();
+        let log_clone = output_log.clone();
+        let mut readers = Vec::new();
         if let Some(mut stdout) = child.stdout.take() {
-            thread::spawn(move || {
+            readers.push(thread::spawn(move || {
                 let mut buffer = [0u8; 1024];
+                let mut matcher = DiagnosticMatcher::new();
+                let mut parser = AnsiParser::new();
                 loop {
                     match stdout.read(&mut buffer) {
                         Ok(0) => break, // EOF
                         Ok(n) => {
+                            log_clone.lock().unwrap().push(&buffer[..n]);
+
                             // Convert bytes to string, preserving all control characters
-                            let chunk = String::from_utf8_lossy(&buffer[..n]).to_string();
+                            let raw = String::from_utf8_lossy(&buffer[..n]).to_string();
+                            let (chunk, spans) = apply_ansi_mode(ansi, &mut parser, raw);
                             let timestamp = crate::store::now_timestamp();
 
                             // Store in buffer
@@ -202,6 +480,8 @@ impl ActionRunner {
                                 });
                             }
 
+                            let diagnostics = matcher.push(&chunk);
+
                             // Emit event
                             let _ = app_clone.emit(
                                 "action_output",
@@ -209,27 +489,36 @@ impl ActionRunner {
                                     execution_id: exec_id.clone(),
                                     chunk,
                                     stream: "stdout".to_string(),
+                                    spans,
                                 },
                             );
+                            emit_diagnostics(&app_clone, &exec_id, "stdout", diagnostics);
                         }
                         Err(_) => break,
                     }
                 }
-            });
+                emit_diagnostics(&app_clone, &exec_id, "stdout", matcher.finish());
+            }));
         }
 
         let exec_id = execution_id.clone();
         let app_clone = app.clone();
         let buffer_clone = output_buffer.clone();
+        let log_clone = output_log.clone();
         if let Some(mut stderr) = child.stderr.take() {
-            thread::spawn(move || {
+            readers.push(thread::spawn(move || {
                 let mut buffer = [0u8; 1024];
+                let mut matcher = DiagnosticMatcher::new();
+                let mut parser = AnsiParser::new();
                 loop {
                     match stderr.read(&mut buffer) {
                         Ok(0) => break, // EOF
                         Ok(n) => {
+                            log_clone.lock().unwrap().push(&buffer[..n]);
+
                             // Convert bytes to string, preserving all control characters
-                            let chunk = String::from_utf8_lossy(&buffer[..n]).to_string();
+                            let raw = String::from_utf8_lossy(&buffer[..n]).to_string();
+                            let (chunk, spans) = apply_ansi_mode(ansi, &mut parser, raw);
                             let timestamp = crate::store::now_timestamp();
 
                             // Store in buffer
@@ -242,6 +531,8 @@ impl ActionRunner {
                                 });
                             }
 
+                            let diagnostics = matcher.push(&chunk);
+
                             // Emit event
                             let _ = app_clone.emit(
                                 "action_output",
@@ -249,13 +540,16 @@ impl ActionRunner {
                                     execution_id: exec_id.clone(),
                                     chunk,
                                     stream: "stderr".to_string(),
+                                    spans,
                                 },
                             );
+                            emit_diagnostics(&app_clone, &exec_id, "stderr", diagnostics);
                         }
                         Err(_) => break,
                     }
                 }
-            });
+                emit_diagnostics(&app_clone, &exec_id, "stderr", matcher.finish());
+            }));
         }
 
         // Spawn thread to wait for completion
@@ -265,22 +559,53 @@ impl ActionRunner {
         let _store_clone = store.clone();
         let branch_id_clone = branch_id.clone();
         let worktree_path_clone = worktree_path.clone();
-        let auto_commit = action.auto_commit;
         let action_name = action.name.clone();
+        let action_command = action.command.clone();
+        let history = self.history.clone();
 
         thread::spawn(move || {
             let exit_status = child.wait();
             let exit_code = exit_status.as_ref().ok().and_then(|s| s.code());
             let completed_at = crate::store::now_timestamp();
 
-            // Remove from running actions
-            {
+            // Remove from running actions. If it's already gone, the run was stopped.
+            let was_stopped = {
                 let mut running = running_clone.lock().unwrap();
-                running.remove(&exec_id);
-            }
+                running.remove(&exec_id).is_none()
+            };
 
             let success = exit_status.as_ref().map(|s| s.success()).unwrap_or(false);
 
+            // Record the run once all output has been drained
+            if let Some(history) = history {
+                for reader in readers {
+                    let _ = reader.join();
+                }
+                let status = if was_stopped {
+                    ActionStatus::Stopped
+                } else if success {
+                    ActionStatus::Completed
+                } else {
+                    ActionStatus::Failed
+                };
+                let run = ActionRun {
+                    id: exec_id.clone(),
+                    repo_path: worktree_path_clone.clone(),
+                    action_id: action_id.clone(),
+                    action_name: action_name.clone(),
+                    command: action_command.clone(),
+                    status,
+                    exit_code,
+                    started_at,
+                    completed_at,
+                    duration_ms: completed_at - started_at,
+                    output_tail: history::output_tail(&output_buffer.lock().unwrap()),
+                };
+                if let Err(e) = history.record_run(&run) {
+                    log::error!(target: logging::ACTIONS, "Failed to record action run: {}", e);
+                }
+            }
+
             // Emit completion status
             let _ = app_clone.emit(
                 "action_status",
@@ -300,20 +625,32 @@ impl ActionRunner {
                 },
             );
 
-            // If auto_commit is enabled and action succeeded, commit changes
-            if auto_commit && success {
-                if let Err(e) = Self::auto_commit_changes(&worktree_path_clone, &action_name) {
-                    eprintln!("Failed to auto-commit changes: {}", e);
-                } else {
-                    // Emit event to notify frontend of the commit
-                    let _ = app_clone.emit(
-                        "action_auto_commit",
-                        serde_json::json!({
-                            "executionId": exec_id,
-                            "branchId": branch_id_clone,
-                            "actionName": action_name,
-                        }),
-                    );
+            // If auto_commit is enabled and action succeeded, commit its changes
+            if let Some(dirty_before) = dirty_before.filter(|_| success && !was_stopped) {
+                match Self::auto_commit_changes(
+                    Path::new(&worktree_path_clone),
+                    &action_command,
+                    &dirty_before,
+                ) {
+                    Ok(true) => {
+                        // Emit event to notify frontend of the commit
+                        let _ = app_clone.emit(
+                            "action_auto_commit",
+                            serde_json::json!({
+                                "executionId": exec_id,
+                                "branchId": branch_id_clone,
+                                "actionName": action_name,
+                            }),
+                        );
+                    }
+                    Ok(false) => {}
+                    Err(e) => {
+                        log::error!(
+                            target: logging::ACTIONS,
+                            "Failed to auto-commit changes: {}",
+                            e
+                        );
+                    }
                 }
             }
         });
@@ -321,36 +658,43 @@ impl ActionRunner {
         Ok(execution_id)
     }
 
-    /// Auto-commit changes after a successful action
-    fn auto_commit_changes(worktree_path: &str, action_name: &str) -> Result<()> {
-        // Check if there are any changes
-        let status = Command::new("git")
-            .arg("diff")
-            .arg("--exit-code")
-            .current_dir(worktree_path)
-            .status()?;
-
-        // If exit code is 0, no changes exist
-        if status.success() {
-            return Ok(());
+    /// Auto-commit changes after a successful action.
+    ///
+    /// Commits only the paths the action made dirty (including new files),
+    /// with a message generated from the command. Paths in `dirty_before`
+    /// had uncommitted changes before the action ran and are left alone, as
+    /// is anything else already staged. Returns `false` without committing
+    /// when the action changed nothing else.
+    fn auto_commit_changes(
+        worktree_path: &Path,
+        command: &str,
+        dirty_before: &HashSet<Vec<u8>>,
+    ) -> Result<bool> {
+        let mut changed: Vec<Vec<u8>> = dirty_paths(worktree_path)?
+            .into_iter()
+            .filter(|path| !dirty_before.contains(path))
+            .collect();
+        if changed.is_empty() {
+            return Ok(false);
         }
-
-        // Stage all changes
-        Command::new("git")
-            .args(["add", "-A"])
-            .current_dir(worktree_path)
-            .status()
-            .context("Failed to stage changes")?;
-
-        // Commit with action name
-        let commit_message = format!("chore: {}", action_name);
-        Command::new("git")
-            .args(["commit", "-m", &commit_message])
-            .current_dir(worktree_path)
-            .status()
+        changed.sort();
+
+        let pathspecs: Vec<u8> = changed
+            .iter()
+            .flat_map(|path| path.iter().copied().chain([0]))
+            .collect();
+        let pathspec_args = ["--pathspec-from-file=-", "--pathspec-file-nul"];
+        let mut add = vec!["--literal-pathspecs", "add", "-A"];
+        add.extend(pathspec_args);
+        run_git_with_input(worktree_path, &add, &pathspecs).context("Failed to stage changes")?;
+
+        let message = auto_commit_message(command);
+        let mut commit = vec!["--literal-pathspecs", "commit", "-m", &message];
+        commit.extend(pathspec_args);
+        run_git_with_input(worktree_path, &commit, &pathspecs)
             .context("Failed to commit changes")?;
 
-        Ok(())
+        Ok(true)
     }
 
     /// Stop a running action
@@ -401,6 +745,14 @@ impl ActionRunner {
             .collect()
     }
 
+    /// Read an execution's output from byte `from_offset` on, for catching up
+    /// after a reconnect. Works for finished runs until they are evicted.
+    pub fn get_output_from(&self, execution_id: &str, from_offset: u64) -> Option<OutputSlice> {
+        let log = self.output_logs.lock().unwrap().get(execution_id)?;
+        let slice = log.lock().unwrap().read_from(from_offset);
+        Some(slice)
+    }
+
     /// Get buffered output for an execution
     pub fn get_buffered_output(&self, execution_id: &str) -> Option<Vec<OutputChunk>> {
         let running = self.running.lock().unwrap();
@@ -412,3 +764,119 @@ impl ActionRunner {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_support::{git, init_test_repo};
+
+    /// Run `script` through the same shell setup actions use and return stdout.
+    fn run_in_shell(working_dir: &Path, env: &HashMap<String, String>, script: &str) -> String {
+        let mut child = shell_command("/bin/sh", working_dir, env).spawn().unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(format!("{script}\nexit\n").as_bytes())
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        String::from_utf8_lossy(&output.stdout).to_string()
+    }
+
+    #[test]
+    fn test_injected_env_is_visible() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = HashMap::from([("STAGED_TEST_VAR".to_string(), "injected".to_string())]);
+        let stdout = run_in_shell(dir.path(), &env, "echo \"value=$STAGED_TEST_VAR\"");
+        assert!(stdout.contains("value=injected"), "stdout: {stdout}");
+    }
+
+    #[test]
+    fn test_runs_in_subpath() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("packages/web")).unwrap();
+
+        let working_dir = resolve_working_dir(dir.path(), Some("packages/web")).unwrap();
+        let stdout = run_in_shell(&working_dir, &HashMap::new(), "echo \"cwd=$(pwd -P)\"");
+
+        let expected = dir.path().join("packages/web").canonicalize().unwrap();
+        assert!(
+            stdout.contains(&format!("cwd={}", expected.display())),
+            "stdout: {stdout}"
+        );
+    }
+
+    #[test]
+    fn test_auto_commit_after_formatting() {
+        let (dir, _) = init_test_repo();
+        let repo = dir.path();
+        std::fs::write(repo.join("main.rs"), "fn main(){}\n").unwrap();
+        git(repo, &["add", "-A"]);
+        git(repo, &["commit", "-m", "initial"]);
+
+        // Nothing to commit yet
+        let clean = HashSet::new();
+        assert!(!ActionRunner::auto_commit_changes(repo, "cargo fmt", &clean).unwrap());
+
+        // A "formatter" rewrites the file
+        run_in_shell(repo, &HashMap::new(), "printf 'fn main() {}\\n' > main.rs");
+        assert!(ActionRunner::auto_commit_changes(repo, "cargo fmt", &clean).unwrap());
+
+        let log = git(repo, &["log", "--format=%s"]);
+        assert_eq!(
+            log.lines().collect::<Vec<_>>(),
+            ["chore: ran cargo fmt", "initial"]
+        );
+        assert!(git(repo, &["status", "--porcelain"]).trim().is_empty());
+    }
+
+    #[test]
+    fn test_auto_commit_leaves_earlier_changes_alone() {
+        let (dir, _) = init_test_repo();
+        let repo = dir.path();
+        for name in ["main.rs", "notes.md", "staged.md"] {
+            std::fs::write(repo.join(name), "original\n").unwrap();
+        }
+        git(repo, &["add", "-A"]);
+        git(repo, &["commit", "-m", "initial"]);
+
+        // The user has unrelated work in progress, one change of it staged
+        std::fs::write(repo.join("notes.md"), "draft\n").unwrap();
+        std::fs::write(repo.join("staged.md"), "staged\n").unwrap();
+        git(repo, &["add", "staged.md"]);
+        std::fs::write(repo.join("scratch.txt"), "todo\n").unwrap();
+        let dirty_before = dirty_paths(repo).unwrap();
+
+        // The action rewrites one file and generates another
+        run_in_shell(
+            repo,
+            &HashMap::new(),
+            "printf 'formatted\\n' > main.rs && printf 'gen\\n' > 'out [1].txt'",
+        );
+        assert!(ActionRunner::auto_commit_changes(repo, "fmt", &dirty_before).unwrap());
+
+        let committed = git(repo, &["show", "--name-only", "--format=", "HEAD"]);
+        assert_eq!(
+            committed.lines().collect::<Vec<_>>(),
+            ["main.rs", "out [1].txt"]
+        );
+        let status = git(repo, &["status", "--porcelain"]);
+        assert_eq!(
+            status.lines().collect::<Vec<_>>(),
+            [" M notes.md", "M  staged.md", "?? scratch.txt"]
+        );
+    }
+
+    #[test]
+    fn test_subpath_must_stay_in_worktree() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("repo")).unwrap();
+        let repo = dir.path().join("repo");
+
+        assert!(resolve_working_dir(&repo, Some("../")).is_err());
+        assert!(resolve_working_dir(&repo, Some("/tmp")).is_err());
+        assert!(resolve_working_dir(&repo, Some("missing")).is_err());
+        assert_eq!(resolve_working_dir(&repo, None).unwrap(), repo);
+        assert_eq!(resolve_working_dir(&repo, Some("")).unwrap(), repo);
+    }
+}
@@ -0,0 +1,332 @@
+//! Tracking of spawned agent processes.
+//!
+//! Agent subprocesses are spawned with `kill_on_drop`, but that only helps
+//! when the owning future is dropped normally. If the app crashes, agents
+//! can outlive it. Every spawned agent is recorded here and in a pid file,
+//! so a fresh launch can reap orphans left behind by the previous run.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::logging;
+
+/// A tracked agent process (returned to frontend).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChildInfo {
+    pub pid: u32,
+    pub agent_id: String,
+    pub session_id: String,
+    pub alive: bool,
+}
+
+/// What we persist per child. `command` is checked against the running
+/// process before reaping, so a recycled pid isn't killed by mistake.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ChildRecord {
+    pid: u32,
+    agent_id: String,
+    session_id: String,
+    command: String,
+}
+
+impl ChildRecord {
+    fn info(&self) -> ChildInfo {
+        ChildInfo {
+            pid: self.pid,
+            agent_id: self.agent_id.clone(),
+            session_id: self.session_id.clone(),
+            alive: is_alive(self.pid),
+        }
+    }
+}
+
+/// Tracks live agent processes and mirrors them to a pid file.
+pub struct AgentManager {
+    pid_file: PathBuf,
+    children: Mutex<HashMap<u32, ChildRecord>>,
+}
+
+impl AgentManager {
+    /// Create a manager persisting to `pid_file`. Doesn't touch records
+    /// left by a previous run; call `reap_orphans` for that.
+    pub fn new(pid_file: PathBuf) -> Self {
+        Self {
+            pid_file,
+            children: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record a spawned agent process.
+    pub fn register(&self, pid: u32, agent_id: &str, session_id: &str, command: &str) {
+        let mut children = self.children.lock().unwrap();
+        children.insert(
+            pid,
+            ChildRecord {
+                pid,
+                agent_id: agent_id.to_string(),
+                session_id: session_id.to_string(),
+                command: command.to_string(),
+            },
+        );
+        self.persist(&children);
+    }
+
+    /// Forget a process once it has exited.
+    pub fn unregister(&self, pid: u32) {
+        let mut children = self.children.lock().unwrap();
+        if children.remove(&pid).is_some() {
+            self.persist(&children);
+        }
+    }
+
+    /// List tracked agent processes, sorted by pid.
+    pub fn list_children(&self) -> Vec<ChildInfo> {
+        let children = self.children.lock().unwrap();
+        let mut infos: Vec<ChildInfo> = children.values().map(ChildRecord::info).collect();
+        infos.sort_by_key(|c| c.pid);
+        infos
+    }
+
+    /// Terminate every tracked agent process. Returns how many were signalled.
+    pub fn kill_all_children(&self) -> usize {
+        let mut children = self.children.lock().unwrap();
+        let mut killed = 0;
+        for record in children.values() {
+            if is_alive(record.pid) {
+                log::info!(
+                    target: logging::AI,
+                    "Killing agent {} (PID {})",
+                    record.agent_id,
+                    record.pid
+                );
+                terminate(record.pid);
+                killed += 1;
+            }
+        }
+        children.clear();
+        self.persist(&children);
+        killed
+    }
+
+    /// Kill agent processes recorded by a previous run that are still alive,
+    /// then reset the pid file to this run's children.
+    pub fn reap_orphans(&self) -> Vec<ChildInfo> {
+        let mut reaped = Vec::new();
+        for record in read_records(&self.pid_file) {
+            if is_alive(record.pid) && process_matches(record.pid, &record.command) {
+                log::info!(
+                    target: logging::AI,
+                    "Reaping orphaned agent {} (PID {})",
+                    record.agent_id,
+                    record.pid
+                );
+                terminate(record.pid);
+                reaped.push(record.info());
+            }
+        }
+
+        let children = self.children.lock().unwrap();
+        self.persist(&children);
+        reaped
+    }
+
+    fn persist(&self, children: &HashMap<u32, ChildRecord>) {
+        let records: Vec<&ChildRecord> = children.values().collect();
+        let result = serde_json::to_string(&records)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                if let Some(parent) = self.pid_file.parent() {
+                    std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                std::fs::write(&self.pid_file, json).map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            log::warn!(target: logging::AI, "Failed to write agent pid file: {e}");
+        }
+    }
+}
+
+fn read_records(pid_file: &Path) -> Vec<ChildRecord> {
+    std::fs::read_to_string(pid_file)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Check whether a process exists.
+fn is_alive(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        // Signal 0 performs error checking only
+        unsafe { libc::kill(pid as i32, 0) == 0 }
+    }
+    #[cfg(windows)]
+    {
+        std::process::Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {pid}"), "/NH"])
+            .output()
+            .map(|o| String::from_utf8_lossy(&o.stdout).contains(&pid.to_string()))
+            .unwrap_or(false)
+    }
+}
+
+/// Check that a pid still belongs to the command we spawned.
+///
+/// Windows only reports the image name, so there `command` must name the
+/// executable itself; a process started through a shim (e.g. a `.cmd` file)
+/// doesn't match and is left running.
+fn process_matches(pid: u32, command: &str) -> bool {
+    #[cfg(unix)]
+    {
+        std::process::Command::new("ps")
+            .args(["-p", &pid.to_string(), "-o", "args="])
+            .output()
+            .map(|o| String::from_utf8_lossy(&o.stdout).contains(command))
+            .unwrap_or(false)
+    }
+    #[cfg(windows)]
+    {
+        let stem = |name: &str| {
+            name.to_ascii_lowercase()
+                .trim_end_matches(".exe")
+                .to_string()
+        };
+        std::process::Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {pid}"), "/FO", "CSV", "/NH"])
+            .output()
+            .map(|o| {
+                // `"goose.exe","1234",...`, or an INFO line if there's no match
+                let stdout = String::from_utf8_lossy(&o.stdout);
+                let image = stdout.trim().split(',').next().unwrap_or("");
+                stem(image.trim_matches('"')) == stem(command)
+            })
+            .unwrap_or(false)
+    }
+}
+
+/// Ask a process to exit.
+fn terminate(pid: u32) {
+    #[cfg(unix)]
+    {
+        let _ = std::process::Command::new("kill")
+            .args(["-TERM", &pid.to_string()])
+            .output();
+    }
+    #[cfg(windows)]
+    {
+        let _ = std::process::Command::new("taskkill")
+            .args(["/PID", &pid.to_string(), "/F"])
+            .output();
+    }
+}
+
+// =============================================================================
+// Global manager
+// =============================================================================
+
+static MANAGER: OnceLock<AgentManager> = OnceLock::new();
+
+/// Initialize the global manager and reap orphans from a previous run.
+/// Call this once during Tauri app setup.
+pub fn init_agent_manager(pid_file: PathBuf) -> &'static AgentManager {
+    let manager = MANAGER.get_or_init(|| AgentManager::new(pid_file));
+    let reaped = manager.reap_orphans();
+    if !reaped.is_empty() {
+        log::info!(
+            target: logging::AI,
+            "Reaped {} orphaned agent process(es)",
+            reaped.len()
+        );
+    }
+    manager
+}
+
+/// Get the global manager, if it has been initialized.
+pub fn agent_manager() -> Option<&'static AgentManager> {
+    MANAGER.get()
+}
+
+/// Unregisters a tracked child when dropped, covering early returns.
+pub struct ChildGuard {
+    pid: u32,
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        if let Some(manager) = agent_manager() {
+            manager.unregister(self.pid);
+        }
+    }
+}
+
+/// Register a spawned agent with the global manager, if there is one.
+pub fn track_child(
+    pid: u32,
+    agent_id: &str,
+    session_id: &str,
+    command: &str,
+) -> Option<ChildGuard> {
+    let manager = agent_manager()?;
+    manager.register(pid, agent_id, session_id, command);
+    Some(ChildGuard { pid })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_reaps_orphan_from_previous_run() {
+        let dir = tempdir().unwrap();
+        let pid_file = dir.path().join("agent_pids.json");
+
+        let mut stub = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let pid = stub.id();
+
+        // Previous run: records the agent, then "crashes" without cleanup
+        let previous = AgentManager::new(pid_file.clone());
+        previous.register(pid, "goose", "session-1", "sleep");
+        assert_eq!(
+            previous.list_children(),
+            vec![ChildInfo {
+                pid,
+                agent_id: "goose".to_string(),
+                session_id: "session-1".to_string(),
+                alive: true,
+            }]
+        );
+        drop(previous);
+
+        // Fresh launch reaps it
+        let fresh = AgentManager::new(pid_file.clone());
+        let reaped = fresh.reap_orphans();
+        assert_eq!(reaped.len(), 1);
+        assert_eq!(reaped[0].pid, pid);
+
+        let status = stub.wait().unwrap();
+        assert!(!status.success());
+        assert!(read_records(&pid_file).is_empty());
+    }
+
+    #[test]
+    fn test_unregister_updates_pid_file() {
+        let dir = tempdir().unwrap();
+        let pid_file = dir.path().join("agent_pids.json");
+        let manager = AgentManager::new(pid_file.clone());
+
+        manager.register(4_000_000, "claude", "s1", "claude-code-acp");
+        assert_eq!(read_records(&pid_file).len(), 1);
+
+        manager.unregister(4_000_000);
+        assert!(read_records(&pid_file).is_empty());
+        assert!(manager.list_children().is_empty());
+    }
+}
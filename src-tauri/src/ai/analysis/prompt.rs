@@ -3,9 +3,14 @@
 //! Implements a tiered strategy for prompt construction:
 //! - Tier 1: Full AFTER content + unified diff (default, for smaller changesets)
 //! - Tier 2: Unified diff only (fallback for large changesets)
+//! - Trimmed: Tier 2 with the largest diffs cut to fit the token budget
 //!
 //! Per-file rule: Files > 1,000 lines get diff-only treatment even in Tier 1.
 
+use crate::ai::budget::{self, PromptSection, DEFAULT_PROMPT_TOKEN_BUDGET};
+use crate::logging;
+use crate::util::fenced_block;
+
 /// Threshold for individual files: above this, only include diff (no full content)
 pub const LARGE_FILE_THRESHOLD: usize = 1000;
 
@@ -23,6 +28,8 @@ pub enum PromptStrategy {
     FullContext,
     /// Tier 2: diff only for all files
     DiffOnly,
+    /// Tier 2 with the largest diffs truncated to fit the token budget
+    TrimmedDiff,
 }
 
 /// Input for analyzing a single file
@@ -72,15 +79,18 @@ fn count_file_lines(input: &FileAnalysisInput, include_content: bool) -> usize {
 ///
 /// For Codex, enforces a stricter byte limit (9MB) to avoid API errors.
 pub fn build_prompt_with_strategy(files: &[FileAnalysisInput]) -> (String, PromptStrategy) {
-    build_prompt_with_strategy_for_provider(files, None)
+    build_prompt_with_strategy_for_provider(files, None, DEFAULT_PROMPT_TOKEN_BUDGET)
 }
 
 /// Build a prompt with automatic tier selection based on size and provider.
 ///
 /// If provider is "codex", uses stricter size limits to avoid API errors.
+/// Prompts estimated above `token_budget` drop to diff-only, and then have
+/// their largest diffs trimmed until they fit.
 pub fn build_prompt_with_strategy_for_provider(
     files: &[FileAnalysisInput],
     provider: Option<&str>,
+    token_budget: usize,
 ) -> (String, PromptStrategy) {
     let is_codex = provider == Some("codex");
 
@@ -101,24 +111,76 @@ pub fn build_prompt_with_strategy_for_provider(
         // For Codex, check byte size and fall back to Tier 2 if too large
         if is_codex && prompt.len() > CODEX_MAX_BYTES {
             log::info!(
+                target: logging::AI,
                 "Prompt too large for Codex ({} bytes, limit {}), using diff-only mode",
                 prompt.len(),
                 CODEX_MAX_BYTES
             );
-            let prompt = build_tier2_prompt(files);
-            return (prompt, PromptStrategy::DiffOnly);
+            return build_tier2_within_budget(files, token_budget);
+        }
+
+        let tokens = budget::estimate_tokens(&prompt);
+        if tokens > token_budget {
+            log::info!(
+                target: logging::AI,
+                "Prompt over token budget (~{tokens} tokens, budget {token_budget}), using diff-only mode"
+            );
+            return build_tier2_within_budget(files, token_budget);
         }
 
         return (prompt, PromptStrategy::FullContext);
     }
 
     // Tier 2: diff only for all files
-    log::info!("Changeset too large for full context ({tier1_lines} lines), using diff-only mode");
-    let prompt = build_tier2_prompt(files);
+    log::info!(
+        target: logging::AI,
+        "Changeset too large for full context ({tier1_lines} lines), using diff-only mode"
+    );
 
     // Note: For Codex, byte-size validation for Tier 2 happens in runner.rs so
-    // we can surface a clear error to the UI. There's no smaller tier here.
-    (prompt, PromptStrategy::DiffOnly)
+    // we can surface a clear error to the UI.
+    build_tier2_within_budget(files, token_budget)
+}
+
+/// Build the Tier 2 prompt, trimming the largest diffs if it's over budget.
+fn build_tier2_within_budget(
+    files: &[FileAnalysisInput],
+    token_budget: usize,
+) -> (String, PromptStrategy) {
+    let prompt = build_tier2_prompt(files);
+    let tokens = budget::estimate_tokens(&prompt);
+    if tokens <= token_budget {
+        return (prompt, PromptStrategy::DiffOnly);
+    }
+
+    // Everything but the diffs themselves is fixed cost
+    let diff_chars: usize = files.iter().map(|f| f.diff.chars().count()).sum();
+    let overhead = budget::tokens_for_chars(prompt.chars().count() - diff_chars);
+
+    let mut sections: Vec<PromptSection> = files
+        .iter()
+        .filter(|f| !f.diff.is_empty())
+        .map(|f| PromptSection::new(f.path.clone(), f.diff.clone(), 0))
+        .collect();
+    budget::fit_to_budget(&mut sections, token_budget.saturating_sub(overhead));
+
+    let mut sections = sections.into_iter();
+    let trimmed: Vec<FileAnalysisInput> = files
+        .iter()
+        .map(|f| {
+            let mut f = f.clone();
+            if !f.diff.is_empty() {
+                f.diff = sections.next().map(|s| s.text).unwrap_or_default();
+            }
+            f
+        })
+        .collect();
+
+    log::info!(
+        target: logging::AI,
+        "Diffs over token budget (~{tokens} tokens, budget {token_budget}), trimming largest diffs"
+    );
+    (build_tier2_prompt(&trimmed), PromptStrategy::TrimmedDiff)
 }
 
 /// Build Tier 1 prompt: diff + after content for small files
@@ -145,20 +207,16 @@ fn build_tier1_prompt(files: &[FileAnalysisInput]) -> String {
                 file_sections.push_str("(no changes)\n\n");
             }
         } else {
-            file_sections.push_str("```diff\n");
-            file_sections.push_str(&input.diff);
-            if !input.diff.ends_with('\n') {
-                file_sections.push('\n');
-            }
-            file_sections.push_str("```\n\n");
+            file_sections.push_str(&fenced_block("diff", &input.diff));
+            file_sections.push('\n');
         }
 
         // Include full content for small files (not deleted, not too large)
         if let Some(ref content) = input.after_content {
             if input.after_line_count <= LARGE_FILE_THRESHOLD {
-                file_sections.push_str("### Full Content (after):\n```\n");
-                file_sections.push_str(&format_with_line_numbers(content));
-                file_sections.push_str("\n```\n\n");
+                file_sections.push_str("### Full Content (after):\n");
+                file_sections.push_str(&fenced_block("", &format_with_line_numbers(content)));
+                file_sections.push('\n');
             }
         }
     }
@@ -194,12 +252,8 @@ fn build_tier2_prompt(files: &[FileAnalysisInput]) -> String {
                 file_sections.push_str("(no changes)\n\n");
             }
         } else {
-            file_sections.push_str("```diff\n");
-            file_sections.push_str(&input.diff);
-            if !input.diff.ends_with('\n') {
-                file_sections.push('\n');
-            }
-            file_sections.push_str("```\n\n");
+            file_sections.push_str(&fenced_block("diff", &input.diff));
+            file_sections.push('\n');
         }
     }
 
@@ -313,17 +367,17 @@ pub fn build_unified_changeset_prompt(files: &[(&str, &str, &str)]) -> String {
         if before.is_empty() {
             file_sections.push_str("### BEFORE:\n(new file - no previous content)\n\n");
         } else {
-            file_sections.push_str("### BEFORE:\n```\n");
-            file_sections.push_str(&format_with_line_numbers(before));
-            file_sections.push_str("\n```\n\n");
+            file_sections.push_str("### BEFORE:\n");
+            file_sections.push_str(&fenced_block("", &format_with_line_numbers(before)));
+            file_sections.push('\n');
         }
 
         if after.is_empty() {
             file_sections.push_str("### AFTER:\n(deleted file - no new content)\n\n");
         } else {
-            file_sections.push_str("### AFTER:\n```\n");
-            file_sections.push_str(&format_with_line_numbers(after));
-            file_sections.push_str("\n```\n\n");
+            file_sections.push_str("### AFTER:\n");
+            file_sections.push_str(&fenced_block("", &format_with_line_numbers(after)));
+            file_sections.push('\n');
         }
     }
 
@@ -470,7 +524,11 @@ mod tests {
     fn test_codex_large_prompt_falls_back_to_tier2() {
         let files = vec![oversized_file_input()];
 
-        let (prompt, strategy) = build_prompt_with_strategy_for_provider(&files, Some("codex"));
+        let (prompt, strategy) = build_prompt_with_strategy_for_provider(
+            &files,
+            Some("codex"),
+            DEFAULT_PROMPT_TOKEN_BUDGET,
+        );
 
         assert_eq!(strategy, PromptStrategy::DiffOnly);
         assert!(prompt.contains("unified diffs only"));
@@ -481,10 +539,45 @@ mod tests {
     fn test_non_codex_large_prompt_keeps_tier1() {
         let files = vec![oversized_file_input()];
 
-        let (prompt, strategy) = build_prompt_with_strategy_for_provider(&files, Some("claude"));
+        let (prompt, strategy) =
+            build_prompt_with_strategy_for_provider(&files, Some("claude"), usize::MAX);
 
         assert_eq!(strategy, PromptStrategy::FullContext);
         assert!(prompt.len() > CODEX_MAX_BYTES);
         assert!(prompt.contains("### Full Content (after):"));
     }
+
+    #[test]
+    fn test_prompt_over_token_budget_trims_largest_diffs() {
+        let small_diff = "@@ -1,1 +1,1 @@\n-old();\n+new();".to_string();
+        let files = vec![
+            FileAnalysisInput {
+                path: "src/small.rs".to_string(),
+                diff: small_diff.clone(),
+                after_content: Some("new();".to_string()),
+                is_new_file: false,
+                is_deleted: false,
+                after_line_count: 1,
+            },
+            FileAnalysisInput {
+                path: "src/generated.rs".to_string(),
+                diff: format!(
+                    "@@ -0,0 +1,4000 @@\n{}",
+                    "+let generated = 1;\n".repeat(4000)
+                ),
+                after_content: None,
+                is_new_file: true,
+                is_deleted: false,
+                after_line_count: 4000,
+            },
+        ];
+
+        let (prompt, strategy) = build_prompt_with_strategy_for_provider(&files, None, 5000);
+
+        assert_eq!(strategy, PromptStrategy::TrimmedDiff);
+        assert!(budget::estimate_tokens(&prompt) <= 5000);
+        assert!(prompt.contains(&small_diff));
+        assert!(prompt.contains(budget::TRUNCATED_MARKER));
+        assert!(!prompt.contains("### Full Content (after):"));
+    }
 }
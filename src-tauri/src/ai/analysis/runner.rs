@@ -10,17 +10,20 @@ use super::prompt::{
     LARGE_FILE_THRESHOLD,
 };
 use super::types::ChangesetAnalysis;
-use crate::ai::{find_acp_agent, find_acp_agent_by_id, run_acp_prompt, AcpAgent};
+use crate::ai::config::AgentsConfig;
+use crate::ai::{find_acp_agent_by_id, find_acp_agent_for_repo, run_acp_prompt, AcpAgent};
 use crate::git::{self, DiffSpec, FileContent};
+use crate::logging;
 
 /// Find an available AI agent, optionally by provider ID.
 ///
 /// If `provider` is specified, looks for that specific provider.
-/// Otherwise, returns the default (Goose if available, then Claude).
-pub fn find_ai_tool(provider: Option<&str>) -> Option<AcpAgent> {
+/// Otherwise, returns the repo's preferred provider from `.staged.toml`, or
+/// the default (Goose if available, then Claude).
+pub fn find_ai_tool(repo_path: &Path, provider: Option<&str>) -> Option<AcpAgent> {
     match provider {
         Some(id) => find_acp_agent_by_id(id),
-        None => find_acp_agent(),
+        None => find_acp_agent_for_repo(repo_path),
     }
 }
 
@@ -55,8 +58,8 @@ fn parse_response(response: &str) -> Result<ChangesetAnalysis, String> {
     let json_str = extract_json(response);
 
     serde_json::from_str(json_str).map_err(|e| {
-        log::error!("Failed to parse response as JSON: {e}");
-        log::error!("Response was:\n{response}");
+        log::error!(target: logging::AI, "Failed to parse response as JSON: {e}");
+        log::error!(target: logging::AI, "Response was:\n{response}");
         format!("Failed to parse AI response: {e}")
     })
 }
@@ -73,7 +76,7 @@ fn load_after_content_if_small(
 
     let (content, line_count) = match &diff.after {
         Some(f) => match &f.content {
-            FileContent::Text { lines } => {
+            FileContent::Text { lines, .. } => {
                 let count = lines.len();
                 if count <= LARGE_FILE_THRESHOLD {
                     (Some(lines.join("\n")), count)
@@ -82,7 +85,7 @@ fn load_after_content_if_small(
                     (None, count)
                 }
             }
-            FileContent::Binary => (None, 0),
+            FileContent::Binary | FileContent::TooLarge { .. } => (None, 0),
         },
         None => (None, 0), // Deleted file
     };
@@ -106,7 +109,7 @@ pub async fn analyze_diff(
     provider: Option<&str>,
 ) -> Result<ChangesetAnalysis, String> {
     // Find AI agent first (fail fast)
-    let agent = find_ai_tool(provider).ok_or_else(|| match provider {
+    let agent = find_ai_tool(repo_path, provider).ok_or_else(|| match provider {
         Some(id) => format!(
             "Provider '{id}' not found. Run discover_acp_providers to see available providers."
         ),
@@ -173,13 +176,15 @@ pub async fn analyze_diff(
     }
 
     // Build prompt with automatic tier selection (provider-aware for size limits)
-    let (prompt, strategy) = build_prompt_with_strategy_for_provider(&inputs, provider);
+    let token_budget = AgentsConfig::load().prompt_token_budget(agent.name());
+    let (prompt, strategy) =
+        build_prompt_with_strategy_for_provider(&inputs, provider, token_budget);
 
-    log::info!("=== DIFF ANALYSIS (ACP) ===");
-    log::info!("Files: {}", inputs.len());
-    log::info!("Strategy: {strategy:?}");
-    log::info!("Using: {}", agent.name());
-    log::debug!("Prompt:\n{prompt}");
+    log::info!(target: logging::AI, "=== DIFF ANALYSIS (ACP) ===");
+    log::info!(target: logging::AI, "Files: {}", inputs.len());
+    log::info!(target: logging::AI, "Strategy: {strategy:?}");
+    log::info!(target: logging::AI, "Using: {}", agent.name());
+    log::debug!(target: logging::AI, "Prompt:\n{prompt}");
 
     // Codex has strict input size limits (10MB). Fail early if exceeded.
     if provider == Some("codex") && prompt.len() > CODEX_MAX_BYTES {
@@ -198,7 +203,7 @@ pub async fn analyze_diff(
         return Err(error_msg);
     }
 
-    log::debug!("Raw response:\n{response}");
+    log::debug!(target: logging::AI, "Raw response:\n{response}");
 
     parse_response(&response)
 }
@@ -0,0 +1,154 @@
+//! Context-window budgeting for prompts.
+//!
+//! Token counts are estimated (about four characters per token), which is
+//! close enough for the models we talk to and needs no tokenizer. Prompts are
+//! assembled from sections with a priority; when they don't fit, the least
+//! important sections are trimmed first, largest first within a priority.
+
+/// Prompt budget used when a provider doesn't set `max_prompt_tokens`.
+pub const DEFAULT_PROMPT_TOKEN_BUDGET: usize = 100_000;
+
+/// Rough characters-per-token ratio for English text and code.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Sections trimmed below this many tokens are dropped instead.
+const MIN_SECTION_TOKENS: usize = 64;
+
+/// Appended to a section that was cut short.
+pub const TRUNCATED_MARKER: &str = "\n... (truncated to fit context budget)\n";
+
+/// Replaces a section that was dropped entirely.
+pub const OMITTED_MARKER: &str = "(omitted to fit context budget)\n";
+
+/// Estimate how many tokens a piece of text uses.
+pub fn estimate_tokens(text: &str) -> usize {
+    tokens_for_chars(text.chars().count())
+}
+
+/// Estimate how many tokens `chars` characters use.
+pub fn tokens_for_chars(chars: usize) -> usize {
+    chars.div_ceil(CHARS_PER_TOKEN)
+}
+
+/// A piece of a prompt that may be trimmed to fit the budget.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptSection {
+    pub label: String,
+    pub text: String,
+    /// Higher priorities are kept longer
+    pub priority: u8,
+}
+
+impl PromptSection {
+    pub fn new(label: impl Into<String>, text: impl Into<String>, priority: u8) -> Self {
+        Self {
+            label: label.into(),
+            text: text.into(),
+            priority,
+        }
+    }
+}
+
+/// Trim sections until their combined estimate fits in `budget` tokens.
+///
+/// Sections are visited from lowest priority (and, within a priority,
+/// largest) upwards; each is cut just enough to cover the overflow, or
+/// replaced by `OMITTED_MARKER` if too little of it would remain. Order is
+/// preserved. Returns true if anything was trimmed.
+pub fn fit_to_budget(sections: &mut [PromptSection], budget: usize) -> bool {
+    let total = |sections: &[PromptSection]| -> usize {
+        sections.iter().map(|s| estimate_tokens(&s.text)).sum()
+    };
+    if total(sections) <= budget {
+        return false;
+    }
+
+    let mut order: Vec<usize> = (0..sections.len()).collect();
+    order.sort_by_key(|&i| {
+        (
+            sections[i].priority,
+            std::cmp::Reverse(estimate_tokens(&sections[i].text)),
+        )
+    });
+
+    for i in order {
+        let used = total(sections);
+        if used <= budget {
+            break;
+        }
+        let overflow = used - budget;
+        let own = estimate_tokens(&sections[i].text);
+        let keep = own.saturating_sub(overflow + estimate_tokens(TRUNCATED_MARKER));
+        sections[i].text = if keep < MIN_SECTION_TOKENS {
+            OMITTED_MARKER.to_string()
+        } else {
+            let mut text = truncate_to_tokens(&sections[i].text, keep).to_string();
+            text.push_str(TRUNCATED_MARKER);
+            text
+        };
+    }
+    true
+}
+
+/// The longest prefix of `text` within `tokens`, cut at a line break when
+/// there is one.
+pub fn truncate_to_tokens(text: &str, tokens: usize) -> &str {
+    let max_chars = tokens * CHARS_PER_TOKEN;
+    let end = text
+        .char_indices()
+        .nth(max_chars)
+        .map_or(text.len(), |(i, _)| i);
+    let prefix = &text[..end];
+    match prefix.rfind('\n') {
+        Some(newline) if end < text.len() && newline > 0 => &prefix[..newline],
+        _ => prefix,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trims_lowest_priority_first() {
+        let lockfile = "dependency = \"1.0\"\n".repeat(2000);
+        let mut sections = vec![
+            PromptSection::new("justfile", "test:\n    cargo test\n", 3),
+            PromptSection::new("Cargo.lock", lockfile.clone(), 0),
+            PromptSection::new("Cargo.toml", "[package]\nname = \"x\"\n", 2),
+        ];
+
+        assert!(fit_to_budget(&mut sections, 2000));
+        let used: usize = sections.iter().map(|s| estimate_tokens(&s.text)).sum();
+        assert!(used <= 2000, "used {used} tokens");
+
+        assert_eq!(sections[0].text, "test:\n    cargo test\n");
+        assert_eq!(sections[2].text, "[package]\nname = \"x\"\n");
+        assert!(sections[1].text.ends_with(TRUNCATED_MARKER));
+        assert!(lockfile.starts_with(sections[1].text.trim_end_matches(TRUNCATED_MARKER)));
+    }
+
+    #[test]
+    fn test_drops_sections_that_would_be_tiny() {
+        let mut sections = vec![
+            PromptSection::new("a", "x".repeat(400), 1),
+            PromptSection::new("b", "y".repeat(400), 0),
+        ];
+        assert!(fit_to_budget(&mut sections, 110));
+        assert_eq!(sections[1].text, OMITTED_MARKER);
+        assert_eq!(sections[0].text, "x".repeat(400));
+    }
+
+    #[test]
+    fn test_under_budget_is_untouched() {
+        let mut sections = vec![PromptSection::new("a", "short", 0)];
+        assert!(!fit_to_budget(&mut sections, 100));
+        assert_eq!(sections[0].text, "short");
+    }
+
+    #[test]
+    fn test_truncate_respects_char_boundaries() {
+        let text = "é".repeat(100);
+        assert_eq!(truncate_to_tokens(&text, 5).chars().count(), 20);
+    }
+}
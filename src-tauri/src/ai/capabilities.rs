@@ -0,0 +1,189 @@
+//! What an ACP agent advertises about itself.
+//!
+//! Collected from the `initialize` response (agent info, prompt and session
+//! capabilities), the `session/new` or `session/load` response (modes), and
+//! `available_commands_update` notifications (slash commands). Everything is
+//! optional in the protocol, so agents that report nothing yield the defaults.
+//!
+//! Parsing works on the JSON wire form so it doesn't depend on the exact
+//! shape of the SDK's Rust types.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A slash command the agent accepts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentCommand {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// A mode the agent can operate in (e.g. "ask", "code").
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentMode {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Capabilities reported by an agent for a session.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentCapabilities {
+    pub agent_name: Option<String>,
+    pub agent_version: Option<String>,
+    /// Agent can resume sessions with `session/load`
+    pub load_session: bool,
+    pub prompt_images: bool,
+    pub prompt_audio: bool,
+    pub prompt_embedded_context: bool,
+    pub commands: Vec<AgentCommand>,
+    pub modes: Vec<AgentMode>,
+    pub current_mode: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct InitWire {
+    agent_info: Option<AgentInfoWire>,
+    agent_capabilities: AgentCapabilitiesWire,
+}
+
+#[derive(Deserialize)]
+struct AgentInfoWire {
+    name: String,
+    #[serde(default)]
+    version: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct AgentCapabilitiesWire {
+    load_session: bool,
+    prompt_capabilities: PromptCapabilitiesWire,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct PromptCapabilitiesWire {
+    image: bool,
+    audio: bool,
+    embedded_context: bool,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct SessionWire {
+    modes: Option<ModesWire>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModesWire {
+    current_mode_id: Option<String>,
+    #[serde(default)]
+    available_modes: Vec<AgentMode>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct CommandsWire {
+    available_commands: Vec<AgentCommand>,
+}
+
+impl AgentCapabilities {
+    /// Read agent info and capabilities from an `initialize` response.
+    pub fn from_init_response(response: &Value) -> Self {
+        let wire: InitWire = serde_json::from_value(response.clone()).unwrap_or_default();
+        let prompt = wire.agent_capabilities.prompt_capabilities;
+        Self {
+            agent_name: wire.agent_info.as_ref().map(|info| info.name.clone()),
+            agent_version: wire.agent_info.and_then(|info| info.version),
+            load_session: wire.agent_capabilities.load_session,
+            prompt_images: prompt.image,
+            prompt_audio: prompt.audio,
+            prompt_embedded_context: prompt.embedded_context,
+            ..Self::default()
+        }
+    }
+
+    /// Record the modes from a `session/new` or `session/load` response.
+    pub fn set_session_modes(&mut self, response: &Value) {
+        let wire: SessionWire = serde_json::from_value(response.clone()).unwrap_or_default();
+        if let Some(modes) = wire.modes {
+            self.modes = modes.available_modes;
+            self.current_mode = modes.current_mode_id;
+        }
+    }
+
+    /// Record the commands from an `available_commands_update` notification.
+    pub fn set_commands(&mut self, update: &Value) {
+        let wire: CommandsWire = serde_json::from_value(update.clone()).unwrap_or_default();
+        self.commands = wire.available_commands;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parses_init_session_and_commands() {
+        let mut caps = AgentCapabilities::from_init_response(&json!({
+            "protocolVersion": 1,
+            "agentInfo": { "name": "goose", "version": "1.9.0" },
+            "agentCapabilities": {
+                "loadSession": true,
+                "promptCapabilities": { "image": true, "embeddedContext": true }
+            }
+        }));
+        caps.set_session_modes(&json!({
+            "sessionId": "abc",
+            "modes": {
+                "currentModeId": "code",
+                "availableModes": [
+                    { "id": "ask", "name": "Ask" },
+                    { "id": "code", "name": "Code", "description": "Edit files" }
+                ]
+            }
+        }));
+        caps.set_commands(&json!({
+            "sessionUpdate": "available_commands_update",
+            "availableCommands": [
+                { "name": "compact", "description": "Summarize the conversation" }
+            ]
+        }));
+
+        assert_eq!(caps.agent_name.as_deref(), Some("goose"));
+        assert_eq!(caps.agent_version.as_deref(), Some("1.9.0"));
+        assert!(caps.load_session);
+        assert!(caps.prompt_images && caps.prompt_embedded_context);
+        assert!(!caps.prompt_audio);
+        assert_eq!(caps.current_mode.as_deref(), Some("code"));
+        assert_eq!(caps.modes.len(), 2);
+        assert_eq!(caps.modes[1].description.as_deref(), Some("Edit files"));
+        assert_eq!(
+            caps.commands,
+            vec![AgentCommand {
+                name: "compact".to_string(),
+                description: "Summarize the conversation".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_agent_reporting_nothing_gets_defaults() {
+        let mut caps = AgentCapabilities::from_init_response(&json!({ "protocolVersion": 1 }));
+        caps.set_session_modes(&json!({ "sessionId": "abc" }));
+        assert_eq!(caps, AgentCapabilities::default());
+
+        // Unexpected shapes are ignored rather than failing the session
+        let caps = AgentCapabilities::from_init_response(&json!("not an object"));
+        assert_eq!(caps, AgentCapabilities::default());
+    }
+}
@@ -9,24 +9,39 @@
 //! - "session-complete": Custom event with finalized transcript
 
 use std::collections::HashMap;
+use std::io::Read;
 use std::path::{Path, PathBuf};
-use std::process::Stdio;
+use std::process::{ExitStatus, Stdio};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use agent_client_protocol::{
-    Agent, ClientSideConnection, ContentBlock as AcpContentBlock, Implementation,
-    InitializeRequest, LoadSessionRequest, NewSessionRequest, PermissionOptionId, PromptRequest,
+    Agent, BlobResourceContents, ClientSideConnection, ContentBlock as AcpContentBlock,
+    EmbeddedResource, EmbeddedResourceResource, ImageContent, Implementation, InitializeRequest,
+    LoadSessionRequest, NewSessionRequest, PermissionOptionId, PromptCapabilities, PromptRequest,
     ProtocolVersion, RequestPermissionOutcome, RequestPermissionRequest, RequestPermissionResponse,
-    Result as AcpResult, SelectedPermissionOutcome, SessionId, SessionNotification, SessionUpdate,
-    TextContent, ToolCall,
+    ResourceLink, Result as AcpResult, SelectedPermissionOutcome, SessionId, SessionNotification,
+    SessionUpdate, TextContent, TextResourceContents, ToolCall,
+    ToolCallStatus as AcpToolCallStatus,
 };
 use async_trait::async_trait;
+use base64::Engine;
 
 use tauri::Emitter;
 use tokio::process::Command;
 use tokio::sync::Mutex;
 use tokio_util::compat::{TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
 
+use super::agents;
+use super::capabilities::AgentCapabilities;
+use super::config::AgentsConfig;
+use super::permissions::{
+    self, PermissionChoice, PermissionDecision, PermissionPolicy, PermissionRequestEvent,
+};
+use crate::logging;
+use crate::repo_config::RepoConfig;
+use crate::store::ToolCallStatus;
+
 /// System context prepended to the first message in new sessions.
 /// This guides the agent's behavior for Staged's code review use case.
 const STAGED_SYSTEM_CONTEXT: &str = r#"[System Context for Staged - Code Review Assistant]
@@ -44,6 +59,18 @@ The user is viewing a diff. Context tags like [Changeset: ...], [Viewing: ...],
 
 "#;
 
+/// The system context for a new session in `working_dir`, with the repo's
+/// `.staged.toml` instructions added before the separator.
+fn system_context_for(working_dir: &Path) -> String {
+    match RepoConfig::load(working_dir).agent.instructions() {
+        Some(instructions) => {
+            let base = STAGED_SYSTEM_CONTEXT.trim_end_matches("---\n\n");
+            format!("{base}Repository instructions:\n{instructions}\n\n---\n\n")
+        }
+        None => STAGED_SYSTEM_CONTEXT.to_string(),
+    }
+}
+
 /// Supported ACP-compatible AI agents
 #[derive(Debug, Clone)]
 pub enum AcpAgent {
@@ -69,7 +96,7 @@ impl AcpAgent {
         }
     }
 
-    /// Get the arguments to start ACP mode
+    /// Get the default arguments to start ACP mode
     pub fn acp_args(&self) -> Vec<&str> {
         match self {
             // Include developer extension for file/shell access, and extensionmanager
@@ -79,6 +106,16 @@ impl AcpAgent {
             AcpAgent::Codex(_) => vec![],  // codex-acp runs in ACP mode by default
         }
     }
+
+    /// Get the arguments to start ACP mode, honoring any configured override
+    pub fn launch_args(&self, config: &AgentsConfig) -> Vec<String> {
+        config.args_for(self.name(), &self.acp_args())
+    }
+
+    /// Get extra environment variables configured for this agent
+    pub fn launch_env(&self, config: &AgentsConfig) -> HashMap<String, String> {
+        config.env_for(self.name())
+    }
 }
 
 /// Common paths where CLIs might be installed (for GUI apps that don't inherit shell PATH)
@@ -89,8 +126,63 @@ const COMMON_PATHS: &[&str] = &[
     "/home/linuxbrew/.linuxbrew/bin",
 ];
 
-/// Find goose CLI using login shell (to get user's PATH)
+/// Per-command login-shell lookups, cached for the process lifetime.
+///
+/// Login shells source the user's rc files, so spawning one for every
+/// `find_agent` call (discovery, each prompt, ...) is slow. Misses are cached
+/// too; call `refresh_agent_paths` after installing a new agent.
+///
+/// Each command gets its own cell, so a slow lookup only holds up callers
+/// waiting on the same command.
+struct LoginShellCache {
+    entries: std::sync::Mutex<HashMap<String, Arc<std::sync::OnceLock<Option<PathBuf>>>>>,
+}
+
+impl LoginShellCache {
+    fn new() -> Self {
+        Self {
+            entries: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Return the cached resolution for `cmd`, running `resolver` on a miss.
+    fn resolve<F>(&self, cmd: &str, resolver: F) -> Option<PathBuf>
+    where
+        F: FnOnce(&str) -> Option<PathBuf>,
+    {
+        let cell = Arc::clone(
+            self.entries
+                .lock()
+                .unwrap()
+                .entry(cmd.to_string())
+                .or_default(),
+        );
+        cell.get_or_init(|| resolver(cmd)).clone()
+    }
+
+    fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+static LOGIN_SHELL_CACHE: std::sync::OnceLock<LoginShellCache> = std::sync::OnceLock::new();
+
+fn login_shell_cache() -> &'static LoginShellCache {
+    LOGIN_SHELL_CACHE.get_or_init(LoginShellCache::new)
+}
+
+/// Forget cached login-shell lookups so the next discovery re-resolves agents.
+pub fn refresh_agent_paths() {
+    login_shell_cache().clear();
+}
+
+/// Find a CLI using login shell (to get user's PATH), cached per command
 fn find_via_login_shell(cmd: &str) -> Option<PathBuf> {
+    login_shell_cache().resolve(cmd, resolve_via_login_shell)
+}
+
+/// Run `which` in a login shell, trying zsh then bash
+fn resolve_via_login_shell(cmd: &str) -> Option<PathBuf> {
     let which_cmd = format!("which {cmd}");
 
     // Try zsh first (default on macOS)
@@ -136,8 +228,8 @@ fn verify_command(path: &Path) -> bool {
     }
 
     // Try --version first (works for most tools)
-    if let Ok(output) = std::process::Command::new(path).arg("--version").output() {
-        if output.status.success() {
+    if let Some((status, _)) = run_probe(path, "--version", PROBE_TIMEOUT) {
+        if status.success() {
             return true;
         }
     }
@@ -145,8 +237,8 @@ fn verify_command(path: &Path) -> bool {
     // If --version fails, try --help.
     // codex-acp doesn't implement --version but does respond to --help,
     // so this avoids false negatives when checking availability.
-    if let Ok(output) = std::process::Command::new(path).arg("--help").output() {
-        if output.status.success() {
+    if let Some((status, _)) = run_probe(path, "--help", PROBE_TIMEOUT) {
+        if status.success() {
             return true;
         }
     }
@@ -155,39 +247,116 @@ fn verify_command(path: &Path) -> bool {
     false
 }
 
-/// Information about an available ACP provider
-#[derive(Debug, Clone, serde::Serialize)]
-pub struct AcpProviderInfo {
-    pub id: String,
-    pub label: String,
+/// Get the version reported by `<path> --version`, if any.
+/// Takes the first version-looking token (e.g. "1.2.0" from "goose 1.2.0"),
+/// falling back to the whole first line.
+fn probe_version(path: &Path) -> Option<String> {
+    let (status, stdout) = run_probe(path, "--version", PROBE_TIMEOUT)?;
+    if !status.success() {
+        return None;
+    }
+    parse_version(&String::from_utf8_lossy(&stdout))
 }
 
-/// Discover all available ACP providers on the system
-pub fn discover_acp_providers() -> Vec<AcpProviderInfo> {
-    let mut providers = Vec::new();
+/// How long an agent gets to answer `--version` or `--help`.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
 
-    if find_agent("goose", AcpAgent::Goose).is_some() {
-        providers.push(AcpProviderInfo {
-            id: "goose".to_string(),
-            label: "Goose".to_string(),
+/// Run `<path> <arg>` and collect its stdout. The process is killed if it
+/// hasn't exited within `timeout`, and None is returned, as when it can't
+/// be started.
+fn run_probe(path: &Path, arg: &str, timeout: Duration) -> Option<(ExitStatus, Vec<u8>)> {
+    let mut child = std::process::Command::new(path)
+        .arg(arg)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    // Read on a thread so a chatty agent can't fill the pipe and stall
+    let (tx, rx) = std::sync::mpsc::channel();
+    if let Some(mut stdout) = child.stdout.take() {
+        std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = stdout.read_to_end(&mut buf);
+            let _ = tx.send(buf);
         });
     }
 
-    if find_agent("claude-code-acp", AcpAgent::Claude).is_some() {
-        providers.push(AcpProviderInfo {
-            id: "claude".to_string(),
-            label: "Claude Code".to_string(),
-        });
-    }
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(10)),
+            _ => {
+                log::warn!(
+                    target: logging::AI,
+                    "{} {arg} did not finish within {timeout:?}; killing it",
+                    path.display()
+                );
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    };
+    // Anything it spawned may still hold the pipe open, so don't wait past
+    // the deadline for EOF
+    let stdout = rx
+        .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        .unwrap_or_default();
+    Some((status, stdout))
+}
 
-    if find_agent("codex-acp", AcpAgent::Codex).is_some() {
-        providers.push(AcpProviderInfo {
-            id: "codex".to_string(),
-            label: "Codex".to_string(),
-        });
+fn parse_version(output: &str) -> Option<String> {
+    let line = output.lines().map(str::trim).find(|l| !l.is_empty())?;
+    let token = line.split_whitespace().find_map(|word| {
+        let word = word.strip_prefix('v').unwrap_or(word);
+        word.starts_with(|c: char| c.is_ascii_digit())
+            .then(|| word.trim_end_matches(',').to_string())
+    });
+    Some(token.unwrap_or_else(|| line.to_string()))
+}
+
+/// Information about a known ACP provider and whether it was found
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AcpProviderInfo {
+    pub id: String,
+    pub label: String,
+    /// Whether the agent was found and responds to `--version`/`--help`
+    pub available: bool,
+    /// Resolved path to the agent binary, if found
+    pub path: Option<String>,
+    /// Version reported by the agent, if it has one
+    pub version: Option<String>,
+}
+
+impl AcpProviderInfo {
+    fn new(id: &str, label: &str, agent: Option<&AcpAgent>) -> Self {
+        Self {
+            id: id.to_string(),
+            label: label.to_string(),
+            available: agent.is_some(),
+            path: agent.map(|a| a.path().display().to_string()),
+            // Only probe agents that were actually found
+            version: agent.and_then(|a| probe_version(a.path())),
+        }
     }
+}
+
+/// Provider ids and display labels, in order of preference
+const KNOWN_PROVIDERS: &[(&str, &str)] = &[
+    ("goose", "Goose"),
+    ("claude", "Claude Code"),
+    ("codex", "Codex"),
+];
 
-    providers
+/// Discover all known ACP providers, including ones that weren't found
+pub fn discover_acp_providers() -> Vec<AcpProviderInfo> {
+    KNOWN_PROVIDERS
+        .iter()
+        .map(|&(id, label)| AcpProviderInfo::new(id, label, find_acp_agent_by_id(id).as_ref()))
+        .collect()
 }
 
 /// Find a specific ACP agent by provider ID
@@ -212,6 +381,14 @@ pub fn find_acp_agent() -> Option<AcpAgent> {
     find_agent("claude-code-acp", AcpAgent::Claude)
 }
 
+/// Find the agent to use in a repo: the provider named in its `.staged.toml`
+/// if that's installed, otherwise the global default.
+pub fn find_acp_agent_for_repo(repo: &Path) -> Option<AcpAgent> {
+    RepoConfig::load(repo)
+        .agent
+        .resolve_agent(find_acp_agent_by_id, find_acp_agent)
+}
+
 /// Find a specific agent by command name
 fn find_agent<F>(cmd: &str, constructor: F) -> Option<AcpAgent>
 where
@@ -252,17 +429,30 @@ where
 struct ToolCallState {
     id: String,
     title: String,
-    status: String,
+    status: ToolCallStatus,
     locations: Vec<String>,
     result_preview: Option<String>,
 }
 
+/// Map the SDK's tool-call status onto our stored status
+fn tool_call_status(status: &AcpToolCallStatus) -> ToolCallStatus {
+    match status {
+        AcpToolCallStatus::Pending => ToolCallStatus::Pending,
+        AcpToolCallStatus::InProgress => ToolCallStatus::InProgress,
+        AcpToolCallStatus::Completed => ToolCallStatus::Completed,
+        AcpToolCallStatus::Failed => ToolCallStatus::Failed,
+        // The SDK enum is non-exhaustive; treat anything new as not yet finished
+        #[allow(unreachable_patterns)]
+        _ => ToolCallStatus::Pending,
+    }
+}
+
 impl From<&ToolCall> for ToolCallState {
     fn from(tc: &ToolCall) -> Self {
         Self {
             id: tc.tool_call_id.0.to_string(),
             title: tc.title.clone(),
-            status: format!("{:?}", tc.status).to_lowercase(),
+            status: tool_call_status(&tc.status),
             locations: tc
                 .locations
                 .iter()
@@ -312,6 +502,13 @@ struct StreamingAcpClient {
     /// - Set this callback when streaming to a session that might be viewed live (e.g., SessionManager)
     /// - Leave as None for fire-and-forget prompts or internal operations (e.g., legacy paths)
     buffer_update_callback: Option<Arc<dyn Fn(Vec<crate::store::ContentSegment>) + Send + Sync>>,
+    /// Which tool permission requests are approved without asking
+    permissions: PermissionPolicy,
+    /// What the agent has advertised (init, session modes, commands)
+    capabilities: Mutex<AgentCapabilities>,
+    /// Called with the full capabilities each time they change, so a live
+    /// session has them from connect time rather than only after a prompt
+    capabilities_callback: Option<Arc<dyn Fn(AgentCapabilities) + Send + Sync>>,
 }
 
 impl StreamingAcpClient {
@@ -323,6 +520,9 @@ impl StreamingAcpClient {
             tool_call_indices: Mutex::new(HashMap::new()),
             suppress_emit: Mutex::new(false),
             buffer_update_callback: None,
+            permissions: PermissionPolicy::default(),
+            capabilities: Mutex::new(AgentCapabilities::default()),
+            capabilities_callback: None,
         }
     }
 
@@ -338,6 +538,9 @@ impl StreamingAcpClient {
             tool_call_indices: Mutex::new(HashMap::new()),
             suppress_emit: Mutex::new(false),
             buffer_update_callback: Some(callback),
+            permissions: PermissionPolicy::default(),
+            capabilities: Mutex::new(AgentCapabilities::default()),
+            capabilities_callback: None,
         }
     }
 
@@ -357,7 +560,7 @@ impl StreamingAcpClient {
             let mut patched = notification.clone();
             patched.session_id = SessionId::new(&*self.internal_session_id);
             if let Err(e) = app_handle.emit("session-update", &patched) {
-                log::warn!("Failed to emit session-update event: {e}");
+                log::warn!(target: logging::AI, "Failed to emit session-update event: {e}");
             }
         }
     }
@@ -374,7 +577,7 @@ impl StreamingAcpClient {
                 ContentSegment::ToolCall(tc) => crate::store::ContentSegment::ToolCall {
                     id: tc.id.clone(),
                     title: tc.title.clone(),
-                    status: tc.status.clone(),
+                    status: tc.status,
                     locations: tc.locations.clone(),
                 },
             })
@@ -394,12 +597,66 @@ impl StreamingAcpClient {
             .join("")
     }
 
+    /// What the agent has advertised so far
+    async fn get_capabilities(&self) -> AgentCapabilities {
+        self.capabilities.lock().await.clone()
+    }
+
+    /// Apply `change` to the capabilities and report the result.
+    async fn update_capabilities(&self, change: impl FnOnce(&mut AgentCapabilities)) {
+        let mut capabilities = self.capabilities.lock().await;
+        change(&mut capabilities);
+        if let Some(ref callback) = self.capabilities_callback {
+            callback(capabilities.clone());
+        }
+    }
+
     /// Clear accumulated state (used after loading session history)
     async fn clear(&self) {
         self.segments.lock().await.clear();
         self.tool_call_indices.lock().await.clear();
     }
 
+    /// Ask the frontend to choose a permission option. Without a frontend, or
+    /// if the user doesn't answer in time, the request is rejected.
+    async fn prompt_permission(
+        &self,
+        title: &str,
+        choices: Vec<PermissionChoice>,
+    ) -> Option<String> {
+        let reject = choices
+            .iter()
+            .find(|c| c.kind.starts_with("reject"))
+            .map(|c| c.option_id.clone());
+        let Some(ref app_handle) = self.app_handle else {
+            log::info!(target: logging::AI, "Rejecting tool without a frontend to ask: {title}");
+            return reject;
+        };
+
+        let pending = permissions::pending_permissions();
+        let (request_id, answer) = pending.register();
+        let event = PermissionRequestEvent {
+            request_id: request_id.clone(),
+            session_id: self.internal_session_id.clone(),
+            tool_title: title.to_string(),
+            options: choices,
+        };
+        if let Err(e) = app_handle.emit("permission-request", &event) {
+            log::warn!(target: logging::AI, "Failed to emit permission-request event: {e}");
+            pending.forget(&request_id);
+            return reject;
+        }
+
+        match tokio::time::timeout(permissions::PROMPT_TIMEOUT, answer).await {
+            Ok(Ok(choice)) => choice.or(reject),
+            _ => {
+                log::info!(target: logging::AI, "Permission prompt timed out: {title}");
+                pending.forget(&request_id);
+                reject
+            }
+        }
+    }
+
     /// Notify buffer callback with current segments
     async fn notify_buffer_update(&self) {
         if let Some(ref callback) = self.buffer_update_callback {
@@ -419,18 +676,55 @@ impl agent_client_protocol::Client for StreamingAcpClient {
         &self,
         args: RequestPermissionRequest,
     ) -> AcpResult<RequestPermissionResponse> {
-        // Auto-approve permissions (Staged doesn't use tools that need approval)
-        log::debug!("Permission requested: {args:?}");
-
-        let option_id = args
+        log::debug!(target: logging::AI, "Permission requested: {args:?}");
+
+        // Identify the tool by its kind and title (e.g. "read", "read_file: src/lib.rs")
+        let tool_call = serde_json::to_value(&args.tool_call).unwrap_or_default();
+        let title = tool_call
+            .get("title")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+        let kind = tool_call.get("kind").and_then(|v| v.as_str()).unwrap_or("");
+
+        let choices: Vec<PermissionChoice> = args
             .options
-            .first()
-            .map(|opt| opt.option_id.clone())
-            .unwrap_or_else(|| PermissionOptionId::new("approve"));
+            .iter()
+            .map(|opt| PermissionChoice {
+                option_id: opt.option_id.0.to_string(),
+                name: opt.name.clone(),
+                kind: serde_json::to_value(&opt.kind)
+                    .ok()
+                    .and_then(|v| v.as_str().map(str::to_string))
+                    .unwrap_or_default(),
+            })
+            .collect();
+
+        let selected = match self.permissions.decide(kind, &title) {
+            PermissionDecision::Approve => Some(
+                choices
+                    .iter()
+                    .find(|c| c.kind.starts_with("allow"))
+                    .or(choices.first())
+                    .map(|c| c.option_id.clone())
+                    .unwrap_or_else(|| "approve".to_string()),
+            ),
+            PermissionDecision::Prompt => self.prompt_permission(&title, choices).await,
+        };
 
-        Ok(RequestPermissionResponse::new(
-            RequestPermissionOutcome::Selected(SelectedPermissionOutcome::new(option_id)),
-        ))
+        let outcome = match selected {
+            Some(id) => {
+                let option_id = args
+                    .options
+                    .iter()
+                    .find(|opt| *opt.option_id.0 == *id)
+                    .map(|opt| opt.option_id.clone())
+                    .unwrap_or_else(|| PermissionOptionId::new(id));
+                RequestPermissionOutcome::Selected(SelectedPermissionOutcome::new(option_id))
+            }
+            None => RequestPermissionOutcome::Cancelled,
+        };
+        Ok(RequestPermissionResponse::new(outcome))
     }
 
     async fn session_notification(&self, notification: SessionNotification) -> AcpResult<()> {
@@ -467,7 +761,7 @@ impl agent_client_protocol::Client for StreamingAcpClient {
                     let mut segments = self.segments.lock().await;
                     if let Some(ContentSegment::ToolCall(tc)) = segments.get_mut(idx) {
                         if let Some(ref status) = update.fields.status {
-                            tc.status = format!("{status:?}").to_lowercase();
+                            tc.status = tool_call_status(status);
                         }
                         if let Some(ref title) = update.fields.title {
                             tc.title = title.clone();
@@ -479,8 +773,16 @@ impl agent_client_protocol::Client for StreamingAcpClient {
                     }
                 }
             }
+            SessionUpdate::AvailableCommandsUpdate(update) => {
+                let update = serde_json::to_value(update).unwrap_or_default();
+                self.update_capabilities(|c| c.set_commands(&update)).await;
+            }
             _ => {
-                log::debug!("Ignoring session update: {:?}", notification.update);
+                log::debug!(
+                    target: logging::AI,
+                    "Ignoring session update: {:?}",
+                    notification.update
+                );
             }
         }
 
@@ -493,6 +795,14 @@ impl agent_client_protocol::Client for StreamingAcpClient {
     }
 }
 
+/// Record the modes from a `session/new` or `session/load` response.
+async fn record_session_modes(client: &StreamingAcpClient, response: &impl serde::Serialize) {
+    let response = serde_json::to_value(response).unwrap_or_default();
+    client
+        .update_capabilities(|c| c.set_session_modes(&response))
+        .await;
+}
+
 /// Extract a preview string from tool call content
 fn extract_content_preview(content: &[agent_client_protocol::ToolCallContent]) -> Option<String> {
     for item in content {
@@ -529,6 +839,145 @@ fn extract_content_preview(content: &[agent_client_protocol::ToolCallContent]) -
     None
 }
 
+// =============================================================================
+// Protocol Version
+// =============================================================================
+
+/// Oldest ACP protocol version Staged can talk to
+const MIN_PROTOCOL_VERSION: ProtocolVersion = ProtocolVersion::V1;
+
+/// Human-readable protocol version (the bare number, as sent on the wire)
+fn protocol_version_label(version: &ProtocolVersion) -> String {
+    serde_json::to_string(version).unwrap_or_else(|_| format!("{version:?}"))
+}
+
+/// Check the protocol version the agent negotiated in `initialize`.
+///
+/// Agents reply with the version they actually speak, which may be older than
+/// what we asked for. Rather than failing later with an opaque error, report
+/// both versions and point the user at upgrading the agent.
+fn check_protocol_version(agent_name: &str, negotiated: &ProtocolVersion) -> Result<(), String> {
+    if *negotiated >= MIN_PROTOCOL_VERSION && *negotiated <= ProtocolVersion::LATEST {
+        return Ok(());
+    }
+
+    Err(format!(
+        "{agent_name} speaks ACP protocol version {}, but Staged supports versions {} through {}. \
+         Try upgrading {agent_name} to a newer release.",
+        protocol_version_label(negotiated),
+        protocol_version_label(&MIN_PROTOCOL_VERSION),
+        protocol_version_label(&ProtocolVersion::LATEST),
+    ))
+}
+
+// =============================================================================
+// Prompt Attachments
+// =============================================================================
+
+/// Largest file we'll attach to a prompt. Bigger files are replaced by a note.
+const MAX_ATTACHMENT_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Image MIME type for a path, based on its extension
+fn image_mime_type(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    match ext.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+fn attachment_note(path: &Path, reason: &str) -> AcpContentBlock {
+    AcpContentBlock::Text(TextContent::new(format!(
+        "[Attachment {}: {reason}]",
+        path.display()
+    )))
+}
+
+/// Build the content block for a single attachment.
+///
+/// Images become base64 image blocks, other files become embedded resources
+/// (text or base64 blob). When the agent doesn't advertise support for a block
+/// type, it gets a resource link or a text note instead. Files over
+/// `MAX_ATTACHMENT_BYTES` or that can't be read are replaced by a note.
+fn attachment_block(path: &Path, capabilities: &PromptCapabilities) -> AcpContentBlock {
+    let size = match std::fs::metadata(path) {
+        Ok(meta) => meta.len(),
+        Err(e) => return attachment_note(path, &format!("could not be read ({e})")),
+    };
+    if size > MAX_ATTACHMENT_BYTES {
+        return attachment_note(
+            path,
+            &format!("skipped, {size} bytes exceeds the {MAX_ATTACHMENT_BYTES} byte limit"),
+        );
+    }
+
+    let uri = file_uri(path);
+    let image_mime = image_mime_type(path);
+
+    if image_mime.is_some() && !capabilities.image {
+        return attachment_note(path, "image omitted, this agent does not accept images");
+    }
+    if image_mime.is_none() && !capabilities.embedded_context {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string());
+        return AcpContentBlock::ResourceLink(ResourceLink::new(name, uri));
+    }
+
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => return attachment_note(path, &format!("could not be read ({e})")),
+    };
+    let encoded = || base64::engine::general_purpose::STANDARD.encode(&bytes);
+
+    if let Some(mime) = image_mime {
+        return AcpContentBlock::Image(ImageContent::new(encoded(), mime));
+    }
+
+    let resource = match std::str::from_utf8(&bytes) {
+        Ok(text) => {
+            EmbeddedResourceResource::TextResourceContents(TextResourceContents::new(text, uri))
+        }
+        Err(_) => EmbeddedResourceResource::BlobResourceContents(BlobResourceContents::new(
+            encoded(),
+            uri,
+        )),
+    };
+    AcpContentBlock::Resource(EmbeddedResource::new(resource))
+}
+
+/// The `file://` URI for an attachment, percent-encoded and with Windows
+/// drive paths in URI form. A relative path is resolved first.
+fn file_uri(path: &Path) -> String {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+    };
+    url::Url::from_file_path(&absolute)
+        .map(String::from)
+        .unwrap_or_else(|()| format!("file://{}", path.display()))
+}
+
+/// Build the prompt content: the text prompt followed by one block per attachment
+fn build_prompt_blocks(
+    prompt: String,
+    attachments: &[PathBuf],
+    capabilities: &PromptCapabilities,
+) -> Vec<AcpContentBlock> {
+    let mut blocks = vec![AcpContentBlock::Text(TextContent::new(prompt))];
+    blocks.extend(
+        attachments
+            .iter()
+            .map(|path| attachment_block(path, capabilities)),
+    );
+    blocks
+}
+
 // =============================================================================
 // Public API
 // =============================================================================
@@ -541,6 +990,8 @@ pub struct AcpPromptResult {
     pub session_id: String,
     /// Content segments in order (for storage)
     pub segments: Vec<crate::store::ContentSegment>,
+    /// What the agent advertised during this connection
+    pub capabilities: AgentCapabilities,
 }
 
 /// Run a one-shot prompt through ACP and return the response (no streaming)
@@ -557,9 +1008,20 @@ pub async fn run_acp_prompt(
     prompt: &str,
 ) -> Result<String, String> {
     // No streaming, no events emitted — internal_session_id is unused
-    let result =
-        run_acp_prompt_internal(agent, working_dir, prompt, None, None, "", true, None, None)
-            .await?;
+    let result = run_acp_prompt_internal(
+        agent,
+        working_dir,
+        prompt,
+        Vec::new(),
+        None,
+        None,
+        "",
+        true,
+        None,
+        None,
+        None,
+    )
+    .await?;
     Ok(result.response)
 }
 
@@ -576,12 +1038,39 @@ pub async fn run_acp_prompt_raw(
         agent,
         working_dir,
         prompt,
+        Vec::new(),
+        None,
+        None,
+        "",
+        false,
+        None,
+        None,
+        None,
+    )
+    .await?;
+    Ok(result.response)
+}
+
+/// Like `run_acp_prompt_raw`, but the agent process is registered with
+/// `cancellation` so the prompt can be aborted by killing it.
+pub async fn run_acp_prompt_raw_cancellable(
+    agent: &AcpAgent,
+    working_dir: &Path,
+    prompt: &str,
+    cancellation: Arc<CancellationHandle>,
+) -> Result<String, String> {
+    let result = run_acp_prompt_internal(
+        agent,
+        working_dir,
+        prompt,
+        Vec::new(),
         None,
         None,
         "",
         false,
         None,
         None,
+        Some(cancellation),
     )
     .await?;
     Ok(result.response)
@@ -592,23 +1081,29 @@ pub async fn run_acp_prompt_raw(
 /// If `session_id` is provided, attempts to load and resume that session.
 /// Otherwise, creates a new session. Returns both the response and the
 /// session ID for future resumption.
+///
+/// `attachments` are files (screenshots, binaries, etc.) sent alongside the
+/// prompt as image or resource blocks, subject to `MAX_ATTACHMENT_BYTES`.
 pub async fn run_acp_prompt_with_session(
     agent: &AcpAgent,
     working_dir: &Path,
     prompt: &str,
     session_id: Option<&str>,
+    attachments: &[PathBuf],
 ) -> Result<AcpPromptResult, String> {
     // No streaming, no events emitted — internal_session_id is unused
     run_acp_prompt_internal(
         agent,
         working_dir,
         prompt,
+        attachments.to_vec(),
         session_id,
         None,
         "",
         true,
         None,
         None,
+        None,
     )
     .await
 }
@@ -623,6 +1118,9 @@ use super::session::CancellationHandle;
 ///
 /// If `cancellation` is provided, the PID of the spawned agent process will be
 /// registered with it, allowing external cancellation via process kill.
+///
+/// `capabilities_callback` is called whenever the agent reports capabilities,
+/// starting right after it connects.
 #[allow(clippy::too_many_arguments)]
 pub async fn run_acp_prompt_streaming(
     agent: &AcpAgent,
@@ -632,17 +1130,20 @@ pub async fn run_acp_prompt_streaming(
     internal_session_id: &str,
     app_handle: tauri::AppHandle,
     buffer_callback: Option<Arc<dyn Fn(Vec<crate::store::ContentSegment>) + Send + Sync>>,
+    capabilities_callback: Option<Arc<dyn Fn(AgentCapabilities) + Send + Sync>>,
     cancellation: Option<Arc<CancellationHandle>>,
 ) -> Result<AcpPromptResult, String> {
     run_acp_prompt_internal(
         agent,
         working_dir,
         prompt,
+        Vec::new(),
         acp_session_id,
         Some(app_handle),
         internal_session_id,
         true,
         buffer_callback,
+        capabilities_callback,
         cancellation,
     )
     .await
@@ -654,16 +1155,22 @@ async fn run_acp_prompt_internal(
     agent: &AcpAgent,
     working_dir: &Path,
     prompt: &str,
+    attachments: Vec<PathBuf>,
     acp_session_id: Option<&str>,
     app_handle: Option<tauri::AppHandle>,
     internal_session_id: &str,
     prepend_system_context: bool,
     buffer_callback: Option<Arc<dyn Fn(Vec<crate::store::ContentSegment>) + Send + Sync>>,
+    capabilities_callback: Option<Arc<dyn Fn(AgentCapabilities) + Send + Sync>>,
     cancellation: Option<Arc<CancellationHandle>>,
 ) -> Result<AcpPromptResult, String> {
     let agent_path = agent.path().to_path_buf();
     let agent_name = agent.name().to_string();
-    let agent_args: Vec<String> = agent.acp_args().iter().map(|s| s.to_string()).collect();
+    let agents_config = AgentsConfig::load();
+    let agent_args = agent.launch_args(&agents_config);
+    let agent_env = agent.launch_env(&agents_config);
+    let permission_policy = agents_config.permission_policy(agent.name());
+    let system_context = prepend_system_context.then(|| system_context_for(working_dir));
     let working_dir = working_dir.to_path_buf();
     let prompt = prompt.to_string();
     let acp_session_id = acp_session_id.map(|s| s.to_string());
@@ -685,14 +1192,18 @@ async fn run_acp_prompt_internal(
                 &agent_path,
                 &agent_name,
                 &agent_args,
+                &agent_env,
                 &working_dir,
                 &prompt,
+                &attachments,
                 acp_session_id.as_deref(),
                 app_handle,
                 &internal_session_id,
-                prepend_system_context,
+                system_context.as_deref(),
                 buffer_callback,
+                capabilities_callback,
                 cancellation,
+                permission_policy,
             )
             .await
         })
@@ -707,18 +1218,23 @@ async fn run_acp_session_inner(
     agent_path: &Path,
     agent_name: &str,
     agent_args: &[String],
+    agent_env: &HashMap<String, String>,
     working_dir: &Path,
     prompt: &str,
+    attachments: &[PathBuf],
     existing_session_id: Option<&str>,
     app_handle: Option<tauri::AppHandle>,
     internal_session_id: &str,
-    prepend_system_context: bool,
+    system_context: Option<&str>,
     buffer_callback: Option<Arc<dyn Fn(Vec<crate::store::ContentSegment>) + Send + Sync>>,
+    capabilities_callback: Option<Arc<dyn Fn(AgentCapabilities) + Send + Sync>>,
     cancellation: Option<Arc<CancellationHandle>>,
+    permission_policy: PermissionPolicy,
 ) -> Result<AcpPromptResult, String> {
     // Spawn the agent process with ACP mode
     let mut cmd = Command::new(agent_path);
     cmd.args(agent_args)
+        .envs(agent_env)
         .current_dir(working_dir)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
@@ -732,11 +1248,20 @@ async fn run_acp_session_inner(
     // Register the PID with the cancellation handle so it can be killed externally
     if let Some(ref cancel) = cancellation {
         if let Some(pid) = child.id() {
-            log::debug!("Registering agent PID {pid} for cancellation");
+            log::debug!(target: logging::AI, "Registering agent PID {pid} for cancellation");
             cancel.set_pid(pid);
         }
     }
 
+    // Track the process so it can be reaped if the app dies before it exits
+    let command = agent_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| agent_name.to_string());
+    let _child_guard = child
+        .id()
+        .and_then(|pid| agents::track_child(pid, agent_name, internal_session_id, &command));
+
     // Get stdin/stdout
     let stdin = child
         .stdin
@@ -752,7 +1277,7 @@ async fn run_acp_session_inner(
     let stdout_compat = stdout.compat();
 
     // Create streaming client with our internal session ID for event correlation
-    let client = Arc::new(if let Some(callback) = buffer_callback {
+    let mut client = if let Some(callback) = buffer_callback {
         StreamingAcpClient::with_buffer_callback(
             app_handle.clone(),
             internal_session_id.to_string(),
@@ -760,7 +1285,10 @@ async fn run_acp_session_inner(
         )
     } else {
         StreamingAcpClient::new(app_handle.clone(), internal_session_id.to_string())
-    });
+    };
+    client.permissions = permission_policy;
+    client.capabilities_callback = capabilities_callback;
+    let client = Arc::new(client);
     let client_for_connection = Arc::clone(&client);
 
     // Create the ACP connection
@@ -772,7 +1300,7 @@ async fn run_acp_session_inner(
     // Spawn the IO task
     tokio::task::spawn_local(async move {
         if let Err(e) = io_future.await {
-            log::error!("ACP IO error: {e:?}");
+            log::error!(target: logging::AI, "ACP IO error: {e:?}");
         }
     });
 
@@ -787,12 +1315,24 @@ async fn run_acp_session_inner(
 
     if let Some(agent_info) = &init_response.agent_info {
         log::info!(
+            target: logging::AI,
             "Connected to agent: {} v{}",
             agent_info.name,
             agent_info.version
         );
     }
 
+    check_protocol_version(agent_name, &init_response.protocol_version)?;
+    let init_value = serde_json::to_value(&init_response).unwrap_or_default();
+    client
+        .update_capabilities(|c| *c = AgentCapabilities::from_init_response(&init_value))
+        .await;
+    log::info!(
+        target: logging::AI,
+        "Negotiated ACP protocol version {}",
+        protocol_version_label(&init_response.protocol_version)
+    );
+
     // Get or create session, track if this is a new session
     let (session_id, is_new_session): (SessionId, bool) =
         if let Some(existing_id) = existing_session_id {
@@ -800,22 +1340,27 @@ async fn run_acp_session_inner(
             // Suppress emit during load to avoid replaying history to frontend
             client.set_suppress_emit(true).await;
 
-            log::info!("Attempting to load session: {existing_id}");
+            log::info!(target: logging::AI, "Attempting to load session: {existing_id}");
             let load_request =
                 LoadSessionRequest::new(SessionId::new(existing_id), working_dir.to_path_buf());
 
             let result = match connection.load_session(load_request).await {
-                Ok(_) => {
-                    log::info!("Resumed session: {existing_id}");
+                Ok(load_response) => {
+                    log::info!(target: logging::AI, "Resumed session: {existing_id}");
+                    record_session_modes(&client, &load_response).await;
                     (SessionId::new(existing_id), false)
                 }
                 Err(e) => {
                     // Session not found or error - create a new one
-                    log::warn!("Failed to load session {existing_id}: {e:?}, creating new session");
+                    log::warn!(
+                        target: logging::AI,
+                        "Failed to load session {existing_id}: {e:?}, creating new session"
+                    );
                     let session_response = connection
                         .new_session(NewSessionRequest::new(working_dir.to_path_buf()))
                         .await
                         .map_err(|e| format!("Failed to create ACP session: {e:?}"))?;
+                    record_session_modes(&client, &session_response).await;
                     (session_response.session_id, true)
                 }
             };
@@ -830,7 +1375,12 @@ async fn run_acp_session_inner(
                 .new_session(NewSessionRequest::new(working_dir.to_path_buf()))
                 .await
                 .map_err(|e| format!("Failed to create ACP session: {e:?}"))?;
-            log::info!("Created new session: {}", session_response.session_id.0);
+            record_session_modes(&client, &session_response).await;
+            log::info!(
+                target: logging::AI,
+                "Created new session: {}",
+                session_response.session_id.0
+            );
             (session_response.session_id, true)
         };
 
@@ -839,16 +1389,19 @@ async fn run_acp_session_inner(
     client.clear().await;
 
     // For new sessions, optionally prepend system context to guide the agent's behavior
-    let full_prompt = if is_new_session && prepend_system_context {
-        format!("{STAGED_SYSTEM_CONTEXT}{prompt}")
-    } else {
-        prompt.to_string()
+    let full_prompt = match system_context {
+        Some(context) if is_new_session => format!("{context}{prompt}"),
+        _ => prompt.to_string(),
     };
 
-    // Send the prompt
+    // Send the prompt, with attachments shaped to what the agent accepts
     let prompt_request = PromptRequest::new(
         session_id.clone(),
-        vec![AcpContentBlock::Text(TextContent::new(full_prompt))],
+        build_prompt_blocks(
+            full_prompt,
+            attachments,
+            &init_response.agent_capabilities.prompt_capabilities,
+        ),
     );
 
     let prompt_result = connection.prompt(prompt_request).await;
@@ -863,11 +1416,13 @@ async fn run_acp_session_inner(
         Ok(_) => {
             let response = client.get_response().await;
             let segments = client.get_segments().await;
+            let capabilities = client.get_capabilities().await;
 
             Ok(AcpPromptResult {
                 response,
                 session_id: session_id_str,
                 segments,
+                capabilities,
             })
         }
         Err(e) => Err(format!("Failed to send prompt: {e:?}")),
@@ -884,4 +1439,235 @@ mod tests {
         // Actual availability depends on the system
         let _ = find_acp_agent();
     }
+
+    #[test]
+    fn test_protocol_version_mismatch_error() {
+        assert!(check_protocol_version("goose", &ProtocolVersion::LATEST).is_ok());
+
+        let err = check_protocol_version("goose", &ProtocolVersion::V0).unwrap_err();
+        assert!(err.starts_with(
+            "goose speaks ACP protocol version 0, but Staged supports versions 1 through"
+        ));
+        assert!(err.contains("Try upgrading goose"));
+    }
+
+    #[test]
+    fn test_build_prompt_blocks_with_image_attachment() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("screenshot.png");
+        std::fs::write(&image, [0x89, b'P', b'N', b'G']).unwrap();
+
+        let capabilities: PromptCapabilities =
+            serde_json::from_value(serde_json::json!({ "image": true })).unwrap();
+        let blocks = build_prompt_blocks("What is this?".to_string(), &[image], &capabilities);
+
+        assert_eq!(blocks.len(), 2);
+        assert!(matches!(&blocks[0], AcpContentBlock::Text(t) if t.text == "What is this?"));
+        match &blocks[1] {
+            AcpContentBlock::Image(img) => {
+                assert_eq!(img.mime_type, "image/png");
+                assert_eq!(img.data, "iVBORw==");
+            }
+            other => panic!("expected image block, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_image_attachment_falls_back_to_note() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("screenshot.png");
+        std::fs::write(&image, [0x89, b'P', b'N', b'G']).unwrap();
+
+        let blocks = build_prompt_blocks(
+            "What is this?".to_string(),
+            &[image],
+            &PromptCapabilities::default(),
+        );
+
+        assert!(
+            matches!(&blocks[1], AcpContentBlock::Text(t) if t.text.contains("does not accept images"))
+        );
+    }
+
+    #[test]
+    fn test_attachment_uri_is_encoded() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("my notes #1.txt");
+        std::fs::write(&file, "hi\n").unwrap();
+
+        let blocks = build_prompt_blocks(
+            "Read this".to_string(),
+            &[file.clone()],
+            &PromptCapabilities::default(),
+        );
+
+        match &blocks[1] {
+            AcpContentBlock::ResourceLink(link) => {
+                assert!(link.uri.ends_with("/my%20notes%20%231.txt"), "{}", link.uri);
+                let parsed = url::Url::parse(&link.uri).unwrap();
+                assert_eq!(parsed.to_file_path().unwrap(), file);
+            }
+            other => panic!("expected resource link, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_tool_call_status_mapping() {
+        assert_eq!(
+            tool_call_status(&AcpToolCallStatus::Pending),
+            ToolCallStatus::Pending
+        );
+        assert_eq!(
+            tool_call_status(&AcpToolCallStatus::InProgress),
+            ToolCallStatus::InProgress
+        );
+        assert_eq!(
+            tool_call_status(&AcpToolCallStatus::Completed),
+            ToolCallStatus::Completed
+        );
+        assert_eq!(
+            tool_call_status(&AcpToolCallStatus::Failed),
+            ToolCallStatus::Failed
+        );
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("goose 1.2.0\n"), Some("1.2.0".to_string()));
+        assert_eq!(
+            parse_version("\ncodex-acp v0.4.1, build abc\n"),
+            Some("0.4.1".to_string())
+        );
+        assert_eq!(parse_version("dev build"), Some("dev build".to_string()));
+        assert_eq!(parse_version("  \n"), None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_provider_info_for_available_stub() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let stub = dir.path().join("goose");
+        std::fs::write(&stub, "#!/bin/sh\necho \"goose 1.2.3\"\n").unwrap();
+        std::fs::set_permissions(&stub, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let agent = AcpAgent::Goose(stub.clone());
+        let info = AcpProviderInfo::new("goose", "Goose", Some(&agent));
+        assert!(info.available);
+        assert_eq!(info.path, Some(stub.display().to_string()));
+        assert_eq!(info.version, Some("1.2.3".to_string()));
+
+        let missing = AcpProviderInfo::new("codex", "Codex", None);
+        assert!(!missing.available);
+        assert_eq!(missing.path, None);
+        assert_eq!(missing.version, None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_probe_kills_hung_agent() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let stub = dir.path().join("goose");
+        std::fs::write(&stub, "#!/bin/sh\nexec sleep 30\n").unwrap();
+        std::fs::set_permissions(&stub, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let start = Instant::now();
+        assert_eq!(
+            run_probe(&stub, "--version", Duration::from_millis(100)),
+            None
+        );
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_login_shell_cache_resolves_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let cache = LoginShellCache::new();
+        let calls = AtomicUsize::new(0);
+        let resolver = |cmd: &str| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            (cmd == "goose").then(|| PathBuf::from("/usr/local/bin/goose"))
+        };
+
+        let first = cache.resolve("goose", resolver);
+        let second = cache.resolve("goose", resolver);
+        assert_eq!(first, Some(PathBuf::from("/usr/local/bin/goose")));
+        assert_eq!(second, first);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Misses are cached as well
+        assert_eq!(cache.resolve("codex-acp", resolver), None);
+        assert_eq!(cache.resolve("codex-acp", resolver), None);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Clearing forces a fresh lookup
+        cache.clear();
+        cache.resolve("goose", resolver);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_login_shell_lookup_does_not_block_other_commands() {
+        let cache = Arc::new(LoginShellCache::new());
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+        let slow = {
+            let cache = Arc::clone(&cache);
+            std::thread::spawn(move || {
+                cache.resolve("goose", |_| {
+                    started_tx.send(()).unwrap();
+                    release_rx.recv().unwrap();
+                    Some(PathBuf::from("/usr/local/bin/goose"))
+                })
+            })
+        };
+        started_rx.recv().unwrap();
+
+        // Resolves while the goose lookup is still running
+        assert_eq!(cache.resolve("codex-acp", |_| None), None);
+        release_tx.send(()).unwrap();
+        assert_eq!(
+            slow.join().unwrap(),
+            Some(PathBuf::from("/usr/local/bin/goose"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_capabilities_are_reported_as_they_arrive() {
+        let reported = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&reported);
+        let mut client = StreamingAcpClient::new(None, "s1".to_string());
+        client.capabilities_callback = Some(Arc::new(move |caps: AgentCapabilities| {
+            sink.lock().unwrap().push(caps);
+        }));
+
+        let init = serde_json::json!({
+            "agentInfo": { "name": "goose", "version": "1.9.0" },
+            "agentCapabilities": { "loadSession": true }
+        });
+        client
+            .update_capabilities(|c| *c = AgentCapabilities::from_init_response(&init))
+            .await;
+        record_session_modes(
+            &client,
+            &serde_json::json!({
+                "sessionId": "abc",
+                "modes": { "currentModeId": "code", "availableModes": [] }
+            }),
+        )
+        .await;
+
+        // Known before any prompt has been sent
+        let current = client.get_capabilities().await;
+        let reported = reported.lock().unwrap();
+        assert_eq!(reported.len(), 2);
+        assert_eq!(reported[0].agent_name.as_deref(), Some("goose"));
+        assert_eq!(reported[1].current_mode.as_deref(), Some("code"));
+        assert!(reported[1].load_session);
+        assert_eq!(reported[1], current);
+    }
 }
@@ -0,0 +1,170 @@
+//! Per-provider agent launch configuration.
+//!
+//! Read from ~/.config/staged/agents.json, keyed by provider ID:
+//!
+//! ```json
+//! {
+//!   "goose": {
+//!     "args": ["acp", "--with-builtin", "developer"],
+//!     "env": { "GOOSE_MODEL": "gpt-4o" },
+//!     "auto_approve": ["read", "search"],
+//!     "max_prompt_tokens": 120000
+//!   }
+//! }
+//! ```
+//!
+//! `args` replaces the provider's default ACP arguments entirely; `env` is
+//! added to the agent's environment. `auto_approve` limits which tools run
+//! without asking, by ACP tool kind or exact title (see `permissions`). `max_prompt_tokens` caps the size of
+//! generated prompts (see `budget`). Providers without an entry keep the
+//! built-in defaults.
+
+use super::budget::DEFAULT_PROMPT_TOKEN_BUDGET;
+use super::permissions::PermissionPolicy;
+use crate::logging;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Launch overrides for a single provider.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProviderConfig {
+    /// Replaces the default ACP arguments when set
+    #[serde(default)]
+    pub args: Option<Vec<String>>,
+    /// Extra environment variables for the agent process
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Tool kinds or exact titles approved without asking; when unset,
+    /// every tool is approved
+    #[serde(default)]
+    pub auto_approve: Option<Vec<String>>,
+    /// Token budget for generated prompts (diff analysis, action detection)
+    #[serde(default)]
+    pub max_prompt_tokens: Option<usize>,
+}
+
+/// Launch overrides for all providers, keyed by provider ID.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct AgentsConfig {
+    providers: HashMap<String, ProviderConfig>,
+}
+
+/// Get the agents config file path.
+fn config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|p| p.join("staged").join("agents.json"))
+}
+
+impl AgentsConfig {
+    /// Load the config file, falling back to defaults if it's missing or invalid.
+    pub fn load() -> Self {
+        let Some(path) = config_path() else {
+            return Self::default();
+        };
+
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => return Self::default(),
+        };
+
+        match Self::parse(&content) {
+            Ok(config) => config,
+            Err(e) => {
+                log::warn!(
+                    target: logging::AI,
+                    "Ignoring invalid agents config {}: {e}",
+                    path.display()
+                );
+                Self::default()
+            }
+        }
+    }
+
+    /// Parse a config from JSON.
+    pub fn parse(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// ACP arguments for a provider: the configured list, or `defaults`.
+    pub fn args_for(&self, provider_id: &str, defaults: &[&str]) -> Vec<String> {
+        match self
+            .providers
+            .get(provider_id)
+            .and_then(|p| p.args.as_ref())
+        {
+            Some(args) => args.clone(),
+            None => defaults.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    /// Extra environment variables for a provider.
+    pub fn env_for(&self, provider_id: &str) -> HashMap<String, String> {
+        self.providers
+            .get(provider_id)
+            .map(|p| p.env.clone())
+            .unwrap_or_default()
+    }
+
+    /// Tool permission policy for a provider.
+    pub fn permission_policy(&self, provider_id: &str) -> PermissionPolicy {
+        match self
+            .providers
+            .get(provider_id)
+            .and_then(|p| p.auto_approve.as_ref())
+        {
+            Some(allowed) => PermissionPolicy::Allowlist(allowed.clone()),
+            None => PermissionPolicy::ApproveAll,
+        }
+    }
+
+    /// Token budget for prompts sent to a provider.
+    pub fn prompt_token_budget(&self, provider_id: &str) -> usize {
+        self.providers
+            .get(provider_id)
+            .and_then(|p| p.max_prompt_tokens)
+            .unwrap_or(DEFAULT_PROMPT_TOKEN_BUDGET)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOOSE_DEFAULTS: &[&str] = &["acp", "--with-builtin", "developer,extensionmanager"];
+
+    #[test]
+    fn test_configured_args_override_goose_defaults() {
+        let config = AgentsConfig::parse(
+            r#"{
+                "goose": {
+                    "args": ["acp", "--with-builtin", "developer,memory"],
+                    "env": { "GOOSE_MODEL": "gpt-4o" }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.args_for("goose", GOOSE_DEFAULTS),
+            vec!["acp", "--with-builtin", "developer,memory"]
+        );
+        assert_eq!(
+            config
+                .env_for("goose")
+                .get("GOOSE_MODEL")
+                .map(String::as_str),
+            Some("gpt-4o")
+        );
+    }
+
+    #[test]
+    fn test_unconfigured_provider_keeps_defaults() {
+        let config = AgentsConfig::parse(r#"{ "goose": { "env": { "A": "1" } } }"#).unwrap();
+
+        assert_eq!(config.args_for("goose", GOOSE_DEFAULTS), GOOSE_DEFAULTS);
+        assert!(config.args_for("claude", &[]).is_empty());
+        assert!(config.env_for("claude").is_empty());
+    }
+}
@@ -5,7 +5,13 @@
 //! ## Architecture
 //!
 //! - `session.rs` - SessionManager for live agent connections + streaming
+//! - `agents.rs` - Tracking of spawned agent processes and orphan cleanup
 //! - `client.rs` - Core ACP client implementation (agent discovery, protocol)
+//! - `config.rs` - Per-provider launch arguments and env from agents.json
+//! - `budget.rs` - Token estimates and trimming prompts to a context budget
+//! - `capabilities.rs` - Commands, modes and prompt capabilities agents advertise
+//! - `permissions.rs` - Tool auto-approve allowlists and frontend permission prompts
+//! - `transcript.rs` - Append-only JSON-lines transcripts written as turns complete
 //! - `analysis/` - Structured diff analysis: prompts, runner, and types for "Analyze with AI"
 //!
 //! Session/message persistence is handled by the unified Store (see `crate::store`).
@@ -14,21 +20,32 @@
 //!
 //! 1. Frontend calls `create_session` → creates in SQLite + live session
 //! 2. Frontend calls `send_prompt` → stores user message, streams response
-//! 3. On turn complete → assistant message persisted to SQLite
+//! 3. On turn complete → assistant message persisted to SQLite (and appended
+//!    to the session's `.jsonl` transcript when enabled)
 //! 4. Frontend can `get_session` to load full history from SQLite
 //!
 //! Live sessions (agent connections) are ephemeral. History survives app restart.
 
+pub mod agents;
 pub mod analysis;
+pub mod budget;
+pub mod capabilities;
 mod client;
+pub mod config;
+pub mod permissions;
 pub mod session;
+pub mod transcript;
 
 // Re-export core ACP client functionality
 pub use client::{
-    discover_acp_providers, find_acp_agent, find_acp_agent_by_id, run_acp_prompt,
-    run_acp_prompt_raw, run_acp_prompt_streaming, run_acp_prompt_with_session, AcpAgent,
-    AcpPromptResult, AcpProviderInfo,
+    discover_acp_providers, find_acp_agent, find_acp_agent_by_id, find_acp_agent_for_repo,
+    refresh_agent_paths, run_acp_prompt, run_acp_prompt_raw, run_acp_prompt_raw_cancellable,
+    run_acp_prompt_streaming, run_acp_prompt_with_session, AcpAgent, AcpPromptResult,
+    AcpProviderInfo,
 };
 
 // Re-export session manager types
-pub use session::{LiveSessionInfo, SessionManager, SessionStatus, SessionStatusEvent};
+pub use capabilities::AgentCapabilities;
+pub use session::{
+    CancellationHandle, LiveSessionInfo, SessionManager, SessionStatus, SessionStatusEvent,
+};
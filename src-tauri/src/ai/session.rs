@@ -12,13 +12,17 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 
 use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Emitter};
-use tokio::sync::RwLock;
+use tokio::sync::{Notify, RwLock};
 
+use super::capabilities::AgentCapabilities;
 use super::client::{self, AcpAgent, AcpPromptResult};
-use crate::store::{generate_session_id, ContentSegment, MessageRole, Session, Store};
+use super::transcript::{TranscriptTurn, TranscriptWriter};
+use crate::logging;
+use crate::store::{ContentSegment, MessageRole, Session, Store};
 
 // =============================================================================
 // Types
@@ -46,12 +50,79 @@ pub struct SessionStatusEvent {
     pub status: SessionStatus,
 }
 
+/// Event emitted when a live session is shut down to stay under the cap
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionEvictedEvent {
+    pub session_id: String,
+}
+
+/// Overrides the default maximum number of live sessions.
+pub const MAX_LIVE_SESSIONS_ENV_VAR: &str = "STAGED_MAX_LIVE_SESSIONS";
+
+/// Selects the `LivePolicy` used at the cap: `evict` or `queue`.
+pub const LIVE_SESSION_POLICY_ENV_VAR: &str = "STAGED_LIVE_SESSION_POLICY";
+
+/// Live sessions kept before new ones displace old ones.
+pub const DEFAULT_MAX_LIVE_SESSIONS: usize = 8;
+
+/// What to do when a session needs to go live and the cap is reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LivePolicy {
+    /// Shut down the least recently used idle session; fail if all are busy
+    Evict,
+    /// Like `Evict`, but if all are busy wait for one to finish its turn
+    Queue,
+}
+
+/// Cap on concurrent live sessions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionLimit {
+    pub max_live: usize,
+    pub policy: LivePolicy,
+}
+
+impl Default for SessionLimit {
+    fn default() -> Self {
+        Self {
+            max_live: DEFAULT_MAX_LIVE_SESSIONS,
+            policy: LivePolicy::Evict,
+        }
+    }
+}
+
+impl SessionLimit {
+    /// The default limit, adjusted by `STAGED_MAX_LIVE_SESSIONS` and
+    /// `STAGED_LIVE_SESSION_POLICY`. Invalid values are ignored.
+    pub fn from_env() -> Self {
+        let mut limit = Self::default();
+        if let Some(max) = std::env::var(MAX_LIVE_SESSIONS_ENV_VAR)
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|&max| max > 0)
+        {
+            limit.max_live = max;
+        }
+        match std::env::var(LIVE_SESSION_POLICY_ENV_VAR).as_deref() {
+            Ok("queue") => limit.policy = LivePolicy::Queue,
+            Ok("evict") | Err(_) => {}
+            Ok(other) => log::warn!(
+                target: logging::AI,
+                "Ignoring unknown {LIVE_SESSION_POLICY_ENV_VAR} '{other}'"
+            ),
+        }
+        limit
+    }
+}
+
 /// Info about a live session (for the frontend)
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LiveSessionInfo {
     pub session_id: String,
     pub status: SessionStatus,
+    /// What the agent reported, once it has connected
+    pub capabilities: Option<AgentCapabilities>,
 }
 
 /// Cancellation handle for an active session.
@@ -79,21 +150,7 @@ impl CancellationHandle {
         // Kill the subprocess if we have a PID
         let pid = self.pid.load(Ordering::SeqCst);
         if pid != 0 {
-            log::info!("Killing agent subprocess with PID {pid}");
-            #[cfg(unix)]
-            {
-                // Send SIGTERM to the process using the kill command
-                let _ = std::process::Command::new("kill")
-                    .args(["-TERM", &pid.to_string()])
-                    .output();
-            }
-            #[cfg(windows)]
-            {
-                // On Windows, use taskkill
-                let _ = std::process::Command::new("taskkill")
-                    .args(["/PID", &pid.to_string(), "/F"])
-                    .output();
-            }
+            kill_agent(pid);
         }
     }
 
@@ -102,9 +159,33 @@ impl CancellationHandle {
         self.cancelled.load(Ordering::SeqCst)
     }
 
-    /// Set the PID of the agent subprocess
+    /// Set the PID of the agent subprocess.
+    /// If cancellation was already requested, the process is killed right away
+    /// so a cancel that races the spawn doesn't leave it running.
     pub fn set_pid(&self, pid: u32) {
         self.pid.store(pid, Ordering::SeqCst);
+        if self.is_cancelled() {
+            kill_agent(pid);
+        }
+    }
+}
+
+/// Terminate an agent subprocess by PID.
+fn kill_agent(pid: u32) {
+    log::info!(target: logging::AI, "Killing agent subprocess with PID {pid}");
+    #[cfg(unix)]
+    {
+        // Send SIGTERM to the process using the kill command
+        let _ = std::process::Command::new("kill")
+            .args(["-TERM", &pid.to_string()])
+            .output();
+    }
+    #[cfg(windows)]
+    {
+        // On Windows, use taskkill
+        let _ = std::process::Command::new("taskkill")
+            .args(["/PID", &pid.to_string(), "/F"])
+            .output();
     }
 }
 
@@ -122,6 +203,55 @@ struct LiveSession {
     status: SessionStatus,
     /// Cancellation handle for the current operation (if any)
     cancellation: Option<Arc<CancellationHandle>>,
+    /// When a prompt was last sent (or the session went live)
+    last_used: Instant,
+    /// What the agent reported on its last connection. Shared with the
+    /// running prompt, which fills it in as soon as the agent connects.
+    capabilities: SharedCapabilities,
+    /// Copied history to prepend to the first prompt of a forked session,
+    /// so the fresh agent session starts with the parent's context
+    history_seed: Option<String>,
+}
+
+impl LiveSession {
+    /// Kill the agent process of any operation still attached.
+    fn shutdown(&self) {
+        if let Some(ref cancellation) = self.cancellation {
+            cancellation.cancel();
+        }
+    }
+}
+
+type LiveSessions = HashMap<String, Arc<RwLock<LiveSession>>>;
+
+type SharedCapabilities = Arc<std::sync::Mutex<Option<AgentCapabilities>>>;
+
+/// Remove the least recently used session that isn't processing a prompt
+/// and shut it down. Returns its ID, or None if every session is busy.
+async fn evict_lru_idle(sessions: &mut LiveSessions) -> Option<String> {
+    let mut lru: Option<(Instant, String)> = None;
+    for (id, session) in sessions.iter() {
+        let s = session.read().await;
+        if s.status == SessionStatus::Processing {
+            continue;
+        }
+        if lru.as_ref().map_or(true, |(used, _)| s.last_used < *used) {
+            lru = Some((s.last_used, id.clone()));
+        }
+    }
+
+    let (_, id) = lru?;
+    if let Some(session) = sessions.remove(&id) {
+        session.read().await.shutdown();
+    }
+    Some(id)
+}
+
+/// Capabilities reported to a live session, if it has connected.
+async fn live_capabilities(sessions: &LiveSessions, session_id: &str) -> Option<AgentCapabilities> {
+    let session = sessions.get(session_id)?;
+    let capabilities = session.read().await.capabilities.lock().unwrap().clone();
+    capabilities
 }
 
 // =============================================================================
@@ -139,6 +269,12 @@ pub struct SessionManager {
     /// In-memory buffer for streaming messages (session_id -> segments)
     /// Stores messages as they arrive during streaming, before DB persistence
     streaming_buffer: Arc<RwLock<HashMap<String, Vec<ContentSegment>>>>,
+    /// When set, each finalized turn is also appended to a per-session `.jsonl` file
+    transcripts: Option<TranscriptWriter>,
+    /// Cap on concurrent live sessions
+    limit: SessionLimit,
+    /// Signalled when a live session closes or finishes a turn
+    slot_freed: Arc<Notify>,
 }
 
 impl SessionManager {
@@ -149,9 +285,64 @@ impl SessionManager {
             app_handle,
             store,
             streaming_buffer: Arc::new(RwLock::new(HashMap::new())),
+            transcripts: None,
+            limit: SessionLimit::default(),
+            slot_freed: Arc::new(Notify::new()),
         }
     }
 
+    /// Cap the number of concurrent live sessions.
+    pub fn with_limit(mut self, limit: SessionLimit) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Make room for one more live session, per the limit's policy.
+    /// Returns the sessions lock so the caller can insert while holding it.
+    async fn make_room(&self) -> Result<tokio::sync::RwLockWriteGuard<'_, LiveSessions>, String> {
+        loop {
+            // Register before checking, so a turn finishing in between isn't missed
+            let slot_freed = self.slot_freed.notified();
+            let mut sessions = self.sessions.write().await;
+            if sessions.len() < self.limit.max_live {
+                return Ok(sessions);
+            }
+            if let Some(evicted) = evict_lru_idle(&mut sessions).await {
+                log::info!(
+                    target: logging::AI,
+                    "Evicted live session {evicted} (limit {})",
+                    self.limit.max_live
+                );
+                let _ = self.app_handle.emit(
+                    "session-evicted",
+                    &SessionEvictedEvent {
+                        session_id: evicted,
+                    },
+                );
+                continue;
+            }
+            match self.limit.policy {
+                LivePolicy::Evict => {
+                    return Err(format!(
+                        "Too many live sessions ({}), and all are busy",
+                        self.limit.max_live
+                    ))
+                }
+                LivePolicy::Queue => {
+                    drop(sessions);
+                    log::info!(target: logging::AI, "Waiting for a live session slot");
+                    slot_freed.await;
+                }
+            }
+        }
+    }
+
+    /// Also write JSON-lines transcripts into `dir` as turns complete.
+    pub fn with_transcripts(mut self, dir: PathBuf) -> Self {
+        self.transcripts = Some(TranscriptWriter::new(dir));
+        self
+    }
+
     /// Create a new session (persisted + live)
     pub async fn create_session(
         &self,
@@ -162,11 +353,15 @@ impl SessionManager {
         let agent = if let Some(id) = agent_id {
             client::find_acp_agent_by_id(id).ok_or_else(|| format!("Agent '{id}' not found"))?
         } else {
-            client::find_acp_agent().ok_or_else(|| "No AI agent found".to_string())?
+            client::find_acp_agent_for_repo(&working_dir)
+                .ok_or_else(|| "No AI agent found".to_string())?
         };
 
         // Generate session ID and create in store
-        let session_id = generate_session_id();
+        let session_id = self
+            .store
+            .new_session_id()
+            .map_err(|e| format!("Failed to generate session id: {e}"))?;
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
@@ -179,6 +374,8 @@ impl SessionManager {
             title: None,
             created_at: now,
             updated_at: now,
+            forked_from: None,
+            acp_session_id: None,
         };
 
         self.store
@@ -193,12 +390,15 @@ impl SessionManager {
             working_dir,
             status: SessionStatus::Idle,
             cancellation: None,
+            last_used: Instant::now(),
+            capabilities: SharedCapabilities::default(),
+            history_seed: None,
         };
 
-        let mut sessions = self.sessions.write().await;
+        let mut sessions = self.make_room().await?;
         sessions.insert(session_id.clone(), Arc::new(RwLock::new(live_session)));
 
-        log::info!("Created session: {session_id}");
+        log::info!(target: logging::AI, "Created session: {session_id}");
         Ok(session_id)
     }
 
@@ -226,22 +426,54 @@ impl SessionManager {
             .or_else(client::find_acp_agent)
             .ok_or_else(|| "No AI agent found".to_string())?;
 
+        // A fork that hasn't talked to its agent yet needs the copied history
+        let history_seed = match (&session.forked_from, &session.acp_session_id) {
+            (Some(_), None) => self.history_seed(session_id)?,
+            _ => None,
+        };
+
         let live_session = LiveSession {
             session_id: session_id.to_string(),
-            acp_session_id: None, // Will be set on first prompt
+            // Resumed on the next prompt; the client falls back to a new
+            // agent session if this one can't be loaded
+            acp_session_id: session.acp_session_id,
             agent,
             working_dir: PathBuf::from(&session.working_dir),
             status: SessionStatus::Idle,
             cancellation: None,
+            last_used: Instant::now(),
+            capabilities: SharedCapabilities::default(),
+            history_seed,
         };
 
         let arc = Arc::new(RwLock::new(live_session));
-        let mut sessions = self.sessions.write().await;
+        let mut sessions = self.make_room().await?;
+        // Another prompt may have brought it live while we waited
+        if let Some(existing) = sessions.get(session_id) {
+            return Ok(existing.clone());
+        }
         sessions.insert(session_id.to_string(), arc.clone());
 
         Ok(arc)
     }
 
+    /// The preamble that hands a forked session's copied messages to a fresh
+    /// agent session. `None` if there is nothing to copy.
+    fn history_seed(&self, session_id: &str) -> Result<Option<String>, String> {
+        let full = self
+            .store
+            .get_session_full(session_id)
+            .map_err(|e| format!("Failed to load session history: {e}"))?;
+        Ok(full.filter(|full| !full.messages.is_empty()).map(|full| {
+            format!(
+                "This conversation continues an earlier one. The transcript so far is below; \
+                     treat it as context and respond to the new message that follows it.\n\n\
+                     <transcript>\n{}</transcript>\n\n",
+                full.to_markdown()
+            )
+        }))
+    }
+
     /// List live sessions
     pub async fn list_live_sessions(&self) -> Vec<LiveSessionInfo> {
         let sessions = self.sessions.read().await;
@@ -252,6 +484,7 @@ impl SessionManager {
             infos.push(LiveSessionInfo {
                 session_id: s.session_id.clone(),
                 status: s.status.clone(),
+                capabilities: s.capabilities.lock().unwrap().clone(),
             });
         }
 
@@ -276,6 +509,12 @@ impl SessionManager {
         Ok(SessionStatus::Idle)
     }
 
+    /// Commands, modes and prompt capabilities the session's agent reported.
+    /// None until the session is live and has connected to its agent.
+    pub async fn session_capabilities(&self, session_id: &str) -> Option<AgentCapabilities> {
+        live_capabilities(&*self.sessions.read().await, session_id).await
+    }
+
     /// Check if a session has a live connection (is in the sessions HashMap).
     /// This is different from get_session_status which returns Idle for sessions
     /// that exist in the store but aren't live.
@@ -288,7 +527,8 @@ impl SessionManager {
     pub async fn close_live_session(&self, session_id: &str) -> Result<(), String> {
         let mut sessions = self.sessions.write().await;
         sessions.remove(session_id);
-        log::info!("Closed live session: {session_id}");
+        self.slot_freed.notify_one();
+        log::info!(target: logging::AI, "Closed live session: {session_id}");
         Ok(())
     }
 
@@ -301,7 +541,7 @@ impl SessionManager {
         let cancellation = Arc::new(CancellationHandle::new());
 
         // Check status and prepare for prompt
-        let (agent, working_dir, acp_session_id) = {
+        let (agent, working_dir, acp_session_id, history_seed, capabilities) = {
             let mut session = session_arc.write().await;
 
             if session.status == SessionStatus::Processing {
@@ -311,19 +551,34 @@ impl SessionManager {
             // Update status to processing and store cancellation handle
             session.status = SessionStatus::Processing;
             session.cancellation = Some(cancellation.clone());
+            session.last_used = Instant::now();
             self.emit_status(&session.session_id, &session.status);
 
             (
                 session.agent.clone(),
                 session.working_dir.clone(),
                 session.acp_session_id.clone(),
+                session.history_seed.clone(),
+                Arc::clone(&session.capabilities),
             )
         };
 
+        // A fork's first prompt goes to a fresh agent session, so it carries
+        // the copied history with it. Only the prompt itself is stored.
+        let agent_prompt = match (&acp_session_id, history_seed) {
+            (None, Some(seed)) => seed + &prompt,
+            _ => prompt.clone(),
+        };
+
         // Store the user message
         self.store
             .add_message(session_id, MessageRole::User, &prompt)
             .map_err(|e| format!("Failed to store message: {e}"))?;
+        append_transcript(
+            self.transcripts.as_ref(),
+            session_id,
+            &TranscriptTurn::user(prompt.as_str()),
+        );
 
         // Spawn background task to run the prompt
         let app_handle = self.app_handle.clone();
@@ -331,6 +586,8 @@ impl SessionManager {
         let session_arc_clone = session_arc.clone();
         let store = self.store.clone();
         let streaming_buffer = Arc::clone(&self.streaming_buffer);
+        let transcripts = self.transcripts.clone();
+        let slot_freed = Arc::clone(&self.slot_freed);
 
         // Create callback to update buffer during streaming
         let session_id_for_callback = session_id_owned.clone();
@@ -345,16 +602,21 @@ impl SessionManager {
             });
         });
 
+        let capabilities_callback = Arc::new(move |reported: AgentCapabilities| {
+            *capabilities.lock().unwrap() = Some(reported);
+        });
+
         tokio::spawn(async move {
             // Run the ACP prompt with streaming
             let result = client::run_acp_prompt_streaming(
                 &agent,
                 &working_dir,
-                &prompt,
+                &agent_prompt,
                 acp_session_id.as_deref(),
                 &session_id_owned,
                 app_handle.clone(),
                 Some(buffer_callback),
+                Some(capabilities_callback),
                 Some(cancellation.clone()),
             )
             .await;
@@ -367,7 +629,7 @@ impl SessionManager {
 
             // Check if we were cancelled
             if cancellation.is_cancelled() {
-                log::info!("Session {session_id_owned} was cancelled");
+                log::info!(target: logging::AI, "Session {session_id_owned} was cancelled");
                 session.status = SessionStatus::Cancelled;
                 // Clear buffer on cancellation
                 let mut buffer = streaming_buffer.write().await;
@@ -375,16 +637,37 @@ impl SessionManager {
             } else {
                 match result {
                     Ok(acp_result) => {
-                        // Store the ACP session ID for future resumption
-                        session.acp_session_id = Some(acp_result.session_id.clone());
+                        // Store the ACP session ID for future resumption. It
+                        // differs from the one we sent when that couldn't be
+                        // loaded and the agent started a new session.
+                        if session.acp_session_id.as_deref() != Some(&acp_result.session_id) {
+                            if let Err(e) =
+                                store.set_acp_session_id(&session_id_owned, &acp_result.session_id)
+                            {
+                                log::error!(
+                                    target: logging::AI,
+                                    "Failed to persist ACP session id: {e}"
+                                );
+                            }
+                            session.acp_session_id = Some(acp_result.session_id.clone());
+                        }
+                        session.history_seed = None;
                         session.status = SessionStatus::Idle;
 
                         // Persist the assistant response
                         if let Err(e) =
                             persist_assistant_turn(&store, &session_id_owned, &acp_result)
                         {
-                            log::error!("Failed to persist assistant turn: {e}");
+                            log::error!(
+                                target: logging::AI,
+                                "Failed to persist assistant turn: {e}"
+                            );
                         }
+                        append_transcript(
+                            transcripts.as_ref(),
+                            &session_id_owned,
+                            &TranscriptTurn::assistant(acp_result.segments.clone()),
+                        );
 
                         // Clear buffer after persistence attempt (success or failure)
                         // The callback has been updating the buffer during streaming
@@ -393,11 +676,14 @@ impl SessionManager {
 
                         // Auto-generate title from first user message if not set
                         if let Err(e) = maybe_set_title(&store, &session_id_owned, &prompt) {
-                            log::warn!("Failed to set session title: {e}");
+                            log::warn!(target: logging::AI, "Failed to set session title: {e}");
                         }
                     }
                     Err(e) => {
-                        log::error!("Session {session_id_owned} prompt failed: {e}");
+                        log::error!(
+                            target: logging::AI,
+                            "Session {session_id_owned} prompt failed: {e}"
+                        );
                         session.status = SessionStatus::Error { message: e };
                         // Clear buffer on error too
                         let mut buffer = streaming_buffer.write().await;
@@ -412,6 +698,8 @@ impl SessionManager {
                 status: session.status.clone(),
             };
             let _ = app_handle.emit("session-status", &event);
+            drop(session);
+            slot_freed.notify_one();
         });
 
         Ok(())
@@ -431,7 +719,7 @@ impl SessionManager {
         }
 
         if let Some(ref cancellation) = session.cancellation {
-            log::info!("Cancelling session {session_id}");
+            log::info!(target: logging::AI, "Cancelling session {session_id}");
             cancellation.cancel();
             Ok(())
         } else {
@@ -478,6 +766,17 @@ fn persist_assistant_turn(
     Ok(())
 }
 
+/// Append a turn to the session's transcript, if transcripts are enabled.
+/// Failures are logged; the SQLite store remains the source of truth.
+fn append_transcript(writer: Option<&TranscriptWriter>, session_id: &str, turn: &TranscriptTurn) {
+    let Some(writer) = writer else {
+        return;
+    };
+    if let Err(e) = writer.append(session_id, turn) {
+        log::warn!(target: logging::AI, "Failed to append transcript for {session_id}: {e}");
+    }
+}
+
 /// Set session title from first prompt if not already set
 fn maybe_set_title(store: &Store, session_id: &str, prompt: &str) -> Result<(), String> {
     let session = store
@@ -508,3 +807,102 @@ fn maybe_set_title(store: &Store, session_id: &str, prompt: &str) -> Result<(),
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cancel_before_spawn_kills_process() {
+        let handle = CancellationHandle::new();
+        handle.cancel();
+
+        // Stands in for an agent that would never respond
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        handle.set_pid(child.id());
+
+        let status = child.wait().unwrap();
+        assert!(!status.success());
+        assert!(handle.is_cancelled());
+    }
+
+    fn live_session(id: &str, status: SessionStatus, last_used: Instant) -> LiveSession {
+        LiveSession {
+            session_id: id.to_string(),
+            acp_session_id: None,
+            agent: AcpAgent::Goose(PathBuf::from("goose")),
+            working_dir: PathBuf::from("."),
+            status,
+            cancellation: None,
+            last_used,
+            capabilities: SharedCapabilities::default(),
+            history_seed: None,
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_evicts_least_recently_used_idle_session() {
+        let start = Instant::now();
+        let at = |secs| start + std::time::Duration::from_secs(secs);
+
+        // The oldest session is busy, so the oldest idle one goes
+        let mut oldest_idle = live_session("b", SessionStatus::Idle, at(1));
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let handle = Arc::new(CancellationHandle::new());
+        handle.set_pid(child.id());
+        oldest_idle.cancellation = Some(handle.clone());
+
+        let mut sessions: LiveSessions = [
+            live_session("a", SessionStatus::Processing, at(0)),
+            oldest_idle,
+            live_session("c", SessionStatus::Idle, at(2)),
+        ]
+        .into_iter()
+        .map(|s| (s.session_id.clone(), Arc::new(RwLock::new(s))))
+        .collect();
+
+        assert_eq!(evict_lru_idle(&mut sessions).await.as_deref(), Some("b"));
+        assert!(!sessions.contains_key("b"));
+        assert!(handle.is_cancelled());
+        assert!(!child.wait().unwrap().success());
+
+        assert_eq!(evict_lru_idle(&mut sessions).await.as_deref(), Some("c"));
+        // Only the busy session is left, and it isn't evictable
+        assert_eq!(evict_lru_idle(&mut sessions).await, None);
+        assert!(sessions.contains_key("a"));
+    }
+
+    #[tokio::test]
+    async fn test_capabilities_from_init_response_are_retrievable() {
+        let connected = live_session("connected", SessionStatus::Idle, Instant::now());
+        *connected.capabilities.lock().unwrap() =
+            Some(AgentCapabilities::from_init_response(&serde_json::json!({
+                "agentInfo": { "name": "claude-code", "version": "0.5.0" },
+                "agentCapabilities": { "loadSession": true }
+            })));
+        let sessions: LiveSessions = [
+            connected,
+            live_session("fresh", SessionStatus::Idle, Instant::now()),
+        ]
+        .into_iter()
+        .map(|s| (s.session_id.clone(), Arc::new(RwLock::new(s))))
+        .collect();
+
+        let caps = live_capabilities(&sessions, "connected").await.unwrap();
+        assert_eq!(caps.agent_name.as_deref(), Some("claude-code"));
+        assert!(caps.load_session);
+        assert!(caps.commands.is_empty());
+
+        // Not yet connected, or not live at all
+        assert_eq!(live_capabilities(&sessions, "fresh").await, None);
+        assert_eq!(live_capabilities(&sessions, "missing").await, None);
+    }
+}
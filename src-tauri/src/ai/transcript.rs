@@ -0,0 +1,232 @@
+//! Append-only JSON-lines transcripts.
+//!
+//! Each completed turn is appended to `<dir>/<session_id>.jsonl` as soon as it
+//! finishes, so a killed app still leaves the conversation on disk. External
+//! tools can tail the file; `replay_jsonl` reads it back.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::logging;
+use crate::store::{now_timestamp, ContentSegment, Result, Store};
+
+/// One finalized turn in a transcript.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "role", rename_all = "lowercase")]
+pub enum TranscriptTurn {
+    User {
+        text: String,
+        created_at: i64,
+    },
+    Assistant {
+        segments: Vec<ContentSegment>,
+        created_at: i64,
+    },
+}
+
+impl TranscriptTurn {
+    pub fn user(text: impl Into<String>) -> Self {
+        Self::User {
+            text: text.into(),
+            created_at: now_timestamp(),
+        }
+    }
+
+    pub fn assistant(segments: Vec<ContentSegment>) -> Self {
+        Self::Assistant {
+            segments,
+            created_at: now_timestamp(),
+        }
+    }
+}
+
+/// Enables transcripts when set; they are off by default.
+pub const TRANSCRIPTS_ENV_VAR: &str = "STAGED_TRANSCRIPTS";
+
+/// Whether transcripts were enabled through [`TRANSCRIPTS_ENV_VAR`].
+pub fn enabled_from_env() -> bool {
+    std::env::var_os(TRANSCRIPTS_ENV_VAR).is_some()
+}
+
+/// Writes per-session `.jsonl` transcripts into a directory.
+#[derive(Debug, Clone)]
+pub struct TranscriptWriter {
+    dir: PathBuf,
+}
+
+impl TranscriptWriter {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Path of the transcript file for a session.
+    ///
+    /// Session ids become file names, so anything other than ASCII letters,
+    /// digits, `_` and `-` is rejected rather than joined onto the directory.
+    pub fn path_for(&self, session_id: &str) -> io::Result<PathBuf> {
+        let valid = !session_id.is_empty()
+            && session_id
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-');
+        if !valid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid session id for a transcript: {session_id:?}"),
+            ));
+        }
+        Ok(self.dir.join(format!("{session_id}.jsonl")))
+    }
+
+    /// Delete a session's transcript, if it has one.
+    pub fn remove(&self, session_id: &str) -> io::Result<()> {
+        match fs::remove_file(self.path_for(session_id)?) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Append a turn as a single line and flush it to disk.
+    pub fn append(&self, session_id: &str, turn: &TranscriptTurn) -> io::Result<()> {
+        let path = self.path_for(session_id)?;
+        fs::create_dir_all(&self.dir)?;
+        let mut line = serde_json::to_string(turn)?;
+        line.push('\n');
+
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(line.as_bytes())?;
+        file.sync_data()
+    }
+}
+
+/// Delete a session from the store, then its transcript.
+///
+/// The session is gone once the store delete succeeds, so failing to remove
+/// the file is only logged.
+pub fn delete_session(store: &Store, writer: &TranscriptWriter, session_id: &str) -> Result<()> {
+    store.delete_session(session_id)?;
+    if let Err(e) = writer.remove(session_id) {
+        log::warn!(target: logging::AI, "Failed to delete transcript for {session_id}: {e}");
+    }
+    Ok(())
+}
+
+/// Reconstruct the turns of a transcript file.
+///
+/// A final line that doesn't parse is dropped, since that's what a crash
+/// mid-write leaves behind. Bad lines anywhere else are an error.
+pub fn replay_jsonl(path: &Path) -> io::Result<Vec<TranscriptTurn>> {
+    let reader = BufReader::new(fs::File::open(path)?);
+    let lines: Vec<String> = reader.lines().collect::<io::Result<_>>()?;
+    let last = lines.len().saturating_sub(1);
+
+    let mut turns = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(turn) => turns.push(turn),
+            Err(_) if i == last => break,
+            Err(e) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}:{}: {e}", path.display(), i + 1),
+                ))
+            }
+        }
+    }
+    Ok(turns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{Session, ToolCallStatus};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_write_and_replay() {
+        let dir = tempdir().unwrap();
+        let writer = TranscriptWriter::new(dir.path().join("transcripts"));
+
+        let user = TranscriptTurn::user("Explain this diff");
+        let assistant = TranscriptTurn::assistant(vec![
+            ContentSegment::ToolCall {
+                id: "t1".to_string(),
+                title: "Read file".to_string(),
+                status: ToolCallStatus::Completed,
+                locations: vec!["src/lib.rs".to_string()],
+            },
+            ContentSegment::Text {
+                text: "It renames a function.".to_string(),
+            },
+        ]);
+        writer.append("s1", &user).unwrap();
+        writer.append("s1", &assistant).unwrap();
+
+        let turns = replay_jsonl(&writer.path_for("s1").unwrap()).unwrap();
+        assert_eq!(turns, vec![user, assistant]);
+    }
+
+    #[test]
+    fn test_replay_drops_truncated_last_line() {
+        let dir = tempdir().unwrap();
+        let writer = TranscriptWriter::new(dir.path());
+        let user = TranscriptTurn::user("hello");
+        writer.append("s1", &user).unwrap();
+
+        let path = writer.path_for("s1").unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"role\":\"assistant\",\"segm").unwrap();
+
+        assert_eq!(replay_jsonl(&path).unwrap(), vec![user]);
+    }
+
+    #[test]
+    fn test_rejects_session_ids_that_are_not_file_names() {
+        let dir = tempdir().unwrap();
+        let writer = TranscriptWriter::new(dir.path().join("transcripts"));
+        let turn = TranscriptTurn::user("hi");
+        for id in ["", "../escape", "a/b", "..", "s1.jsonl", "C:\\x"] {
+            assert!(writer.path_for(id).is_err(), "{id:?}");
+            assert!(writer.append(id, &turn).is_err(), "{id:?}");
+            assert!(writer.remove(id).is_err(), "{id:?}");
+        }
+        assert!(!dir.path().join("escape.jsonl").exists());
+        assert!(writer.path_for("sess_01-AbC").is_ok());
+    }
+
+    #[test]
+    fn test_delete_session_removes_transcript() {
+        let dir = tempdir().unwrap();
+        let store = Store::open(dir.path().join("test.db")).unwrap();
+        let writer = TranscriptWriter::new(dir.path().join("transcripts"));
+
+        let now = now_timestamp();
+        for id in ["s1", "s2"] {
+            store
+                .create_session(&Session {
+                    id: id.to_string(),
+                    working_dir: "/tmp/repo".to_string(),
+                    agent_id: "goose".to_string(),
+                    title: None,
+                    created_at: now,
+                    updated_at: now,
+                    forked_from: None,
+                    acp_session_id: None,
+                })
+                .unwrap();
+        }
+        writer.append("s1", &TranscriptTurn::user("hi")).unwrap();
+
+        delete_session(&store, &writer, "s1").unwrap();
+        assert!(!writer.path_for("s1").unwrap().exists());
+        assert!(store.get_session("s1").unwrap().is_none());
+        // A session without a transcript deletes cleanly
+        delete_session(&store, &writer, "s2").unwrap();
+        assert!(store.get_session("s2").unwrap().is_none());
+    }
+}
@@ -0,0 +1,202 @@
+//! Environment diagnostics for support requests.
+//!
+//! Collects everything needed to tell whether Staged can run on a machine:
+//! which ACP agents were found, whether git is usable, where data and config
+//! live and whether they're writable, and what each SQLite store contains.
+//! Nothing here mutates state; databases are opened read-only.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use rusqlite::{Connection, OpenFlags};
+use serde::Serialize;
+
+use crate::ai::{self, AcpProviderInfo};
+
+/// The app's SQLite stores: (name, file name within the app data dir).
+const STORES: &[(&str, &str)] = &[
+    ("data", "data.db"),
+    ("review", "staged.db"),
+    ("action_history", "action_history.db"),
+];
+
+/// A snapshot of the environment.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostics {
+    pub app_version: String,
+    pub os: String,
+    pub providers: Vec<AcpProviderInfo>,
+    pub git: GitDiagnostics,
+    pub data_dir: DirDiagnostics,
+    /// `None` if the platform has no config directory
+    pub config_dir: Option<DirDiagnostics>,
+    pub stores: Vec<StoreDiagnostics>,
+}
+
+/// Availability of the git CLI and the bundled libgit2.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitDiagnostics {
+    /// Output of `git --version`, or `None` if git couldn't be run
+    pub cli_version: Option<String>,
+    pub libgit2_version: String,
+}
+
+/// A directory the app reads from or writes to.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirDiagnostics {
+    pub path: PathBuf,
+    pub exists: bool,
+    pub writable: bool,
+}
+
+/// One SQLite store.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoreDiagnostics {
+    pub name: String,
+    pub path: PathBuf,
+    pub exists: bool,
+    pub size_bytes: Option<u64>,
+    /// `PRAGMA user_version`
+    pub schema_version: Option<i64>,
+    /// Tables present, which is what the additive migrations key off
+    pub tables: Vec<String>,
+    /// Why the store couldn't be inspected, if it couldn't
+    pub error: Option<String>,
+}
+
+impl Diagnostics {
+    /// Collect diagnostics for an app data directory.
+    ///
+    /// Blocking: provider discovery and `git --version` spawn processes.
+    pub fn collect(data_dir: &Path) -> Self {
+        Self {
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: format!("{} {}", std::env::consts::OS, std::env::consts::ARCH),
+            providers: ai::discover_acp_providers(),
+            git: GitDiagnostics::collect(),
+            data_dir: DirDiagnostics::inspect(data_dir),
+            config_dir: dirs::config_dir().map(|dir| DirDiagnostics::inspect(&dir.join("staged"))),
+            stores: STORES
+                .iter()
+                .map(|(name, file)| StoreDiagnostics::inspect(name, &data_dir.join(file)))
+                .collect(),
+        }
+    }
+}
+
+impl GitDiagnostics {
+    fn collect() -> Self {
+        let cli_version = Command::new("git")
+            .arg("--version")
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+        let (major, minor, rev) = git2::Version::get().libgit2_version();
+        Self {
+            cli_version,
+            libgit2_version: format!("{major}.{minor}.{rev}"),
+        }
+    }
+}
+
+impl DirDiagnostics {
+    fn inspect(path: &Path) -> Self {
+        let exists = path.is_dir();
+        Self {
+            path: path.to_path_buf(),
+            exists,
+            writable: exists && is_writable(path),
+        }
+    }
+}
+
+/// Whether a file can be created in `dir`, checked by creating one.
+fn is_writable(dir: &Path) -> bool {
+    let probe = dir.join(format!(".staged-write-test-{}", uuid::Uuid::new_v4()));
+    let ok = std::fs::write(&probe, b"").is_ok();
+    let _ = std::fs::remove_file(&probe);
+    ok
+}
+
+impl StoreDiagnostics {
+    fn inspect(name: &str, path: &Path) -> Self {
+        let mut store = Self {
+            name: name.to_string(),
+            path: path.to_path_buf(),
+            exists: path.is_file(),
+            size_bytes: None,
+            schema_version: None,
+            tables: Vec::new(),
+            error: None,
+        };
+        if !store.exists {
+            return store;
+        }
+        store.size_bytes = std::fs::metadata(path).ok().map(|m| m.len());
+
+        let result =
+            Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).and_then(|conn| {
+                let version = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+                let mut stmt = conn.prepare(
+                    "SELECT name FROM sqlite_master
+                     WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+                )?;
+                let tables = stmt
+                    .query_map([], |row| row.get(0))?
+                    .collect::<rusqlite::Result<Vec<String>>>()?;
+                Ok((version, tables))
+            });
+        match result {
+            Ok((version, tables)) => {
+                store.schema_version = Some(version);
+                store.tables = tables;
+            }
+            Err(e) => store.error = Some(e.to_string()),
+        }
+        store
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::review::ReviewStore;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_collect_populates_report() {
+        let dir = tempdir().unwrap();
+        ReviewStore::open(dir.path().join("staged.db")).unwrap();
+
+        let report = Diagnostics::collect(dir.path());
+        assert!(!report.app_version.is_empty());
+        assert!(!report.git.libgit2_version.is_empty());
+        assert!(report.data_dir.exists && report.data_dir.writable);
+        assert_eq!(report.stores.len(), STORES.len());
+
+        let review = report.stores.iter().find(|s| s.name == "review").unwrap();
+        assert!(review.exists);
+        assert_eq!(review.error, None);
+        assert!(review.tables.iter().any(|t| t == "reviews"));
+
+        let data = report.stores.iter().find(|s| s.name == "data").unwrap();
+        assert!(!data.exists);
+        assert!(data.tables.is_empty());
+
+        // Serializes for the frontend
+        serde_json::to_string(&report).unwrap();
+    }
+
+    #[test]
+    fn test_missing_dir_is_not_writable() {
+        let dir = tempdir().unwrap();
+        let status = DirDiagnostics::inspect(&dir.path().join("missing"));
+        assert!(!status.exists);
+        assert!(!status.writable);
+    }
+}
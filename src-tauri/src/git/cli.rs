@@ -1,3 +1,4 @@
+use std::ffi::OsStr;
 use std::path::Path;
 use std::process::Command;
 use thiserror::Error;
@@ -18,10 +19,56 @@ pub enum GitError {
 
     #[error("path contains invalid UTF-8: {0}")]
     InvalidPath(String),
+
+    #[error("invalid repository path: {0}")]
+    InvalidRepoPath(String),
+
+    #[error("invalid glob pattern: {0}")]
+    InvalidGlob(String),
+
+    #[error("nothing to commit - no changes are staged")]
+    NothingToCommit,
+
+    #[error("commit message is empty")]
+    EmptyCommitMessage,
+
+    #[error("invalid commit identity: {0}")]
+    InvalidIdentity(String),
+
+    #[error("git operation timed out after {0:?}")]
+    Timeout(std::time::Duration),
 }
 
 /// Run a git command and return stdout as a string
 pub fn run(repo: &Path, args: &[&str]) -> Result<String, GitError> {
+    run_with_env(repo, args, &[])
+}
+
+/// Run a git command with extra environment variables (e.g. `GIT_INDEX_FILE`)
+pub fn run_with_env(
+    repo: &Path,
+    args: &[&str],
+    envs: &[(&str, &OsStr)],
+) -> Result<String, GitError> {
+    String::from_utf8(run_raw(repo, args, envs)?).map_err(|_| GitError::InvalidUtf8)
+}
+
+/// Run a git command and return stdout as raw bytes, for file contents that
+/// may not be UTF-8
+pub fn run_bytes(repo: &Path, args: &[&str]) -> Result<Vec<u8>, GitError> {
+    run_raw(repo, args, &[])
+}
+
+/// [`run_bytes`] with extra environment variables
+pub fn run_bytes_with_env(
+    repo: &Path,
+    args: &[&str],
+    envs: &[(&str, &OsStr)],
+) -> Result<Vec<u8>, GitError> {
+    run_raw(repo, args, envs)
+}
+
+fn run_raw(repo: &Path, args: &[&str], envs: &[(&str, &OsStr)]) -> Result<Vec<u8>, GitError> {
     let repo_str = repo
         .to_str()
         .ok_or_else(|| GitError::InvalidPath(repo.display().to_string()))?;
@@ -29,6 +76,7 @@ pub fn run(repo: &Path, args: &[&str]) -> Result<String, GitError> {
     let output = Command::new("git")
         .args(["-C", repo_str])
         .args(args)
+        .envs(envs.iter().copied())
         .output()
         .map_err(|e| {
             if e.kind() == std::io::ErrorKind::NotFound {
@@ -46,5 +94,5 @@ pub fn run(repo: &Path, args: &[&str]) -> Result<String, GitError> {
         return Err(GitError::CommandFailed(stderr.into_owned()));
     }
 
-    String::from_utf8(output.stdout).map_err(|_| GitError::InvalidUtf8)
+    Ok(output.stdout)
 }
@@ -1,14 +1,62 @@
 //! Git commit operations.
 
 use super::cli::{self, GitError};
+use super::refs::{self, EMPTY_TREE};
+use serde::Deserialize;
+use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 
+/// Overrides for the pre-flight checks run before committing, trailers to
+/// append to the message, and the identity to record. Unset fields take
+/// their defaults when deserialized.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CommitOptions {
+    /// Allow a commit whose tree is identical to HEAD's
+    pub allow_empty: bool,
+    /// Allow an empty or whitespace-only commit message
+    pub allow_empty_message: bool,
+    /// Co-authors as `Name <email>`, each added as a `Co-authored-by:` trailer
+    pub co_authors: Vec<String>,
+    /// Add a `Signed-off-by:` trailer for the committer
+    pub sign_off: bool,
+    /// Author name; unset fields fall back to the repo's configured identity
+    pub author_name: Option<String>,
+    pub author_email: Option<String>,
+    /// Committer name; unset fields fall back to the repo's configured identity
+    pub committer_name: Option<String>,
+    pub committer_email: Option<String>,
+}
+
 /// Create a commit with the specified files.
 /// All listed files are fully staged, then committed together.
 /// Returns the short SHA of the new commit.
-pub fn commit(repo: &Path, paths: &[PathBuf], message: &str) -> Result<String, GitError> {
+///
+/// Fails with `EmptyCommitMessage` or `NothingToCommit` unless the
+/// corresponding override is set in `options`, and with `InvalidIdentity`
+/// if an identity override is malformed.
+pub fn commit(
+    repo: &Path,
+    paths: &[PathBuf],
+    message: &str,
+    options: &CommitOptions,
+) -> Result<String, GitError> {
+    if message.trim().is_empty() && !options.allow_empty_message {
+        return Err(GitError::EmptyCommitMessage);
+    }
+    let identity = identity_env(options)?;
+    let envs: Vec<(&str, &OsStr)> = identity
+        .iter()
+        .map(|(key, value)| (*key, OsStr::new(value)))
+        .collect();
+
     // Reset the index to HEAD first to ensure clean state
-    cli::run(repo, &["reset", "HEAD"])?;
+    // (or empty it, when there are no commits yet)
+    if refs::is_head_unborn(repo) {
+        cli::run(repo, &["read-tree", "--empty"])?;
+    } else {
+        cli::run(repo, &["reset", "HEAD"])?;
+    }
 
     // Stage each file
     for path in paths {
@@ -16,15 +64,431 @@ pub fn commit(repo: &Path, paths: &[PathBuf], message: &str) -> Result<String, G
         cli::run(repo, &["add", "--", &path_str])?;
     }
 
+    if !options.allow_empty && index_matches_head(repo)? {
+        return Err(GitError::NothingToCommit);
+    }
+
+    let message = with_trailers(repo, message, options, &envs)?;
+
     // Create the commit
-    cli::run(repo, &["commit", "-m", message])?;
+    let mut args = vec!["commit", "-m", &message];
+    if options.allow_empty {
+        args.push("--allow-empty");
+    }
+    if options.allow_empty_message {
+        args.push("--allow-empty-message");
+    }
+    cli::run_with_env(repo, &args, &envs)?;
 
     // Get the short SHA of the new commit
     let output = cli::run(repo, &["rev-parse", "--short", "HEAD"])?;
     Ok(output.trim().to_string())
 }
 
+/// Git environment variables for the identity overrides in `options`.
+fn identity_env(options: &CommitOptions) -> Result<Vec<(&'static str, String)>, GitError> {
+    let fields = [
+        ("GIT_AUTHOR_NAME", &options.author_name, false),
+        ("GIT_AUTHOR_EMAIL", &options.author_email, true),
+        ("GIT_COMMITTER_NAME", &options.committer_name, false),
+        ("GIT_COMMITTER_EMAIL", &options.committer_email, true),
+    ];
+
+    let mut envs = Vec::new();
+    for (key, value, is_email) in fields {
+        let Some(value) = value.as_deref().map(str::trim) else {
+            continue;
+        };
+        let valid = if is_email {
+            is_valid_email(value)
+        } else {
+            !value.is_empty() && !value.contains(['<', '>', '\n'])
+        };
+        if !valid {
+            let kind = if is_email { "email" } else { "name" };
+            return Err(GitError::InvalidIdentity(format!(
+                "invalid {kind}: {value:?}"
+            )));
+        }
+        envs.push((key, value.to_string()));
+    }
+    Ok(envs)
+}
+
+/// Loose `local@domain` check: one `@`, both sides non-empty, a dot in the
+/// domain, and nothing that would break git's `Name <email>` ident format.
+fn is_valid_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+    let forbidden = |c: char| c.is_whitespace() || matches!(c, '<' | '>' | '@');
+    !local.is_empty()
+        && !local.contains(forbidden)
+        && !domain.contains(forbidden)
+        && domain
+            .split_once('.')
+            .is_some_and(|(host, rest)| !host.is_empty() && !rest.is_empty())
+}
+
+/// Append the co-author and sign-off trailers requested in `options`.
+fn with_trailers(
+    repo: &Path,
+    message: &str,
+    options: &CommitOptions,
+    envs: &[(&str, &OsStr)],
+) -> Result<String, GitError> {
+    let mut trailers: Vec<(&str, String)> = options
+        .co_authors
+        .iter()
+        .map(|author| author.trim())
+        .filter(|author| !author.is_empty())
+        .map(|author| ("Co-authored-by", author.to_string()))
+        .collect();
+    if options.sign_off {
+        trailers.push(("Signed-off-by", committer_ident(repo, envs)?));
+    }
+    if trailers.is_empty() {
+        return Ok(message.to_string());
+    }
+    Ok(append_trailers(message, &trailers))
+}
+
+/// The committer as `Name <email>`, per git's own identity resolution
+/// (including any overrides in `envs`).
+fn committer_ident(repo: &Path, envs: &[(&str, &OsStr)]) -> Result<String, GitError> {
+    // Format is `Name <email> <timestamp> <tz>`
+    let ident = cli::run_with_env(repo, &["var", "GIT_COMMITTER_IDENT"], envs)?;
+    let ident = ident.trim();
+    let end = ident.rfind('>').map_or(ident.len(), |i| i + 1);
+    Ok(ident[..end].to_string())
+}
+
+/// Append `Token: value` trailers to a commit message.
+///
+/// If the message already ends in a trailer block, the new trailers join it;
+/// otherwise they start a new paragraph after a blank line. Trailers already
+/// present in the message are not repeated.
+fn append_trailers(message: &str, trailers: &[(&str, String)]) -> String {
+    let message = message.trim_end();
+    let existing = trailer_block(message);
+
+    let mut added = Vec::new();
+    for (token, value) in trailers {
+        let duplicate = existing
+            .iter()
+            .chain(added.iter())
+            .any(|(t, v)| t.eq_ignore_ascii_case(token) && v == value);
+        if !duplicate {
+            added.push((token.to_string(), value.clone()));
+        }
+    }
+
+    if added.is_empty() {
+        return format!("{message}\n");
+    }
+
+    let mut result = message.to_string();
+    if message.is_empty() {
+        // Nothing to separate from
+    } else if existing.is_empty() {
+        result.push_str("\n\n");
+    } else {
+        result.push('\n');
+    }
+    let lines: Vec<String> = added.iter().map(|(t, v)| format!("{t}: {v}")).collect();
+    result.push_str(&lines.join("\n"));
+    result.push('\n');
+    result
+}
+
+/// Parse the trailers in the message's final paragraph. Empty if that
+/// paragraph isn't entirely trailers, or if it's the subject line itself.
+fn trailer_block(message: &str) -> Vec<(String, String)> {
+    let Some((_, last)) = message.rsplit_once("\n\n") else {
+        return Vec::new();
+    };
+    last.lines()
+        .map(parse_trailer)
+        .collect::<Option<Vec<_>>>()
+        .unwrap_or_default()
+}
+
+/// Parse a `Token: value` line, where the token has no spaces.
+fn parse_trailer(line: &str) -> Option<(String, String)> {
+    let (token, value) = line.split_once(':')?;
+    let valid = !token.is_empty() && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    valid.then(|| (token.to_string(), value.trim().to_string()))
+}
+
+/// Read the repo's configured commit template (`commit.template`), for
+/// prefilling new commit messages.
+///
+/// Comment lines (starting with `#`) are stripped as git does when
+/// committing. Returns None if no template is configured, the file can't be
+/// read, or nothing is left after stripping.
+pub fn commit_template(repo: &Path) -> Option<String> {
+    // --path expands `~/`; relative paths are taken from the repo root
+    let configured = cli::run(repo, &["config", "--path", "commit.template"]).ok()?;
+    let configured = configured.trim();
+    if configured.is_empty() {
+        return None;
+    }
+
+    let content = std::fs::read_to_string(repo.join(configured)).ok()?;
+    let template = content
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let template = template.trim_end_matches('\n');
+    (!template.trim().is_empty()).then(|| format!("{template}\n"))
+}
+
+/// Whether the index tree is identical to HEAD's tree (nothing staged).
+fn index_matches_head(repo: &Path) -> Result<bool, GitError> {
+    let index_tree = cli::run(repo, &["write-tree"])?;
+    let head_tree = cli::run(repo, &["rev-parse", "--verify", "--quiet", "HEAD^{tree}"])
+        .unwrap_or_else(|_| EMPTY_TREE.to_string());
+    Ok(index_tree.trim() == head_tree.trim())
+}
+
 #[cfg(test)]
 mod tests {
-    // Integration tests would require a real git repo
+    use super::*;
+    use crate::git::test_support::{git, init_test_repo};
+
+    fn init_repo() -> tempfile::TempDir {
+        let (dir, _) = init_test_repo();
+        std::fs::write(dir.path().join("a.txt"), "one\n").unwrap();
+        git(dir.path(), &["add", "a.txt"]);
+        git(dir.path(), &["commit", "-m", "initial"]);
+        dir
+    }
+
+    #[test]
+    fn test_rejects_empty_index() {
+        let dir = init_repo();
+        let result = commit(
+            dir.path(),
+            &[PathBuf::from("a.txt")],
+            "no changes",
+            &CommitOptions::default(),
+        );
+        assert!(matches!(result, Err(GitError::NothingToCommit)));
+    }
+
+    #[test]
+    fn test_rejects_empty_message() {
+        let dir = init_repo();
+        std::fs::write(dir.path().join("a.txt"), "two\n").unwrap();
+        let result = commit(
+            dir.path(),
+            &[PathBuf::from("a.txt")],
+            "  \n\t",
+            &CommitOptions::default(),
+        );
+        assert!(matches!(result, Err(GitError::EmptyCommitMessage)));
+    }
+
+    #[test]
+    fn test_allow_empty_override() {
+        let dir = init_repo();
+        let options = CommitOptions {
+            allow_empty: true,
+            ..Default::default()
+        };
+        let sha = commit(dir.path(), &[], "checkpoint", &options).unwrap();
+        assert!(!sha.is_empty());
+
+        let log = cli::run(dir.path(), &["log", "--oneline"]).unwrap();
+        assert_eq!(log.lines().count(), 2);
+    }
+
+    #[test]
+    fn test_commits_staged_changes() {
+        let dir = init_repo();
+        std::fs::write(dir.path().join("a.txt"), "two\n").unwrap();
+        let sha = commit(
+            dir.path(),
+            &[PathBuf::from("a.txt")],
+            "update a",
+            &CommitOptions::default(),
+        )
+        .unwrap();
+        assert!(!sha.is_empty());
+    }
+
+    #[test]
+    fn test_first_commit_in_empty_repo() {
+        let (dir, _) = init_test_repo();
+        std::fs::write(dir.path().join("a.txt"), "one\n").unwrap();
+
+        let sha = commit(
+            dir.path(),
+            &[PathBuf::from("a.txt")],
+            "initial",
+            &CommitOptions::default(),
+        )
+        .unwrap();
+        assert!(!sha.is_empty());
+    }
+
+    fn head_message(repo: &Path) -> String {
+        cli::run(repo, &["log", "-1", "--format=%B"]).unwrap()
+    }
+
+    #[test]
+    fn test_single_co_author_trailer() {
+        let dir = init_repo();
+        std::fs::write(dir.path().join("a.txt"), "two\n").unwrap();
+        let options = CommitOptions {
+            co_authors: vec!["Ada Lovelace <ada@example.com>".to_string()],
+            ..Default::default()
+        };
+        commit(dir.path(), &[PathBuf::from("a.txt")], "update a", &options).unwrap();
+
+        assert_eq!(
+            head_message(dir.path()).trim_end(),
+            "update a\n\nCo-authored-by: Ada Lovelace <ada@example.com>"
+        );
+        // git itself recognizes the block as trailers
+        let parsed = cli::run(dir.path(), &["log", "-1", "--format=%(trailers:only)"]).unwrap();
+        assert_eq!(
+            parsed.trim_end(),
+            "Co-authored-by: Ada Lovelace <ada@example.com>"
+        );
+    }
+
+    #[test]
+    fn test_multiple_co_authors_and_sign_off() {
+        let dir = init_repo();
+        std::fs::write(dir.path().join("a.txt"), "two\n").unwrap();
+        let options = CommitOptions {
+            co_authors: vec![
+                "Ada Lovelace <ada@example.com>".to_string(),
+                "Alan Turing <alan@example.com>".to_string(),
+            ],
+            sign_off: true,
+            ..Default::default()
+        };
+        commit(
+            dir.path(),
+            &[PathBuf::from("a.txt")],
+            "update a\n\nLonger explanation.",
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(
+            head_message(dir.path()).trim_end(),
+            "update a\n\nLonger explanation.\n\n\
+             Co-authored-by: Ada Lovelace <ada@example.com>\n\
+             Co-authored-by: Alan Turing <alan@example.com>\n\
+             Signed-off-by: Test <test@example.com>"
+        );
+    }
+
+    #[test]
+    fn test_explicit_identity_is_recorded() {
+        let dir = init_repo();
+        std::fs::write(dir.path().join("a.txt"), "two\n").unwrap();
+        let options = CommitOptions {
+            author_name: Some("Ada Lovelace".to_string()),
+            author_email: Some("ada@example.com".to_string()),
+            committer_name: Some("CI Bot".to_string()),
+            committer_email: Some("ci@example.com".to_string()),
+            sign_off: true,
+            ..Default::default()
+        };
+        commit(dir.path(), &[PathBuf::from("a.txt")], "update a", &options).unwrap();
+
+        let idents = cli::run(dir.path(), &["log", "-1", "--format=%an <%ae>%n%cn <%ce>"]).unwrap();
+        assert_eq!(
+            idents.trim_end(),
+            "Ada Lovelace <ada@example.com>\nCI Bot <ci@example.com>"
+        );
+        assert!(head_message(dir.path()).contains("Signed-off-by: CI Bot <ci@example.com>"));
+
+        // Unset fields fall back to the repo's configured identity, including
+        // options passed from the frontend
+        std::fs::write(dir.path().join("a.txt"), "three\n").unwrap();
+        let options: CommitOptions =
+            serde_json::from_str(r#"{"authorName": "Ada Lovelace"}"#).unwrap();
+        commit(dir.path(), &[PathBuf::from("a.txt")], "update a", &options).unwrap();
+        let idents = cli::run(dir.path(), &["log", "-1", "--format=%an <%ae>%n%cn <%ce>"]).unwrap();
+        assert_eq!(
+            idents.trim_end(),
+            "Ada Lovelace <test@example.com>\nTest <test@example.com>"
+        );
+    }
+
+    #[test]
+    fn test_rejects_malformed_identity() {
+        let dir = init_repo();
+        std::fs::write(dir.path().join("a.txt"), "two\n").unwrap();
+        for email in [
+            "ada",
+            "ada@",
+            "@example.com",
+            "ada@example",
+            "a da@example.com",
+        ] {
+            let options = CommitOptions {
+                author_email: Some(email.to_string()),
+                ..Default::default()
+            };
+            let result = commit(dir.path(), &[PathBuf::from("a.txt")], "update a", &options);
+            assert!(
+                matches!(result, Err(GitError::InvalidIdentity(_))),
+                "{email} was accepted"
+            );
+        }
+        let options = CommitOptions {
+            committer_name: Some("Eve <eve@example.com>".to_string()),
+            ..Default::default()
+        };
+        let result = commit(dir.path(), &[PathBuf::from("a.txt")], "update a", &options);
+        assert!(matches!(result, Err(GitError::InvalidIdentity(_))));
+
+        let log = cli::run(dir.path(), &["log", "--oneline"]).unwrap();
+        assert_eq!(log.lines().count(), 1);
+    }
+
+    #[test]
+    fn test_trailers_join_existing_block() {
+        let trailers = [
+            ("Co-authored-by", "Ada <ada@example.com>".to_string()),
+            ("Co-authored-by", "Alan <alan@example.com>".to_string()),
+        ];
+        let message = "fix parser\n\nRefs: #12\nCo-authored-by: Ada <ada@example.com>\n";
+        assert_eq!(
+            append_trailers(message, &trailers),
+            "fix parser\n\nRefs: #12\nCo-authored-by: Ada <ada@example.com>\n\
+             Co-authored-by: Alan <alan@example.com>\n"
+        );
+
+        // A subject that happens to look like a trailer isn't a trailer block
+        assert_eq!(
+            append_trailers("docs: fix typo", &trailers[..1]),
+            "docs: fix typo\n\nCo-authored-by: Ada <ada@example.com>\n"
+        );
+    }
+
+    #[test]
+    fn test_commit_template() {
+        let dir = init_repo();
+        assert_eq!(commit_template(dir.path()), None);
+
+        std::fs::write(
+            dir.path().join(".gitmessage"),
+            "feat: \n\n# Explain why this change is needed\nRefs: \n\n# Lines starting with # are removed\n",
+        )
+        .unwrap();
+        git(dir.path(), &["config", "commit.template", ".gitmessage"]);
+
+        assert_eq!(
+            commit_template(dir.path()),
+            Some("feat: \n\nRefs: \n".to_string())
+        );
+    }
 }
@@ -0,0 +1,190 @@
+//! Merge conflict marker parsing.
+//!
+//! Recognizes both the default two-way style and diff3 style (which adds a
+//! `|||||||` base section). Markers must be exactly seven characters,
+//! optionally followed by a space and a label.
+
+use super::types::Span;
+use serde::{Deserialize, Serialize};
+
+/// A conflicted region. Spans are 0-indexed, end-exclusive line ranges of
+/// each side's content, not including the marker lines.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConflictRegion {
+    /// Whole region, from `<<<<<<<` through `>>>>>>>` inclusive
+    pub span: Span,
+    pub ours: Span,
+    /// Common ancestor, present only with diff3-style markers
+    pub base: Option<Span>,
+    pub theirs: Span,
+    /// Label after `<<<<<<<` (e.g. "HEAD")
+    pub ours_label: Option<String>,
+    /// Label after `>>>>>>>` (e.g. the merged branch)
+    pub theirs_label: Option<String>,
+}
+
+enum Marker {
+    Ours(Option<String>),
+    Base,
+    Separator,
+    Theirs(Option<String>),
+}
+
+fn parse_marker(line: &str) -> Option<Marker> {
+    let line = line.trim_end_matches(['\r', '\n']);
+    let label = |rest: &str| -> Option<Option<String>> {
+        if rest.is_empty() {
+            Some(None)
+        } else {
+            let label = rest.strip_prefix(' ')?.trim();
+            Some((!label.is_empty()).then(|| label.to_string()))
+        }
+    };
+
+    if let Some(rest) = line.strip_prefix("<<<<<<<") {
+        label(rest).map(Marker::Ours)
+    } else if let Some(rest) = line.strip_prefix(">>>>>>>") {
+        label(rest).map(Marker::Theirs)
+    } else if let Some(rest) = line.strip_prefix("|||||||") {
+        label(rest).map(|_| Marker::Base)
+    } else if line == "=======" {
+        Some(Marker::Separator)
+    } else {
+        None
+    }
+}
+
+/// Find all complete conflict regions in a file's lines.
+/// Unterminated or malformed regions are ignored.
+pub fn parse_conflicts<S: AsRef<str>>(lines: &[S]) -> Vec<ConflictRegion> {
+    let mut regions = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let Some(Marker::Ours(ours_label)) = parse_marker(lines[i].as_ref()) else {
+            i += 1;
+            continue;
+        };
+        let start = i;
+
+        match parse_region(lines, start, ours_label) {
+            Some(region) => {
+                i = region.span.end as usize;
+                regions.push(region);
+            }
+            None => i = start + 1,
+        }
+    }
+
+    regions
+}
+
+/// Parse one region whose `<<<<<<<` marker is at `start`.
+fn parse_region<S: AsRef<str>>(
+    lines: &[S],
+    start: usize,
+    ours_label: Option<String>,
+) -> Option<ConflictRegion> {
+    let mut base_marker = None;
+    let mut separator = None;
+
+    for (offset, line) in lines[start + 1..].iter().enumerate() {
+        let idx = start + 1 + offset;
+        match parse_marker(line.as_ref()) {
+            // A nested start before this region closes means it's malformed
+            Some(Marker::Ours(_)) => return None,
+            Some(Marker::Base) if separator.is_none() && base_marker.is_none() => {
+                base_marker = Some(idx);
+            }
+            Some(Marker::Separator) if separator.is_none() => separator = Some(idx),
+            Some(Marker::Theirs(theirs_label)) => {
+                let sep = separator?;
+                let ours_end = base_marker.unwrap_or(sep);
+                let span = |from: usize, to: usize| Span::new(from as u32, to as u32);
+                return Some(ConflictRegion {
+                    span: span(start, idx + 1),
+                    ours: span(start + 1, ours_end),
+                    base: base_marker.map(|b| span(b + 1, sep)),
+                    theirs: span(sep + 1, idx),
+                    ours_label,
+                    theirs_label,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_two_way_conflict() {
+        let content = lines(
+            "fn main() {\n\
+             <<<<<<< HEAD\n\
+             \x20   println!(\"ours\");\n\
+             =======\n\
+             \x20   println!(\"theirs\");\n\
+             \x20   println!(\"more\");\n\
+             >>>>>>> feature\n\
+             }",
+        );
+
+        let conflicts = parse_conflicts(&content);
+        assert_eq!(
+            conflicts,
+            vec![ConflictRegion {
+                span: Span::new(1, 7),
+                ours: Span::new(2, 3),
+                base: None,
+                theirs: Span::new(4, 6),
+                ours_label: Some("HEAD".to_string()),
+                theirs_label: Some("feature".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_diff3_conflict() {
+        let content = lines(
+            "<<<<<<< ours\n\
+             a = 1\n\
+             ||||||| base\n\
+             a = 0\n\
+             =======\n\
+             a = 2\n\
+             >>>>>>> theirs\n\
+             unrelated\n\
+             <<<<<<<\n\
+             =======\n\
+             b\n\
+             >>>>>>>",
+        );
+
+        let conflicts = parse_conflicts(&content);
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].ours, Span::new(1, 2));
+        assert_eq!(conflicts[0].base, Some(Span::new(3, 4)));
+        assert_eq!(conflicts[0].theirs, Span::new(5, 6));
+
+        // Empty ours side, no labels
+        assert_eq!(conflicts[1].span, Span::new(8, 12));
+        assert_eq!(conflicts[1].ours, Span::new(9, 9));
+        assert_eq!(conflicts[1].theirs, Span::new(10, 11));
+        assert_eq!(conflicts[1].ours_label, None);
+    }
+
+    #[test]
+    fn test_ignores_incomplete_and_lookalike_markers() {
+        let content = lines("<<<<<<< HEAD\nours\n========\n<<<<<<<<\nno end");
+        assert!(parse_conflicts(&content).is_empty());
+    }
+}
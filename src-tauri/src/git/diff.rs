@@ -1,13 +1,143 @@
+//! Listing changed files and computing per-file diffs.
+//!
+//! Compatibility: `FileDiff` and `Review` are serialized as JSON for the
+//! frontend and external tools, and each carries a `schema_version`. Adding an
+//! optional field is backwards compatible and doesn't change the version;
+//! renaming or removing a field, or changing what one means, must bump
+//! `FileDiff::SCHEMA_VERSION` (or `Review::SCHEMA_VERSION`) so consumers can
+//! tell the shapes apart.
+
 use super::cli::{self, GitError};
+use super::conflict;
+use super::generated;
 use super::refs;
 use super::types::*;
-use git2::{DiffOptions, Repository};
+use crate::util::{display_path, escape_path_bytes, unescape_path, GlobSet, GlobVars};
+use git2::{DiffFindOptions, DiffOptions, Repository};
 use std::cell::RefCell;
-use std::path::Path;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+/// Default upper bound for computing a single file diff.
+pub const DEFAULT_DIFF_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default size above which a file's content isn't loaded for diffing.
+pub const DEFAULT_MAX_DIFF_FILE_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Limits applied when computing a single file diff.
+#[derive(Debug, Clone, Copy)]
+pub struct FileDiffOptions {
+    /// Give up on the diff after this long
+    pub timeout: Duration,
+    /// Files larger than this are reported as `FileContent::TooLarge`
+    pub max_file_bytes: u64,
+    /// Algorithm used to compute hunks
+    pub algorithm: DiffAlgorithm,
+}
+
+impl Default for FileDiffOptions {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_DIFF_TIMEOUT,
+            max_file_bytes: DEFAULT_MAX_DIFF_FILE_BYTES,
+            algorithm: DiffAlgorithm::default(),
+        }
+    }
+}
+
+/// Similarity (percent) a deleted/added pair needs to count as a rename,
+/// matching git's `-M` default.
+pub const DEFAULT_RENAME_THRESHOLD: u16 = 50;
+
+/// Default number of files considered for rename detection, matching git's
+/// `diff.renameLimit` default.
+pub const DEFAULT_RENAME_LIMIT: usize = 1000;
+
+/// Git config key for the rename similarity threshold. Git has no config for
+/// this, only the `-M<n>%` flag, so it lives under our own section.
+pub const RENAME_THRESHOLD_CONFIG: &str = "staged.renameThreshold";
+
+/// How renames are detected when listing a commit range's files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenameOptions {
+    /// Minimum similarity percentage (0-100) for a rename
+    pub threshold: u16,
+    /// Rename detection is skipped for changesets with more files than this;
+    /// 0 means no limit, as in git
+    pub limit: usize,
+}
+
+impl Default for RenameOptions {
+    fn default() -> Self {
+        Self {
+            threshold: DEFAULT_RENAME_THRESHOLD,
+            limit: DEFAULT_RENAME_LIMIT,
+        }
+    }
+}
+
+impl RenameOptions {
+    /// Read `diff.renameLimit` and [`RENAME_THRESHOLD_CONFIG`] from the repo's
+    /// git config, falling back to the defaults for anything unset or invalid.
+    pub fn from_config(repo: &Path) -> Self {
+        let mut options = Self::default();
+        let Ok(config) = Repository::discover(repo).and_then(|r| r.config()) else {
+            return options;
+        };
+        if let Ok(limit) = config.get_i64("diff.renameLimit") {
+            // As in git, 0 (or less) means no limit
+            options.limit = usize::try_from(limit)
+                .ok()
+                .filter(|&l| l > 0)
+                .unwrap_or(usize::MAX);
+        }
+        if let Ok(threshold) = config.get_i64(RENAME_THRESHOLD_CONFIG) {
+            if let Ok(threshold @ 0..=100) = u16::try_from(threshold) {
+                options.threshold = threshold;
+            }
+        }
+        options
+    }
+
+    fn find_options(&self) -> DiffFindOptions {
+        let mut find = DiffFindOptions::new();
+        // libgit2 reads 0 as its default of 1000
+        let limit = if self.limit == 0 {
+            usize::MAX
+        } else {
+            self.limit
+        };
+        find.renames(true)
+            .rename_threshold(self.threshold)
+            .rename_limit(limit);
+        find
+    }
+
+    /// Equivalent `git diff` flags.
+    fn cli_args(&self) -> [String; 2] {
+        // git's -l treats 0 as unlimited
+        let limit = if self.limit == 0 || self.limit > i32::MAX as usize {
+            0
+        } else {
+            self.limit
+        };
+        [
+            format!("--find-renames={}%", self.threshold),
+            format!("-l{limit}"),
+        ]
+    }
+}
 
 /// Resolve a GitRef, converting MergeBase/MergeBaseOf to a concrete SHA.
+/// HEAD in a repo with no commits resolves to the empty tree.
 fn resolve_ref(repo: &Path, git_ref: &GitRef) -> Result<GitRef, GitError> {
     match git_ref {
+        GitRef::Rev(rev) if rev == "HEAD" && refs::is_head_unborn(repo) => {
+            Ok(GitRef::Rev(refs::EMPTY_TREE.to_string()))
+        }
         GitRef::MergeBase => {
             let default_branch = refs::detect_default_branch(repo)?;
             let sha = refs::merge_base(repo, &default_branch, "HEAD")?;
@@ -22,13 +152,29 @@ fn resolve_ref(repo: &Path, git_ref: &GitRef) -> Result<GitRef, GitError> {
 }
 
 /// Resolve a DiffSpec, converting any MergeBase refs to concrete SHAs.
-fn resolve_spec(repo: &Path, spec: &DiffSpec) -> Result<DiffSpec, GitError> {
+pub(super) fn resolve_spec(repo: &Path, spec: &DiffSpec) -> Result<DiffSpec, GitError> {
     Ok(DiffSpec {
         base: resolve_ref(repo, &spec.base)?,
         head: resolve_ref(repo, &spec.head)?,
     })
 }
 
+/// Build the storage key for a DiffSpec, resolving each side to a SHA.
+///
+/// The working tree is keyed as `@`. An unborn HEAD resolves to the empty
+/// tree, matching how the diff itself is computed.
+pub fn diff_id(repo: &Path, spec: &DiffSpec) -> Result<DiffId, GitError> {
+    let key = |git_ref: &GitRef| -> Result<String, GitError> {
+        match resolve_ref(repo, git_ref)? {
+            GitRef::WorkingTree => Ok("@".to_string()),
+            GitRef::Rev(rev) if rev == refs::EMPTY_TREE => Ok(rev),
+            GitRef::Rev(rev) => refs::resolve_ref(repo, &rev),
+            GitRef::MergeBase | GitRef::MergeBaseOf(_) => unreachable!("resolved above"),
+        }
+    };
+    Ok(DiffId::new(key(&spec.base)?, key(&spec.head)?))
+}
+
 /// Get unified diff output for a single file.
 ///
 /// Returns the standard unified diff format (like `git diff`).
@@ -83,19 +229,31 @@ struct Hunk {
 ///
 /// For commit..commit diffs: uses `git diff --name-status -z` since status doesn't
 /// support arbitrary commit ranges.
+///
+/// Each file is flagged `generated` from attributes, path patterns and
+/// content markers, matching the flag on its `FileDiff`.
 pub fn list_diff_files(repo: &Path, spec: &DiffSpec) -> Result<Vec<FileDiffSummary>, GitError> {
     // Resolve MergeBase to concrete SHA
     let spec = resolve_spec(repo, spec)?;
 
-    match (&spec.base, &spec.head) {
+    let mut files = match (&spec.base, &spec.head) {
         (GitRef::Rev(base), GitRef::WorkingTree) => {
             // Working tree diff - use git status for fsmonitor support
             list_working_tree_changes(repo, base)
         }
         (GitRef::Rev(base), GitRef::Rev(head)) => {
             // Commit range - use git diff
-            let args = ["diff", "--name-status", "-z", base.as_str(), head.as_str()];
-            let output = cli::run(repo, &args)?;
+            let [find_renames, rename_limit] = RenameOptions::from_config(repo).cli_args();
+            let args = [
+                "diff",
+                "--name-status",
+                "-z",
+                &find_renames,
+                &rename_limit,
+                base.as_str(),
+                head.as_str(),
+            ];
+            let output = run_nul_separated(repo, &args)?;
             parse_name_status(&output)
         }
         (GitRef::WorkingTree, _) => Err(GitError::CommandFailed(
@@ -105,28 +263,208 @@ pub fn list_diff_files(repo: &Path, spec: &DiffSpec) -> Result<Vec<FileDiffSumma
         | (_, GitRef::MergeBase | GitRef::MergeBaseOf(_)) => {
             unreachable!("MergeBase/MergeBaseOf should have been resolved")
         }
+    }?;
+
+    generated::mark_generated(repo, &spec, &mut files)?;
+    Ok(files)
+}
+
+/// Like [`list_diff_files`], reporting progress as it goes.
+///
+/// Commit ranges are diffed with libgit2 rather than the git CLI, so the
+/// walk over changed files can report through `on_progress`. The `diff` and
+/// `renames` phases are only reported as they begin, since libgit2 has no
+/// callback inside them; the `files` phase advances once per file. Working
+/// tree diffs still go through `git status`, which has no progress hooks, so
+/// they report only the start and end.
+pub fn list_diff_files_with_progress(
+    repo: &Path,
+    spec: &DiffSpec,
+    on_progress: &mut dyn FnMut(GitProgress),
+) -> Result<Vec<FileDiffSummary>, GitError> {
+    list_diff_files_with_renames(repo, spec, None, on_progress)
+}
+
+/// Like [`list_diff_files_with_progress`], detecting renames in commit ranges
+/// with `renames` instead of [`RenameOptions::from_config`].
+pub fn list_diff_files_with_renames(
+    repo: &Path,
+    spec: &DiffSpec,
+    renames: Option<RenameOptions>,
+    on_progress: &mut dyn FnMut(GitProgress),
+) -> Result<Vec<FileDiffSummary>, GitError> {
+    let spec = resolve_spec(repo, spec)?;
+
+    let mut files = match (&spec.base, &spec.head) {
+        (GitRef::Rev(base), GitRef::Rev(head)) => {
+            let renames = renames.unwrap_or_else(|| RenameOptions::from_config(repo));
+            list_range_with_progress(repo, base, head, &renames, on_progress)?
+        }
+        _ => {
+            on_progress(GitProgress {
+                phase: "status",
+                fraction: 0.0,
+            });
+            let files = list_diff_files(repo, &spec)?;
+            on_progress(GitProgress {
+                phase: "status",
+                fraction: 1.0,
+            });
+            return Ok(files);
+        }
+    };
+
+    generated::mark_generated(repo, &spec, &mut files)?;
+    Ok(files)
+}
+
+/// List a commit range's changed files via libgit2, detecting renames.
+fn list_range_with_progress(
+    repo_path: &Path,
+    base: &str,
+    head: &str,
+    renames: &RenameOptions,
+    on_progress: &mut dyn FnMut(GitProgress),
+) -> Result<Vec<FileDiffSummary>, GitError> {
+    let repo = Repository::discover(repo_path).map_err(|e| GitError::NotARepo(e.to_string()))?;
+    let tree = |rev: &str| {
+        repo.revparse_single(rev)
+            .and_then(|obj| obj.peel_to_tree())
+            .map_err(|e| GitError::CommandFailed(format!("Cannot resolve {rev}: {e}")))
+    };
+    let (base_tree, head_tree) = (tree(base)?, tree(head)?);
+
+    on_progress(GitProgress {
+        phase: "diff",
+        fraction: 0.0,
+    });
+    let mut diff = repo
+        .diff_tree_to_tree(Some(&base_tree), Some(&head_tree), None)
+        .map_err(|e| GitError::CommandFailed(format!("Failed to compute diff: {e}")))?;
+
+    on_progress(GitProgress {
+        phase: "renames",
+        fraction: 0.0,
+    });
+    diff.find_similar(Some(&mut renames.find_options()))
+        .map_err(|e| GitError::CommandFailed(format!("Failed to detect renames: {e}")))?;
+
+    // libgit2's own fraction stays at 0 without hunk callbacks, so count files
+    let total = diff.deltas().len();
+    let mut files = Vec::with_capacity(total);
+    diff.foreach(
+        &mut |delta, _| {
+            on_progress(GitProgress {
+                phase: "files",
+                fraction: files.len() as f32 / total as f32,
+            });
+            let old = delta.old_file().path().map(Path::to_path_buf);
+            let new = delta.new_file().path().map(Path::to_path_buf);
+            files.push(match delta.status() {
+                git2::Delta::Added | git2::Delta::Untracked => FileDiffSummary {
+                    before: None,
+                    after: new,
+                    generated: false,
+                },
+                git2::Delta::Deleted => FileDiffSummary {
+                    before: old,
+                    after: None,
+                    generated: false,
+                },
+                _ => FileDiffSummary {
+                    before: old,
+                    after: new,
+                    generated: false,
+                },
+            });
+            true
+        },
+        None,
+        None,
+        None,
+    )
+    .map_err(|e| GitError::CommandFailed(format!("Failed to iterate diff: {e}")))?;
+    on_progress(GitProgress {
+        phase: "files",
+        fraction: 1.0,
+    });
+
+    Ok(files)
+}
+
+/// Drop files whose path matches any of `exclude_globs`.
+///
+/// Patterns use the shared glob syntax (see `crate::util::glob`) against
+/// repo-relative paths. Unlike `.gitignore`, a pattern only anchors to the
+/// repo root when it starts with `/` (or `$REPO`); otherwise it matches at any
+/// depth, so `__snapshots__/**` excludes every snapshot directory. A pattern
+/// matching a directory excludes everything in it, and `!pattern` keeps files
+/// an earlier pattern excluded. A renamed file is dropped if either side of
+/// the rename matches. An empty list keeps every file.
+pub fn exclude_files(
+    files: Vec<FileDiffSummary>,
+    exclude_globs: &[String],
+) -> Result<Vec<FileDiffSummary>, GitError> {
+    if exclude_globs.is_empty() {
+        return Ok(files);
     }
+
+    let matcher = GlobSet::new(exclude_globs, &GlobVars::repo_relative())
+        .map_err(|e| GitError::InvalidGlob(e.to_string()))?;
+
+    let excluded =
+        |path: &Option<PathBuf>| path.as_ref().is_some_and(|p| matcher.is_match_or_parent(p));
+    Ok(files
+        .into_iter()
+        .filter(|f| !excluded(&f.before) && !excluded(&f.after))
+        .collect())
+}
+
+/// Diff what changed in `spec` since its head was at `previous_head`.
+///
+/// Returns one diff per file that is part of `spec` and differs between
+/// `previous_head` and the current head, so a reviewer who already reviewed
+/// `previous_head` only sees the new work. Files that changed only on the
+/// base side (e.g. after merging main in) aren't in `spec` and are skipped.
+pub fn get_incremental_diff(
+    repo: &Path,
+    spec: &DiffSpec,
+    previous_head: &str,
+) -> Result<Vec<FileDiff>, GitError> {
+    let in_review: std::collections::HashSet<PathBuf> = list_diff_files(repo, spec)?
+        .iter()
+        .map(|f| f.path().clone())
+        .collect();
+
+    let delta = DiffSpec {
+        base: GitRef::Rev(refs::resolve_ref(repo, previous_head)?),
+        head: spec.head.clone(),
+    };
+    list_diff_files(repo, &delta)?
+        .iter()
+        .filter(|f| in_review.contains(f.path()))
+        .map(|f| get_file_diff(repo, &delta, f.path()))
+        .collect()
 }
 
-/// List working tree changes using `git status --porcelain -z`.
 /// This uses fsmonitor when available, making it fast on large repos.
 ///
 /// When base is HEAD, we show all uncommitted changes (staged + unstaged + untracked).
 /// When base is another ref, we show what would change if you committed now and compared to that ref.
 fn list_working_tree_changes(repo: &Path, base: &str) -> Result<Vec<FileDiffSummary>, GitError> {
     // Get status (includes staged, unstaged, and untracked)
-    let output = cli::run(repo, &["status", "--porcelain", "-z"])?;
+    let output = run_nul_separated(repo, &["status", "--porcelain", "-z"])?;
     let status_files = parse_porcelain_status(repo, &output)?;
 
-    // If base is HEAD, status gives us exactly what we need
-    if base == "HEAD" {
+    // If base is HEAD (or there are no commits yet), status gives us exactly what we need
+    if base == "HEAD" || (base == refs::EMPTY_TREE && refs::is_head_unborn(repo)) {
         return Ok(status_files);
     }
 
     // For other bases (e.g., main), we need to combine:
     // 1. Files changed between base and HEAD (committed changes)
     // 2. Files with uncommitted changes (from status)
-    let diff_output = cli::run(repo, &["diff", "--name-status", "-z", base, "HEAD"])?;
+    let diff_output = run_nul_separated(repo, &["diff", "--name-status", "-z", base, "HEAD"])?;
     let committed_files = parse_name_status(&diff_output)?;
 
     // Merge: status files take precedence (they reflect current working tree state)
@@ -147,6 +485,18 @@ fn list_working_tree_changes(repo: &Path, base: &str) -> Result<Vec<FileDiffSumm
     Ok(result_map.into_values().collect())
 }
 
+/// Run a git command with `-z` output, escaping each NUL-separated field with
+/// `escape_path_bytes` so file names that aren't UTF-8 survive parsing. The
+/// parsers undo it with `unescape_path`.
+fn run_nul_separated(repo: &Path, args: &[&str]) -> Result<String, GitError> {
+    let output = cli::run_bytes(repo, args)?;
+    Ok(output
+        .split(|&b| b == 0)
+        .map(escape_path_bytes)
+        .collect::<Vec<_>>()
+        .join("\0"))
+}
+
 /// Parse `git status --porcelain -z` output.
 /// Format: XY PATH\0 (or XY OLD\0NEW\0 for renames)
 /// X = index status, Y = worktree status
@@ -194,13 +544,15 @@ fn parse_porcelain_status(repo: &Path, output: &str) -> Result<Vec<FileDiffSumma
                         for file in files {
                             results.push(FileDiffSummary {
                                 before: None,
-                                after: Some(file.into()),
+                                after: Some(unescape_path(&file)),
+                                generated: false,
                             });
                         }
                     } else {
                         results.push(FileDiffSummary {
                             before: None,
-                            after: Some(p.clone().into()),
+                            after: Some(unescape_path(p)),
+                            generated: false,
                         });
                     }
                 }
@@ -208,25 +560,29 @@ fn parse_porcelain_status(repo: &Path, output: &str) -> Result<Vec<FileDiffSumma
             ('A', _) | (_, 'A') => {
                 results.push(FileDiffSummary {
                     before: None,
-                    after: new_path.map(Into::into),
+                    after: new_path.map(|p| unescape_path(&p)),
+                    generated: false,
                 });
             }
             ('D', _) | (_, 'D') => {
                 results.push(FileDiffSummary {
-                    before: new_path.map(Into::into),
+                    before: new_path.map(|p| unescape_path(&p)),
                     after: None,
+                    generated: false,
                 });
             }
             ('R', _) | ('C', _) => {
                 results.push(FileDiffSummary {
-                    before: old_path.map(Into::into),
-                    after: new_path.map(Into::into),
+                    before: old_path.map(|p| unescape_path(&p)),
+                    after: new_path.map(|p| unescape_path(&p)),
+                    generated: false,
                 });
             }
             _ => {
                 results.push(FileDiffSummary {
-                    before: new_path.clone().map(Into::into),
-                    after: new_path.map(Into::into),
+                    before: new_path.clone().map(|p| unescape_path(&p)),
+                    after: new_path.map(|p| unescape_path(&p)),
+                    generated: false,
                 });
             }
         };
@@ -238,9 +594,10 @@ fn parse_porcelain_status(repo: &Path, output: &str) -> Result<Vec<FileDiffSumma
 /// Expand an untracked directory into its individual files.
 /// Uses `git ls-files --others --exclude-standard` to list untracked files.
 fn expand_untracked_dir(repo: &Path, dir: &str) -> Result<Vec<String>, GitError> {
-    let output = cli::run(
+    let dir = display_path(&unescape_path(dir));
+    let output = run_nul_separated(
         repo,
-        &["ls-files", "--others", "--exclude-standard", "-z", dir],
+        &["ls-files", "--others", "--exclude-standard", "-z", &dir],
     )?;
 
     Ok(output
@@ -269,7 +626,8 @@ fn parse_name_status(output: &str) -> Result<Vec<FileDiffSummary>, GitError> {
                 if let Some(path) = parts.next() {
                     results.push(FileDiffSummary {
                         before: None,
-                        after: Some(path.into()),
+                        after: Some(unescape_path(path)),
+                        generated: false,
                     });
                 }
             }
@@ -277,8 +635,9 @@ fn parse_name_status(output: &str) -> Result<Vec<FileDiffSummary>, GitError> {
                 // Deleted: just one path
                 if let Some(path) = parts.next() {
                     results.push(FileDiffSummary {
-                        before: Some(path.into()),
+                        before: Some(unescape_path(path)),
                         after: None,
+                        generated: false,
                     });
                 }
             }
@@ -286,8 +645,9 @@ fn parse_name_status(output: &str) -> Result<Vec<FileDiffSummary>, GitError> {
                 // Modified or Type changed: just one path
                 if let Some(path) = parts.next() {
                     results.push(FileDiffSummary {
-                        before: Some(path.into()),
-                        after: Some(path.into()),
+                        before: Some(unescape_path(path)),
+                        after: Some(unescape_path(path)),
+                        generated: false,
                     });
                 }
             }
@@ -296,8 +656,9 @@ fn parse_name_status(output: &str) -> Result<Vec<FileDiffSummary>, GitError> {
                 // Status might include similarity percentage like R100
                 if let (Some(old), Some(new)) = (parts.next(), parts.next()) {
                     results.push(FileDiffSummary {
-                        before: Some(old.into()),
-                        after: Some(new.into()),
+                        before: Some(unescape_path(old)),
+                        after: Some(unescape_path(new)),
+                        generated: false,
                     });
                 }
             }
@@ -314,9 +675,83 @@ fn parse_name_status(output: &str) -> Result<Vec<FileDiffSummary>, GitError> {
 /// Get full diff content for a single file using libgit2.
 /// This is reliable and battle-tested - we use git CLI only for list_diff_files
 /// where fsmonitor support matters for performance.
+///
+/// Uses the default limits; see `get_file_diff_with_options`.
 pub fn get_file_diff(repo_path: &Path, spec: &DiffSpec, path: &Path) -> Result<FileDiff, GitError> {
+    get_file_diff_with_options(repo_path, spec, path, FileDiffOptions::default())
+}
+
+/// Like `get_file_diff`, but with explicit limits.
+///
+/// The diff is computed on a worker thread. If it doesn't finish in time the
+/// caller gets `GitError::Timeout` and the worker is told to stop, so it drops
+/// the repository at its next checkpoint instead of running to completion.
+///
+/// Files over `max_file_bytes` aren't loaded; the diff is returned with
+/// `too_large` set and the sizes of each side instead of content.
+pub fn get_file_diff_with_options(
+    repo_path: &Path,
+    spec: &DiffSpec,
+    path: &Path,
+    options: FileDiffOptions,
+) -> Result<FileDiff, GitError> {
+    let repo_path = repo_path.to_path_buf();
+    let spec = spec.clone();
+    let path = path.to_path_buf();
+    run_with_timeout(options.timeout, move |cancelled| {
+        compute_file_diff(&repo_path, &spec, &path, &options, cancelled)
+    })
+}
+
+/// Run `work` on a worker thread, returning `GitError::Timeout` if it doesn't
+/// finish within `timeout`. The worker receives a flag that is set on timeout
+/// and should bail out as soon as it notices.
+fn run_with_timeout<T, F>(timeout: Duration, work: F) -> Result<T, GitError>
+where
+    T: Send + 'static,
+    F: FnOnce(&AtomicBool) -> Result<T, GitError> + Send + 'static,
+{
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let (tx, rx) = mpsc::channel();
+
+    let worker_cancelled = Arc::clone(&cancelled);
+    std::thread::spawn(move || {
+        // The receiver is gone if we timed out; nothing to report then.
+        let _ = tx.send(work(&worker_cancelled));
+    });
+
+    match rx.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            cancelled.store(true, Ordering::Relaxed);
+            Err(GitError::Timeout(timeout))
+        }
+        Err(mpsc::RecvTimeoutError::Disconnected) => Err(GitError::CommandFailed(
+            "Diff worker exited unexpectedly".to_string(),
+        )),
+    }
+}
+
+/// Bail out of a diff computation once the caller has given up on it.
+fn check_cancelled(cancelled: &AtomicBool) -> Result<(), GitError> {
+    if cancelled.load(Ordering::Relaxed) {
+        return Err(GitError::CommandFailed("Diff cancelled".to_string()));
+    }
+    Ok(())
+}
+
+fn compute_file_diff(
+    repo_path: &Path,
+    spec: &DiffSpec,
+    path: &Path,
+    options: &FileDiffOptions,
+    cancelled: &AtomicBool,
+) -> Result<FileDiff, GitError> {
+    let max_file_bytes = options.max_file_bytes;
+
     // Resolve MergeBase to concrete SHA
     let spec = resolve_spec(repo_path, spec)?;
+    check_cancelled(cancelled)?;
 
     let repo = Repository::discover(repo_path).map_err(|e| GitError::NotARepo(e.to_string()))?;
 
@@ -326,13 +761,51 @@ pub fn get_file_diff(repo_path: &Path, spec: &DiffSpec, path: &Path) -> Result<F
     let is_working_tree = matches!(spec.head, GitRef::WorkingTree);
 
     // Load file content
-    let before = load_file_from_tree(&repo, base_tree.as_ref(), path)?;
+    let before = load_file_from_tree(&repo, base_tree.as_ref(), path, max_file_bytes)?;
     let after = if is_working_tree {
-        load_file_from_workdir(&repo, path)?
+        load_file_from_workdir(&repo, path, max_file_bytes)?
+    } else {
+        load_file_from_tree(&repo, head_tree.as_ref(), path, max_file_bytes)?
+    };
+    check_cancelled(cancelled)?;
+
+    let side = if after.is_some() {
+        &spec.head
     } else {
-        load_file_from_tree(&repo, head_tree.as_ref(), path)?
+        &spec.base
+    };
+    let generated = generated::is_generated(&repo, side, path)?;
+    let had_invalid_utf8 = [&before, &after]
+        .iter()
+        .any(|f| f.as_ref().is_some_and(|f| f.had_invalid_utf8));
+    let has_conflicts = match after.as_ref().map(|f| &f.content) {
+        Some(FileContent::Text { lines, .. }) => !conflict::parse_conflicts(lines).is_empty(),
+        _ => false,
     };
 
+    // Don't diff oversized files; report them with their sizes instead
+    let too_large = [&before, &after].iter().any(|f| {
+        matches!(
+            f,
+            Some(File {
+                content: FileContent::TooLarge { .. },
+                ..
+            })
+        )
+    });
+    if too_large {
+        return Ok(FileDiff {
+            schema_version: FileDiff::SCHEMA_VERSION,
+            before,
+            after,
+            alignments: vec![],
+            too_large,
+            generated,
+            has_conflicts,
+            had_invalid_utf8,
+        });
+    }
+
     // Get hunks via libgit2
     let hunks = get_hunks_libgit2(
         &repo,
@@ -340,15 +813,23 @@ pub fn get_file_diff(repo_path: &Path, spec: &DiffSpec, path: &Path) -> Result<F
         head_tree.as_ref(),
         is_working_tree,
         path,
+        options.algorithm,
+        cancelled,
     )?;
+    check_cancelled(cancelled)?;
 
     // Compute alignments from hunks
     let alignments = compute_alignments_from_hunks(&hunks, &before, &after);
 
     Ok(FileDiff {
+        schema_version: FileDiff::SCHEMA_VERSION,
         before,
         after,
         alignments,
+        too_large: false,
+        generated,
+        has_conflicts,
+        had_invalid_utf8,
     })
 }
 
@@ -360,6 +841,8 @@ fn resolve_to_tree<'a>(
 ) -> Result<Option<git2::Tree<'a>>, GitError> {
     match git_ref {
         GitRef::WorkingTree => Ok(None),
+        // No tree at all: every file on the other side is added
+        GitRef::Rev(rev) if rev == refs::EMPTY_TREE => Ok(None),
         GitRef::Rev(rev) => {
             let obj = repo
                 .revparse_single(rev)
@@ -382,6 +865,7 @@ fn load_file_from_tree(
     repo: &Repository,
     tree: Option<&git2::Tree>,
     path: &Path,
+    max_file_bytes: u64,
 ) -> Result<Option<File>, GitError> {
     let tree = match tree {
         Some(t) => t,
@@ -393,6 +877,23 @@ fn load_file_from_tree(
         Err(_) => return Ok(None), // File doesn't exist in this tree
     };
 
+    // Check the size from the object header before reading the blob
+    if entry.kind() == Some(git2::ObjectType::Blob) {
+        let (size, _) = repo
+            .odb()
+            .and_then(|odb| odb.read_header(entry.id()))
+            .map_err(|e| GitError::CommandFailed(format!("Cannot read object header: {e}")))?;
+        if size as u64 > max_file_bytes {
+            return Ok(Some(
+                File::new(
+                    display_path(path),
+                    FileContent::TooLarge { size: size as u64 },
+                )
+                .with_raw_path(path),
+            ));
+        }
+    }
+
     let obj = entry
         .to_object(repo)
         .map_err(|e| GitError::CommandFailed(format!("Cannot load object: {e}")))?;
@@ -402,16 +903,17 @@ fn load_file_from_tree(
         None => return Ok(None), // Not a file (maybe a submodule)
     };
 
-    let content = bytes_to_content(blob.content());
-
-    Ok(Some(File {
-        path: path.to_string_lossy().to_string(),
-        content,
-    }))
+    Ok(Some(
+        File::from_bytes(display_path(path), blob.content()).with_raw_path(path),
+    ))
 }
 
 /// Load file content from the working directory
-fn load_file_from_workdir(repo: &Repository, path: &Path) -> Result<Option<File>, GitError> {
+fn load_file_from_workdir(
+    repo: &Repository,
+    path: &Path,
+    max_file_bytes: u64,
+) -> Result<Option<File>, GitError> {
     let workdir = repo
         .workdir()
         .ok_or_else(|| GitError::CommandFailed("Bare repository".into()))?;
@@ -426,27 +928,105 @@ fn load_file_from_workdir(repo: &Repository, path: &Path) -> Result<Option<File>
         return Ok(None);
     }
 
+    let size = std::fs::metadata(&full_path)
+        .map_err(|e| GitError::CommandFailed(format!("Cannot read file: {e}")))?
+        .len();
+    if size > max_file_bytes {
+        return Ok(Some(
+            File::new(display_path(path), FileContent::TooLarge { size }).with_raw_path(path),
+        ));
+    }
+
     let bytes = std::fs::read(&full_path)
         .map_err(|e| GitError::CommandFailed(format!("Cannot read file: {e}")))?;
 
-    Ok(Some(File {
-        path: path.to_string_lossy().to_string(),
-        content: bytes_to_content(&bytes),
-    }))
+    Ok(Some(
+        File::from_bytes(display_path(path), &bytes).with_raw_path(path),
+    ))
+}
+
+/// Which of `paths` are modified files whose every change is formatting:
+/// indentation, other whitespace within lines, line endings or blank lines.
+///
+/// Uses a whitespace-insensitive diff restricted to `paths`, so a file
+/// qualifies when that diff has no hunks left. Added, deleted, renamed and
+/// binary files never qualify, nor do files whose mode changed or that exceed
+/// `options.max_file_bytes`. Gives up with `GitError::Timeout` after
+/// `options.timeout`.
+pub fn list_formatting_only_files(
+    repo_path: &Path,
+    spec: &DiffSpec,
+    paths: &[PathBuf],
+    options: FileDiffOptions,
+) -> Result<HashSet<PathBuf>, GitError> {
+    if paths.is_empty() {
+        return Ok(HashSet::new());
+    }
+    let repo_path = repo_path.to_path_buf();
+    let spec = spec.clone();
+    let paths = paths.to_vec();
+    run_with_timeout(options.timeout, move |cancelled| {
+        compute_formatting_only(&repo_path, &spec, &paths, &options, cancelled)
+    })
 }
 
-/// Convert raw bytes to FileContent, detecting binary
-fn bytes_to_content(bytes: &[u8]) -> FileContent {
-    // Check for binary: look for null bytes in first 8KB
-    let check_len = bytes.len().min(8192);
-    if bytes[..check_len].contains(&0) {
-        return FileContent::Binary;
+fn compute_formatting_only(
+    repo_path: &Path,
+    spec: &DiffSpec,
+    paths: &[PathBuf],
+    options: &FileDiffOptions,
+    cancelled: &AtomicBool,
+) -> Result<HashSet<PathBuf>, GitError> {
+    let spec = resolve_spec(repo_path, spec)?;
+    let repo = Repository::discover(repo_path).map_err(|e| GitError::NotARepo(e.to_string()))?;
+    let base_tree = resolve_to_tree(&repo, &spec.base)?;
+    let head_tree = resolve_to_tree(&repo, &spec.head)?;
+    check_cancelled(cancelled)?;
+
+    let mut opts = DiffOptions::new();
+    opts.context_lines(0)
+        .ignore_whitespace(true)
+        .ignore_whitespace_eol(true)
+        .ignore_blank_lines(true)
+        .disable_pathspec_match(true)
+        // Larger files are treated as binary, which never qualifies
+        .max_size(i64::try_from(options.max_file_bytes).unwrap_or(i64::MAX));
+    for path in paths {
+        opts.pathspec(path);
+    }
+    let diff = if matches!(spec.head, GitRef::WorkingTree) {
+        repo.diff_tree_to_workdir_with_index(base_tree.as_ref(), Some(&mut opts))
+    } else {
+        repo.diff_tree_to_tree(base_tree.as_ref(), head_tree.as_ref(), Some(&mut opts))
     }
+    .map_err(|e| GitError::CommandFailed(format!("Failed to compute diff: {e}")))?;
 
-    // Parse as UTF-8 (lossy for display)
-    let text = String::from_utf8_lossy(bytes);
-    let lines: Vec<String> = text.lines().map(|s| s.to_string()).collect();
-    FileContent::Text { lines }
+    let mut formatting_only = HashSet::new();
+    for (idx, delta) in diff.deltas().enumerate() {
+        check_cancelled(cancelled)?;
+        let is_blob = matches!(
+            delta.new_file().mode(),
+            git2::FileMode::Blob | git2::FileMode::BlobExecutable
+        );
+        if delta.status() != git2::Delta::Modified
+            || !is_blob
+            || delta.old_file().mode() != delta.new_file().mode()
+            || delta.old_file().size() > options.max_file_bytes
+            || delta.new_file().size() > options.max_file_bytes
+        {
+            continue;
+        }
+        let patch = git2::Patch::from_diff(&diff, idx)
+            .map_err(|e| GitError::CommandFailed(format!("Failed to compute diff: {e}")))?;
+        let Some(patch) = patch else { continue };
+        if patch.delta().flags().is_binary() || patch.num_hunks() > 0 {
+            continue;
+        }
+        if let Some(path) = delta.new_file().path() {
+            formatting_only.insert(path.to_path_buf());
+        }
+    }
+    Ok(formatting_only)
 }
 
 /// Get hunks for a single file using libgit2
@@ -456,10 +1036,21 @@ fn get_hunks_libgit2(
     head_tree: Option<&git2::Tree>,
     is_working_tree: bool,
     path: &Path,
+    algorithm: DiffAlgorithm,
+    cancelled: &AtomicBool,
 ) -> Result<Vec<Hunk>, GitError> {
     let mut opts = DiffOptions::new();
     opts.context_lines(0); // No context, just the changes
     opts.pathspec(path);
+    match algorithm {
+        DiffAlgorithm::Myers => {}
+        DiffAlgorithm::Patience => {
+            opts.patience(true);
+        }
+        DiffAlgorithm::Minimal => {
+            opts.minimal(true);
+        }
+    }
 
     let diff = if is_working_tree {
         repo.diff_tree_to_workdir_with_index(base_tree, Some(&mut opts))
@@ -467,22 +1058,26 @@ fn get_hunks_libgit2(
         repo.diff_tree_to_tree(base_tree, head_tree, Some(&mut opts))
     }
     .map_err(|e| GitError::CommandFailed(format!("Failed to compute diff: {e}")))?;
+    check_cancelled(cancelled)?;
 
     // Collect hunks
     let hunks: RefCell<Vec<Hunk>> = RefCell::new(Vec::new());
 
     diff.foreach(
-        &mut |_delta, _progress| true, // file callback
-        None,                          // binary callback
+        // File callback; returning false aborts iteration once the caller has given up
+        &mut |_delta, _progress| !cancelled.load(Ordering::Relaxed),
+        None, // binary callback
         Some(&mut |_delta, hunk| {
-            // Git uses 1-indexed line numbers, convert to 0-indexed
-            let old_start = if hunk.old_start() == 0 {
-                0
+            // Git uses 1-indexed line numbers, convert to 0-indexed.
+            // An empty side instead names the line the change follows,
+            // which is already the 0-indexed insertion point.
+            let old_start = if hunk.old_lines() == 0 {
+                hunk.old_start()
             } else {
                 hunk.old_start() - 1
             };
-            let new_start = if hunk.new_start() == 0 {
-                0
+            let new_start = if hunk.new_lines() == 0 {
+                hunk.new_start()
             } else {
                 hunk.new_start() - 1
             };
@@ -493,11 +1088,12 @@ fn get_hunks_libgit2(
                 new_start,
                 new_lines: hunk.new_lines(),
             });
-            true
+            !cancelled.load(Ordering::Relaxed)
         }),
         None, // line callback
     )
     .map_err(|e| GitError::CommandFailed(format!("Failed to iterate diff: {e}")))?;
+    check_cancelled(cancelled)?;
 
     Ok(hunks.into_inner())
 }
@@ -511,14 +1107,14 @@ fn compute_alignments_from_hunks(
 ) -> Vec<Alignment> {
     let before_len = match before {
         Some(File {
-            content: FileContent::Text { lines },
+            content: FileContent::Text { lines, .. },
             ..
         }) => lines.len() as u32,
         _ => 0,
     };
     let after_len = match after {
         Some(File {
-            content: FileContent::Text { lines },
+            content: FileContent::Text { lines, .. },
             ..
         }) => lines.len() as u32,
         _ => 0,
@@ -602,9 +1198,76 @@ fn compute_alignments_from_hunks(
     alignments
 }
 
+/// Flatten a file diff's side-by-side alignments into unified rows: context
+/// lines once, and each changed region as its removed lines followed by its
+/// added lines. Binary and oversized files produce no rows.
+pub fn render_unified(diff: &FileDiff) -> Vec<DiffRow> {
+    render_unified_with_options(diff, &RenderOptions::default())
+}
+
+/// Like [`render_unified`], optionally flagging trailing whitespace and hard
+/// tabs on added rows. Off by default since it scans every added line.
+pub fn render_unified_with_options(diff: &FileDiff, options: &RenderOptions) -> Vec<DiffRow> {
+    fn text_lines(file: &Option<File>) -> &[String] {
+        match file {
+            Some(File {
+                content: FileContent::Text { lines, .. },
+                ..
+            }) => lines,
+            _ => &[],
+        }
+    }
+    let before = text_lines(&diff.before);
+    let after = text_lines(&diff.after);
+    let line = |lines: &[String], n: u32| lines.get(n as usize).cloned().unwrap_or_default();
+
+    let mut rows = Vec::new();
+    for alignment in &diff.alignments {
+        if !alignment.changed && alignment.before.len() == alignment.after.len() {
+            for (b, a) in (alignment.before.start..alignment.before.end)
+                .zip(alignment.after.start..alignment.after.end)
+            {
+                rows.push(DiffRow {
+                    kind: DiffRowKind::Context,
+                    before_line: Some(b),
+                    after_line: Some(a),
+                    content: line(after, a),
+                    has_trailing_whitespace: false,
+                    has_tabs: false,
+                });
+            }
+            continue;
+        }
+        for b in alignment.before.start..alignment.before.end {
+            rows.push(DiffRow {
+                kind: DiffRowKind::Removed,
+                before_line: Some(b),
+                after_line: None,
+                content: line(before, b),
+                has_trailing_whitespace: false,
+                has_tabs: false,
+            });
+        }
+        for a in alignment.after.start..alignment.after.end {
+            let content = line(after, a);
+            let markers = options.whitespace_markers;
+            rows.push(DiffRow {
+                kind: DiffRowKind::Added,
+                before_line: None,
+                after_line: Some(a),
+                has_trailing_whitespace: markers && content.ends_with([' ', '\t']),
+                has_tabs: markers && content.contains('\t'),
+                content,
+            });
+        }
+    }
+    rows
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::git::test_support::init_test_repo;
 
     #[test]
     fn test_parse_name_status_added() {
@@ -721,16 +1384,9 @@ mod tests {
     #[test]
     fn test_parse_porcelain_untracked_directory() {
         // Create a temp git repo with an untracked directory
-        let dir = tempfile::tempdir().unwrap();
+        let (dir, _) = init_test_repo();
         let repo_path = dir.path();
 
-        // Initialize git repo
-        std::process::Command::new("git")
-            .args(["init"])
-            .current_dir(repo_path)
-            .output()
-            .unwrap();
-
         // Create untracked directory with files
         let newdir = repo_path.join("newdir");
         std::fs::create_dir_all(newdir.join("subdir")).unwrap();
@@ -752,4 +1408,682 @@ mod tests {
         assert!(paths.contains(&"newdir/file1.txt"));
         assert!(paths.contains(&"newdir/subdir/file2.txt"));
     }
+
+    #[test]
+    fn test_slow_diff_times_out_and_worker_stops() {
+        let (released_tx, released_rx) = mpsc::channel();
+
+        // Simulate a read path that stalls (e.g. a slow network filesystem)
+        let result: Result<(), GitError> =
+            run_with_timeout(Duration::from_millis(50), move |cancelled| {
+                while !cancelled.load(Ordering::Relaxed) {
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+                released_tx.send(()).unwrap();
+                check_cancelled(cancelled)
+            });
+
+        assert!(matches!(result, Err(GitError::Timeout(_))));
+        // The worker notices the cancellation and lets go of its resources
+        released_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    }
+
+    #[test]
+    fn test_fast_diff_completes_within_timeout() {
+        let result = run_with_timeout(Duration::from_secs(5), |_| Ok(42));
+        assert_eq!(result.unwrap(), 42);
+    }
+
+    #[test]
+    fn test_diff_id_on_unborn_head() {
+        let (dir, _) = init_test_repo();
+        let repo_path = dir.path();
+        std::fs::write(repo_path.join("new.txt"), "hello\n").unwrap();
+
+        let id = diff_id(repo_path, &DiffSpec::uncommitted()).unwrap();
+        assert_eq!(id, DiffId::new(refs::EMPTY_TREE, "@"));
+    }
+
+    #[test]
+    fn test_oversized_file_is_not_loaded() {
+        let (dir, git) = init_test_repo();
+        let repo_path = dir.path();
+
+        std::fs::write(repo_path.join("big.txt"), "line\n".repeat(100)).unwrap();
+        git(&["add", "big.txt"]);
+        git(&["commit", "-m", "initial"]);
+        std::fs::write(repo_path.join("big.txt"), "line\n".repeat(200)).unwrap();
+
+        let options = FileDiffOptions {
+            max_file_bytes: 600,
+            ..Default::default()
+        };
+        let diff = get_file_diff_with_options(
+            repo_path,
+            &DiffSpec::uncommitted(),
+            Path::new("big.txt"),
+            options,
+        )
+        .unwrap();
+
+        assert!(diff.too_large);
+        assert!(diff.alignments.is_empty());
+        // Committed side is under the limit and still loaded
+        assert!(matches!(
+            diff.before.unwrap().content,
+            FileContent::Text { ref lines, .. } if lines.len() == 100
+        ));
+        // Working tree side is over the limit: only its size is reported
+        assert_eq!(
+            diff.after.unwrap().content,
+            FileContent::TooLarge { size: 1000 }
+        );
+    }
+
+    #[test]
+    fn test_diff_stash_entry() {
+        let (dir, git) = init_test_repo();
+        let repo_path = dir.path();
+
+        std::fs::write(repo_path.join("notes.txt"), "one\n").unwrap();
+        git(&["add", "notes.txt"]);
+        git(&["commit", "-m", "initial"]);
+        std::fs::write(repo_path.join("notes.txt"), "one\ntwo\n").unwrap();
+        git(&["stash", "push", "-m", "add two"]);
+
+        let stashes = refs::list_stashes(repo_path).unwrap();
+        assert_eq!(stashes.len(), 1);
+        assert!(stashes[0].message.contains("add two"));
+
+        let spec = DiffSpec::stash(0);
+        let files = list_diff_files(repo_path, &spec).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path(), Path::new("notes.txt"));
+
+        let diff = get_file_diff(repo_path, &spec, Path::new("notes.txt")).unwrap();
+        assert_eq!(
+            diff.after.unwrap().content,
+            FileContent::Text {
+                lines: vec!["one".to_string(), "two".to_string()],
+                crlf_lines: vec![],
+                missing_final_newline: false,
+            }
+        );
+        assert!(diff.alignments.iter().any(|a| a.changed));
+    }
+
+    #[test]
+    fn test_incremental_diff_skips_files_unchanged_since_previous_head() {
+        let (dir, git) = init_test_repo();
+        let repo_path = dir.path();
+        std::fs::write(repo_path.join("a.txt"), "a\n").unwrap();
+        std::fs::write(repo_path.join("b.txt"), "b\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-m", "base"]);
+        let base = git(&["rev-parse", "HEAD"]);
+
+        // First push: both files change, and the reviewer reviews this head
+        std::fs::write(repo_path.join("a.txt"), "a\na2\n").unwrap();
+        std::fs::write(repo_path.join("b.txt"), "b\nb2\n").unwrap();
+        git(&["commit", "-am", "first"]);
+        let reviewed_head = git(&["rev-parse", "HEAD"]);
+
+        // Second push only touches b.txt
+        std::fs::write(repo_path.join("b.txt"), "b\nb2\nb3\n").unwrap();
+        git(&["commit", "-am", "second"]);
+
+        let spec = DiffSpec {
+            base: GitRef::Rev(base),
+            head: GitRef::Rev("HEAD".to_string()),
+        };
+        assert_eq!(list_diff_files(repo_path, &spec).unwrap().len(), 2);
+
+        let delta = get_incremental_diff(repo_path, &spec, &reviewed_head).unwrap();
+        assert_eq!(delta.len(), 1);
+        let after = delta[0].after.as_ref().unwrap();
+        assert_eq!(after.path, "b.txt");
+        // Only the new line shows as changed
+        let before = delta[0].before.as_ref().unwrap();
+        assert!(matches!(
+            &before.content,
+            FileContent::Text { lines, .. } if lines == &["b", "b2"]
+        ));
+    }
+
+    #[test]
+    fn test_invalid_utf8_is_flagged() {
+        let (dir, git) = init_test_repo();
+        let repo_path = dir.path();
+        // Latin-1 "café", which isn't valid UTF-8
+        std::fs::write(repo_path.join("latin1.txt"), b"caf\xe9\n").unwrap();
+        std::fs::write(repo_path.join("utf8.txt"), "café\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-m", "initial"]);
+        std::fs::write(repo_path.join("latin1.txt"), b"caf\xe9\nth\xe9\n").unwrap();
+        std::fs::write(repo_path.join("utf8.txt"), "café\nthé\n").unwrap();
+
+        let spec = DiffSpec::uncommitted();
+        let diff = get_file_diff(repo_path, &spec, Path::new("latin1.txt")).unwrap();
+        assert!(diff.had_invalid_utf8);
+        assert!(diff.before.as_ref().unwrap().had_invalid_utf8);
+        assert_eq!(
+            diff.before.unwrap().content,
+            FileContent::from_text("caf\u{FFFD}\n")
+        );
+
+        let diff = get_file_diff(repo_path, &spec, Path::new("utf8.txt")).unwrap();
+        assert!(!diff.had_invalid_utf8);
+        assert!(!diff.after.unwrap().had_invalid_utf8);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_non_utf8_path_round_trips() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let (dir, git) = init_test_repo();
+        let repo_path = dir.path();
+        // Latin-1 "café.txt", which isn't valid UTF-8
+        let name = Path::new(OsStr::from_bytes(b"caf\xe9.txt"));
+        std::fs::write(repo_path.join(name), "one\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-m", "initial"]);
+        std::fs::write(repo_path.join(name), "one\ntwo\n").unwrap();
+        git(&["commit", "-am", "second"]);
+        std::fs::write(repo_path.join(name), "one\ntwo\nthree\n").unwrap();
+
+        let specs = [
+            DiffSpec::uncommitted(),
+            DiffSpec {
+                base: GitRef::Rev("HEAD~1".into()),
+                head: GitRef::Rev("HEAD".into()),
+            },
+        ];
+        for spec in specs {
+            let files = list_diff_files(repo_path, &spec).unwrap();
+            assert_eq!(files.len(), 1);
+            assert_eq!(files[0].path(), name);
+
+            // Through JSON, as the frontend sees it, and back
+            // The name is shown lossily, with the raw form alongside
+            let json = serde_json::to_string(&files[0]).unwrap();
+            assert!(json.contains("\"after\":\"caf\u{FFFD}.txt\""), "{json}");
+            assert!(json.contains(r#""after_raw":"\u0000caf%E9.txt""#), "{json}");
+            let summary: FileDiffSummary = serde_json::from_str(&json).unwrap();
+            assert_eq!(summary, files[0]);
+
+            let diff = get_file_diff(repo_path, &spec, summary.path()).unwrap();
+            let after = diff.after.unwrap();
+            assert_eq!(after.path, "caf\u{FFFD}.txt");
+            let raw = after.raw_path.unwrap();
+            assert_eq!(crate::util::decode_path(&raw), name);
+        }
+    }
+
+    #[test]
+    fn test_literal_percent_escape_in_path_round_trips() {
+        let (dir, git) = init_test_repo();
+        let repo_path = dir.path();
+        // UTF-8 names that happen to spell escapes, one of them of a Latin-1 byte
+        let names = ["caf%E9.txt", "my%20notes.md"];
+        for name in names {
+            std::fs::write(repo_path.join(name), "one\n").unwrap();
+        }
+        git(&["add", "."]);
+        git(&["commit", "-m", "initial"]);
+        for name in names {
+            std::fs::write(repo_path.join(name), "one\ntwo\n").unwrap();
+        }
+        git(&["commit", "-am", "second"]);
+        for name in names {
+            std::fs::write(repo_path.join(name), "one\ntwo\nthree\n").unwrap();
+        }
+
+        let specs = [
+            DiffSpec::uncommitted(),
+            DiffSpec {
+                base: GitRef::Rev("HEAD~1".into()),
+                head: GitRef::Rev("HEAD".into()),
+            },
+        ];
+        for spec in specs {
+            let mut files = list_diff_files(repo_path, &spec).unwrap();
+            files.sort_by(|a, b| a.path().cmp(b.path()));
+            assert_eq!(files.len(), 2);
+            for (file, name) in files.iter().zip(names) {
+                assert_eq!(file.path(), Path::new(name));
+
+                // The frontend sees and sends back the name unchanged
+                let json = serde_json::to_string(file).unwrap();
+                assert!(json.contains(&format!(r#""after":"{name}""#)), "{json}");
+                assert!(!json.contains("after_raw"), "{json}");
+                let summary: FileDiffSummary = serde_json::from_str(&json).unwrap();
+                assert_eq!(&summary, file);
+
+                let diff = get_file_diff(repo_path, &spec, Path::new(name)).unwrap();
+                let after = diff.after.unwrap();
+                assert_eq!(after.path, name);
+                assert_eq!(after.raw_path, None);
+                assert!(!diff.alignments.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_exclude_snapshot_directories() {
+        let (dir, git) = init_test_repo();
+        let repo_path = dir.path();
+        let paths = [
+            "src/app.ts",
+            "src/__snapshots__/app.test.ts.snap",
+            "tests/__snapshots__/nested/b.snap",
+        ];
+        for path in paths {
+            let full = repo_path.join(path);
+            std::fs::create_dir_all(full.parent().unwrap()).unwrap();
+            std::fs::write(&full, "before\n").unwrap();
+        }
+        git(&["add", "."]);
+        git(&["commit", "-m", "initial"]);
+        for path in paths {
+            std::fs::write(repo_path.join(path), "after\n").unwrap();
+        }
+
+        let files = list_diff_files(repo_path, &DiffSpec::uncommitted()).unwrap();
+        assert_eq!(exclude_files(files.clone(), &[]).unwrap().len(), 3);
+
+        let kept = exclude_files(files, &["__snapshots__/**".to_string()]).unwrap();
+        let kept: Vec<_> = kept.iter().map(|f| f.path().clone()).collect();
+        assert_eq!(kept, vec![PathBuf::from("src/app.ts")]);
+    }
+
+    #[test]
+    fn test_progress_reported_during_rename_detection() {
+        let (dir, git) = init_test_repo();
+        let repo_path = dir.path();
+        std::fs::create_dir(repo_path.join("old")).unwrap();
+        for i in 0..40 {
+            let content: String = (0..20).map(|l| format!("file {i} line {l}\n")).collect();
+            std::fs::write(repo_path.join(format!("old/f{i}.txt")), content).unwrap();
+        }
+        git(&["add", "."]);
+        git(&["commit", "-m", "initial"]);
+        git(&["mv", "old", "new"]);
+        git(&["commit", "-m", "move"]);
+
+        let mut events = Vec::new();
+        let files = list_diff_files_with_progress(repo_path, &DiffSpec::last_commit(), &mut |p| {
+            events.push(p)
+        })
+        .unwrap();
+
+        assert_eq!(files.len(), 40);
+        assert!(files.iter().all(|f| f.is_renamed()));
+        // Rename detection is only announced; it has no progress of its own
+        let renames: Vec<f32> = events
+            .iter()
+            .filter(|p| p.phase == "renames")
+            .map(|p| p.fraction)
+            .collect();
+        assert_eq!(renames, [0.0]);
+        // The file walk advances once per file
+        let file_fractions: Vec<f32> = events
+            .iter()
+            .filter(|p| p.phase == "files")
+            .map(|p| p.fraction)
+            .collect();
+        assert_eq!(file_fractions.len(), 41);
+        assert!(file_fractions.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(file_fractions[20], 0.5);
+        assert_eq!(file_fractions.last(), Some(&1.0));
+
+        // Same answer as the non-callback path
+        let plain = list_diff_files(repo_path, &DiffSpec::last_commit()).unwrap();
+        let paths = |files: &[FileDiffSummary]| {
+            let mut paths: Vec<_> = files.iter().map(|f| f.path().clone()).collect();
+            paths.sort();
+            paths
+        };
+        assert_eq!(paths(&files), paths(&plain));
+    }
+
+    #[test]
+    fn test_rename_threshold() {
+        let (dir, git) = init_test_repo();
+        let repo_path = dir.path();
+        let lines = |edited: usize| -> String {
+            (0..40)
+                .map(|l| {
+                    if l < edited {
+                        format!("rewritten line {l} of the moved file\n")
+                    } else {
+                        format!("original line {l} of the file\n")
+                    }
+                })
+                .collect()
+        };
+        std::fs::write(repo_path.join("before.txt"), lines(0)).unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-m", "initial"]);
+        std::fs::remove_file(repo_path.join("before.txt")).unwrap();
+        std::fs::write(repo_path.join("after.txt"), lines(16)).unwrap();
+        git(&["add", "-A"]);
+        git(&["commit", "-m", "move and rewrite"]);
+
+        let list = |threshold| {
+            let renames = RenameOptions {
+                threshold,
+                ..RenameOptions::default()
+            };
+            list_diff_files_with_renames(
+                repo_path,
+                &DiffSpec::last_commit(),
+                Some(renames),
+                &mut |_| {},
+            )
+            .unwrap()
+        };
+        let loose = list(30);
+        assert_eq!(loose.len(), 1);
+        assert!(loose[0].is_renamed());
+        let strict = list(90);
+        assert_eq!(strict.len(), 2);
+        assert!(strict.iter().all(|f| !f.is_renamed()));
+
+        // The configured threshold applies when there's no override, on both
+        // the libgit2 and the git CLI path
+        assert_eq!(
+            RenameOptions::from_config(repo_path),
+            RenameOptions::default()
+        );
+        git(&["config", RENAME_THRESHOLD_CONFIG, "90"]);
+        git(&["config", "diff.renameLimit", "0"]);
+        let configured = RenameOptions::from_config(repo_path);
+        assert_eq!(configured.threshold, 90);
+        assert_eq!(configured.limit, usize::MAX);
+        let files = list_diff_files_with_progress(repo_path, &DiffSpec::last_commit(), &mut |_| {})
+            .unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(
+            list_diff_files(repo_path, &DiffSpec::last_commit())
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_zero_rename_limit_means_unlimited() {
+        let (dir, git) = init_test_repo();
+        let repo_path = dir.path();
+        // More files than libgit2's default limit of 1000
+        std::fs::create_dir(repo_path.join("old")).unwrap();
+        for i in 0..1050 {
+            std::fs::write(
+                repo_path.join(format!("old/f{i}.txt")),
+                format!("file {i}\n"),
+            )
+            .unwrap();
+        }
+        git(&["add", "."]);
+        git(&["commit", "-m", "initial"]);
+        git(&["mv", "old", "new"]);
+        git(&["commit", "-m", "move"]);
+
+        let list = |limit| {
+            let renames = RenameOptions {
+                limit,
+                ..RenameOptions::default()
+            };
+            list_diff_files_with_renames(
+                repo_path,
+                &DiffSpec::last_commit(),
+                Some(renames),
+                &mut |_| {},
+            )
+            .unwrap()
+        };
+        let unlimited = list(0);
+        assert_eq!(unlimited.len(), 1050);
+        assert!(unlimited.iter().all(|f| f.is_renamed()));
+        // libgit2's default of 1000 would leave some unpaired
+        assert!(!list(1000).iter().all(|f| f.is_renamed()));
+    }
+
+    #[test]
+    fn test_new_file_in_repo_without_commits() {
+        let (dir, _) = init_test_repo();
+        let repo_path = dir.path();
+        std::fs::write(repo_path.join("new.txt"), "hello\nworld\n").unwrap();
+
+        let spec = DiffSpec::uncommitted();
+        let files = list_diff_files(repo_path, &spec).unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].is_added());
+
+        let diff = get_file_diff(repo_path, &spec, Path::new("new.txt")).unwrap();
+        assert!(diff.before.is_none());
+        assert!(matches!(
+            diff.after.unwrap().content,
+            FileContent::Text { ref lines, .. } if lines.len() == 2
+        ));
+
+        let unified = get_unified_diff(repo_path, &spec, Path::new("new.txt"));
+        assert!(unified.is_ok());
+    }
+
+    #[test]
+    fn test_patience_anchors_on_unique_lines() {
+        let (dir, git) = init_test_repo();
+        let repo_path = dir.path();
+
+        // "x" is the only line unique to both sides; the braces repeat
+        std::fs::write(repo_path.join("f.txt"), "}\n{\n{\nx\n").unwrap();
+        git(&["add", "f.txt"]);
+        git(&["commit", "-m", "initial"]);
+        std::fs::write(repo_path.join("f.txt"), "x\n}\n{\ny\n").unwrap();
+
+        let unchanged = |algorithm| {
+            let options = FileDiffOptions {
+                algorithm,
+                ..Default::default()
+            };
+            let diff = get_file_diff_with_options(
+                repo_path,
+                &DiffSpec::uncommitted(),
+                Path::new("f.txt"),
+                options,
+            )
+            .unwrap();
+            diff.alignments
+                .into_iter()
+                .filter(|a| !a.changed)
+                .map(|a| (a.before, a.after))
+                .collect::<Vec<_>>()
+        };
+
+        // Myers keeps the leading braces and re-adds "x" above them
+        assert_eq!(
+            unchanged(DiffAlgorithm::Myers),
+            vec![(Span::new(0, 2), Span::new(1, 3))]
+        );
+        // Patience keeps "x" and rewrites the braces around it
+        assert_eq!(
+            unchanged(DiffAlgorithm::Patience),
+            vec![(Span::new(3, 4), Span::new(0, 1))]
+        );
+    }
+
+    #[test]
+    fn test_line_ending_change_is_visible() {
+        let (dir, git) = init_test_repo();
+        let repo_path = dir.path();
+        git(&["config", "core.autocrlf", "false"]);
+
+        std::fs::write(repo_path.join("win.txt"), "one\r\ntwo\r\nthree\r\n").unwrap();
+        git(&["add", "win.txt"]);
+        git(&["commit", "-m", "initial"]);
+        std::fs::write(repo_path.join("win.txt"), "one\ntwo\r\nthree\n").unwrap();
+
+        let diff =
+            get_file_diff(repo_path, &DiffSpec::uncommitted(), Path::new("win.txt")).unwrap();
+        let crlf = |file: Option<File>| match file.unwrap().content {
+            FileContent::Text {
+                lines, crlf_lines, ..
+            } => (lines, crlf_lines),
+            other => panic!("expected text, got {other:?}"),
+        };
+        let (before_lines, before_crlf) = crlf(diff.before);
+        let (after_lines, after_crlf) = crlf(diff.after);
+
+        // Same text once terminators are stripped, but the endings differ
+        assert_eq!(before_lines, after_lines);
+        assert_eq!(before_crlf, [0, 1, 2]);
+        assert_eq!(after_crlf, [1]);
+        let changed: Vec<_> = diff
+            .alignments
+            .iter()
+            .filter(|a| a.changed)
+            .map(|a| a.after)
+            .collect();
+        assert_eq!(changed, [Span::new(0, 1), Span::new(2, 3)]);
+    }
+
+    #[test]
+    fn test_render_unified_preserves_line_numbers() {
+        let text = |path: &str, lines: &[&str]| {
+            File::new(
+                path,
+                FileContent::Text {
+                    lines: lines.iter().map(|l| l.to_string()).collect(),
+                    crlf_lines: vec![],
+                    missing_final_newline: false,
+                },
+            )
+        };
+        let before_lines = ["fn main() {", "    old();", "}"];
+        let after_lines = ["fn main() {", "    new();", "    more();", "}", ""];
+        let aligned = |before: (u32, u32), after: (u32, u32), changed| Alignment {
+            before: Span::new(before.0, before.1),
+            after: Span::new(after.0, after.1),
+            changed,
+        };
+        let diff = FileDiff {
+            schema_version: FileDiff::SCHEMA_VERSION,
+            before: Some(text("main.rs", &before_lines)),
+            after: Some(text("main.rs", &after_lines)),
+            alignments: vec![
+                aligned((0, 1), (0, 1), false),
+                aligned((1, 2), (1, 3), true),
+                aligned((2, 3), (3, 4), false),
+                aligned((3, 3), (4, 5), true),
+            ],
+            too_large: false,
+            generated: false,
+            has_conflicts: false,
+            had_invalid_utf8: false,
+        };
+
+        let rows = render_unified(&diff);
+        let summary: Vec<_> = rows
+            .iter()
+            .map(|r| (r.kind, r.before_line, r.after_line))
+            .collect();
+        use DiffRowKind::*;
+        assert_eq!(
+            summary,
+            [
+                (Context, Some(0), Some(0)),
+                (Removed, Some(1), None),
+                (Added, None, Some(1)),
+                (Added, None, Some(2)),
+                (Context, Some(2), Some(3)),
+                (Added, None, Some(4)),
+            ]
+        );
+
+        // Each side's rows reproduce that side, in order, at the recorded lines
+        for row in &rows {
+            if let Some(b) = row.before_line {
+                assert_eq!(row.content, before_lines[b as usize]);
+            }
+            if let Some(a) = row.after_line {
+                assert_eq!(row.content, after_lines[a as usize]);
+            }
+        }
+        let before_seq: Vec<_> = rows.iter().filter_map(|r| r.before_line).collect();
+        let after_seq: Vec<_> = rows.iter().filter_map(|r| r.after_line).collect();
+        assert_eq!(before_seq, [0, 1, 2]);
+        assert_eq!(after_seq, [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_whitespace_markers_on_added_rows() {
+        let text = |lines: &[&str]| {
+            File::new(
+                "f.txt",
+                FileContent::Text {
+                    lines: lines.iter().map(|l| l.to_string()).collect(),
+                    crlf_lines: vec![],
+                    missing_final_newline: false,
+                },
+            )
+        };
+        let diff = FileDiff {
+            schema_version: FileDiff::SCHEMA_VERSION,
+            before: Some(text(&["keep  ", "old\t"])),
+            after: Some(text(&[
+                "keep  ",
+                "trailing  ",
+                "\tindented",
+                "tab\t",
+                "clean",
+            ])),
+            alignments: vec![
+                Alignment {
+                    before: Span::new(0, 1),
+                    after: Span::new(0, 1),
+                    changed: false,
+                },
+                Alignment {
+                    before: Span::new(1, 2),
+                    after: Span::new(1, 5),
+                    changed: true,
+                },
+            ],
+            too_large: false,
+            generated: false,
+            has_conflicts: false,
+            had_invalid_utf8: false,
+        };
+
+        let markers = |rows: &[DiffRow]| -> Vec<_> {
+            rows.iter()
+                .map(|r| (r.kind, r.has_trailing_whitespace, r.has_tabs))
+                .collect()
+        };
+        use DiffRowKind::*;
+
+        // Context and removed rows are never flagged
+        let options = RenderOptions {
+            whitespace_markers: true,
+        };
+        assert_eq!(
+            markers(&render_unified_with_options(&diff, &options)),
+            [
+                (Context, false, false),
+                (Removed, false, false),
+                (Added, true, false),
+                (Added, false, true),
+                (Added, true, true),
+                (Added, false, false),
+            ]
+        );
+
+        // Off by default
+        assert!(render_unified(&diff)
+            .iter()
+            .all(|r| !r.has_trailing_whitespace && !r.has_tabs));
+    }
 }
@@ -2,12 +2,51 @@
 //!
 //! This module provides:
 //! - `search_files`: Fuzzy search for files in a git tree
+//! - `list_files`: Bounded listing of the files in a git tree
 //! - `get_file_at_ref`: Load file content at a specific ref
+//! - `read_text_at_ref`: The same, as plain text, for side panels
 
+use std::io::{BufRead, BufReader, Read};
 use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
 
 use super::cli::{self, GitError};
+use super::refs;
 use super::types::{File, FileContent, WORKDIR};
+use crate::util::{decode_path, display_path, encode_path_bytes};
+use git2::Repository;
+
+/// Bounds on a repository file walk, so huge monorepos can't stall the UI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileWalkLimits {
+    /// Stop after this many files
+    pub max_files: usize,
+    /// Stop after this long
+    pub max_time: Duration,
+}
+
+impl Default for FileWalkLimits {
+    fn default() -> Self {
+        Self {
+            max_files: 500_000,
+            max_time: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Repository paths from a bounded file walk (or a search over one).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileList {
+    pub paths: Vec<String>,
+    /// True if the walk hit `max_files` or `max_time`, so only part of the
+    /// tree was seen
+    pub truncated: bool,
+}
 
 /// Search for files matching a query in the repository at a given ref.
 ///
@@ -17,34 +56,37 @@ use super::types::{File, FileContent, WORKDIR};
 /// Returns up to `limit` matching file paths, sorted by match quality:
 /// - Exact filename matches first
 /// - Then by path length (shorter paths ranked higher)
+///
+/// The underlying file walk is bounded by `walk_limits`; if it stops early
+/// the result is marked `truncated`.
 pub fn search_files(
     repo: &Path,
     ref_name: &str,
     query: &str,
     limit: usize,
-) -> Result<Vec<String>, GitError> {
+    walk_limits: &FileWalkLimits,
+) -> Result<FileList, GitError> {
     let query_lower = query.to_lowercase();
 
     // Use HEAD for WORKDIR since we're listing tracked files
+    // (nothing is tracked in a commit yet if HEAD is unborn)
     let tree_ref = if ref_name == WORKDIR {
-        "HEAD"
+        if refs::is_head_unborn(repo) {
+            refs::EMPTY_TREE
+        } else {
+            "HEAD"
+        }
     } else {
         ref_name
     };
 
-    // git ls-tree -r --name-only <ref>
-    let output = cli::run(repo, &["ls-tree", "-r", "--name-only", tree_ref])?;
+    let files = list_files(repo, tree_ref, walk_limits)?;
 
     let mut matches: Vec<(String, MatchScore)> = Vec::new();
 
-    for line in output.lines() {
-        let path = line.trim();
-        if path.is_empty() {
-            continue;
-        }
-
-        if let Some(score) = fuzzy_match(path, &query_lower) {
-            matches.push((path.to_string(), score));
+    for path in files.paths {
+        if let Some(score) = fuzzy_match(&path, &query_lower) {
+            matches.push((path, score));
         }
     }
 
@@ -52,11 +94,115 @@ pub fn search_files(
     matches.sort_by(|a, b| b.1.cmp(&a.1));
 
     // Return top results
-    Ok(matches
-        .into_iter()
-        .take(limit)
-        .map(|(path, _)| path)
-        .collect())
+    Ok(FileList {
+        paths: matches
+            .into_iter()
+            .take(limit)
+            .map(|(path, _)| path)
+            .collect(),
+        truncated: files.truncated,
+    })
+}
+
+/// List the files in a tree (`git ls-tree -r`), stopping once `limits` are hit.
+///
+/// Output is streamed, so hitting a limit kills git instead of waiting for
+/// the full listing. Paths that aren't UTF-8 come back in their raw form
+/// (see `util::encode_path`).
+pub fn list_files(
+    repo: &Path,
+    tree_ref: &str,
+    limits: &FileWalkLimits,
+) -> Result<FileList, GitError> {
+    let deadline = Instant::now() + limits.max_time;
+
+    let mut child = KillOnDrop(
+        Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(["ls-tree", "-r", "--name-only", "-z", tree_ref])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    GitError::GitNotFound
+                } else {
+                    GitError::CommandFailed(e.to_string())
+                }
+            })?,
+    );
+
+    // Read entries on a separate thread so a slow git can't block past the deadline
+    let stdout = child.0.stdout.take().expect("stdout is piped");
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for entry in BufReader::new(stdout).split(b'\0') {
+            if tx.send(entry).is_err() {
+                break;
+            }
+        }
+    });
+    // Drain stderr too, so git can't block on a full pipe
+    let mut stderr = child.0.stderr.take().expect("stderr is piped");
+    let stderr = std::thread::spawn(move || {
+        let mut text = Vec::new();
+        let _ = stderr.read_to_end(&mut text);
+        String::from_utf8_lossy(&text).into_owned()
+    });
+
+    let mut paths = Vec::new();
+    let mut truncated = false;
+    loop {
+        if paths.len() >= limits.max_files {
+            truncated = true;
+            break;
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match rx.recv_timeout(remaining) {
+            Ok(Ok(entry)) => {
+                if !entry.is_empty() {
+                    paths.push(encode_path_bytes(&entry));
+                }
+            }
+            Ok(Err(e)) => return Err(GitError::CommandFailed(e.to_string())),
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                truncated = true;
+                break;
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    if truncated {
+        // Dropping the guard kills git
+        return Ok(FileList { paths, truncated });
+    }
+
+    let status = child
+        .0
+        .wait()
+        .map_err(|e| GitError::CommandFailed(e.to_string()))?;
+    if !status.success() {
+        let stderr = stderr.join().unwrap_or_default();
+        if stderr.contains("not a git repository") {
+            return Err(GitError::NotARepo(repo.display().to_string()));
+        }
+        return Err(GitError::CommandFailed(stderr));
+    }
+
+    Ok(FileList { paths, truncated })
+}
+
+/// Kills and reaps a child process when dropped, so no return path can
+/// leave it running.
+struct KillOnDrop(Child);
+
+impl Drop for KillOnDrop {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
 }
 
 /// Match quality score for sorting results.
@@ -126,17 +272,52 @@ fn fuzzy_match(path: &str, query_lower: &str) -> Option<MatchScore> {
     })
 }
 
+/// Ref name for the staged content of a file.
+pub const INDEX_REF: &str = ":index";
+
 /// Get the content of a file at a specific ref.
 ///
-/// For WORKDIR, reads from the working directory.
+/// For WORKDIR (or `@`, as `GitRef` writes the working tree), reads from
+/// the working directory.
+/// For `:index`, reads the staged version.
 /// For other refs, reads from the git tree.
+///
+/// `path` may be the raw form of a path that isn't UTF-8 (see
+/// `util::encode_path`).
 pub fn get_file_at_ref(repo: &Path, ref_name: &str, path: &str) -> Result<File, GitError> {
-    if ref_name == WORKDIR {
+    let bytes = read_bytes_at_ref(repo, ref_name, path)?
+        .ok_or_else(|| GitError::CommandFailed(format!("File not found: {path}")))?;
+    let file = decode_path(path);
+    Ok(File::from_bytes(display_path(&file), &bytes).with_raw_path(&file))
+}
+
+/// Get a file's text at a ref, accepting the same refs as [`get_file_at_ref`].
+///
+/// Returns `None` if the file doesn't exist there or isn't text, so callers
+/// can show an empty side without treating it as a failure. Bad refs are
+/// still errors.
+pub fn read_text_at_ref(
+    repo: &Path,
+    ref_name: &str,
+    path: &str,
+) -> Result<Option<String>, GitError> {
+    let Some(bytes) = read_bytes_at_ref(repo, ref_name, path)? else {
+        return Ok(None);
+    };
+    if matches!(File::from_bytes(path, &bytes).content, FileContent::Binary) {
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+fn read_bytes_at_ref(repo: &Path, ref_name: &str, path: &str) -> Result<Option<Vec<u8>>, GitError> {
+    let file = decode_path(path);
+    if ref_name == WORKDIR || ref_name == "@" {
         // Read from working directory
-        let full_path = repo.join(path);
+        let full_path = repo.join(&file);
 
         if !full_path.exists() {
-            return Err(GitError::CommandFailed(format!("File not found: {path}")));
+            return Ok(None);
         }
 
         if full_path.is_dir() {
@@ -148,57 +329,59 @@ pub fn get_file_at_ref(repo: &Path, ref_name: &str, path: &str) -> Result<File,
         let bytes = std::fs::read(&full_path)
             .map_err(|e| GitError::CommandFailed(format!("Cannot read file: {e}")))?;
 
-        let content = if is_binary(&bytes) {
-            FileContent::Binary
-        } else {
-            let text = String::from_utf8_lossy(&bytes);
-            text_to_content(&text)
-        };
-
-        Ok(File {
-            path: path.to_string(),
-            content,
-        })
-    } else {
-        // Read from git tree: git show <ref>:<path>
-        let spec = format!("{ref_name}:{path}");
-        let output = cli::run(repo, &["show", &spec]).map_err(|e| match e {
-            GitError::CommandFailed(msg) if msg.contains("does not exist") => {
-                GitError::CommandFailed(format!("File not found: {path}"))
-            }
-            other => other,
-        })?;
+        return Ok(Some(bytes));
+    }
 
-        // git show returns text, check if it looks binary
-        let bytes = output.as_bytes();
-        let content = if is_binary(bytes) {
-            FileContent::Binary
-        } else {
-            text_to_content(&output)
-        };
+    // git show can't be given a name that isn't UTF-8
+    if file.to_str().is_none() {
+        return read_blob_at_ref(repo, ref_name, &file);
+    }
 
-        Ok(File {
-            path: path.to_string(),
-            content,
-        })
+    // Read from the index or a git tree: git show :<path> / <ref>:<path>
+    let spec = if ref_name == INDEX_REF {
+        format!(":{path}")
+    } else {
+        format!("{ref_name}:{path}")
+    };
+    match cli::run_bytes(repo, &["show", &spec]) {
+        Ok(output) => Ok(Some(output)),
+        Err(GitError::CommandFailed(msg))
+            if msg.contains("does not exist") || msg.contains("but not in") =>
+        {
+            Ok(None)
+        }
+        Err(e) => Err(e),
     }
 }
 
-/// Check if data appears to be binary (contains null bytes in first 8KB)
-fn is_binary(data: &[u8]) -> bool {
-    let check_len = data.len().min(8192);
-    data[..check_len].contains(&0)
-}
+/// Like the `git show` path of [`read_bytes_at_ref`], through libgit2.
+fn read_blob_at_ref(repo: &Path, ref_name: &str, path: &Path) -> Result<Option<Vec<u8>>, GitError> {
+    let repo = Repository::discover(repo).map_err(|e| GitError::NotARepo(e.to_string()))?;
+    let git_err = |e: git2::Error| GitError::CommandFailed(e.message().to_string());
 
-/// Convert text to FileContent with lines
-fn text_to_content(text: &str) -> FileContent {
-    let lines: Vec<String> = text.lines().map(|s| s.to_string()).collect();
-    FileContent::Text { lines }
+    let id = if ref_name == INDEX_REF {
+        match repo.index().map_err(git_err)?.get_path(path, 0) {
+            Some(entry) => entry.id,
+            None => return Ok(None),
+        }
+    } else {
+        let tree = repo
+            .revparse_single(ref_name)
+            .and_then(|object| object.peel_to_tree())
+            .map_err(git_err)?;
+        match tree.get_path(path) {
+            Ok(entry) => entry.id(),
+            Err(_) => return Ok(None),
+        }
+    };
+    let blob = repo.find_blob(id).map_err(git_err)?;
+    Ok(Some(blob.content().to_vec()))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::git::test_support::{git, init_test_repo};
 
     #[test]
     fn test_fuzzy_match_basic() {
@@ -232,4 +415,110 @@ mod tests {
         // Empty query matches everything
         assert!(fuzzy_match("any/path.rs", "").is_some());
     }
+
+    fn repo_with_files(count: usize) -> tempfile::TempDir {
+        let (dir, _) = init_test_repo();
+        for i in 0..count {
+            std::fs::write(dir.path().join(format!("file{i}.txt")), "x\n").unwrap();
+        }
+        git(dir.path(), &["add", "."]);
+        git(dir.path(), &["commit", "-m", "files"]);
+        dir
+    }
+
+    #[test]
+    fn test_list_files_reports_truncation() {
+        let dir = repo_with_files(10);
+
+        let all = list_files(dir.path(), "HEAD", &FileWalkLimits::default()).unwrap();
+        assert_eq!(all.paths.len(), 10);
+        assert!(!all.truncated);
+
+        let limits = FileWalkLimits {
+            max_files: 4,
+            ..Default::default()
+        };
+        let capped = list_files(dir.path(), "HEAD", &limits).unwrap();
+        assert_eq!(capped.paths.len(), 4);
+        assert!(capped.truncated);
+
+        let result = search_files(dir.path(), "HEAD", "file", 20, &limits).unwrap();
+        assert_eq!(result.paths.len(), 4);
+        assert!(result.truncated);
+    }
+
+    #[test]
+    fn test_read_text_at_ref() {
+        let dir = repo_with_files(1);
+        let repo = dir.path();
+        std::fs::write(repo.join("file0.txt"), "staged\n").unwrap();
+        git(repo, &["add", "file0.txt"]);
+        std::fs::write(repo.join("file0.txt"), "working\n").unwrap();
+        std::fs::write(repo.join("untracked.txt"), "new\n").unwrap();
+        std::fs::write(repo.join("blob.bin"), [0u8, 159, 146, 150]).unwrap();
+
+        let read = |ref_name: &str, path: &str| read_text_at_ref(repo, ref_name, path).unwrap();
+        let head = git(repo, &["rev-parse", "HEAD"]);
+
+        assert_eq!(read("HEAD", "file0.txt").as_deref(), Some("x\n"));
+        assert_eq!(read(&head, "file0.txt").as_deref(), Some("x\n"));
+        assert_eq!(read(INDEX_REF, "file0.txt").as_deref(), Some("staged\n"));
+        assert_eq!(read("@", "file0.txt").as_deref(), Some("working\n"));
+        assert_eq!(read(WORKDIR, "file0.txt").as_deref(), Some("working\n"));
+
+        // Missing files and binary content read as None
+        assert_eq!(read("HEAD", "untracked.txt"), None);
+        assert_eq!(read(INDEX_REF, "untracked.txt"), None);
+        assert_eq!(read("@", "untracked.txt").as_deref(), Some("new\n"));
+        assert_eq!(read(WORKDIR, "untracked.txt").as_deref(), Some("new\n"));
+        assert_eq!(read("HEAD", "missing.txt"), None);
+        assert_eq!(read("@", "missing.txt"), None);
+        assert_eq!(read(WORKDIR, "blob.bin"), None);
+
+        // A bad ref is still an error
+        assert!(read_text_at_ref(repo, "no-such-ref", "file0.txt").is_err());
+        assert!(matches!(
+            get_file_at_ref(repo, "HEAD", "missing.txt"),
+            Err(GitError::CommandFailed(msg)) if msg == "File not found: missing.txt"
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_get_file_at_ref_by_raw_path() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = repo_with_files(1);
+        let repo = dir.path();
+        let name = Path::new(OsStr::from_bytes(b"caf\xe9.txt"));
+        std::fs::write(repo.join(name), "committed\n").unwrap();
+        git(repo, &["add", "."]);
+        git(repo, &["commit", "-m", "latin-1 name"]);
+        std::fs::write(repo.join(name), "working\n").unwrap();
+
+        let raw = crate::util::encode_path(name);
+        for (ref_name, expected) in [
+            ("HEAD", "committed\n"),
+            (INDEX_REF, "committed\n"),
+            (WORKDIR, "working\n"),
+        ] {
+            let file = get_file_at_ref(repo, ref_name, &raw).unwrap();
+            assert_eq!(file.path, "caf\u{FFFD}.txt");
+            assert_eq!(file.raw_path.as_deref(), Some(raw.as_str()));
+            assert_eq!(file.content, FileContent::from_text(expected));
+        }
+        assert_eq!(read_text_at_ref(repo, "HEAD~1", &raw).unwrap(), None);
+
+        // Listing yields the same raw form, so a listed file opens by it
+        let listed = list_files(repo, "HEAD", &FileWalkLimits::default()).unwrap();
+        assert_eq!(listed.paths, [raw.as_str(), "file0.txt"]);
+    }
+
+    #[test]
+    fn test_list_files_bad_ref_errors() {
+        let dir = repo_with_files(1);
+        let result = list_files(dir.path(), "no-such-ref", &FileWalkLimits::default());
+        assert!(matches!(result, Err(GitError::CommandFailed(_))));
+    }
 }
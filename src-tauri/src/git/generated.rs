@@ -0,0 +1,308 @@
+//! Classification of generated and vendored files.
+//!
+//! Reviewers usually want lockfiles, build output and codegen collapsed by
+//! default. A file counts as generated if `.gitattributes` marks it
+//! `linguist-generated`, its path matches a well-known pattern, or its first
+//! few lines carry a generated-code marker. An explicit
+//! `-linguist-generated` attribute overrides the path and content checks.
+
+use super::cli::GitError;
+use super::types::{DiffSpec, FileDiffSummary, GitRef};
+use crate::util::{display_path, GlobSet, GlobVars};
+use git2::{AttrCheckFlags, AttrValue, ObjectType, Repository};
+use std::io::Read;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Path patterns (see `crate::util::glob`) for generated and vendored files:
+/// build output and vendored directories at any depth, lockfiles and
+/// minified or codegen suffixes.
+const GENERATED_PATTERNS: &[&str] = &[
+    "dist/",
+    "vendor/",
+    "node_modules/",
+    "package-lock.json",
+    "pnpm-lock.yaml",
+    "go.sum",
+    "npm-shrinkwrap.json",
+    "*.lock",
+    "*.min.js",
+    "*.min.css",
+    "*.pb.go",
+    "*_pb2.py",
+    "*.g.dart",
+    "*.js.map",
+];
+
+/// Markers that identify generated content near the top of a file.
+const GENERATED_MARKERS: &[&str] = &["@generated", "DO NOT EDIT"];
+
+/// How many leading lines to scan for a marker.
+const MARKER_SCAN_LINES: usize = 10;
+
+/// Leading bytes of a file read when scanning for a marker.
+const MARKER_SCAN_BYTES: usize = 4096;
+
+/// Largest blob loaded whole when scanning for a marker; bigger ones are
+/// streamed if the object database allows it.
+const MARKER_BLOB_MAX_BYTES: usize = 1024 * 1024;
+
+/// Whether a repo-relative path matches a well-known generated pattern.
+pub fn is_generated_path(path: &str) -> bool {
+    static PATTERNS: OnceLock<GlobSet> = OnceLock::new();
+    PATTERNS
+        .get_or_init(|| {
+            GlobSet::new(GENERATED_PATTERNS, &GlobVars::repo_relative())
+                .expect("built-in generated patterns are valid")
+        })
+        .is_match(path)
+}
+
+/// Whether the first few lines of a file carry a generated-code marker.
+pub fn has_generated_marker(lines: &[String]) -> bool {
+    lines
+        .iter()
+        .take(MARKER_SCAN_LINES)
+        .any(|line| GENERATED_MARKERS.iter().any(|m| line.contains(m)))
+}
+
+/// Combine the signals. `attr` is the explicit `linguist-generated` value,
+/// if one is set; it wins over the heuristics.
+pub fn classify(attr: Option<bool>, path: &str, lines: Option<&[String]>) -> bool {
+    if let Some(explicit) = attr {
+        return explicit;
+    }
+    is_generated_path(path) || lines.is_some_and(has_generated_marker)
+}
+
+/// Parse a `linguist-generated` attribute value into an explicit setting.
+pub fn parse_attr_value(value: AttrValue) -> Option<bool> {
+    match value {
+        AttrValue::True | AttrValue::String("true") => Some(true),
+        AttrValue::False | AttrValue::String("false") => Some(false),
+        _ => None,
+    }
+}
+
+/// The explicit `linguist-generated` attribute of a path, if one is set.
+fn linguist_generated_attr(repo: &Repository, path: &Path) -> Result<Option<bool>, GitError> {
+    let value = repo
+        .get_attr_bytes(path, "linguist-generated", AttrCheckFlags::default())
+        .map_err(|e| GitError::CommandFailed(format!("Cannot read attributes: {e}")))?;
+    Ok(parse_attr_value(AttrValue::from_bytes(value)))
+}
+
+/// Set the `generated` flag on each file in a changed-file list.
+///
+/// Markers are looked for at the top of the `head` side of each file, or the
+/// `base` side of a deletion, so the flag agrees with [`is_generated`] and
+/// with `FileDiff::generated`. `spec` must already be resolved.
+pub fn mark_generated(
+    repo_path: &Path,
+    spec: &DiffSpec,
+    files: &mut [FileDiffSummary],
+) -> Result<(), GitError> {
+    let repo = Repository::discover(repo_path).map_err(|e| GitError::NotARepo(e.to_string()))?;
+
+    for file in files.iter_mut() {
+        let side = if file.after.is_some() {
+            &spec.head
+        } else {
+            &spec.base
+        };
+        file.generated = is_generated(&repo, side, file.path())?;
+    }
+
+    Ok(())
+}
+
+/// Classify a single file the way [`mark_generated`] does, scanning the
+/// content of `path` at `side` for markers only when the attribute and path
+/// don't settle it.
+pub fn is_generated(repo: &Repository, side: &GitRef, path: &Path) -> Result<bool, GitError> {
+    let attr = linguist_generated_attr(repo, path)?;
+    let display = display_path(path);
+    if attr.is_some() || is_generated_path(&display) {
+        return Ok(classify(attr, &display, None));
+    }
+    let lines = leading_lines(repo, side, path);
+    Ok(classify(None, &display, lines.as_deref()))
+}
+
+/// The first lines of a file at a resolved ref, read from a bounded prefix.
+fn leading_lines(repo: &Repository, side: &GitRef, path: &Path) -> Option<Vec<String>> {
+    let prefix = match side {
+        GitRef::WorkingTree => {
+            let file = std::fs::File::open(repo.workdir()?.join(path)).ok()?;
+            read_prefix(file)?
+        }
+        GitRef::Rev(rev) => {
+            let tree = repo.revparse_single(rev).ok()?.peel_to_tree().ok()?;
+            let id = tree.get_path(path).ok()?.id();
+            let odb = repo.odb().ok()?;
+            let (size, kind) = odb.read_header(id).ok()?;
+            if kind != ObjectType::Blob {
+                return None;
+            }
+            if size <= MARKER_BLOB_MAX_BYTES {
+                let blob = repo.find_blob(id).ok()?;
+                blob.content()[..size.min(MARKER_SCAN_BYTES)].to_vec()
+            } else {
+                // Too big to load whole. Only loose objects can be streamed,
+                // so a large packed blob goes unscanned.
+                let (reader, _, _) = odb.reader(id).ok()?;
+                read_prefix(reader)?
+            }
+        }
+        GitRef::MergeBase | GitRef::MergeBaseOf(_) => return None,
+    };
+    Some(
+        String::from_utf8_lossy(&prefix)
+            .lines()
+            .take(MARKER_SCAN_LINES)
+            .map(String::from)
+            .collect(),
+    )
+}
+
+/// Read up to [`MARKER_SCAN_BYTES`] from the start of `reader`.
+fn read_prefix(reader: impl Read) -> Option<Vec<u8>> {
+    let mut prefix = Vec::new();
+    reader
+        .take(MARKER_SCAN_BYTES as u64)
+        .read_to_end(&mut prefix)
+        .ok()?;
+    Some(prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_support::init_test_repo;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_lockfile_is_generated() {
+        assert!(is_generated_path("Cargo.lock"));
+        assert!(is_generated_path("web/package-lock.json"));
+        assert!(is_generated_path("frontend/dist/app.js"));
+        assert!(is_generated_path("vendor/github.com/x/y.go"));
+        assert!(classify(None, "yarn.lock", None));
+    }
+
+    #[test]
+    fn test_generated_header_is_generated() {
+        let content = lines("// @generated by protoc-gen-go\npackage api\n");
+        assert!(has_generated_marker(&content));
+        assert!(classify(None, "api/service.go", Some(&content)));
+
+        let content = lines("# Code generated by sqlc. DO NOT EDIT.\n");
+        assert!(classify(None, "db/query.py", Some(&content)));
+    }
+
+    #[test]
+    fn test_normal_source_is_not_generated() {
+        let content = lines("fn main() {\n    println!(\"hi\");\n}\n");
+        assert!(!classify(None, "src/main.rs", Some(&content)));
+        assert!(!is_generated_path("src/distance.rs"));
+    }
+
+    #[test]
+    fn test_attribute_overrides_heuristics() {
+        assert!(classify(Some(true), "src/schema.rs", None));
+        assert!(!classify(Some(false), "Cargo.lock", None));
+        assert_eq!(parse_attr_value(AttrValue::True), Some(true));
+        assert_eq!(parse_attr_value(AttrValue::String("false")), Some(false));
+        assert_eq!(parse_attr_value(AttrValue::Unspecified), None);
+    }
+
+    #[test]
+    fn test_mark_generated_uses_gitattributes() {
+        let (dir, _) = init_test_repo();
+        let repo = dir.path();
+        std::fs::write(
+            repo.join(".gitattributes"),
+            "schema.rs linguist-generated\nCargo.lock -linguist-generated\n",
+        )
+        .unwrap();
+
+        let mut files: Vec<FileDiffSummary> = ["schema.rs", "Cargo.lock", "main.rs", "app.min.js"]
+            .iter()
+            .map(|p| FileDiffSummary {
+                before: None,
+                after: Some(p.into()),
+                generated: false,
+            })
+            .collect();
+        mark_generated(repo, &DiffSpec::uncommitted(), &mut files).unwrap();
+
+        let flags: Vec<bool> = files.iter().map(|f| f.generated).collect();
+        assert_eq!(flags, vec![true, false, false, true]);
+    }
+
+    #[test]
+    fn test_list_and_file_diff_agree() {
+        let (dir, git) = init_test_repo();
+        let repo = dir.path();
+        std::fs::write(
+            repo.join("api.go"),
+            "// @generated by protoc\npackage api\n",
+        )
+        .unwrap();
+        std::fs::write(repo.join("old.go"), "// DO NOT EDIT.\npackage old\n").unwrap();
+        std::fs::write(repo.join("main.go"), "package main\n").unwrap();
+        git(&["add", "-A"]);
+        git(&["commit", "-m", "initial"]);
+
+        std::fs::write(
+            repo.join("api.go"),
+            "// @generated by protoc\npackage api2\n",
+        )
+        .unwrap();
+        std::fs::write(repo.join("main.go"), "package main\n\nfunc main() {}\n").unwrap();
+        std::fs::remove_file(repo.join("old.go")).unwrap();
+
+        let check = |spec: &DiffSpec| {
+            let mut files = super::super::list_diff_files(repo, spec).unwrap();
+            files.sort_by(|a, b| a.path().cmp(b.path()));
+            let flags: Vec<_> = files
+                .iter()
+                .map(|f| (f.path().to_string_lossy().to_string(), f.generated))
+                .collect();
+            assert_eq!(
+                flags,
+                [
+                    ("api.go".to_string(), true),
+                    ("main.go".to_string(), false),
+                    ("old.go".to_string(), true),
+                ]
+            );
+            for file in &files {
+                let diff = super::super::get_file_diff(repo, spec, file.path()).unwrap();
+                assert_eq!(diff.generated, file.generated, "{}", file.path().display());
+            }
+        };
+        check(&DiffSpec::uncommitted());
+        git(&["commit", "-am", "change"]);
+        check(&DiffSpec::last_commit());
+    }
+
+    #[test]
+    fn test_marker_found_in_large_blob() {
+        let (dir, git) = init_test_repo();
+        let repo = dir.path();
+        let body = "var x = 1\n".repeat(MARKER_BLOB_MAX_BYTES / 8);
+        std::fs::write(repo.join("big.go"), format!("// @generated\n{body}")).unwrap();
+        std::fs::write(repo.join("small.go"), "package small\n").unwrap();
+        git(&["add", "-A"]);
+        git(&["commit", "-m", "initial"]);
+
+        let repo = Repository::open(repo).unwrap();
+        let head = GitRef::Rev("HEAD".to_string());
+        assert!(is_generated(&repo, &head, Path::new("big.go")).unwrap());
+        assert!(!is_generated(&repo, &head, Path::new("small.go")).unwrap());
+    }
+}
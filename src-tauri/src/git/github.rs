@@ -6,6 +6,7 @@
 use super::cli::GitError;
 use super::DiffSpec;
 use super::GitRef;
+use crate::logging;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::Path;
@@ -560,20 +561,29 @@ fn convert_comment(
         // For single-line comments, don't use start_line
         let is_multiline = comment.span.end > comment.span.start + 1;
 
+        // GitHub comments can't target columns, so note them in the body
+        let body = match comment.column_label() {
+            Some(columns) => format!("{}\n\n_({columns})_", comment.content),
+            None => comment.content.clone(),
+        };
+
         Ok(GitHubReviewComment {
             path: comment.path.clone(),
-            body: comment.content.clone(),
+            body,
             line,
             side: "RIGHT", // Always RIGHT since we only support comments on new code
             start_line: if is_multiline { Some(start_line) } else { None },
             start_side: if is_multiline { Some("RIGHT") } else { None },
         })
     } else {
-        let line_info = if comment.span.end > comment.span.start + 1 {
+        let mut line_info = if comment.span.end > comment.span.start + 1 {
             format!("Lines {start_line}-{line}")
         } else {
             format!("Line {line}")
         };
+        if let Some(columns) = comment.column_label() {
+            line_info.push_str(&format!(", {columns}"));
+        }
 
         Err(OutOfDiffComment {
             path: comment.path.clone(),
@@ -594,7 +604,7 @@ async fn fetch_pr_diff_lines(
 ) -> Result<std::collections::HashMap<String, std::collections::HashSet<u32>>, GitError> {
     let url = format!("https://api.github.com/repos/{owner}/{repo}/pulls/{pr_number}/files");
 
-    log::info!("Fetching PR files from: {url}");
+    log::info!(target: logging::GIT, "Fetching PR files from: {url}");
 
     let response = client
         .get(&url)
@@ -779,6 +789,7 @@ pub async fn sync_review_to_github(
     let token = get_github_token()?;
     let (owner, repo_name) = get_github_repo(repo)?;
     log::info!(
+        target: logging::GIT,
         "Syncing {} comments to GitHub PR #{} in {}/{}",
         comments.len(),
         pr_number,
@@ -798,7 +809,7 @@ pub async fn sync_review_to_github(
     if let Some(existing) =
         find_pending_review(&client, &token, &owner, &repo_name, pr_number, &username).await?
     {
-        log::info!("Deleting existing pending review {}", existing.id);
+        log::info!(target: logging::GIT, "Deleting existing pending review {}", existing.id);
         delete_pending_review(&client, &token, &owner, &repo_name, pr_number, existing.id).await?;
     }
 
@@ -1150,4 +1161,18 @@ mod tests {
         // Either authenticated or has a setup hint
         assert!(status.authenticated || status.setup_hint.is_some());
     }
+
+    #[test]
+    fn test_convert_comment_notes_columns() {
+        let comment = Comment::new("src/lib.rs", crate::git::Span::new(4, 5), "Typo")
+            .with_columns(Some(8), Some(14));
+
+        let converted = convert_comment(&comment, None).ok().unwrap();
+        assert_eq!(converted.line, 5);
+        assert_eq!(converted.body, "Typo\n\n_(cols 9-14)_");
+
+        let valid_lines = std::collections::HashSet::from([1]);
+        let outside = convert_comment(&comment, Some(&valid_lines)).err().unwrap();
+        assert_eq!(outside.line_info, "Line 5, cols 9-14");
+    }
 }
@@ -1,26 +1,43 @@
 mod cli;
 mod commit;
+mod conflict;
 mod diff;
 mod files;
+mod generated;
 pub mod github;
+mod patch;
 mod refs;
+mod repo;
+#[cfg(test)]
+pub(crate) mod test_support;
 mod types;
 mod worktree;
 
 pub use cli::GitError;
-pub use commit::commit;
-pub use diff::{get_file_diff, get_unified_diff, list_diff_files};
-pub use files::{get_file_at_ref, search_files};
+pub use commit::{commit, commit_template, CommitOptions};
+pub use conflict::{parse_conflicts, ConflictRegion};
+pub use diff::{
+    diff_id, exclude_files, get_file_diff, get_file_diff_with_options, get_incremental_diff,
+    get_unified_diff, list_diff_files, list_diff_files_with_progress, list_diff_files_with_renames,
+    list_formatting_only_files, render_unified, render_unified_with_options, FileDiffOptions,
+    RenameOptions, DEFAULT_MAX_DIFF_FILE_BYTES,
+};
+pub use files::{
+    get_file_at_ref, list_files, read_text_at_ref, search_files, FileList, FileWalkLimits,
+    INDEX_REF,
+};
 pub use github::{
     check_github_auth, create_pull_request, fetch_pr, get_pr_for_branch,
     invalidate_cache as invalidate_pr_cache, list_issues, list_pull_requests, push_branch,
     search_issues, search_pull_requests, sync_review_to_github, update_pull_request,
     CreatePrResult, GitHubAuthStatus, GitHubSyncResult, Issue, PullRequest, PullRequestInfo,
 };
+pub use patch::{format_patch, hunk_to_patch};
 pub use refs::{
-    detect_default_branch, get_repo_root, list_branches, list_refs, merge_base, resolve_ref,
-    BranchRef,
+    commit_spec, detect_default_branch, get_repo_root, list_branches, list_refs, list_stashes,
+    merge_base, resolve_ref, BranchRef, StashEntry,
 };
+pub use repo::{git_dirs, normalize_repo_dir, normalize_repo_path, GitDirs};
 pub use types::*;
 pub use worktree::{
     branch_exists, create_worktree, create_worktree_for_existing_branch, create_worktree_from_pr,
@@ -0,0 +1,494 @@
+//! Patch export: a diff range, or a single hunk of a file diff, as a unified
+//! patch that `git apply` accepts.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use super::cli::{self, GitError};
+use super::diff::resolve_spec;
+use super::types::*;
+use crate::util::{decode_path, path_bytes};
+
+/// Options shared by every patch: full blob IDs and binary hunks so binary
+/// files apply, rename detection, and fixed a/ b/ prefixes regardless of the
+/// user's diff config.
+const PATCH_ARGS: &[&str] = &[
+    "--binary",
+    "--full-index",
+    "--find-renames",
+    "--no-color",
+    "--no-ext-diff",
+    "--src-prefix=a/",
+    "--dst-prefix=b/",
+];
+
+/// Produce a unified patch for a diff range.
+///
+/// For working tree diffs, untracked (non-ignored) files are included. They're
+/// staged into a throwaway index so the repository's real index is untouched.
+/// The patch is returned as git wrote it: file contents in it needn't be
+/// UTF-8.
+pub fn format_patch(repo: &Path, spec: &DiffSpec) -> Result<Vec<u8>, GitError> {
+    let spec = resolve_spec(repo, spec)?;
+
+    match (&spec.base, &spec.head) {
+        (GitRef::Rev(base), GitRef::Rev(head)) => {
+            let mut args = vec!["diff"];
+            args.extend_from_slice(PATCH_ARGS);
+            args.extend([base.as_str(), head.as_str()]);
+            cli::run_bytes(repo, &args)
+        }
+        (GitRef::Rev(base), GitRef::WorkingTree) => working_tree_patch(repo, base),
+        (GitRef::WorkingTree, _) => Err(GitError::CommandFailed(
+            "Cannot use working tree as base".to_string(),
+        )),
+        (GitRef::MergeBase | GitRef::MergeBaseOf(_), _)
+        | (_, GitRef::MergeBase | GitRef::MergeBaseOf(_)) => {
+            unreachable!("MergeBase/MergeBaseOf should have been resolved")
+        }
+    }
+}
+
+/// Lines of context kept around a hunk, as `git diff` does by default.
+const HUNK_CONTEXT: u32 = 3;
+
+/// A patch containing only the `hunk_index`-th changed region of a file diff
+/// (as returned by `get_file_diff`), with up to three lines of context.
+///
+/// Line terminators aren't kept in `FileContent`, so each line is written
+/// with the ending recorded for it: CRLF, LF, or none for a last line
+/// missing its newline.
+pub fn hunk_to_patch(diff: &FileDiff, hunk_index: usize) -> Result<String, GitError> {
+    let changed: Vec<usize> = (0..diff.alignments.len())
+        .filter(|&i| diff.alignments[i].changed)
+        .collect();
+    let &index = changed.get(hunk_index).ok_or_else(|| {
+        GitError::CommandFailed(format!(
+            "Hunk {hunk_index} out of range ({} hunks)",
+            changed.len()
+        ))
+    })?;
+    let hunk = &diff.alignments[index];
+
+    let before = Side::of(diff.before.as_ref())?;
+    let after = Side::of(diff.after.as_ref())?;
+    let name_of = |prefix, file: Option<&File>| file.map(|f| patch_name(prefix, f));
+    let old_name = name_of("a/", diff.before.as_ref());
+    let new_name = name_of("b/", diff.after.as_ref());
+
+    // Context comes from the unchanged regions on either side
+    let unchanged_len = |i: Option<usize>| {
+        i.and_then(|i| diff.alignments.get(i))
+            .filter(|a| !a.changed)
+            .map_or(0, |a| a.before.len())
+    };
+    let leading = unchanged_len(index.checked_sub(1)).min(HUNK_CONTEXT);
+    let trailing = unchanged_len(Some(index + 1)).min(HUNK_CONTEXT);
+
+    let old_start = hunk.before.start - leading;
+    let old_count = leading + hunk.before.len() + trailing;
+    let new_start = hunk.after.start - leading;
+    let new_count = leading + hunk.after.len() + trailing;
+
+    // A context line whose ending differs between the sides (e.g. it gains
+    // a newline because lines are appended after it) is removed and re-added
+    let context = |old: u32, new: u32| -> Result<String, GitError> {
+        let line = before.line(' ', old)?;
+        if line == after.line(' ', new)? {
+            Ok(line)
+        } else {
+            Ok(before.line('-', old)? + &after.line('+', new)?)
+        }
+    };
+
+    let header_file = diff.after.as_ref().or(diff.before.as_ref());
+    let mut patch = format!(
+        "diff --git {} {}\n",
+        name_of("a/", header_file).unwrap_or_default(),
+        name_of("b/", header_file).unwrap_or_default()
+    );
+    patch.push_str(&format!(
+        "--- {}\n",
+        old_name.as_deref().unwrap_or("/dev/null")
+    ));
+    patch.push_str(&format!(
+        "+++ {}\n",
+        new_name.as_deref().unwrap_or("/dev/null")
+    ));
+    patch.push_str(&format!(
+        "@@ -{} +{} @@\n",
+        hunk_range(old_start, old_count),
+        hunk_range(new_start, new_count)
+    ));
+    for i in 0..leading {
+        patch.push_str(&context(old_start + i, new_start + i)?);
+    }
+    for n in hunk.before.start..hunk.before.end {
+        patch.push_str(&before.line('-', n)?);
+    }
+    for n in hunk.after.start..hunk.after.end {
+        patch.push_str(&after.line('+', n)?);
+    }
+    for i in 0..trailing {
+        patch.push_str(&context(hunk.before.end + i, hunk.after.end + i)?);
+    }
+    Ok(patch)
+}
+
+/// One side of a diff as text; a missing side (added/deleted file) is empty.
+struct Side<'a> {
+    lines: &'a [String],
+    crlf_lines: HashSet<u32>,
+    missing_final_newline: bool,
+}
+
+impl<'a> Side<'a> {
+    fn of(file: Option<&'a File>) -> Result<Self, GitError> {
+        match file.map(|f| &f.content) {
+            None => Ok(Self {
+                lines: &[],
+                crlf_lines: HashSet::new(),
+                missing_final_newline: false,
+            }),
+            Some(FileContent::Text {
+                lines,
+                crlf_lines,
+                missing_final_newline,
+            }) => Ok(Self {
+                lines,
+                crlf_lines: crlf_lines.iter().copied().collect(),
+                missing_final_newline: *missing_final_newline,
+            }),
+            Some(_) => Err(GitError::CommandFailed(
+                "Cannot make a patch for a binary or oversized file".to_string(),
+            )),
+        }
+    }
+
+    /// Line `n` (0-indexed) as a patch line under `prefix`, with its ending.
+    fn line(&self, prefix: char, n: u32) -> Result<String, GitError> {
+        let text = self.lines.get(n as usize).ok_or_else(|| {
+            GitError::CommandFailed(format!(
+                "Hunk refers to line {} of a {}-line file",
+                n + 1,
+                self.lines.len()
+            ))
+        })?;
+        let eol = if self.missing_final_newline && n as usize + 1 == self.lines.len() {
+            "\n\\ No newline at end of file\n"
+        } else if self.crlf_lines.contains(&n) {
+            "\r\n"
+        } else {
+            "\n"
+        };
+        Ok(format!("{prefix}{text}{eol}"))
+    }
+}
+
+/// A file's name in a patch header, under `prefix`. Names that aren't UTF-8,
+/// or that contain quotes, backslashes or control characters, are C-quoted
+/// the way git writes them so `git apply` reads them back byte for byte.
+fn patch_name(prefix: &str, file: &File) -> String {
+    let bytes = match &file.raw_path {
+        Some(raw) => path_bytes(&decode_path(raw)),
+        None => file.path.as_bytes().to_vec(),
+    };
+    let plain = std::str::from_utf8(&bytes).is_ok()
+        && !bytes
+            .iter()
+            .any(|&b| b < 0x20 || b == 0x7f || b == b'"' || b == b'\\');
+    if plain {
+        return format!("{prefix}{}", file.path);
+    }
+
+    let mut quoted = format!("\"{prefix}");
+    for &b in &bytes {
+        match b {
+            b'"' => quoted.push_str("\\\""),
+            b'\\' => quoted.push_str("\\\\"),
+            b'\t' => quoted.push_str("\\t"),
+            b'\n' => quoted.push_str("\\n"),
+            0x20..=0x7e => quoted.push(b as char),
+            _ => quoted.push_str(&format!("\\{b:03o}")),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// A hunk header range: 1-based start and count. An empty range starts at
+/// the line before it, which is 0 at the top of the file.
+fn hunk_range(start: u32, count: u32) -> String {
+    if count == 0 {
+        format!("{start},0")
+    } else {
+        format!("{},{count}", start + 1)
+    }
+}
+
+/// Diff `base` against the whole working tree, untracked files included.
+fn working_tree_patch(repo: &Path, base: &str) -> Result<Vec<u8>, GitError> {
+    let index = git_path(repo, "index")?;
+    let temp_index = git_path(
+        repo,
+        &format!("staged-patch-index-{}", uuid::Uuid::new_v4()),
+    )?;
+
+    // Start from a copy of the real index so unchanged files keep their
+    // cached stat info and `add -A` only hashes what changed.
+    if index.exists() {
+        std::fs::copy(&index, &temp_index)
+            .map_err(|e| GitError::CommandFailed(format!("Failed to copy index: {e}")))?;
+    }
+
+    let envs = [("GIT_INDEX_FILE", temp_index.as_os_str())];
+    let result = cli::run_with_env(repo, &["add", "-A"], &envs).and_then(|_| {
+        let mut args = vec!["diff", "--cached"];
+        args.extend_from_slice(PATCH_ARGS);
+        args.push(base);
+        cli::run_bytes_with_env(repo, &args, &envs)
+    });
+
+    let _ = std::fs::remove_file(&temp_index);
+    result
+}
+
+/// Resolve a path inside the git directory (handles worktrees).
+fn git_path(repo: &Path, name: &str) -> Result<PathBuf, GitError> {
+    let path = PathBuf::from(cli::run(repo, &["rev-parse", "--git-path", name])?.trim());
+    Ok(if path.is_absolute() {
+        path
+    } else {
+        repo.join(path)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_support::{git, init_test_repo};
+
+    fn init_repo() -> tempfile::TempDir {
+        let (dir, _) = init_test_repo();
+        std::fs::write(dir.path().join("a.txt"), "one\ntwo\nthree\n").unwrap();
+        std::fs::write(
+            dir.path().join("old.txt"),
+            "a file that will be renamed\nwith enough content\nto be detected\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("gone.txt"), "delete me\n").unwrap();
+        git(dir.path(), &["add", "."]);
+        git(dir.path(), &["commit", "-m", "initial"]);
+        dir
+    }
+
+    fn read(dir: &Path, name: &str) -> Vec<u8> {
+        std::fs::read(dir.join(name)).unwrap()
+    }
+
+    #[test]
+    fn test_working_tree_patch_round_trips() {
+        let dir = init_repo();
+        let repo = dir.path();
+        std::fs::write(repo.join("a.txt"), "one\n2\nthree\nfour\n").unwrap();
+        std::fs::rename(repo.join("old.txt"), repo.join("new.txt")).unwrap();
+        std::fs::remove_file(repo.join("gone.txt")).unwrap();
+        std::fs::write(repo.join("added.txt"), "untracked\n").unwrap();
+        std::fs::write(repo.join("blob.bin"), [0u8, 159, 146, 150, 0, 1]).unwrap();
+        // Text that isn't UTF-8 goes into the patch unchanged
+        std::fs::write(repo.join("latin1.txt"), b"caf\xe9\n").unwrap();
+
+        let patch = format_patch(repo, &DiffSpec::uncommitted()).unwrap();
+        let text = String::from_utf8_lossy(&patch);
+        assert!(text.contains("rename from old.txt"));
+        assert!(text.contains("GIT binary patch"));
+        // The real index is untouched
+        let status = git(repo, &["status", "--porcelain"]);
+        assert!(status.contains("?? added.txt"));
+
+        let clone_parent = tempfile::tempdir().unwrap();
+        let clone = clone_parent.path().join("clone");
+        git(
+            clone_parent.path(),
+            &["clone", "-q", repo.to_str().unwrap(), "clone"],
+        );
+        let patch_file = clone_parent.path().join("changes.patch");
+        std::fs::write(&patch_file, &patch).unwrap();
+        git(&clone, &["apply", "--index", patch_file.to_str().unwrap()]);
+
+        for name in ["a.txt", "new.txt", "added.txt", "blob.bin", "latin1.txt"] {
+            assert_eq!(read(&clone, name), read(repo, name), "{name} differs");
+        }
+        assert!(!clone.join("old.txt").exists());
+        assert!(!clone.join("gone.txt").exists());
+    }
+
+    #[test]
+    fn test_commit_range_patch_round_trips() {
+        let dir = init_repo();
+        let repo = dir.path();
+        let base = git(repo, &["rev-parse", "HEAD"]);
+        std::fs::write(repo.join("a.txt"), "changed\n").unwrap();
+        git(repo, &["commit", "-am", "change a"]);
+
+        let spec = DiffSpec {
+            base: GitRef::Rev(base.clone()),
+            head: GitRef::Rev("HEAD".to_string()),
+        };
+        let patch = format_patch(repo, &spec).unwrap();
+
+        let checkout = tempfile::tempdir().unwrap();
+        git(
+            checkout.path(),
+            &["clone", "-q", repo.to_str().unwrap(), "c"],
+        );
+        let clone = checkout.path().join("c");
+        git(&clone, &["checkout", "-q", &base]);
+        let patch_file = checkout.path().join("range.patch");
+        std::fs::write(&patch_file, &patch).unwrap();
+        git(&clone, &["apply", patch_file.to_str().unwrap()]);
+        assert_eq!(read(&clone, "a.txt"), b"changed\n");
+    }
+
+    #[test]
+    fn test_single_hunk_patch_applies_alone() {
+        let (dir, _) = init_test_repo();
+        let repo = dir.path();
+        let base: Vec<String> = (1..=20).map(|n| format!("line {n}")).collect();
+        std::fs::write(repo.join("f.txt"), base.join("\n") + "\n").unwrap();
+        git(repo, &["add", "."]);
+        git(repo, &["commit", "-m", "initial"]);
+
+        // Three hunks: at the very top, in the middle, and at the very end
+        let mut changed = base.clone();
+        changed[0] = "first".to_string();
+        changed[9] = "middle".to_string();
+        changed.push("appended".to_string());
+        std::fs::write(repo.join("f.txt"), changed.join("\n") + "\n").unwrap();
+
+        let diff =
+            crate::git::get_file_diff(repo, &DiffSpec::uncommitted(), Path::new("f.txt")).unwrap();
+        let apply_only = |hunk_index: usize| {
+            let patch = hunk_to_patch(&diff, hunk_index).unwrap();
+            git(repo, &["checkout", "--", "f.txt"]);
+            let patch_file = dir.path().join("hunk.patch");
+            std::fs::write(&patch_file, &patch).unwrap();
+            git(repo, &["apply", patch_file.to_str().unwrap()]);
+            std::fs::remove_file(&patch_file).unwrap();
+            (patch, String::from_utf8(read(repo, "f.txt")).unwrap())
+        };
+
+        let (patch, result) = apply_only(0);
+        assert!(patch.contains("@@ -1,4 +1,4 @@"), "{patch}");
+        let mut expected = base.clone();
+        expected[0] = "first".to_string();
+        assert_eq!(result, expected.join("\n") + "\n");
+
+        let (_, result) = apply_only(1);
+        let mut expected = base.clone();
+        expected[9] = "middle".to_string();
+        assert_eq!(result, expected.join("\n") + "\n");
+
+        let (patch, result) = apply_only(2);
+        assert!(patch.contains("@@ -18,3 +18,4 @@"), "{patch}");
+        let mut expected = base.clone();
+        expected.push("appended".to_string());
+        assert_eq!(result, expected.join("\n") + "\n");
+
+        assert!(hunk_to_patch(&diff, 3).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_hunk_patch_names_unusual_files() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let (dir, _) = init_test_repo();
+        let repo = dir.path();
+        let names: [&[u8]; 3] = [b"my%20notes.md", b"caf\xe9.txt", b"say \"hi\".txt"];
+        for name in names {
+            std::fs::write(repo.join(OsStr::from_bytes(name)), "one\n").unwrap();
+        }
+        git(repo, &["add", "."]);
+        git(repo, &["commit", "-m", "initial"]);
+
+        for name in names {
+            let name = Path::new(OsStr::from_bytes(name));
+            std::fs::write(repo.join(name), "one\ntwo\n").unwrap();
+            let diff = crate::git::get_file_diff(repo, &DiffSpec::uncommitted(), name).unwrap();
+            let patch = hunk_to_patch(&diff, 0).unwrap();
+
+            git(repo, &["checkout", "--", "."]);
+            let patch_file = dir.path().join("hunk.patch");
+            std::fs::write(&patch_file, &patch).unwrap();
+            git(repo, &["apply", patch_file.to_str().unwrap()]);
+            std::fs::remove_file(&patch_file).unwrap();
+            assert_eq!(
+                std::fs::read(repo.join(name)).unwrap(),
+                b"one\ntwo\n",
+                "{patch}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_hunk_patch_keeps_missing_final_newline() {
+        let (dir, _) = init_test_repo();
+        let repo = dir.path();
+
+        let cases = [
+            ("one\ntwo", "one\ntwo\nthree"),
+            ("one\ntwo\n", "one\ntwo\nthree"),
+            ("one\ntwo", "one\ntwo\nthree\n"),
+            ("one\ntwo\nthree", "one\nTWO\nthree"),
+            ("one\ntwo", "one"),
+        ];
+        for (old, new) in cases {
+            std::fs::write(repo.join("f.txt"), old).unwrap();
+            git(repo, &["add", "."]);
+            git(repo, &["commit", "-q", "--allow-empty", "-m", "base"]);
+            std::fs::write(repo.join("f.txt"), new).unwrap();
+
+            let diff =
+                crate::git::get_file_diff(repo, &DiffSpec::uncommitted(), Path::new("f.txt"))
+                    .unwrap();
+            let patch = hunk_to_patch(&diff, 0).unwrap();
+            git(repo, &["checkout", "--", "f.txt"]);
+            let patch_file = dir.path().join("hunk.patch");
+            std::fs::write(&patch_file, &patch).unwrap();
+            git(repo, &["apply", patch_file.to_str().unwrap()]);
+            std::fs::remove_file(&patch_file).unwrap();
+            assert_eq!(read(repo, "f.txt"), new.as_bytes(), "{patch}");
+        }
+    }
+
+    #[test]
+    fn test_hunk_past_end_of_file_is_an_error() {
+        let text = |lines: &[&str]| {
+            File::new(
+                "f.txt",
+                FileContent::Text {
+                    lines: lines.iter().map(|l| l.to_string()).collect(),
+                    crlf_lines: vec![],
+                    missing_final_newline: false,
+                },
+            )
+        };
+        let diff = FileDiff {
+            schema_version: FileDiff::SCHEMA_VERSION,
+            before: Some(text(&["a"])),
+            after: Some(text(&["b"])),
+            alignments: vec![Alignment {
+                before: Span::new(0, 5),
+                after: Span::new(0, 1),
+                changed: true,
+            }],
+            too_large: false,
+            generated: false,
+            has_conflicts: false,
+            had_invalid_utf8: false,
+        };
+        assert!(hunk_to_patch(&diff, 0).is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use super::cli::{self, GitError};
+use super::types::{DiffSpec, GitRef};
 use std::path::Path;
 
 /// Get the absolute path to the repository root.
@@ -107,6 +108,43 @@ pub fn list_branches(repo: &Path) -> Result<Vec<BranchRef>, GitError> {
     Ok(branches)
 }
 
+/// A stash entry, diffable as `DiffSpec::stash(index)`
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StashEntry {
+    /// Position in the stash list (the N in `stash@{N}`)
+    pub index: usize,
+    /// SHA of the stash commit
+    pub sha: String,
+    /// Stash message (e.g., "WIP on main: abc1234 Fix parser")
+    pub message: String,
+}
+
+/// List stash entries, most recent first.
+pub fn list_stashes(repo: &Path) -> Result<Vec<StashEntry>, GitError> {
+    let output = cli::run(repo, &["stash", "list", "--format=%H%x00%gs"])?;
+
+    Ok(output
+        .lines()
+        .filter_map(|line| line.split_once('\0'))
+        .enumerate()
+        .map(|(index, (sha, message))| StashEntry {
+            index,
+            sha: sha.to_string(),
+            message: message.to_string(),
+        })
+        .collect())
+}
+
+/// Git's well-known empty tree. Stands in for HEAD when there are no
+/// commits yet, so a fresh repo diffs as "everything added".
+pub const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/// Whether HEAD has no commit yet (a freshly `git init`'d repo).
+pub fn is_head_unborn(repo: &Path) -> bool {
+    cli::run(repo, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_err()
+}
+
 /// Compute the merge-base between two refs
 pub fn merge_base(repo: &Path, ref1: &str, ref2: &str) -> Result<String, GitError> {
     let output = cli::run(repo, &["merge-base", ref1, ref2])?;
@@ -119,6 +157,40 @@ pub fn resolve_ref(repo: &Path, reference: &str) -> Result<String, GitError> {
     Ok(output.trim().to_string())
 }
 
+/// The diff a single commit introduced: `parent..commit`.
+///
+/// `parent` picks which parent to diff against (1-based, as in `commit^N`),
+/// defaulting to the first; it matters only for merge commits. A root commit
+/// diffs against the empty tree, whether its parent is left out or given as 1.
+pub fn commit_spec(repo: &Path, commit: &str, parent: Option<usize>) -> Result<DiffSpec, GitError> {
+    let verify = format!("{commit}^{{commit}}");
+    let output = cli::run(repo, &["rev-list", "--parents", "-n", "1", &verify])?;
+    let mut shas = output.split_whitespace().map(str::to_string);
+    let sha = shas
+        .next()
+        .ok_or_else(|| GitError::CommandFailed(format!("Unknown commit: {commit}")))?;
+    let parents: Vec<String> = shas.collect();
+
+    let base = match (parents.is_empty(), parent) {
+        (true, None | Some(1)) => EMPTY_TREE.to_string(),
+        (_, parent) => {
+            let index = parent.unwrap_or(1);
+            index
+                .checked_sub(1)
+                .and_then(|i| parents.get(i))
+                .cloned()
+                .ok_or_else(|| {
+                    GitError::CommandFailed(format!(
+                        "Commit {commit} has {} parent(s), no parent {index}",
+                        parents.len()
+                    ))
+                })?
+        }
+    };
+
+    Ok(DiffSpec::custom(GitRef::Rev(base), GitRef::Rev(sha)))
+}
+
 /// Detect the default branch for this repository.
 /// Checks for common default branch names in order of preference.
 /// Returns the remote-tracking branch (e.g., "origin/main") if available,
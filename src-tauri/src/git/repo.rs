@@ -0,0 +1,284 @@
+use super::cli::{self, GitError};
+use crate::util::{decode_path, encode_path, escape_path, unescape_path};
+use std::path::{Path, PathBuf};
+
+/// Where a checkout keeps its git state.
+///
+/// In a regular repository both paths are `<repo>/.git`. In a linked worktree
+/// `.git` is a file, and the checkout's own index and HEAD live under
+/// `<common>/worktrees/<name>` while refs, config and `info/` stay shared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitDirs {
+    /// Per-checkout directory holding `index` and `HEAD`
+    pub git_dir: PathBuf,
+    /// Directory shared by all worktrees (refs, objects, `info/exclude`)
+    pub common_dir: PathBuf,
+}
+
+impl GitDirs {
+    /// Whether this checkout is a linked worktree rather than the main one.
+    pub fn is_linked_worktree(&self) -> bool {
+        self.git_dir != self.common_dir
+    }
+}
+
+/// Resolve the git directories for a checkout, following `.git` files.
+pub fn git_dirs(repo: &Path) -> Result<GitDirs, GitError> {
+    let output = cli::run(repo, &["rev-parse", "--git-dir", "--git-common-dir"])?;
+    let mut lines = output.lines().map(|line| absolute_in(repo, line.trim()));
+    match (lines.next(), lines.next()) {
+        (Some(git_dir), Some(common_dir)) => Ok(GitDirs {
+            git_dir,
+            common_dir,
+        }),
+        _ => Err(GitError::CommandFailed(format!(
+            "Unexpected rev-parse output: {output}"
+        ))),
+    }
+}
+
+/// Resolve a path printed by `git -C <repo>` (which may be relative to it).
+fn absolute_in(repo: &Path, path: &str) -> PathBuf {
+    let path = repo.join(path);
+    path.canonicalize().unwrap_or(path)
+}
+
+/// Normalize a file path to the clean, repo-relative, forward-slash form used
+/// throughout diffs and reviews.
+///
+/// Accepts absolute paths inside the repo, `./`-prefixed paths, paths with
+/// `..` segments that stay inside the repo, and (on Windows) backslash
+/// separators. Elsewhere a backslash is an ordinary file name character.
+/// The file itself doesn't need to exist (e.g. deleted files in a diff).
+///
+/// `path` may be the raw form of a path that isn't UTF-8, and the result is
+/// encoded the same way (see `util::encode_path`).
+pub fn normalize_repo_path(repo: &Path, path: &str) -> Result<String, GitError> {
+    let parts = relative_parts(repo, path)?;
+    if parts.is_empty() {
+        return Err(GitError::InvalidRepoPath(format!(
+            "{path} does not name a file in the repository"
+        )));
+    }
+    Ok(join_parts(&parts))
+}
+
+/// Like [`normalize_repo_path`], for a directory. The repo root itself
+/// (`""`, `.` or the repo's absolute path) normalizes to `None`.
+pub fn normalize_repo_dir(repo: &Path, path: &str) -> Result<Option<String>, GitError> {
+    let parts = relative_parts(repo, path)?;
+    Ok((!parts.is_empty()).then(|| join_parts(&parts)))
+}
+
+/// Split `path` into clean repo-relative segments, escaped with
+/// `escape_path_bytes` so names that aren't UTF-8 survive.
+fn relative_parts(repo: &Path, path: &str) -> Result<Vec<String>, GitError> {
+    let outside = || GitError::InvalidRepoPath(format!("{path} is outside the repository"));
+
+    let input = decode_path(path);
+    let unified = forward_slashes(escape_path(&input));
+
+    let relative = if input.is_absolute() {
+        strip_repo_prefix(repo, &unescape_path(&unified)).ok_or_else(outside)?
+    } else {
+        unified
+    };
+
+    let mut parts: Vec<String> = Vec::new();
+    for segment in relative.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                parts.pop().ok_or_else(outside)?;
+            }
+            other => parts.push(other.to_string()),
+        }
+    }
+    Ok(parts)
+}
+
+/// Strip the repo root from an absolute path, trying the repo as given and
+/// its canonical form (so symlinked temp/home directories still match).
+fn strip_repo_prefix(repo: &Path, path: &Path) -> Option<String> {
+    let mut roots = vec![repo.to_path_buf()];
+    if let Ok(canonical) = repo.canonicalize() {
+        roots.push(canonical);
+    }
+
+    let mut candidates = vec![path.to_path_buf()];
+    if let Ok(canonical) = path.canonicalize() {
+        candidates.push(canonical);
+    }
+
+    candidates.iter().find_map(|candidate| {
+        roots.iter().find_map(|root: &PathBuf| {
+            candidate
+                .strip_prefix(root)
+                .ok()
+                .map(|rel| forward_slashes(escape_path(rel)))
+        })
+    })
+}
+
+/// Use `/` as the separator. Only Windows paths use `\\` as one.
+fn forward_slashes(path: String) -> String {
+    if cfg!(windows) {
+        path.replace('\\', "/")
+    } else {
+        path
+    }
+}
+
+/// Join escaped segments back into a path, encoded for the frontend.
+fn join_parts(parts: &[String]) -> String {
+    encode_path(&unescape_path(&parts.join("/")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_absolute_path() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/main.rs"), "").unwrap();
+
+        let abs = dir.path().join("src/main.rs");
+        assert_eq!(
+            normalize_repo_path(dir.path(), abs.to_str().unwrap()).unwrap(),
+            "src/main.rs"
+        );
+
+        // Canonical form of the same path (e.g. /private/var vs /var on macOS)
+        let canonical = abs.canonicalize().unwrap();
+        assert_eq!(
+            normalize_repo_path(dir.path(), canonical.to_str().unwrap()).unwrap(),
+            "src/main.rs"
+        );
+    }
+
+    #[test]
+    fn test_normalize_relative_forms() {
+        let repo = Path::new("/repo");
+        assert_eq!(
+            normalize_repo_path(repo, "./src/main.rs").unwrap(),
+            "src/main.rs"
+        );
+        // Backslashes separate only on Windows; elsewhere they're in the name
+        let expected = if cfg!(windows) {
+            "src/git/diff.rs"
+        } else {
+            "src\\git\\diff.rs"
+        };
+        assert_eq!(
+            normalize_repo_path(repo, "src\\git\\diff.rs").unwrap(),
+            expected
+        );
+        assert_eq!(
+            normalize_repo_path(repo, "src//lib/../main.rs").unwrap(),
+            "src/main.rs"
+        );
+        assert_eq!(
+            normalize_repo_path(repo, "/repo/deleted.txt").unwrap(),
+            "deleted.txt"
+        );
+    }
+
+    #[test]
+    fn test_normalize_keeps_names_intact() {
+        let repo = Path::new("/repo");
+        // Literal escapes in UTF-8 names are left alone
+        assert_eq!(
+            normalize_repo_path(repo, "./docs/my%20notes.md").unwrap(),
+            "docs/my%20notes.md"
+        );
+        // Raw forms of non-UTF-8 names stay raw, and are still confined
+        assert_eq!(
+            normalize_repo_path(repo, "\0docs/../caf%E9.txt").unwrap(),
+            "\0caf%E9.txt"
+        );
+        assert!(matches!(
+            normalize_repo_path(repo, "\0../caf%E9.txt"),
+            Err(GitError::InvalidRepoPath(_))
+        ));
+    }
+
+    #[test]
+    fn test_normalize_rejects_outside_paths() {
+        let repo = Path::new("/repo");
+        assert!(matches!(
+            normalize_repo_path(repo, "../other/file.rs"),
+            Err(GitError::InvalidRepoPath(_))
+        ));
+        assert!(matches!(
+            normalize_repo_path(repo, "/elsewhere/file.rs"),
+            Err(GitError::InvalidRepoPath(_))
+        ));
+        assert!(matches!(
+            normalize_repo_path(repo, "./"),
+            Err(GitError::InvalidRepoPath(_))
+        ));
+    }
+
+    #[test]
+    fn test_linked_worktree_targets_its_own_index_and_head() {
+        use crate::git::{
+            commit, get_file_diff, get_head_sha, list_diff_files, test_support::init_test_repo,
+            CommitOptions, DiffSpec, FileContent,
+        };
+
+        let (dir, git) = init_test_repo();
+        let main = dir.path().to_path_buf();
+        let scratch = tempfile::tempdir().unwrap();
+        let wt = scratch.path().join("wt");
+        std::fs::write(main.join("a.txt"), "a\n").unwrap();
+        std::fs::write(main.join("b.txt"), "b\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-m", "initial"]);
+        git(&["worktree", "add", wt.to_str().unwrap(), "-b", "feature"]);
+        assert!(wt.join(".git").is_file());
+
+        let main_dirs = git_dirs(&main).unwrap();
+        let wt_dirs = git_dirs(&wt).unwrap();
+        assert!(!main_dirs.is_linked_worktree());
+        assert!(wt_dirs.is_linked_worktree());
+        assert_eq!(wt_dirs.common_dir, main_dirs.git_dir);
+        assert!(wt_dirs.git_dir.join("HEAD").exists());
+
+        // Diverge the two checkouts
+        std::fs::write(wt.join("a.txt"), "a\nworktree\n").unwrap();
+        std::fs::write(main.join("b.txt"), "b\nmain\n").unwrap();
+
+        let changed = |repo: &Path| -> Vec<String> {
+            list_diff_files(repo, &DiffSpec::uncommitted())
+                .unwrap()
+                .iter()
+                .map(|f| f.path().to_string_lossy().to_string())
+                .collect()
+        };
+        assert_eq!(changed(&wt), vec!["a.txt"]);
+        assert_eq!(changed(&main), vec!["b.txt"]);
+
+        let diff = get_file_diff(&wt, &DiffSpec::uncommitted(), Path::new("a.txt")).unwrap();
+        assert_eq!(
+            diff.after.unwrap().content,
+            FileContent::from_text("a\nworktree\n")
+        );
+
+        // Committing in the worktree moves its HEAD only
+        let main_head = get_head_sha(&main).unwrap();
+        let wt_head = get_head_sha(&wt).unwrap();
+        commit(
+            &wt,
+            &[PathBuf::from("a.txt")],
+            "worktree change",
+            &CommitOptions::default(),
+        )
+        .unwrap();
+        assert_ne!(get_head_sha(&wt).unwrap(), wt_head);
+        assert_eq!(get_head_sha(&main).unwrap(), main_head);
+        assert!(changed(&wt).is_empty());
+        assert_eq!(changed(&main), vec!["b.txt"]);
+    }
+}
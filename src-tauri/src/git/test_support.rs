@@ -0,0 +1,36 @@
+//! Scratch repositories for tests.
+
+use std::path::Path;
+use std::process::Command;
+
+use tempfile::TempDir;
+
+/// Run git in `repo`, panicking if it fails. Returns stdout without the
+/// trailing newline.
+pub(crate) fn git(repo: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(repo)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "git {args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout)
+        .trim_end()
+        .to_string()
+}
+
+/// A new repository with a committer identity, and a function that runs
+/// git in it.
+pub(crate) fn init_test_repo() -> (TempDir, impl Fn(&[&str]) -> String) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().to_path_buf();
+    let run = move |args: &[&str]| git(&path, args);
+    run(&["init", "-q"]);
+    run(&["config", "user.email", "test@example.com"]);
+    run(&["config", "user.name", "Test"]);
+    (dir, run)
+}
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Sentinel value representing the working tree (uncommitted changes).
 /// Used for DiffId storage keys.
@@ -91,11 +91,32 @@ impl DiffSpec {
         }
     }
 
+    /// A stash entry against the commit it was made on: stash@{N}^..stash@{N}
+    pub fn stash(index: usize) -> Self {
+        Self {
+            base: GitRef::Rev(format!("stash@{{{index}}}^")),
+            head: GitRef::Rev(format!("stash@{{{index}}}")),
+        }
+    }
+
     /// Custom range
     pub fn custom(base: GitRef, head: GitRef) -> Self {
         Self { base, head }
     }
 
+    /// The spec a stored DiffId was resolved from. Review ids record the
+    /// working tree as "@".
+    pub fn from_diff_id(id: &DiffId) -> Self {
+        let to_ref = |r: &str| match r {
+            "@" | WORKDIR => GitRef::WorkingTree,
+            rev => GitRef::Rev(rev.to_string()),
+        };
+        Self {
+            base: to_ref(&id.before),
+            head: to_ref(&id.after),
+        }
+    }
+
     /// Display as "base..head"
     pub fn display(&self) -> String {
         format!("{}..{}", self.base.display(), self.head.display())
@@ -123,28 +144,150 @@ impl Span {
     }
 }
 
-/// Content of a file - either text lines or binary marker
+/// Content of a file - text lines, a binary marker, or a too-large marker
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum FileContent {
-    Text { lines: Vec<String> },
+    Text {
+        /// Lines without their terminators
+        lines: Vec<String>,
+        /// 0-indexed lines that ended in `\r\n` rather than `\n`, so a
+        /// line-ending change still shows up once the terminators are stripped
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        crlf_lines: Vec<u32>,
+        /// The last line has no terminator (`\ No newline at end of file`)
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        missing_final_newline: bool,
+    },
     Binary,
+    /// Over the diff size limit; content was not loaded
+    TooLarge {
+        size: u64,
+    },
+}
+
+impl FileContent {
+    /// Split text into lines, recording which ones end in CRLF.
+    pub fn from_text(text: &str) -> Self {
+        let mut lines = Vec::new();
+        let mut crlf_lines = Vec::new();
+        for (i, line) in text.split_inclusive('\n').enumerate() {
+            if let Some(stripped) = line.strip_suffix("\r\n") {
+                crlf_lines.push(i as u32);
+                lines.push(stripped.to_string());
+            } else {
+                lines.push(line.strip_suffix('\n').unwrap_or(line).to_string());
+            }
+        }
+        Self::Text {
+            lines,
+            crlf_lines,
+            missing_final_newline: !text.is_empty() && !text.ends_with('\n'),
+        }
+    }
 }
 
 /// A file with its path and content
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct File {
+    /// Path for display; lossy if the path isn't UTF-8
     pub path: String,
+    /// Raw form of a path that isn't UTF-8 (see `util::raw_path`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_path: Option<String>,
     pub content: FileContent,
+    /// True if the content wasn't valid UTF-8 and invalid bytes were
+    /// replaced with U+FFFD for display
+    #[serde(default)]
+    pub had_invalid_utf8: bool,
+}
+
+impl File {
+    /// A file whose content wasn't loaded or decoded (e.g. too large).
+    pub fn new(path: impl Into<String>, content: FileContent) -> Self {
+        Self {
+            path: path.into(),
+            raw_path: None,
+            content,
+            had_invalid_utf8: false,
+        }
+    }
+
+    /// Keep the raw form of `path` if it isn't UTF-8.
+    pub fn with_raw_path(mut self, path: &Path) -> Self {
+        self.raw_path = crate::util::raw_path(path);
+        self
+    }
+
+    /// Decode raw file bytes. Content with a null byte in the first 8KB is
+    /// binary; anything else is decoded as UTF-8, lossily.
+    pub fn from_bytes(path: impl Into<String>, bytes: &[u8]) -> Self {
+        let check_len = bytes.len().min(8192);
+        if bytes[..check_len].contains(&0) {
+            return Self::new(path, FileContent::Binary);
+        }
+        let text = String::from_utf8_lossy(bytes);
+        Self {
+            path: path.into(),
+            raw_path: None,
+            content: FileContent::from_text(&text),
+            had_invalid_utf8: matches!(text, std::borrow::Cow::Owned(_)),
+        }
+    }
 }
 
 /// Summary of a file in the diff (for sidebar)
 /// Status inferred: Added (before=None), Deleted (after=None),
 /// Renamed (both Some, different paths), Modified (both Some, same path)
+///
+/// On the wire, paths that aren't UTF-8 are shown lossily in `before` and
+/// `after`, and kept losslessly in `before_raw` and `after_raw`.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "FileDiffSummaryWire", into = "FileDiffSummaryWire")]
 pub struct FileDiffSummary {
     pub before: Option<PathBuf>,
     pub after: Option<PathBuf>,
+    /// Generated or vendored file (lockfile, build output, codegen)
+    pub generated: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FileDiffSummaryWire {
+    before: Option<String>,
+    after: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    before_raw: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    after_raw: Option<String>,
+    #[serde(default)]
+    generated: bool,
+}
+
+impl From<FileDiffSummary> for FileDiffSummaryWire {
+    fn from(summary: FileDiffSummary) -> Self {
+        let raw = |path: &Option<PathBuf>| path.as_deref().and_then(crate::util::raw_path);
+        let display = |path: &Option<PathBuf>| path.as_deref().map(crate::util::display_path);
+        Self {
+            before: display(&summary.before),
+            after: display(&summary.after),
+            before_raw: raw(&summary.before),
+            after_raw: raw(&summary.after),
+            generated: summary.generated,
+        }
+    }
+}
+
+impl From<FileDiffSummaryWire> for FileDiffSummary {
+    fn from(wire: FileDiffSummaryWire) -> Self {
+        let path = |raw: Option<String>, display: Option<String>| {
+            raw.or(display).map(|s| crate::util::decode_path(&s))
+        };
+        Self {
+            before: path(wire.before_raw, wire.before),
+            after: path(wire.after_raw, wire.after),
+            generated: wire.generated,
+        }
+    }
 }
 
 impl FileDiffSummary {
@@ -169,6 +312,29 @@ impl FileDiffSummary {
     }
 }
 
+/// How far a long-running git operation has got, for progress reporting.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitProgress {
+    /// Short label for the current step (e.g. "renames")
+    pub phase: &'static str,
+    /// Completion of the current phase, from 0.0 to 1.0
+    pub fraction: f32,
+}
+
+/// Line diff algorithm used to compute hunks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffAlgorithm {
+    /// git's default
+    #[default]
+    Myers,
+    /// Anchors on unique lines; aligns repeated blocks (braces, blank lines) better
+    Patience,
+    /// Myers, spending extra time to find the smallest diff
+    Minimal,
+}
+
 /// Maps a region in before to a region in after
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Alignment {
@@ -178,13 +344,131 @@ pub struct Alignment {
     pub changed: bool,
 }
 
+/// What a row of a unified diff shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffRowKind {
+    /// Unchanged on both sides
+    Context,
+    /// Only in before
+    Removed,
+    /// Only in after
+    Added,
+}
+
+/// One row of a unified (single-column) diff.
+/// Line numbers are 0-indexed, like `Span`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffRow {
+    pub kind: DiffRowKind,
+    /// Line in before (None for added rows)
+    pub before_line: Option<u32>,
+    /// Line in after (None for removed rows)
+    pub after_line: Option<u32>,
+    pub content: String,
+    /// Added row ending in spaces or tabs (only set with whitespace markers on)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub has_trailing_whitespace: bool,
+    /// Added row containing a hard tab (only set with whitespace markers on)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub has_tabs: bool,
+}
+
+/// Options for `render_unified_with_options`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderOptions {
+    /// Flag trailing whitespace and hard tabs on added rows
+    pub whitespace_markers: bool,
+}
+
 /// Full diff content for rendering a single file
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FileDiff {
+    /// Shape of this JSON; see the compatibility note in `git/diff.rs`
+    #[serde(default = "FileDiff::schema_version")]
+    pub schema_version: u32,
     /// File before the change (None if added)
     pub before: Option<File>,
     /// File after the change (None if deleted)
     pub after: Option<File>,
     /// How lines map between before/after
     pub alignments: Vec<Alignment>,
+    /// True if either side exceeded the size limit and wasn't diffed
+    #[serde(default)]
+    pub too_large: bool,
+    /// Generated or vendored file (lockfile, build output, codegen)
+    #[serde(default)]
+    pub generated: bool,
+    /// True if the after side contains merge conflict markers
+    #[serde(default)]
+    pub has_conflicts: bool,
+    /// True if either side had invalid UTF-8 replaced for display
+    #[serde(default)]
+    pub had_invalid_utf8: bool,
+}
+
+impl FileDiff {
+    /// Current `schema_version`. Bump when a field is renamed, removed or
+    /// changes meaning.
+    pub const SCHEMA_VERSION: u32 = 1;
+
+    pub const fn schema_version() -> u32 {
+        Self::SCHEMA_VERSION
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_text_records_crlf_lines() {
+        assert_eq!(
+            FileContent::from_text("a\r\nb\nc\r\nd"),
+            FileContent::Text {
+                lines: vec!["a".into(), "b".into(), "c".into(), "d".into()],
+                crlf_lines: vec![0, 2],
+                missing_final_newline: true,
+            }
+        );
+        // Matches str::lines(): a lone trailing \r is content
+        assert_eq!(
+            FileContent::from_text("a\r"),
+            FileContent::Text {
+                lines: vec!["a\r".into()],
+                crlf_lines: vec![],
+                missing_final_newline: true,
+            }
+        );
+        assert_eq!(
+            FileContent::from_text("a\n"),
+            FileContent::Text {
+                lines: vec!["a".into()],
+                crlf_lines: vec![],
+                missing_final_newline: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_file_diff_json_carries_schema_version() {
+        let diff = FileDiff {
+            schema_version: FileDiff::SCHEMA_VERSION,
+            before: None,
+            after: Some(File::new("a.txt", FileContent::from_text("x\n"))),
+            alignments: vec![],
+            too_large: false,
+            generated: false,
+            has_conflicts: false,
+            had_invalid_utf8: false,
+        };
+        let json = serde_json::to_value(&diff).unwrap();
+        assert_eq!(json["schema_version"], FileDiff::schema_version());
+
+        // Older payloads without the field read as the current version
+        let mut legacy = json;
+        legacy.as_object_mut().unwrap().remove("schema_version");
+        let parsed: FileDiff = serde_json::from_value(legacy).unwrap();
+        assert_eq!(parsed, diff);
+    }
 }
@@ -5,21 +5,26 @@
 
 pub mod actions;
 pub mod ai;
+pub mod diagnostics;
 pub mod git;
+pub mod logging;
 pub mod project;
 mod recent_repos;
+pub mod repo_config;
 pub mod review;
 pub mod store;
 mod themes;
+pub mod util;
 mod watcher;
 
 use ai::analysis::ChangesetAnalysis;
-use ai::{SessionManager, SessionStatus};
+use ai::{AgentCapabilities, SessionManager, SessionStatus};
 use git::{
     CreatePrResult, DiffId, DiffSpec, File, FileDiff, FileDiffSummary, GitHubAuthStatus,
     GitHubSyncResult, GitRef, PullRequest, PullRequestInfo,
 };
 use review::{Comment, Edit, NewComment, NewEdit, Review};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use store::{now_timestamp, ContentSegment, SessionFull, Store};
@@ -39,23 +44,7 @@ fn get_repo_path(path: Option<&str>) -> &Path {
 /// Create a DiffId from a DiffSpec for review storage.
 /// Resolves refs to SHAs for stable keys.
 fn make_diff_id(repo: &Path, spec: &DiffSpec) -> Result<DiffId, String> {
-    let resolve = |r: &GitRef| -> Result<String, String> {
-        match r {
-            GitRef::WorkingTree => Ok("@".to_string()),
-            GitRef::Rev(rev) => git::resolve_ref(repo, rev).map_err(|e| e.to_string()),
-            GitRef::MergeBase => {
-                // Resolve merge-base to a concrete SHA for stable storage key
-                let default_branch = git::detect_default_branch(repo).map_err(|e| e.to_string())?;
-                git::merge_base(repo, &default_branch, "HEAD").map_err(|e| e.to_string())
-            }
-            GitRef::MergeBaseOf([base_branch, head_ref]) => {
-                // Resolve merge-base between specific refs
-                git::merge_base(repo, base_branch, head_ref).map_err(|e| e.to_string())
-            }
-        }
-    };
-
-    Ok(DiffId::new(resolve(&spec.base)?, resolve(&spec.head)?))
+    git::diff_id(repo, spec).map_err(|e| e.to_string())
 }
 
 // =============================================================================
@@ -335,15 +324,24 @@ fn find_recent_repos(
 /// Search for files matching a query in the repository.
 ///
 /// Uses fuzzy matching - returns up to `limit` matches sorted by relevance.
+/// On very large repos only part of the tree may be searched; the result is
+/// then marked `truncated`.
 #[tauri::command(rename_all = "camelCase")]
 fn search_files(
     repo_path: Option<String>,
     ref_name: String,
     query: String,
     limit: Option<usize>,
-) -> Result<Vec<String>, String> {
+) -> Result<git::FileList, String> {
     let path = get_repo_path(repo_path.as_deref());
-    git::search_files(path, &ref_name, &query, limit.unwrap_or(20)).map_err(|e| e.to_string())
+    git::search_files(
+        path,
+        &ref_name,
+        &query,
+        limit.unwrap_or(20),
+        &git::FileWalkLimits::default(),
+    )
+    .map_err(|e| e.to_string())
 }
 
 /// Get the content of a file at a specific ref.
@@ -375,6 +373,13 @@ fn list_refs(repo_path: Option<String>) -> Result<Vec<String>, String> {
     git::list_refs(path).map_err(|e| e.to_string())
 }
 
+/// List stash entries so they can be reviewed as diffs.
+#[tauri::command(rename_all = "camelCase")]
+fn list_stashes(repo_path: Option<String>) -> Result<Vec<git::StashEntry>, String> {
+    let path = get_repo_path(repo_path.as_deref());
+    git::list_stashes(path).map_err(|e| e.to_string())
+}
+
 /// Resolve a ref to its full SHA. Used for validation.
 #[tauri::command(rename_all = "camelCase")]
 fn resolve_ref(repo_path: Option<String>, reference: String) -> Result<String, String> {
@@ -390,45 +395,151 @@ fn get_merge_base(repo_path: Option<String>, ref1: String, ref2: String) -> Resu
     git::merge_base(path, &ref1, &ref2).map_err(|e| e.to_string())
 }
 
-/// List files changed in a diff (for sidebar).
-/// Runs on a blocking thread to avoid freezing the UI on large repos.
+/// Emit `git-progress` events for a long git operation, skipping updates
+/// that move less than a percent within the same phase.
+fn git_progress_emitter(app_handle: AppHandle) -> impl FnMut(git::GitProgress) {
+    let mut last: Option<git::GitProgress> = None;
+    move |progress| {
+        let skip = last.is_some_and(|prev| {
+            prev.phase == progress.phase
+                && progress.fraction < 1.0
+                && progress.fraction - prev.fraction < 0.01
+        });
+        if !skip {
+            let _ = app_handle.emit("git-progress", progress);
+            last = Some(progress);
+        }
+    }
+}
+
+/// List files changed in a diff (for sidebar), minus any matching `exclude_globs`.
+/// Runs on a blocking thread to avoid freezing the UI on large repos, and
+/// reports progress through `git-progress` events.
+/// `rename_threshold` and `rename_limit` override the repo's git config for
+/// rename detection in commit ranges; a `rename_limit` of 0 means no limit.
 #[tauri::command(rename_all = "camelCase")]
 async fn list_diff_files(
+    app_handle: AppHandle,
     repo_path: Option<String>,
     spec: DiffSpec,
+    exclude_globs: Option<Vec<String>>,
+    rename_threshold: Option<u16>,
+    rename_limit: Option<usize>,
 ) -> Result<Vec<FileDiffSummary>, String> {
     let path = repo_path
         .map(PathBuf::from)
         .unwrap_or_else(|| PathBuf::from("."));
     tokio::task::spawn_blocking(move || {
-        git::list_diff_files(&path, &spec).map_err(|e| e.to_string())
+        let mut renames = git::RenameOptions::from_config(&path);
+        if let Some(threshold) = rename_threshold {
+            renames.threshold = threshold.min(100);
+        }
+        if let Some(limit) = rename_limit {
+            renames.limit = limit;
+        }
+        let mut on_progress = git_progress_emitter(app_handle);
+        let files =
+            git::list_diff_files_with_renames(&path, &spec, Some(renames), &mut on_progress)
+                .map_err(|e| e.to_string())?;
+        git::exclude_files(files, &exclude_globs.unwrap_or_default()).map_err(|e| e.to_string())
     })
     .await
     .map_err(|e| e.to_string())?
 }
 
 /// Get full diff content for a single file.
+/// `timeout_ms` and `max_file_bytes` override the default diff limits;
+/// `diff_algorithm` picks the line diff algorithm (Myers by default).
 #[tauri::command(rename_all = "camelCase")]
 fn get_file_diff(
     repo_path: Option<String>,
     spec: DiffSpec,
     file_path: String,
+    timeout_ms: Option<u64>,
+    max_file_bytes: Option<u64>,
+    diff_algorithm: Option<git::DiffAlgorithm>,
 ) -> Result<FileDiff, String> {
     let path = get_repo_path(repo_path.as_deref());
-    git::get_file_diff(path, &spec, Path::new(&file_path)).map_err(|e| e.to_string())
+    let file_path = git::normalize_repo_path(path, &file_path).map_err(|e| e.to_string())?;
+    let mut options = git::FileDiffOptions::default();
+    if let Some(ms) = timeout_ms {
+        options.timeout = std::time::Duration::from_millis(ms);
+    }
+    if let Some(bytes) = max_file_bytes {
+        options.max_file_bytes = bytes;
+    }
+    if let Some(algorithm) = diff_algorithm {
+        options.algorithm = algorithm;
+    }
+    git::get_file_diff_with_options(path, &spec, &util::decode_path(&file_path), options)
+        .map_err(|e| e.to_string())
+}
+
+/// Flatten a file diff (as returned by `get_file_diff`) into unified rows,
+/// for showing it in a single column without recomputing the diff.
+/// `whitespace_markers` flags trailing whitespace and tabs on added rows.
+#[tauri::command(rename_all = "camelCase")]
+fn render_unified_diff(diff: FileDiff, whitespace_markers: Option<bool>) -> Vec<git::DiffRow> {
+    let options = git::RenderOptions {
+        whitespace_markers: whitespace_markers.unwrap_or(false),
+    };
+    git::render_unified_with_options(&diff, &options)
+}
+
+/// A patch of just one changed region of a file diff, for copying or
+/// applying a single hunk. `hunk_index` counts changed regions only.
+#[tauri::command(rename_all = "camelCase")]
+fn hunk_to_patch(diff: FileDiff, hunk_index: usize) -> Result<String, String> {
+    git::hunk_to_patch(&diff, hunk_index).map_err(|e| e.to_string())
 }
 
 /// Create a commit with the specified files.
 /// Returns the short SHA of the new commit.
+/// Rejects empty commits and empty messages unless `options` allows them.
+/// Co-authors (`Name <email>`) and sign-off are appended as trailers.
+/// Author and committer name/email override the repo's configured identity.
 #[tauri::command(rename_all = "camelCase")]
 fn commit(
     repo_path: Option<String>,
     paths: Vec<String>,
     message: String,
+    options: Option<git::CommitOptions>,
 ) -> Result<String, String> {
     let path = get_repo_path(repo_path.as_deref());
-    let paths: Vec<PathBuf> = paths.into_iter().map(PathBuf::from).collect();
-    git::commit(path, &paths, &message).map_err(|e| e.to_string())
+    let paths: Vec<PathBuf> = paths
+        .iter()
+        .map(|p| git::normalize_repo_path(path, p).map(|p| util::decode_path(&p)))
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    git::commit(path, &paths, &message, &options.unwrap_or_default()).map_err(|e| e.to_string())
+}
+
+/// Get the repo's commit template (`commit.template`) with comment lines
+/// stripped, for prefilling new commit messages.
+#[tauri::command(rename_all = "camelCase")]
+fn get_commit_template(repo_path: Option<String>) -> Option<String> {
+    let path = get_repo_path(repo_path.as_deref());
+    git::commit_template(path)
+}
+
+/// Export a diff to `dest_path` as a unified patch that applies with
+/// `git apply`. Working tree diffs include untracked files. The patch is
+/// written byte for byte, since file contents in it needn't be UTF-8.
+#[tauri::command(rename_all = "camelCase")]
+async fn export_patch(
+    repo_path: Option<String>,
+    spec: DiffSpec,
+    dest_path: String,
+) -> Result<(), String> {
+    let path = repo_path
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+    tokio::task::spawn_blocking(move || {
+        let patch = git::format_patch(&path, &spec).map_err(|e| e.to_string())?;
+        std::fs::write(&dest_path, patch).map_err(|e| format!("Cannot write {dest_path}: {e}"))
+    })
+    .await
+    .map_err(|e| e.to_string())?
 }
 
 // =============================================================================
@@ -545,12 +656,18 @@ async fn sync_review_to_github(
     let id = make_diff_id(&path, &spec)?;
     let review = store.get_or_create(&id).map_err(|e| e.0)?;
 
-    if review.comments.is_empty() {
-        return Err("No comments to sync".to_string());
+    // Drafts stay local until the review is published
+    let comments: Vec<Comment> = review
+        .comments
+        .into_iter()
+        .filter(|c| c.status == review::CommentStatus::Published)
+        .collect();
+    if comments.is_empty() {
+        return Err("No published comments to sync".to_string());
     }
 
     // Sync to GitHub
-    git::sync_review_to_github(&path, pr_number, &review.comments)
+    git::sync_review_to_github(&path, pr_number, &comments)
         .await
         .map_err(|e| e.to_string())
 }
@@ -661,7 +778,7 @@ async fn generate_pr_description(
     let path = PathBuf::from(&repo_path);
 
     // Find AI agent
-    let agent = ai::find_acp_agent().ok_or_else(|| {
+    let agent = ai::find_acp_agent_for_repo(&path).ok_or_else(|| {
         "No AI agent found. Install Goose: https://github.com/block/goose".to_string()
     })?;
 
@@ -728,9 +845,9 @@ async fn generate_pr_description(
                 let diff_lines = diff.lines().count();
                 if total_diff_lines + diff_lines <= MAX_DIFF_LINES {
                     diffs.push_str(&format!(
-                        "\n### {}\n```diff\n{}\n```\n",
+                        "\n### {}\n{}",
                         file_path.display(),
-                        diff
+                        util::fenced_block("diff", &diff)
                     ));
                     total_diff_lines += diff_lines;
                 }
@@ -788,8 +905,8 @@ fn parse_pr_description_response(response: &str) -> Result<GeneratedPrDescriptio
     }
 
     let parsed: PrDescriptionJson = serde_json::from_str(json_str).map_err(|e| {
-        log::error!("Failed to parse PR description response: {e}");
-        log::error!("Response was:\n{response}");
+        log::error!(target: logging::APP, "Failed to parse PR description response: {e}");
+        log::error!(target: logging::APP, "Response was:\n{response}");
         format!("Failed to parse AI response: {e}")
     })?;
 
@@ -843,6 +960,28 @@ async fn discover_acp_providers() -> Vec<AcpProviderInfo> {
         .unwrap_or_default()
 }
 
+/// Re-discover ACP providers, ignoring cached agent paths.
+/// Use after installing or upgrading an agent CLI.
+#[tauri::command]
+async fn refresh_acp_providers() -> Vec<AcpProviderInfo> {
+    tokio::task::spawn_blocking(|| {
+        ai::refresh_agent_paths();
+        ai::discover_acp_providers()
+    })
+    .await
+    .unwrap_or_default()
+}
+
+/// Report environment readiness (agents, git, directories, stores) for
+/// support requests.
+#[tauri::command]
+async fn diagnostics(app: AppHandle) -> Result<diagnostics::Diagnostics, String> {
+    let data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    tokio::task::spawn_blocking(move || diagnostics::Diagnostics::collect(&data_dir))
+        .await
+        .map_err(|e| e.to_string())
+}
+
 /// Check if an AI agent is available (via ACP).
 #[tauri::command(rename_all = "camelCase")]
 fn check_ai_available() -> Result<String, String> {
@@ -887,13 +1026,17 @@ struct AgentPromptResponse {
 ///
 /// The provider parameter specifies which ACP provider to use (e.g., "goose" or "claude").
 /// If not specified, defaults to the first available provider.
+///
+/// Optional `attachments` are file paths (e.g. screenshots) sent alongside the prompt.
 #[tauri::command(rename_all = "camelCase")]
 async fn send_agent_prompt(
     repo_path: Option<String>,
     prompt: String,
     session_id: Option<String>,
     provider: Option<String>,
+    attachments: Option<Vec<PathBuf>>,
 ) -> Result<AgentPromptResponse, String> {
+    let path = get_repo_path(repo_path.as_deref()).to_path_buf();
     let agent = if let Some(provider_id) = provider {
         ai::find_acp_agent_by_id(&provider_id).ok_or_else(|| {
             format!(
@@ -901,15 +1044,19 @@ async fn send_agent_prompt(
             )
         })?
     } else {
-        ai::find_acp_agent().ok_or_else(|| {
+        ai::find_acp_agent_for_repo(&path).ok_or_else(|| {
             "No AI agent found. Install Goose: https://github.com/block/goose".to_string()
         })?
     };
 
-    let path = get_repo_path(repo_path.as_deref()).to_path_buf();
-
-    let result =
-        ai::run_acp_prompt_with_session(&agent, &path, &prompt, session_id.as_deref()).await?;
+    let result = ai::run_acp_prompt_with_session(
+        &agent,
+        &path,
+        &prompt,
+        session_id.as_deref(),
+        &attachments.unwrap_or_default(),
+    )
+    .await?;
 
     Ok(AgentPromptResponse {
         response: result.response,
@@ -933,6 +1080,7 @@ async fn send_agent_prompt_streaming(
     session_id: Option<String>,
     provider: Option<String>,
 ) -> Result<AgentPromptResponse, String> {
+    let path = get_repo_path(repo_path.as_deref()).to_path_buf();
     let agent = if let Some(provider_id) = provider {
         ai::find_acp_agent_by_id(&provider_id).ok_or_else(|| {
             format!(
@@ -940,13 +1088,11 @@ async fn send_agent_prompt_streaming(
             )
         })?
     } else {
-        ai::find_acp_agent().ok_or_else(|| {
+        ai::find_acp_agent_for_repo(&path).ok_or_else(|| {
             "No AI agent found. Install Goose: https://github.com/block/goose".to_string()
         })?
     };
 
-    let path = get_repo_path(repo_path.as_deref()).to_path_buf();
-
     // Legacy path: no internal session ID, use ACP session ID or "legacy" as fallback
     let internal_id = session_id.as_deref().unwrap_or("legacy");
     let result = ai::run_acp_prompt_streaming(
@@ -957,6 +1103,7 @@ async fn send_agent_prompt_streaming(
         internal_id,
         app_handle,
         None, // No buffer callback for legacy path
+        None, // No live session to record capabilities on
         None, // No cancellation handle for legacy path
     )
     .await?;
@@ -1004,6 +1151,16 @@ async fn get_session_status(
     state.get_session_status(&session_id).await
 }
 
+/// Get the commands, modes and prompt capabilities a session's agent reported.
+/// Returns None until the session is live and has connected.
+#[tauri::command(rename_all = "camelCase")]
+async fn get_session_capabilities(
+    state: State<'_, Arc<SessionManager>>,
+    session_id: String,
+) -> Result<Option<AgentCapabilities>, String> {
+    Ok(state.session_capabilities(&session_id).await)
+}
+
 /// Send a prompt to a session.
 /// Streams response via events, persists to database on completion.
 #[tauri::command(rename_all = "camelCase")]
@@ -1027,6 +1184,30 @@ fn update_session_title(
         .map_err(|e| e.to_string())
 }
 
+/// Fork a session at a message. Returns the new session's ID.
+#[tauri::command(rename_all = "camelCase")]
+fn fork_session(
+    state: State<'_, Arc<Store>>,
+    session_id: String,
+    up_to_message_id: i64,
+) -> Result<String, String> {
+    state
+        .fork_session(&session_id, up_to_message_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Edit the text of a stored user message without resending it.
+#[tauri::command(rename_all = "camelCase")]
+fn update_message_content(
+    state: State<'_, Arc<Store>>,
+    message_id: i64,
+    content: String,
+) -> Result<(), String> {
+    state
+        .update_message_content(message_id, &content)
+        .map_err(|e| e.to_string())
+}
+
 /// Get buffered streaming segments for a session (before DB persistence).
 /// Returns None if no buffered segments exist (either already persisted or never streamed).
 #[tauri::command(rename_all = "camelCase")]
@@ -1049,6 +1230,84 @@ fn get_review(repo_path: Option<String>, spec: DiffSpec) -> Result<Review, Strin
     store.get_or_create(&id).map_err(|e| e.0)
 }
 
+/// Get the file list, diffs, stored review and progress in one call.
+/// With `summaries_only`, per-file diffs are omitted for a fast first render.
+/// `path_prefix` scopes the files and progress to a directory subtree.
+#[tauri::command(rename_all = "camelCase")]
+async fn get_review_view(
+    repo_path: Option<String>,
+    spec: DiffSpec,
+    summaries_only: Option<bool>,
+    path_prefix: Option<String>,
+) -> Result<review::ReviewView, String> {
+    let path = repo_path
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+    tokio::task::spawn_blocking(move || {
+        let store = review::get_store().map_err(|e| e.0)?;
+        let id = make_diff_id(&path, &spec)?;
+        let review = store.get_or_create(&id).map_err(|e| e.0)?;
+        let options = review::ReviewViewOptions {
+            summaries_only: summaries_only.unwrap_or(false),
+            path_prefix,
+        };
+        review::build_review_view_with_options(&path, &spec, review, &options).map_err(|e| e.0)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Review view for the changes a single commit introduced.
+/// `parent` (1-based) picks which parent a merge commit is diffed against;
+/// the root commit is diffed against the empty tree.
+/// `path_prefix` scopes the files and progress to a directory subtree.
+#[tauri::command(rename_all = "camelCase")]
+async fn get_commit_review_view(
+    repo_path: Option<String>,
+    sha: String,
+    parent: Option<usize>,
+    summaries_only: Option<bool>,
+    path_prefix: Option<String>,
+) -> Result<review::ReviewView, String> {
+    let path = repo_path
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+    tokio::task::spawn_blocking(move || {
+        let spec = git::commit_spec(&path, &sha, parent).map_err(|e| e.to_string())?;
+        let store = review::get_store().map_err(|e| e.0)?;
+        let id = make_diff_id(&path, &spec)?;
+        let review = store.get_or_create(&id).map_err(|e| e.0)?;
+        let options = review::ReviewViewOptions {
+            summaries_only: summaries_only.unwrap_or(false),
+            path_prefix,
+        };
+        review::build_review_view_with_options(&path, &spec, review, &options).map_err(|e| e.0)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Preview a search-and-replace across the changed files of a diff.
+/// Returns per-file matches and resulting text; nothing is written.
+#[tauri::command(rename_all = "camelCase")]
+async fn preview_replace(
+    repo_path: Option<String>,
+    spec: DiffSpec,
+    find: String,
+    replace: String,
+    is_regex: Option<bool>,
+) -> Result<Vec<review::ReplacePreview>, String> {
+    let path = repo_path
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+    tokio::task::spawn_blocking(move || {
+        review::preview_replace(&path, &spec, &find, &replace, is_regex.unwrap_or(false))
+            .map_err(|e| e.0)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
 #[tauri::command(rename_all = "camelCase")]
 fn add_comment(
     repo_path: Option<String>,
@@ -1058,7 +1317,9 @@ fn add_comment(
     let path = get_repo_path(repo_path.as_deref());
     let store = review::get_store().map_err(|e| e.0)?;
     let id = make_diff_id(path, &spec)?;
-    let comment = Comment::new(comment.path, comment.span, comment.content);
+    let file_path = git::normalize_repo_path(path, &comment.path).map_err(|e| e.to_string())?;
+    let comment = Comment::new(file_path, comment.span, comment.content)
+        .with_columns(comment.col_start, comment.col_end);
     store.add_comment(&id, &comment).map_err(|e| e.0)?;
     Ok(comment)
 }
@@ -1069,6 +1330,16 @@ fn update_comment(comment_id: String, content: String) -> Result<(), String> {
     store.update_comment(&comment_id, &content).map_err(|e| e.0)
 }
 
+/// Submit a review: publish its draft comments so they're included in
+/// exports. Returns how many comments were published.
+#[tauri::command(rename_all = "camelCase")]
+fn publish_review(repo_path: Option<String>, spec: DiffSpec) -> Result<usize, String> {
+    let path = get_repo_path(repo_path.as_deref());
+    let store = review::get_store().map_err(|e| e.0)?;
+    let id = make_diff_id(path, &spec)?;
+    store.publish_review(&id).map_err(|e| e.0)
+}
+
 #[tauri::command(rename_all = "camelCase")]
 fn delete_comment(comment_id: String) -> Result<(), String> {
     let store = review::get_store().map_err(|e| e.0)?;
@@ -1080,7 +1351,54 @@ fn mark_reviewed(repo_path: Option<String>, spec: DiffSpec, path: String) -> Res
     let repo = get_repo_path(repo_path.as_deref());
     let store = review::get_store().map_err(|e| e.0)?;
     let id = make_diff_id(repo, &spec)?;
-    store.mark_reviewed(&id, &path).map_err(|e| e.0)
+    let path = git::normalize_repo_path(repo, &path).map_err(|e| e.to_string())?;
+    store.mark_reviewed(&id, &path).map_err(|e| e.0)?;
+    // Snapshot the head so a later push can be reviewed incrementally
+    let head = match spec.head {
+        GitRef::WorkingTree => git::resolve_ref(repo, "HEAD").ok(),
+        _ => Some(id.after.clone()),
+    };
+    match head {
+        Some(head) => store.record_reviewed_head(&id, &head).map_err(|e| e.0),
+        None => Ok(()),
+    }
+}
+
+/// Diff only what changed in a review's diff since `previous_head` was
+/// reviewed. Without `previous_head`, uses the head recorded by the most
+/// recent earlier review against the same base.
+#[tauri::command(rename_all = "camelCase")]
+async fn get_incremental_diff(
+    repo_path: Option<String>,
+    diff_id: DiffId,
+    previous_head: Option<String>,
+) -> Result<Vec<FileDiff>, String> {
+    let path = repo_path
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+    tokio::task::spawn_blocking(move || {
+        let previous_head = match previous_head {
+            Some(head) => head,
+            None => review::get_store()
+                .map_err(|e| e.0)?
+                .previous_reviewed_head(&diff_id)
+                .map_err(|e| e.0)?
+                .ok_or_else(|| "No previous review to compare against".to_string())?,
+        };
+        let spec = DiffSpec::from_diff_id(&diff_id);
+        git::get_incremental_diff(&path, &spec, &previous_head).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn record_file_view(repo_path: Option<String>, spec: DiffSpec, path: String) -> Result<(), String> {
+    let repo = get_repo_path(repo_path.as_deref());
+    let store = review::get_store().map_err(|e| e.0)?;
+    let id = make_diff_id(repo, &spec)?;
+    let path = git::normalize_repo_path(repo, &path).map_err(|e| e.to_string())?;
+    store.record_view(&id, &path).map_err(|e| e.0)
 }
 
 #[tauri::command(rename_all = "camelCase")]
@@ -1088,26 +1406,140 @@ fn unmark_reviewed(repo_path: Option<String>, spec: DiffSpec, path: String) -> R
     let repo = get_repo_path(repo_path.as_deref());
     let store = review::get_store().map_err(|e| e.0)?;
     let id = make_diff_id(repo, &spec)?;
+    let path = git::normalize_repo_path(repo, &path).map_err(|e| e.to_string())?;
     store.unmark_reviewed(&id, &path).map_err(|e| e.0)
 }
 
+/// Clear every reviewed file in a review (e.g. after a force-push), keeping
+/// its comments and edits. Returns how many files were unmarked.
+#[tauri::command(rename_all = "camelCase")]
+fn reset_reviewed(repo_path: Option<String>, spec: DiffSpec) -> Result<usize, String> {
+    let repo = get_repo_path(repo_path.as_deref());
+    let store = review::get_store().map_err(|e| e.0)?;
+    let id = make_diff_id(repo, &spec)?;
+    store.reset_reviewed(&id).map_err(|e| e.0)
+}
+
 #[tauri::command(rename_all = "camelCase")]
 fn record_edit(repo_path: Option<String>, spec: DiffSpec, edit: NewEdit) -> Result<Edit, String> {
     let path = get_repo_path(repo_path.as_deref());
     let store = review::get_store().map_err(|e| e.0)?;
     let id = make_diff_id(path, &spec)?;
-    let edit = Edit::new(edit.path, edit.diff);
+    let file_path = git::normalize_repo_path(path, &edit.path).map_err(|e| e.to_string())?;
+    let edit = Edit::new(file_path, edit.diff);
     store.add_edit(&id, &edit).map_err(|e| e.0)?;
     Ok(edit)
 }
 
 #[tauri::command(rename_all = "camelCase")]
-fn export_review_markdown(repo_path: Option<String>, spec: DiffSpec) -> Result<String, String> {
+fn export_review_markdown(
+    repo_path: Option<String>,
+    spec: DiffSpec,
+    include_drafts: Option<bool>,
+) -> Result<String, String> {
     let path = get_repo_path(repo_path.as_deref());
     let store = review::get_store().map_err(|e| e.0)?;
     let id = make_diff_id(path, &spec)?;
     let review = store.get_or_create(&id).map_err(|e| e.0)?;
-    Ok(review::export_markdown(&review))
+    let options = review::ExportOptions {
+        include_drafts: include_drafts.unwrap_or(false),
+    };
+    Ok(review::export_markdown_with_options(&review, &options))
+}
+
+/// Write the review markdown export to a file, atomically replacing it.
+/// Draft comments are left out unless `include_drafts` is set.
+#[tauri::command(rename_all = "camelCase")]
+fn export_review_markdown_to_file(
+    repo_path: Option<String>,
+    spec: DiffSpec,
+    path: String,
+    include_drafts: Option<bool>,
+) -> Result<(), String> {
+    let repo = get_repo_path(repo_path.as_deref());
+    let store = review::get_store().map_err(|e| e.0)?;
+    let id = make_diff_id(repo, &spec)?;
+    let review = store.get_or_create(&id).map_err(|e| e.0)?;
+    let options = review::ExportOptions {
+        include_drafts: include_drafts.unwrap_or(false),
+    };
+    review::export_markdown_to_file_with_options(&review, Path::new(&path), &options)
+        .map_err(|e| e.0)
+}
+
+/// A change set with the reviews of its member diffs and aggregate progress.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChangeSetView {
+    changeset: review::ChangeSet,
+    reviews: Vec<review::ChangeSetReview>,
+    progress: review::ChangeSetProgress,
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn create_changeset(name: String) -> Result<review::ChangeSet, String> {
+    let store = review::get_store().map_err(|e| e.0)?;
+    store.create_changeset(&name).map_err(|e| e.0)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn get_changeset(changeset_id: String) -> Result<ChangeSetView, String> {
+    let store = review::get_store().map_err(|e| e.0)?;
+    let changeset = store
+        .get_changeset(&changeset_id)
+        .map_err(|e| e.0)?
+        .ok_or_else(|| format!("Change set not found: {changeset_id}"))?;
+    let reviews = store
+        .get_changeset_reviews(&changeset_id)
+        .map_err(|e| e.0)?;
+    let progress = review::ChangeSetProgress::compute(&reviews);
+    Ok(ChangeSetView {
+        changeset,
+        reviews,
+        progress,
+    })
+}
+
+/// Add a repo's diff to a change set.
+#[tauri::command(rename_all = "camelCase")]
+fn add_changeset_diff(
+    changeset_id: String,
+    repo_path: String,
+    spec: DiffSpec,
+) -> Result<(), String> {
+    let store = review::get_store().map_err(|e| e.0)?;
+    let id = make_diff_id(Path::new(&repo_path), &spec)?;
+    store
+        .add_changeset_diff(&changeset_id, &repo_path, &id)
+        .map_err(|e| e.0)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn remove_changeset_diff(
+    changeset_id: String,
+    repo_path: String,
+    spec: DiffSpec,
+) -> Result<(), String> {
+    let store = review::get_store().map_err(|e| e.0)?;
+    let id = make_diff_id(Path::new(&repo_path), &spec)?;
+    store
+        .remove_changeset_diff(&changeset_id, &repo_path, &id)
+        .map_err(|e| e.0)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn delete_changeset(changeset_id: String) -> Result<(), String> {
+    let store = review::get_store().map_err(|e| e.0)?;
+    store.delete_changeset(&changeset_id).map_err(|e| e.0)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn export_changeset_markdown(changeset_id: String) -> Result<String, String> {
+    let store = review::get_store().map_err(|e| e.0)?;
+    let reviews = store
+        .get_changeset_reviews(&changeset_id)
+        .map_err(|e| e.0)?;
+    Ok(review::export_changeset_markdown(&reviews))
 }
 
 #[tauri::command(rename_all = "camelCase")]
@@ -1127,6 +1559,7 @@ fn add_reference_file(
     let repo = get_repo_path(repo_path.as_deref());
     let store = review::get_store().map_err(|e| e.0)?;
     let id = make_diff_id(repo, &spec)?;
+    let path = git::normalize_repo_path(repo, &path).map_err(|e| e.to_string())?;
     store.add_reference_file(&id, &path).map_err(|e| e.0)
 }
 
@@ -1139,6 +1572,7 @@ fn remove_reference_file(
     let repo = get_repo_path(repo_path.as_deref());
     let store = review::get_store().map_err(|e| e.0)?;
     let id = make_diff_id(repo, &spec)?;
+    let path = git::normalize_repo_path(repo, &path).map_err(|e| e.to_string())?;
     store.remove_reference_file(&id, &path).map_err(|e| e.0)
 }
 
@@ -1244,6 +1678,7 @@ fn save_artifact(
         status: ArtifactStatus::Complete,
         error_message: None,
         session_id: None,
+        created_by: Some(project::ARTIFACT_AUTHOR_USER.to_string()),
     };
 
     state
@@ -1255,7 +1690,7 @@ fn save_artifact(
 // Project Commands (artifact-centric model)
 // =============================================================================
 
-use project::{Artifact as ProjectArtifact, ArtifactData, ArtifactStatus, Project};
+use project::{Artifact as ProjectArtifact, ArtifactData, ArtifactStatus, ArtifactType, Project};
 
 /// Create a new project.
 #[tauri::command(rename_all = "camelCase")]
@@ -1318,6 +1753,7 @@ fn create_artifact(
         status: ArtifactStatus::Complete,
         error_message: None,
         session_id: None,
+        created_by: Some(project::ARTIFACT_AUTHOR_USER.to_string()),
     };
     state
         .create_artifact(&artifact)
@@ -1379,6 +1815,20 @@ fn add_artifact_context(
     Ok(())
 }
 
+/// Export a chat session into a new project artifact.
+/// The artifact links back to the chat and to any artifacts it referenced.
+#[tauri::command(rename_all = "camelCase")]
+fn save_chat_as_artifact(
+    state: State<'_, Arc<Store>>,
+    session_id: String,
+    project_id: String,
+    artifact_type: ArtifactType,
+) -> Result<ProjectArtifact, String> {
+    state
+        .save_chat_as_artifact(&session_id, &project_id, artifact_type)
+        .map_err(|e| e.to_string())
+}
+
 /// Get the artifacts that were used as context when creating an artifact.
 #[tauri::command(rename_all = "camelCase")]
 fn get_artifact_context(
@@ -1426,7 +1876,8 @@ async fn generate_artifact(
     };
 
     // Create the artifact in "generating" state
-    let artifact = ProjectArtifact::new_generating(&project_id, &placeholder_title);
+    let artifact = ProjectArtifact::new_generating(&project_id, &placeholder_title)
+        .with_created_by(project::ARTIFACT_AUTHOR_AGENT);
 
     // Save to database
     state
@@ -1501,7 +1952,20 @@ async fn run_artifact_generation(
     };
 
     // Create a session for this artifact generation
-    let session_id = store::generate_session_id();
+    let session_id = match store.new_session_id() {
+        Ok(id) => id,
+        Err(e) => {
+            let _ = store.update_artifact_status(
+                &artifact.id,
+                ArtifactStatus::Error,
+                Some(&format!("Failed to create a session: {e}")),
+                None,
+                None,
+            );
+            let _ = emit_artifact_updated(&app_handle, &artifact.id);
+            return;
+        }
+    };
     let now = store::now_timestamp();
     let session = store::Session {
         id: session_id.clone(),
@@ -1510,10 +1974,12 @@ async fn run_artifact_generation(
         title: Some(format!("Artifact: {}", artifact.title)),
         created_at: now,
         updated_at: now,
+        forked_from: None,
+        acp_session_id: None,
     };
 
     if let Err(e) = store.create_session(&session) {
-        log::error!("Failed to create session for artifact: {e}");
+        log::error!(target: logging::APP, "Failed to create session for artifact: {e}");
         // Continue without session - artifact will still work, just no session view
     } else {
         // Link session to artifact
@@ -1556,6 +2022,7 @@ async fn run_artifact_generation(
         &session_id,
         app_handle.clone(),
         None, // No buffer callback for legacy code review sessions
+        None, // No live session to record capabilities on
         None, // No cancellation handle for legacy code review sessions
     )
     .await
@@ -1721,12 +2188,14 @@ async fn create_branch(
 
 /// Run prerun actions for a branch.
 /// This is called separately after branch creation so the UI can show running actions.
+/// Like `run_branch_action`, nothing is auto-committed unless `auto_commit: true` is passed.
 #[tauri::command(rename_all = "camelCase")]
 async fn run_prerun_actions(
     app: tauri::AppHandle,
     state: State<'_, Arc<Store>>,
     runner: State<'_, Arc<actions::ActionRunner>>,
     branch_id: String,
+    auto_commit: Option<bool>,
 ) -> Result<(), String> {
     let store = state.inner().clone();
     let runner = runner.inner().clone();
@@ -1742,6 +2211,12 @@ async fn run_prerun_actions(
         .list_project_actions_by_type(&branch.project_id, crate::store::ActionType::Prerun)
         .map_err(|e| e.to_string())?;
 
+    // Actions run in the project's subpath (if any) within the worktree
+    let subpath = store
+        .get_git_project(&branch.project_id)
+        .map_err(|e| e.to_string())?
+        .and_then(|project| project.subpath);
+
     // Execute each prerun action in order
     for action in prerun_actions {
         if let Err(e) = runner.run_action(
@@ -1750,8 +2225,17 @@ async fn run_prerun_actions(
             branch.id.clone(),
             action.id.clone(),
             branch.worktree_path.clone(),
+            subpath.clone(),
+            HashMap::new(),
+            auto_commit.unwrap_or(false),
+            actions::AnsiMode::default(),
         ) {
-            eprintln!("Failed to run prerun action '{}': {}", action.name, e);
+            log::error!(
+                target: logging::ACTIONS,
+                "Failed to run prerun action '{}': {}",
+                action.name,
+                e
+            );
             // Continue with other actions even if one fails
         }
     }
@@ -2384,6 +2868,34 @@ async fn cancel_ai_session(
     session_manager.cancel_session(&ai_session_id).await
 }
 
+/// Answer a `permission-request` event for a tool the agent isn't
+/// auto-approved to use. `option_id: None` rejects the request.
+#[tauri::command(rename_all = "camelCase")]
+fn respond_permission(request_id: String, option_id: Option<String>) -> Result<(), String> {
+    if ai::permissions::pending_permissions().respond(&request_id, option_id) {
+        Ok(())
+    } else {
+        Err(format!("No pending permission request: {request_id}"))
+    }
+}
+
+/// List agent processes spawned by this app run.
+#[tauri::command]
+fn list_agent_children() -> Vec<ai::agents::ChildInfo> {
+    ai::agents::agent_manager()
+        .map(|manager| manager.list_children())
+        .unwrap_or_default()
+}
+
+/// Kill all agent processes spawned by this app run.
+/// Returns the number of processes signalled.
+#[tauri::command]
+fn kill_agent_children() -> usize {
+    ai::agents::agent_manager()
+        .map(|manager| manager.kill_all_children())
+        .unwrap_or(0)
+}
+
 /// Restart a stuck branch session.
 /// Deletes the old session and starts a new one with the same prompt.
 /// Returns the new session IDs.
@@ -2965,12 +3477,14 @@ fn create_project_action(
     action_type: String,
     sort_order: i32,
     auto_commit: bool,
+    env: Option<HashMap<String, String>>,
 ) -> Result<store::ProjectAction, String> {
     let action_type = store::ActionType::parse(&action_type)
         .ok_or_else(|| format!("Invalid action type: {}", action_type))?;
 
     let action = store::ProjectAction::new(project_id, name, command, action_type, sort_order)
-        .with_auto_commit(auto_commit);
+        .with_auto_commit(auto_commit)
+        .with_env(env.unwrap_or_default());
 
     state
         .create_project_action(&action)
@@ -2989,6 +3503,7 @@ fn update_project_action(
     action_type: String,
     sort_order: i32,
     auto_commit: bool,
+    env: Option<HashMap<String, String>>,
 ) -> Result<(), String> {
     let action_type = store::ActionType::parse(&action_type)
         .ok_or_else(|| format!("Invalid action type: {}", action_type))?;
@@ -3005,6 +3520,9 @@ fn update_project_action(
     action.action_type = action_type;
     action.sort_order = sort_order;
     action.auto_commit = auto_commit;
+    if let Some(env) = env {
+        action.env = env;
+    }
 
     state
         .update_project_action(&action)
@@ -3034,29 +3552,121 @@ fn reorder_project_actions(
 #[tauri::command(rename_all = "camelCase")]
 async fn detect_project_actions(
     state: State<'_, Arc<Store>>,
+    detections: State<'_, Arc<actions::DetectionRegistry>>,
     project_id: String,
+) -> Result<Vec<actions::SuggestedAction>, String> {
+    detect_and_remember_actions(&state, &detections, &project_id).await
+}
+
+/// Detect a project's actions and keep the results for confirmation.
+async fn detect_and_remember_actions(
+    store: &Store,
+    detections: &actions::DetectionRegistry,
+    project_id: &str,
 ) -> Result<Vec<actions::SuggestedAction>, String> {
     // Get the project
-    let project = state
-        .get_git_project(&project_id)
+    let project = store
+        .get_git_project(project_id)
         .map_err(|e| e.to_string())?
         .ok_or_else(|| format!("Project not found: {}", project_id))?;
 
     // Detect actions using AI
     let repo_path = std::path::Path::new(&project.repo_path);
-    actions::detect_actions(repo_path, project.subpath.as_deref())
-        .await
+    let cancellation = detections.start(project_id);
+    let result =
+        actions::detect_actions(repo_path, project.subpath.as_deref(), cancellation.clone()).await;
+    detections.finish(project_id, &cancellation);
+    let detected = result.map_err(|e| e.to_string())?;
+    detections.remember(project_id, &detected);
+    Ok(detected)
+}
+
+/// Confirm that a detected action may run. The action is looked up by name
+/// in the project's detection results, detecting first if that hasn't run.
+#[tauri::command(rename_all = "camelCase")]
+async fn confirm_detected_action(
+    state: State<'_, Arc<Store>>,
+    detections: State<'_, Arc<actions::DetectionRegistry>>,
+    project_id: String,
+    action_name: String,
+) -> Result<(), String> {
+    if detections.detected(&project_id).is_none() {
+        detect_and_remember_actions(&state, &detections, &project_id).await?;
+    }
+    detections
+        .confirm(&project_id, &action_name)
+        .map_err(|e| e.to_string())
+}
+
+/// Run a detected action by name on a branch. The frontend only passes the
+/// name; the command comes from detection, and the user must have confirmed
+/// it with `confirm_detected_action`. Detected actions never auto-commit.
+/// Returns the execution id.
+#[tauri::command(rename_all = "camelCase")]
+fn run_detected_action(
+    state: State<'_, Arc<Store>>,
+    detections: State<'_, Arc<actions::DetectionRegistry>>,
+    runner: State<'_, Arc<actions::ActionRunner>>,
+    app: tauri::AppHandle,
+    branch_id: String,
+    action_name: String,
+) -> Result<String, String> {
+    let branch = state
+        .get_branch(&branch_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Branch not found: {}", branch_id))?;
+    let project_id = branch.project_id.clone();
+
+    let action = detections
+        .confirmed_action(&project_id, &action_name)
+        .map_err(|e| e.to_string())?
+        .to_project_action(&project_id);
+
+    let subpath = state
+        .get_git_project(&project_id)
+        .map_err(|e| e.to_string())?
+        .and_then(|project| project.subpath);
+
+    runner
+        .run_project_action(
+            app,
+            state.inner().clone(),
+            branch_id,
+            action,
+            branch.worktree_path,
+            subpath,
+            HashMap::new(),
+            false,
+            actions::AnsiMode::default(),
+        )
         .map_err(|e| e.to_string())
 }
 
-/// Run an action on a branch
+/// Cancel an in-flight action detection, killing its agent process.
+/// Returns false if no detection was running for the project.
+#[tauri::command(rename_all = "camelCase")]
+fn cancel_detect_project_actions(
+    detections: State<'_, Arc<actions::DetectionRegistry>>,
+    project_id: String,
+) -> bool {
+    detections.cancel(&project_id)
+}
+
+/// Run an action on a branch.
+/// `env` is merged over the action's saved default environment for this run only.
+/// Actions configured to auto-commit only commit when `auto_commit: true` is passed.
+/// `ansi` picks whether output events carry raw, stripped or styled output (raw by default).
 #[tauri::command(rename_all = "camelCase")]
+#[allow(clippy::too_many_arguments)]
 fn run_branch_action(
     state: State<'_, Arc<Store>>,
     runner: State<'_, Arc<actions::ActionRunner>>,
     app: tauri::AppHandle,
     branch_id: String,
     action_id: String,
+    env: Option<HashMap<String, String>>,
+    auto_commit: Option<bool>,
+    ansi: Option<actions::AnsiMode>,
 ) -> Result<String, String> {
     // Get the branch to find its worktree path
     let branch = state
@@ -3064,6 +3674,12 @@ fn run_branch_action(
         .map_err(|e| e.to_string())?
         .ok_or_else(|| format!("Branch not found: {}", branch_id))?;
 
+    // Actions run in the project's subpath (if any) within the worktree
+    let subpath = state
+        .get_git_project(&branch.project_id)
+        .map_err(|e| e.to_string())?
+        .and_then(|project| project.subpath);
+
     // Run the action
     runner
         .run_action(
@@ -3072,6 +3688,10 @@ fn run_branch_action(
             branch_id,
             action_id,
             branch.worktree_path,
+            subpath,
+            env.unwrap_or_default(),
+            auto_commit.unwrap_or(false),
+            ansi.unwrap_or_default(),
         )
         .map_err(|e| e.to_string())
 }
@@ -3105,6 +3725,144 @@ fn get_action_output_buffer(
         .ok_or_else(|| format!("No output buffer found for execution: {}", execution_id))
 }
 
+/// Read an execution's output starting at a byte offset.
+/// Returns the bytes (base64-encoded) and the offset to resume from on the
+/// next call.
+#[tauri::command(rename_all = "camelCase")]
+fn get_action_output(
+    runner: State<'_, Arc<actions::ActionRunner>>,
+    run_id: String,
+    from_offset: u64,
+) -> Result<actions::OutputSlice, String> {
+    runner
+        .get_output_from(&run_id, from_offset)
+        .ok_or_else(|| format!("No output found for execution: {}", run_id))
+}
+
+/// List recent completed action runs for a repository (newest first)
+#[tauri::command(rename_all = "camelCase")]
+fn list_action_runs(
+    history: State<'_, Arc<actions::ActionHistory>>,
+    repo_path: String,
+    limit: Option<usize>,
+) -> Result<Vec<actions::ActionRun>, String> {
+    history
+        .list_action_runs(&repo_path, limit.unwrap_or(20))
+        .map_err(|e| e.to_string())
+}
+
+/// Get a single completed action run
+#[tauri::command(rename_all = "camelCase")]
+fn get_action_run(
+    history: State<'_, Arc<actions::ActionHistory>>,
+    run_id: String,
+) -> Result<Option<actions::ActionRun>, String> {
+    history.get_action_run(&run_id).map_err(|e| e.to_string())
+}
+
+// =============================================================================
+// Layout Commands
+// =============================================================================
+
+/// Save the UI layout (selected file, scroll, pane sizes) for a repo
+#[tauri::command(rename_all = "camelCase")]
+fn save_layout(
+    state: State<'_, Arc<Store>>,
+    repo_id: String,
+    layout: store::LayoutState,
+) -> Result<(), String> {
+    state
+        .save_layout(&repo_id, &layout)
+        .map_err(|e| e.to_string())
+}
+
+/// Load the UI layout for a repo, or defaults if none was saved
+#[tauri::command(rename_all = "camelCase")]
+fn load_layout(
+    state: State<'_, Arc<Store>>,
+    repo_id: String,
+) -> Result<store::LayoutState, String> {
+    state.load_layout(&repo_id).map_err(|e| e.to_string())
+}
+
+// =============================================================================
+// Maintenance Commands
+// =============================================================================
+
+/// Result of compacting the app's databases.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CompactionReport {
+    freed_bytes: u64,
+    /// Stores left alone because a transaction was in progress
+    skipped: Vec<&'static str>,
+}
+
+/// Vacuum the review, chat/project and action history databases, reclaiming
+/// space left by deleted rows.
+#[tauri::command(rename_all = "camelCase")]
+async fn compact_stores(
+    state: State<'_, Arc<Store>>,
+    history: State<'_, Arc<actions::ActionHistory>>,
+) -> Result<CompactionReport, String> {
+    let store = state.inner().clone();
+    let history = history.inner().clone();
+    tokio::task::spawn_blocking(move || -> Result<CompactionReport, String> {
+        let results = [
+            (
+                "review",
+                review::get_store()
+                    .and_then(|s| s.compact())
+                    .map_err(|e| e.0),
+            ),
+            ("data", store.compact().map_err(|e| e.0)),
+            (
+                "action_history",
+                history.compact().map_err(|e| e.to_string()),
+            ),
+        ];
+        let mut report = CompactionReport {
+            freed_bytes: 0,
+            skipped: Vec::new(),
+        };
+        for (name, result) in results {
+            match result? {
+                Some(freed) => report.freed_bytes += freed,
+                None => report.skipped.push(name),
+            }
+        }
+        log::info!(
+            target: logging::APP,
+            "Compacted stores: freed {} bytes, skipped {:?}",
+            report.freed_bytes,
+            report.skipped
+        );
+        Ok(report)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Delete reviews that hold no state (see `ReviewStore::prune_empty`).
+/// Reviews for the diffs in `keep`, e.g. the ones open in tabs, are left
+/// alone. Returns how many were removed.
+#[tauri::command(rename_all = "camelCase")]
+fn prune_empty_reviews(
+    repo_path: Option<String>,
+    keep: Option<Vec<DiffSpec>>,
+) -> Result<usize, String> {
+    let repo = get_repo_path(repo_path.as_deref());
+    let store = review::get_store().map_err(|e| e.0)?;
+    let keep = keep
+        .unwrap_or_default()
+        .iter()
+        .map(|spec| make_diff_id(repo, spec))
+        .collect::<Result<Vec<_>, _>>()?;
+    let removed = store.prune_empty(&keep).map_err(|e| e.0)?;
+    log::info!(target: logging::REVIEW, "Pruned {removed} empty reviews");
+    Ok(removed)
+}
+
 // =============================================================================
 // Theme Commands
 // =============================================================================
@@ -3550,14 +4308,27 @@ pub fn run() {
                 Arc::new(Store::open(db_path).map_err(|e| format!("Failed to open store: {e}"))?);
             app.manage(store.clone());
 
-            // Initialize the session manager
-            let session_manager =
-                Arc::new(SessionManager::new(app.handle().clone(), store.clone()));
-            app.manage(session_manager);
+            // Initialize the session manager; transcripts are opt-in
+            let mut session_manager = SessionManager::new(app.handle().clone(), store.clone())
+                .with_limit(ai::session::SessionLimit::from_env());
+            if ai::transcript::enabled_from_env() {
+                session_manager =
+                    session_manager.with_transcripts(app_data_dir.join("transcripts"));
+            }
+            app.manage(Arc::new(session_manager));
+
+            // Track agent processes, reaping any left over from a crashed run
+            ai::agents::init_agent_manager(app_data_dir.join("agent_pids.json"));
 
-            // Initialize the action runner
-            let action_runner = Arc::new(actions::ActionRunner::new());
+            // Initialize the action runner, recording completed runs in history
+            let action_history = Arc::new(
+                actions::ActionHistory::open(app_data_dir.join("action_history.db"))
+                    .map_err(|e| format!("Failed to open action history: {e}"))?,
+            );
+            app.manage(action_history.clone());
+            let action_runner = Arc::new(actions::ActionRunner::with_history(action_history));
             app.manage(action_runner);
+            app.manage(Arc::new(actions::DetectionRegistry::new()));
 
             // Initialize the watcher handle (spawns background thread)
             let watcher = WatcherHandle::new(app.handle().clone());
@@ -3574,11 +4345,14 @@ pub fn run() {
 
             if cfg!(debug_assertions) {
                 app.handle().plugin(tauri_plugin_mcp_bridge::init())?;
-                app.handle().plugin(
-                    tauri_plugin_log::Builder::default()
-                        .level(log::LevelFilter::Info)
-                        .build(),
-                )?;
+                // Per-target levels can be overridden with STAGED_LOG,
+                // e.g. STAGED_LOG=info,staged::ai=debug
+                let levels = logging::LogLevels::from_env(log::LevelFilter::Info);
+                let log_builder = levels.targets.into_iter().fold(
+                    tauri_plugin_log::Builder::default().level(levels.default),
+                    |builder, (target, level)| builder.level_for(target, level),
+                );
+                app.handle().plugin(log_builder.build())?;
             }
             Ok(())
         })
@@ -3593,11 +4367,16 @@ pub fn run() {
             // Git commands
             get_repo_root,
             list_refs,
+            list_stashes,
             resolve_ref,
             get_merge_base,
             list_diff_files,
             get_file_diff,
+            render_unified_diff,
+            hunk_to_patch,
             commit,
+            get_commit_template,
+            export_patch,
             // GitHub commands
             check_github_auth,
             list_pull_requests,
@@ -3615,6 +4394,8 @@ pub fn run() {
             // AI commands (analysis)
             check_ai_available,
             discover_acp_providers,
+            refresh_acp_providers,
+            diagnostics,
             analyze_diff,
             send_agent_prompt,
             send_agent_prompt_streaming,
@@ -3622,19 +4403,36 @@ pub fn run() {
             create_session,
             get_session,
             get_session_status,
+            get_session_capabilities,
             send_prompt,
             update_session_title,
+            fork_session,
+            update_message_content,
             get_buffered_segments,
             // Review commands
             get_review,
+            get_review_view,
+            get_commit_review_view,
+            preview_replace,
             add_comment,
             update_comment,
             delete_comment,
+            publish_review,
             mark_reviewed,
+            get_incremental_diff,
+            record_file_view,
             unmark_reviewed,
+            reset_reviewed,
             record_edit,
             export_review_markdown,
+            export_review_markdown_to_file,
             clear_review,
+            create_changeset,
+            get_changeset,
+            add_changeset_diff,
+            remove_changeset_diff,
+            delete_changeset,
+            export_changeset_markdown,
             add_reference_file,
             remove_reference_file,
             // Legacy artifact commands (DiffSpec-based, used by AgentPanel/Sidebar)
@@ -3653,6 +4451,7 @@ pub fn run() {
             delete_artifact,
             add_artifact_context,
             get_artifact_context,
+            save_chat_as_artifact,
             generate_artifact,
             // Branch commands (git-integrated workflow)
             create_branch,
@@ -3676,6 +4475,9 @@ pub fn run() {
             delete_branch_session_and_commit,
             is_session_alive,
             cancel_ai_session,
+            respond_permission,
+            list_agent_children,
+            kill_agent_children,
             restart_branch_session,
             recover_orphaned_session,
             get_branch_session_by_ai_session,
@@ -3706,11 +4508,23 @@ pub fn run() {
             delete_project_action,
             reorder_project_actions,
             detect_project_actions,
+            cancel_detect_project_actions,
+            confirm_detected_action,
+            run_detected_action,
             run_branch_action,
             run_prerun_actions,
             stop_branch_action,
             get_running_branch_actions,
             get_action_output_buffer,
+            get_action_output,
+            list_action_runs,
+            get_action_run,
+            // Layout commands
+            save_layout,
+            load_layout,
+            // Maintenance commands
+            compact_stores,
+            prune_empty_reviews,
             // Theme commands
             get_custom_themes,
             read_custom_theme,
@@ -3732,8 +4546,16 @@ pub fn run() {
             get_initial_path,
             install_cli,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_app, event| {
+            // kill_on_drop doesn't fire on process exit; clean up agents explicitly
+            if let tauri::RunEvent::Exit = event {
+                if let Some(manager) = ai::agents::agent_manager() {
+                    manager.kill_all_children();
+                }
+            }
+        });
 }
 
 #[cfg(test)]
@@ -0,0 +1,98 @@
+//! Log targets and per-target level configuration.
+//!
+//! Every log site passes one of the targets below so output can be filtered
+//! by subsystem. Levels are configured with an env-filter style spec, e.g.
+//! `STAGED_LOG=info,staged::ai=debug,staged::git=warn`.
+
+use log::LevelFilter;
+
+/// ACP client, chat sessions and diff analysis
+pub const AI: &str = "staged::ai";
+/// Git operations and GitHub integration
+pub const GIT: &str = "staged::git";
+/// Project action detection and execution
+pub const ACTIONS: &str = "staged::actions";
+/// Review storage
+pub const REVIEW: &str = "staged::review";
+/// File system watcher
+pub const WATCHER: &str = "staged::watcher";
+/// Tauri commands and app setup
+pub const APP: &str = "staged::app";
+
+/// Environment variable holding the level spec
+pub const LOG_ENV_VAR: &str = "STAGED_LOG";
+
+/// Parsed level configuration: a default level plus per-target overrides
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogLevels {
+    pub default: LevelFilter,
+    pub targets: Vec<(String, LevelFilter)>,
+}
+
+impl LogLevels {
+    /// Parse a comma-separated spec. A bare level sets the default;
+    /// `target=level` sets a per-target level. Invalid entries are ignored.
+    pub fn parse(spec: &str, default: LevelFilter) -> Self {
+        let mut levels = Self {
+            default,
+            targets: Vec::new(),
+        };
+
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            match entry.split_once('=') {
+                Some((target, level)) => {
+                    let target = target.trim();
+                    if target.is_empty() {
+                        continue;
+                    }
+                    if let Ok(level) = level.trim().parse() {
+                        levels.targets.push((target.to_string(), level));
+                    }
+                }
+                None => {
+                    if let Ok(level) = entry.parse() {
+                        levels.default = level;
+                    }
+                }
+            }
+        }
+
+        levels
+    }
+
+    /// Read the spec from `STAGED_LOG`, falling back to `default`
+    pub fn from_env(default: LevelFilter) -> Self {
+        match std::env::var(LOG_ENV_VAR) {
+            Ok(spec) => Self::parse(&spec, default),
+            Err(_) => Self {
+                default,
+                targets: Vec::new(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_default_and_targets() {
+        let levels = LogLevels::parse("warn, staged::ai=debug,staged::git=off", LevelFilter::Info);
+        assert_eq!(levels.default, LevelFilter::Warn);
+        assert_eq!(
+            levels.targets,
+            vec![
+                (AI.to_string(), LevelFilter::Debug),
+                (GIT.to_string(), LevelFilter::Off),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_ignores_invalid_entries() {
+        let levels = LogLevels::parse("loud,staged::ai=,=debug,,", LevelFilter::Info);
+        assert_eq!(levels.default, LevelFilter::Info);
+        assert!(levels.targets.is_empty());
+    }
+}
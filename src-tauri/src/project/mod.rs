@@ -6,4 +6,7 @@
 //! Storage is handled by the unified Store (see `crate::store`).
 
 // Re-export types from the unified store
-pub use crate::store::{Artifact, ArtifactData, ArtifactStatus, ArtifactType, Project};
+pub use crate::store::{
+    Artifact, ArtifactData, ArtifactStatus, ArtifactType, Project, ARTIFACT_AUTHOR_AGENT,
+    ARTIFACT_AUTHOR_USER,
+};
@@ -8,6 +8,8 @@ use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::time::Instant;
 
+use crate::logging;
+
 /// A recently active git repository.
 #[derive(Debug, Clone, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -115,6 +117,7 @@ pub fn find_recent_repos(hours_ago: u32, limit: usize) -> Vec<RecentRepo> {
     }
 
     log::debug!(
+        target: logging::APP,
         "find_recent_repos: found {} repos in {:?}",
         repos.len(),
         start.elapsed()
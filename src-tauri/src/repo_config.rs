@@ -0,0 +1,202 @@
+//! Repo-local configuration, read from `.staged.toml` at the repository root.
+//!
+//! ```toml
+//! [agent]
+//! # Preferred ACP provider for this repo (falls back to the global default)
+//! provider = "claude"
+//! # Added to the system context of new AI sessions in this repo
+//! instructions = "Use the workspace's make targets rather than cargo directly."
+//!
+//! [actions]
+//! # Build files never sent to action detection
+//! exclude = ["Taskfile.yml"]
+//! # Extra build files to send along with the built-in list
+//! include = ["build.gradle"]
+//! ```
+//!
+//! Every section is optional; a missing or invalid file means defaults.
+
+use serde::Deserialize;
+use std::path::Path;
+
+use crate::logging;
+
+/// File name of the repo config, relative to the repo root.
+pub const CONFIG_FILE: &str = ".staged.toml";
+
+/// Settings for a single repository.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct RepoConfig {
+    pub agent: AgentRepoConfig,
+    pub actions: ActionScanConfig,
+}
+
+/// Which AI agent to use in this repo and what to tell it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct AgentRepoConfig {
+    /// Provider ID (`goose`, `claude`, `codex`) to prefer over the global default
+    pub provider: Option<String>,
+    /// Extra instructions appended to the system context of new sessions
+    pub instructions: Option<String>,
+}
+
+impl AgentRepoConfig {
+    /// Pick an agent: the repo's provider if `find_by_id` finds it, otherwise
+    /// `fallback` (the global default).
+    pub fn resolve_agent<T>(
+        &self,
+        find_by_id: impl FnOnce(&str) -> Option<T>,
+        fallback: impl FnOnce() -> Option<T>,
+    ) -> Option<T> {
+        let preferred = self.provider.as_deref().and_then(|id| {
+            let agent = find_by_id(id);
+            if agent.is_none() {
+                log::warn!(
+                    target: logging::AI,
+                    "Repo prefers provider '{id}', but it isn't available; using the default"
+                );
+            }
+            agent
+        });
+        preferred.or_else(fallback)
+    }
+
+    /// The repo's instructions, if any are non-empty.
+    pub fn instructions(&self) -> Option<&str> {
+        self.instructions
+            .as_deref()
+            .map(str::trim)
+            .filter(|text| !text.is_empty())
+    }
+}
+
+/// Which build/config files action detection reads.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct ActionScanConfig {
+    /// Repo-relative file names to read in addition to the built-in list.
+    /// Paths that lead outside the repo are ignored.
+    pub include: Vec<String>,
+    /// File names to skip even if they're in the built-in list
+    pub exclude: Vec<String>,
+}
+
+impl ActionScanConfig {
+    /// Apply include/exclude to the built-in file names, keeping their order
+    /// and appending includes. Excludes win over includes.
+    pub fn apply(&self, defaults: &[&str]) -> Vec<String> {
+        let mut files: Vec<String> = defaults.iter().map(|f| f.to_string()).collect();
+        for extra in &self.include {
+            if !files.contains(extra) {
+                files.push(extra.clone());
+            }
+        }
+        files.retain(|f| !self.exclude.contains(f));
+        files
+    }
+}
+
+impl RepoConfig {
+    /// Load `.staged.toml` from the repo root, falling back to defaults if
+    /// it's missing or invalid.
+    pub fn load(repo: &Path) -> Self {
+        let path = repo.join(CONFIG_FILE);
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+
+        match Self::parse(&content) {
+            Ok(config) => config,
+            Err(e) => {
+                log::warn!(
+                    target: logging::APP,
+                    "Ignoring invalid repo config {}: {e}",
+                    path.display()
+                );
+                Self::default()
+            }
+        }
+    }
+
+    /// Parse a config from TOML.
+    pub fn parse(content: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_apply_actions() {
+        let config = RepoConfig::parse(
+            r#"
+            [actions]
+            include = ["build.gradle", "Makefile"]
+            exclude = ["Taskfile.yml"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config
+                .actions
+                .apply(&["Makefile", "Taskfile.yml", "package.json"]),
+            ["Makefile", "package.json", "build.gradle"]
+        );
+    }
+
+    #[test]
+    fn test_repo_provider_is_preferred_when_present() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(CONFIG_FILE),
+            r#"
+            [agent]
+            provider = "claude"
+            instructions = """
+            Run tests with `just test`.
+            """
+            "#,
+        )
+        .unwrap();
+        let agent = RepoConfig::load(dir.path()).agent;
+        assert_eq!(agent.provider.as_deref(), Some("claude"));
+        assert_eq!(agent.instructions(), Some("Run tests with `just test`."));
+
+        let installed = ["goose", "claude"];
+        let find = |id: &str| installed.contains(&id).then(|| id.to_string());
+        assert_eq!(
+            agent.resolve_agent(find, || Some("goose".to_string())),
+            Some("claude".to_string())
+        );
+
+        // Not installed: fall back to the global default
+        let missing = AgentRepoConfig {
+            provider: Some("codex".to_string()),
+            instructions: None,
+        };
+        assert_eq!(
+            missing.resolve_agent(find, || Some("goose".to_string())),
+            Some("goose".to_string())
+        );
+        assert_eq!(
+            AgentRepoConfig::default().resolve_agent(find, || Some("goose".to_string())),
+            Some("goose".to_string())
+        );
+    }
+
+    #[test]
+    fn test_missing_or_invalid_config_is_default() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(RepoConfig::load(dir.path()), RepoConfig::default());
+
+        std::fs::write(dir.path().join(CONFIG_FILE), "[actions\n").unwrap();
+        assert_eq!(RepoConfig::load(dir.path()), RepoConfig::default());
+
+        assert_eq!(RepoConfig::parse("").unwrap(), RepoConfig::default());
+    }
+}
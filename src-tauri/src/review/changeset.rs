@@ -0,0 +1,285 @@
+//! Change sets: several diffs, possibly across repos, reviewed as one.
+//!
+//! Each member diff keeps its own `Review` (keyed by `DiffId` as usual); the
+//! change set only records membership and aggregates across the members.
+
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use super::{export_markdown, Result, Review, ReviewError, ReviewStore};
+use crate::git::DiffId;
+use crate::util::lock;
+
+/// A named group of diffs reviewed together.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeSet {
+    pub id: String,
+    pub name: String,
+    /// Member diffs, in the order they were added
+    pub diffs: Vec<ChangeSetDiff>,
+    pub created_at: String,
+}
+
+/// A diff within a change set, qualified by the repo it belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeSetDiff {
+    pub repo_path: String,
+    pub id: DiffId,
+}
+
+/// A member diff together with its review.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeSetReview {
+    pub repo_path: String,
+    pub review: Review,
+}
+
+/// Totals across every review in a change set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeSetProgress {
+    pub diff_count: usize,
+    pub reviewed_files: usize,
+    pub comment_count: usize,
+    pub edit_count: usize,
+}
+
+impl ChangeSetProgress {
+    pub fn compute(reviews: &[ChangeSetReview]) -> Self {
+        Self {
+            diff_count: reviews.len(),
+            reviewed_files: reviews.iter().map(|r| r.review.reviewed.len()).sum(),
+            comment_count: reviews.iter().map(|r| r.review.comments.len()).sum(),
+            edit_count: reviews.iter().map(|r| r.review.edits.len()).sum(),
+        }
+    }
+}
+
+impl ReviewStore {
+    /// Create an empty change set.
+    pub fn create_changeset(&self, name: &str) -> Result<ChangeSet> {
+        let changeset = ChangeSet {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            diffs: Vec::new(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+        };
+        let conn = lock(&self.conn);
+        conn.execute(
+            "INSERT INTO changesets (id, name, created_at) VALUES (?1, ?2, ?3)",
+            params![&changeset.id, &changeset.name, &changeset.created_at],
+        )?;
+        Ok(changeset)
+    }
+
+    /// Get a change set with its member diffs.
+    pub fn get_changeset(&self, changeset_id: &str) -> Result<Option<ChangeSet>> {
+        let conn = lock(&self.conn);
+        let Some((name, created_at)) = conn
+            .query_row(
+                "SELECT name, created_at FROM changesets WHERE id = ?1",
+                params![changeset_id],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()?
+        else {
+            return Ok(None);
+        };
+
+        let mut stmt = conn.prepare(
+            "SELECT repo_path, before_ref, after_ref FROM changeset_diffs
+             WHERE changeset_id = ?1 ORDER BY position",
+        )?;
+        let diffs = stmt
+            .query_map(params![changeset_id], |row| {
+                Ok(ChangeSetDiff {
+                    repo_path: row.get(0)?,
+                    id: DiffId::new(row.get::<_, String>(1)?, row.get::<_, String>(2)?),
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(Some(ChangeSet {
+            id: changeset_id.to_string(),
+            name,
+            diffs,
+            created_at,
+        }))
+    }
+
+    /// Add a diff to a change set, creating its review if needed.
+    /// Adding a diff that is already a member is a no-op.
+    pub fn add_changeset_diff(
+        &self,
+        changeset_id: &str,
+        repo_path: &str,
+        id: &DiffId,
+    ) -> Result<()> {
+        if self.get_changeset(changeset_id)?.is_none() {
+            return Err(ReviewError::new(format!(
+                "Change set not found: {changeset_id}"
+            )));
+        }
+        self.get_or_create(id)?;
+
+        let conn = lock(&self.conn);
+        conn.execute(
+            "INSERT OR IGNORE INTO changeset_diffs
+                 (changeset_id, repo_path, before_ref, after_ref, position)
+             SELECT ?1, ?2, ?3, ?4, COALESCE(MAX(position), -1) + 1
+             FROM changeset_diffs WHERE changeset_id = ?1",
+            params![changeset_id, repo_path, &id.before, &id.after],
+        )?;
+        Ok(())
+    }
+
+    /// Remove a diff from a change set. Its review is kept.
+    pub fn remove_changeset_diff(
+        &self,
+        changeset_id: &str,
+        repo_path: &str,
+        id: &DiffId,
+    ) -> Result<()> {
+        let conn = lock(&self.conn);
+        conn.execute(
+            "DELETE FROM changeset_diffs
+             WHERE changeset_id = ?1 AND repo_path = ?2 AND before_ref = ?3 AND after_ref = ?4",
+            params![changeset_id, repo_path, &id.before, &id.after],
+        )?;
+        Ok(())
+    }
+
+    /// Delete a change set. Member reviews are kept.
+    pub fn delete_changeset(&self, changeset_id: &str) -> Result<()> {
+        let conn = lock(&self.conn);
+        conn.execute(
+            "DELETE FROM changesets WHERE id = ?1",
+            params![changeset_id],
+        )?;
+        Ok(())
+    }
+
+    /// Load the review of every diff in a change set.
+    pub fn get_changeset_reviews(&self, changeset_id: &str) -> Result<Vec<ChangeSetReview>> {
+        let changeset = self
+            .get_changeset(changeset_id)?
+            .ok_or_else(|| ReviewError::new(format!("Change set not found: {changeset_id}")))?;
+        changeset
+            .diffs
+            .into_iter()
+            .map(|diff| {
+                Ok(ChangeSetReview {
+                    review: self.get(&diff.id)?,
+                    repo_path: diff.repo_path,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Export a change set's reviews as markdown, with one section per member diff.
+pub fn export_changeset_markdown(reviews: &[ChangeSetReview]) -> String {
+    let mut md = String::new();
+    for entry in reviews {
+        let id = &entry.review.id;
+        md.push_str(&format!(
+            "# {} ({}..{})\n\n",
+            entry.repo_path,
+            short_ref(&id.before),
+            short_ref(&id.after)
+        ));
+        md.push_str(&export_markdown(&entry.review));
+        if !md.ends_with("\n\n") {
+            md.push('\n');
+        }
+    }
+    if reviews.is_empty() {
+        md.push_str("No diffs in this change set.\n");
+    }
+    md
+}
+
+fn short_ref(r: &str) -> &str {
+    r.get(..12).unwrap_or(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::Span;
+    use crate::review::Comment;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_two_diff_changeset() {
+        let dir = tempdir().unwrap();
+        let store = ReviewStore::open(dir.path().join("test.db")).unwrap();
+
+        let api = DiffId::new("aaa", "bbb");
+        let web = DiffId::new("ccc", "ddd");
+        let changeset = store.create_changeset("Rename user field").unwrap();
+        store
+            .add_changeset_diff(&changeset.id, "/repos/api", &api)
+            .unwrap();
+        store
+            .add_changeset_diff(&changeset.id, "/repos/web", &web)
+            .unwrap();
+        // Re-adding is a no-op
+        store
+            .add_changeset_diff(&changeset.id, "/repos/api", &api)
+            .unwrap();
+
+        store.mark_reviewed(&api, "src/user.rs").unwrap();
+        store.mark_reviewed(&api, "src/db.rs").unwrap();
+        store.mark_reviewed(&web, "src/User.tsx").unwrap();
+        store
+            .add_comment(
+                &web,
+                &Comment::new("src/User.tsx", Span::new(3, 4), "Rename prop"),
+            )
+            .unwrap();
+        store.publish_review(&web).unwrap();
+
+        let loaded = store.get_changeset(&changeset.id).unwrap().unwrap();
+        let repos: Vec<_> = loaded.diffs.iter().map(|d| d.repo_path.as_str()).collect();
+        assert_eq!(repos, ["/repos/api", "/repos/web"]);
+
+        let reviews = store.get_changeset_reviews(&changeset.id).unwrap();
+        assert_eq!(
+            ChangeSetProgress::compute(&reviews),
+            ChangeSetProgress {
+                diff_count: 2,
+                reviewed_files: 3,
+                comment_count: 1,
+                edit_count: 0,
+            }
+        );
+
+        let md = export_changeset_markdown(&reviews);
+        let api_at = md.find("# /repos/api (aaa..bbb)").unwrap();
+        let web_at = md.find("# /repos/web (ccc..ddd)").unwrap();
+        let comment_at = md.find("Rename prop").unwrap();
+        assert!(api_at < web_at && web_at < comment_at);
+
+        store
+            .remove_changeset_diff(&changeset.id, "/repos/api", &api)
+            .unwrap();
+        let reviews = store.get_changeset_reviews(&changeset.id).unwrap();
+        assert_eq!(ChangeSetProgress::compute(&reviews).reviewed_files, 1);
+        // The removed diff keeps its review
+        assert_eq!(store.get(&api).unwrap().reviewed.len(), 2);
+    }
+
+    #[test]
+    fn test_add_to_missing_changeset() {
+        let dir = tempdir().unwrap();
+        let store = ReviewStore::open(dir.path().join("test.db")).unwrap();
+        let err = store
+            .add_changeset_diff("nope", "/repo", &DiffId::new("a", "b"))
+            .unwrap_err();
+        assert!(err.0.contains("not found"));
+    }
+}
@@ -2,7 +2,13 @@
 //!
 //! Reviews are stored separately from git, keyed by DiffId.
 
-use std::path::PathBuf;
+mod changeset;
+mod refactor;
+mod view;
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::{Mutex, OnceLock};
 
 use rusqlite::{params, Connection, OptionalExtension};
@@ -10,6 +16,17 @@ use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Manager};
 
 use crate::git::{DiffId, Span};
+use crate::logging;
+use crate::util::{fenced_block, lock};
+
+pub use changeset::{
+    export_changeset_markdown, ChangeSet, ChangeSetDiff, ChangeSetProgress, ChangeSetReview,
+};
+pub use refactor::{preview_replace, ReplaceMatch, ReplacePreview};
+pub use view::{
+    build_review_view, build_review_view_with_options, ReviewFile, ReviewProgress, ReviewView,
+    ReviewViewOptions,
+};
 
 // =============================================================================
 // Types
@@ -18,6 +35,9 @@ use crate::git::{DiffId, Span};
 /// A review attached to a specific diff.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Review {
+    /// Shape of this JSON; see the compatibility note in `git/diff.rs`
+    #[serde(default = "Review::schema_version")]
+    pub schema_version: u32,
     pub id: DiffId,
     /// Paths that have been marked as reviewed
     pub reviewed: Vec<String>,
@@ -27,16 +47,57 @@ pub struct Review {
     pub edits: Vec<Edit>,
     /// Paths of reference files (files outside the diff that were viewed)
     pub reference_files: Vec<String>,
+    /// When each file was first opened and marked reviewed
+    #[serde(default)]
+    pub file_times: Vec<FileReviewTime>,
+    /// Commit the head pointed at when a file was last marked reviewed.
+    /// For working tree reviews this is HEAD at the time.
+    #[serde(default)]
+    pub reviewed_head: Option<String>,
 }
 
 impl Review {
+    /// Current `schema_version`. Bump when a field is renamed, removed or
+    /// changes meaning.
+    pub const SCHEMA_VERSION: u32 = 1;
+
+    pub const fn schema_version() -> u32 {
+        Self::SCHEMA_VERSION
+    }
+
     pub fn new(id: DiffId) -> Self {
         Self {
+            schema_version: Self::SCHEMA_VERSION,
             id,
             reviewed: Vec::new(),
             comments: Vec::new(),
             edits: Vec::new(),
             reference_files: Vec::new(),
+            file_times: Vec::new(),
+            reviewed_head: None,
+        }
+    }
+
+    /// Timing for a file, if it has been viewed or reviewed.
+    pub fn file_time(&self, path: &str) -> Option<&FileReviewTime> {
+        self.file_times.iter().find(|t| t.path == path)
+    }
+}
+
+/// Review timing for a single file (unix ms timestamps).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileReviewTime {
+    pub path: String,
+    pub first_viewed_at: Option<i64>,
+    pub reviewed_at: Option<i64>,
+}
+
+impl FileReviewTime {
+    /// Time from first view to being marked reviewed.
+    pub fn duration_ms(&self) -> Option<i64> {
+        match (self.first_viewed_at, self.reviewed_at) {
+            (Some(viewed), Some(reviewed)) => Some((reviewed - viewed).max(0)),
+            _ => None,
         }
     }
 }
@@ -53,6 +114,27 @@ fn default_author() -> CommentAuthor {
     CommentAuthor::User
 }
 
+/// Whether a comment has been submitted with the review.
+///
+/// New comments start as drafts and are left out of exports until the
+/// review is published.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CommentStatus {
+    #[default]
+    Draft,
+    Published,
+}
+
+impl CommentStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            CommentStatus::Draft => "draft",
+            CommentStatus::Published => "published",
+        }
+    }
+}
+
 /// A comment attached to a specific location in a file.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Comment {
@@ -66,6 +148,14 @@ pub struct Comment {
     pub category: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub created_at: Option<String>,
+    /// Column where the comment starts on the span's first line (0-indexed, in chars)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub col_start: Option<u32>,
+    /// Column where the comment ends on the span's last line (exclusive)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub col_end: Option<u32>,
+    #[serde(default)]
+    pub status: CommentStatus,
 }
 
 impl Comment {
@@ -78,6 +168,28 @@ impl Comment {
             author: CommentAuthor::User,
             category: None,
             created_at: Some(chrono::Utc::now().to_rfc3339()),
+            col_start: None,
+            col_end: None,
+            status: CommentStatus::Draft,
+        }
+    }
+
+    /// Anchor the comment to a column range within its lines.
+    pub fn with_columns(mut self, col_start: Option<u32>, col_end: Option<u32>) -> Self {
+        self.col_start = col_start;
+        self.col_end = col_end;
+        self
+    }
+
+    /// Human-readable column range (1-indexed, inclusive), if the comment
+    /// is anchored to part of a line.
+    pub fn column_label(&self) -> Option<String> {
+        match (self.col_start, self.col_end) {
+            (None, None) => None,
+            (Some(start), Some(end)) if end <= start + 1 => Some(format!("col {}", start + 1)),
+            (Some(start), Some(end)) => Some(format!("cols {}-{}", start + 1, end)),
+            (Some(start), None) => Some(format!("cols {}-end", start + 1)),
+            (None, Some(end)) => Some(format!("cols 1-{end}")),
         }
     }
 }
@@ -107,6 +219,10 @@ pub struct NewComment {
     pub path: String,
     pub span: Span,
     pub content: String,
+    #[serde(default)]
+    pub col_start: Option<u32>,
+    #[serde(default)]
+    pub col_end: Option<u32>,
 }
 
 /// Input for recording a new edit (from frontend).
@@ -149,6 +265,11 @@ type Result<T> = std::result::Result<T, ReviewError>;
 // Global store
 // =============================================================================
 
+/// Set this environment variable to prune empty reviews on startup.
+/// Otherwise they're only pruned on request (the `prune_empty_reviews`
+/// command).
+pub const PRUNE_EMPTY_REVIEWS_ENV_VAR: &str = "STAGED_PRUNE_EMPTY_REVIEWS";
+
 /// Global store instance - initialized during app setup.
 static STORE: OnceLock<std::result::Result<ReviewStore, String>> = OnceLock::new();
 
@@ -166,14 +287,23 @@ pub fn init_store(app_handle: &AppHandle) -> Result<()> {
     let old_db_path = app_data_dir.join("reviews.db");
     if old_db_path.exists() && !db_path.exists() {
         if let Err(e) = std::fs::rename(&old_db_path, &db_path) {
-            log::warn!("Failed to migrate reviews.db to staged.db: {e}");
+            log::warn!(target: logging::REVIEW, "Failed to migrate reviews.db to staged.db: {e}");
         }
     }
 
     STORE.get_or_init(|| ReviewStore::open(db_path).map_err(|e| e.0));
 
     // Check if initialization succeeded
-    get_store()?;
+    let store = get_store()?;
+
+    // Nothing is open yet, so every empty review is safe to drop
+    if std::env::var_os(PRUNE_EMPTY_REVIEWS_ENV_VAR).is_some() {
+        match store.prune_empty(&[]) {
+            Ok(0) => {}
+            Ok(n) => log::info!(target: logging::REVIEW, "Pruned {n} empty reviews"),
+            Err(e) => log::warn!(target: logging::REVIEW, "Failed to prune empty reviews: {e}"),
+        }
+    }
     Ok(())
 }
 
@@ -217,7 +347,7 @@ impl ReviewStore {
 
     /// Initialize the database schema.
     fn init_schema(&self) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
 
         conn.execute_batch(
             r#"
@@ -235,6 +365,16 @@ impl ReviewStore {
                 FOREIGN KEY (before_ref, after_ref) REFERENCES reviews(before_ref, after_ref) ON DELETE CASCADE
             );
 
+            -- First time each file was opened, reviewed or not
+            CREATE TABLE IF NOT EXISTS file_views (
+                before_ref TEXT NOT NULL,
+                after_ref TEXT NOT NULL,
+                path TEXT NOT NULL,
+                first_viewed_at INTEGER NOT NULL,
+                PRIMARY KEY (before_ref, after_ref, path),
+                FOREIGN KEY (before_ref, after_ref) REFERENCES reviews(before_ref, after_ref) ON DELETE CASCADE
+            );
+
             CREATE TABLE IF NOT EXISTS comments (
                 id TEXT PRIMARY KEY,
                 before_ref TEXT NOT NULL,
@@ -263,6 +403,23 @@ impl ReviewStore {
                 FOREIGN KEY (before_ref, after_ref) REFERENCES reviews(before_ref, after_ref) ON DELETE CASCADE
             );
 
+            -- Change sets group diffs (possibly from different repos) into one review
+            CREATE TABLE IF NOT EXISTS changesets (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS changeset_diffs (
+                changeset_id TEXT NOT NULL,
+                repo_path TEXT NOT NULL,
+                before_ref TEXT NOT NULL,
+                after_ref TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                PRIMARY KEY (changeset_id, repo_path, before_ref, after_ref),
+                FOREIGN KEY (changeset_id) REFERENCES changesets(id) ON DELETE CASCADE
+            );
+
             -- AI analysis results
             CREATE TABLE IF NOT EXISTS ai_changeset_summary (
                 before_ref TEXT NOT NULL,
@@ -293,6 +450,18 @@ impl ReviewStore {
         Self::migrate_add_column(&conn, "comments", "author", "TEXT NOT NULL DEFAULT 'user'")?;
         Self::migrate_add_column(&conn, "comments", "category", "TEXT")?;
         Self::migrate_add_column(&conn, "comments", "created_at", "TEXT")?;
+        Self::migrate_add_column(&conn, "comments", "col_start", "INTEGER")?;
+        Self::migrate_add_column(&conn, "comments", "col_end", "INTEGER")?;
+        // Comments from before drafts existed were already visible in exports
+        Self::migrate_add_column(
+            &conn,
+            "comments",
+            "status",
+            "TEXT NOT NULL DEFAULT 'published'",
+        )?;
+        Self::migrate_add_column(&conn, "reviewed_files", "reviewed_at", "INTEGER")?;
+        Self::migrate_add_column(&conn, "reviews", "reviewed_head", "TEXT")?;
+        Self::migrate_add_column(&conn, "reviews", "reviewed_head_at", "INTEGER")?;
 
         Ok(())
     }
@@ -322,7 +491,7 @@ impl ReviewStore {
 
     /// Get or create a review for the given diff.
     pub fn get_or_create(&self, id: &DiffId) -> Result<Review> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
 
         // Ensure review exists
         conn.execute(
@@ -335,35 +504,72 @@ impl ReviewStore {
 
     /// Get a review by its DiffId.
     pub fn get(&self, id: &DiffId) -> Result<Review> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         self.get_with_conn(&conn, id)
     }
 
     /// Get a review using an existing connection lock.
     fn get_with_conn(&self, conn: &Connection, id: &DiffId) -> Result<Review> {
         // Check if review exists
-        let exists: bool = conn
+        let reviewed_head: Option<Option<String>> = conn
             .query_row(
-                "SELECT 1 FROM reviews WHERE before_ref = ?1 AND after_ref = ?2",
+                "SELECT reviewed_head FROM reviews WHERE before_ref = ?1 AND after_ref = ?2",
                 params![&id.before, &id.after],
-                |_| Ok(true),
+                |row| row.get(0),
             )
-            .optional()?
-            .unwrap_or(false);
+            .optional()?;
 
-        if !exists {
+        let Some(reviewed_head) = reviewed_head else {
             return Ok(Review::new(id.clone()));
-        }
+        };
 
         // Load reviewed files
-        let mut stmt = conn
-            .prepare("SELECT path FROM reviewed_files WHERE before_ref = ?1 AND after_ref = ?2")?;
-        let reviewed: Vec<String> = stmt
-            .query_map(params![&id.before, &id.after], |row| row.get(0))?
+        let mut stmt = conn.prepare(
+            "SELECT path, reviewed_at FROM reviewed_files WHERE before_ref = ?1 AND after_ref = ?2",
+        )?;
+        let reviewed_rows: Vec<(String, Option<i64>)> = stmt
+            .query_map(params![&id.before, &id.after], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?
             .collect::<std::result::Result<Vec<_>, _>>()?;
+        let reviewed: Vec<String> = reviewed_rows.iter().map(|(p, _)| p.clone()).collect();
+
+        // Load view timestamps and merge with review timestamps
+        let mut stmt = conn.prepare(
+            "SELECT path, first_viewed_at FROM file_views WHERE before_ref = ?1 AND after_ref = ?2",
+        )?;
+        let views: Vec<(String, i64)> = stmt
+            .query_map(params![&id.before, &id.after], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let mut times: BTreeMap<String, FileReviewTime> = BTreeMap::new();
+        for (path, first_viewed_at) in views {
+            times
+                .entry(path.clone())
+                .or_insert_with(|| FileReviewTime {
+                    path,
+                    first_viewed_at: None,
+                    reviewed_at: None,
+                })
+                .first_viewed_at = Some(first_viewed_at);
+        }
+        for (path, reviewed_at) in reviewed_rows {
+            times
+                .entry(path.clone())
+                .or_insert_with(|| FileReviewTime {
+                    path,
+                    first_viewed_at: None,
+                    reviewed_at: None,
+                })
+                .reviewed_at = reviewed_at;
+        }
+        let file_times: Vec<FileReviewTime> = times.into_values().collect();
 
         let mut stmt = conn.prepare(
-            "SELECT id, path, span_start, span_end, content, author, category, created_at
+            "SELECT id, path, span_start, span_end, content, author, category, created_at,
+                    col_start, col_end, status
              FROM comments WHERE before_ref = ?1 AND after_ref = ?2",
         )?;
         let comments: Vec<Comment> = stmt
@@ -382,6 +588,12 @@ impl ReviewStore {
                     author,
                     category: row.get(6).ok(),
                     created_at: row.get(7).ok(),
+                    col_start: row.get(8)?,
+                    col_end: row.get(9)?,
+                    status: match row.get::<_, String>(10)?.as_str() {
+                        "draft" => CommentStatus::Draft,
+                        _ => CommentStatus::Published,
+                    },
                 })
             })?
             .collect::<std::result::Result<Vec<_>, _>>()?;
@@ -407,28 +619,74 @@ impl ReviewStore {
             .collect::<std::result::Result<Vec<_>, _>>()?;
 
         Ok(Review {
+            schema_version: Review::SCHEMA_VERSION,
             id: id.clone(),
             reviewed,
             comments,
             edits,
             reference_files,
+            file_times,
+            reviewed_head,
         })
     }
 
+    /// Record that a file was opened. Only the first view is kept, so the
+    /// time until it is marked reviewed covers the whole review of the file.
+    pub fn record_view(&self, id: &DiffId, path: &str) -> Result<()> {
+        self.get_or_create(id)?;
+        let conn = lock(&self.conn);
+        conn.execute(
+            "INSERT OR IGNORE INTO file_views (before_ref, after_ref, path, first_viewed_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![&id.before, &id.after, path, crate::store::now_timestamp()],
+        )?;
+        Ok(())
+    }
+
     /// Mark a file as reviewed.
     pub fn mark_reviewed(&self, id: &DiffId, path: &str) -> Result<()> {
         self.get_or_create(id)?;
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute(
-            "INSERT OR IGNORE INTO reviewed_files (before_ref, after_ref, path) VALUES (?1, ?2, ?3)",
-            params![&id.before, &id.after, path],
+            "INSERT OR IGNORE INTO reviewed_files (before_ref, after_ref, path, reviewed_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![&id.before, &id.after, path, crate::store::now_timestamp()],
         )?;
         Ok(())
     }
 
+    /// Snapshot the commit the diff's head is at, so a later push can be
+    /// reviewed incrementally against it.
+    pub fn record_reviewed_head(&self, id: &DiffId, head: &str) -> Result<()> {
+        self.get_or_create(id)?;
+        let conn = lock(&self.conn);
+        conn.execute(
+            "UPDATE reviews SET reviewed_head = ?3, reviewed_head_at = ?4
+             WHERE before_ref = ?1 AND after_ref = ?2",
+            params![&id.before, &id.after, head, crate::store::now_timestamp()],
+        )?;
+        Ok(())
+    }
+
+    /// The most recently reviewed head among other reviews against the same
+    /// base, i.e. what the reviewer saw before the branch moved on.
+    pub fn previous_reviewed_head(&self, id: &DiffId) -> Result<Option<String>> {
+        let conn = lock(&self.conn);
+        let head = conn
+            .query_row(
+                "SELECT reviewed_head FROM reviews
+                 WHERE before_ref = ?1 AND after_ref != ?2 AND reviewed_head IS NOT NULL
+                 ORDER BY reviewed_head_at DESC LIMIT 1",
+                params![&id.before, &id.after],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(head)
+    }
+
     /// Unmark a file as reviewed.
     pub fn unmark_reviewed(&self, id: &DiffId, path: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute(
             "DELETE FROM reviewed_files WHERE before_ref = ?1 AND after_ref = ?2 AND path = ?3",
             params![&id.before, &id.after, path],
@@ -436,10 +694,22 @@ impl ReviewStore {
         Ok(())
     }
 
+    /// Start a review over: clear every reviewed file but keep comments,
+    /// edits and view times. Returns how many files were unmarked.
+    pub fn reset_reviewed(&self, id: &DiffId) -> Result<usize> {
+        self.get_or_create(id)?;
+        let conn = lock(&self.conn);
+        let cleared = conn.execute(
+            "DELETE FROM reviewed_files WHERE before_ref = ?1 AND after_ref = ?2",
+            params![&id.before, &id.after],
+        )?;
+        Ok(cleared)
+    }
+
     /// Add a comment.
     pub fn add_comment(&self, id: &DiffId, comment: &Comment) -> Result<()> {
         self.get_or_create(id)?;
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
 
         let author_str = match comment.author {
             CommentAuthor::User => "user",
@@ -447,8 +717,8 @@ impl ReviewStore {
         };
 
         conn.execute(
-            "INSERT INTO comments (id, before_ref, after_ref, path, span_start, span_end, content, author, category, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            "INSERT INTO comments (id, before_ref, after_ref, path, span_start, span_end, content, author, category, created_at, col_start, col_end, status)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
             params![
                 &comment.id,
                 &id.before,
@@ -459,7 +729,10 @@ impl ReviewStore {
                 &comment.content,
                 author_str,
                 &comment.category,
-                &comment.created_at
+                &comment.created_at,
+                comment.col_start,
+                comment.col_end,
+                comment.status.as_str()
             ],
         )?;
         Ok(())
@@ -467,7 +740,7 @@ impl ReviewStore {
 
     /// Update a comment's content.
     pub fn update_comment(&self, comment_id: &str, content: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute(
             "UPDATE comments SET content = ?1 WHERE id = ?2",
             params![content, comment_id],
@@ -475,9 +748,28 @@ impl ReviewStore {
         Ok(())
     }
 
+    /// Submit a review: flip all of its draft comments to published.
+    /// Returns how many comments were published.
+    pub fn publish_review(&self, id: &DiffId) -> Result<usize> {
+        let conn = lock(&self.conn);
+        let published = conn.execute(
+            "UPDATE comments SET status = 'published'
+             WHERE before_ref = ?1 AND after_ref = ?2 AND status = 'draft'",
+            params![&id.before, &id.after],
+        )?;
+        Ok(published)
+    }
+
+    /// Reclaim space left by deleted rows. Returns bytes freed, or `None` if
+    /// a transaction was in progress and compaction was skipped.
+    pub fn compact(&self) -> Result<Option<u64>> {
+        let conn = lock(&self.conn);
+        Ok(crate::util::sqlite::compact(&conn)?)
+    }
+
     /// Delete a comment.
     pub fn delete_comment(&self, comment_id: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute("DELETE FROM comments WHERE id = ?1", params![comment_id])?;
         Ok(())
     }
@@ -485,7 +777,7 @@ impl ReviewStore {
     /// Add an edit.
     pub fn add_edit(&self, id: &DiffId, edit: &Edit) -> Result<()> {
         self.get_or_create(id)?;
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute(
             "INSERT INTO edits (id, before_ref, after_ref, path, diff) VALUES (?1, ?2, ?3, ?4, ?5)",
             params![&edit.id, &id.before, &id.after, &edit.path, &edit.diff],
@@ -495,7 +787,7 @@ impl ReviewStore {
 
     /// Delete an edit.
     pub fn delete_edit(&self, edit_id: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute("DELETE FROM edits WHERE id = ?1", params![edit_id])?;
         Ok(())
     }
@@ -503,7 +795,7 @@ impl ReviewStore {
     /// Add a reference file path.
     pub fn add_reference_file(&self, id: &DiffId, path: &str) -> Result<()> {
         self.get_or_create(id)?;
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute(
             "INSERT OR IGNORE INTO reference_files (before_ref, after_ref, path) VALUES (?1, ?2, ?3)",
             params![&id.before, &id.after, path],
@@ -513,7 +805,7 @@ impl ReviewStore {
 
     /// Remove a reference file path.
     pub fn remove_reference_file(&self, id: &DiffId, path: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute(
             "DELETE FROM reference_files WHERE before_ref = ?1 AND after_ref = ?2 AND path = ?3",
             params![&id.before, &id.after, path],
@@ -521,9 +813,67 @@ impl ReviewStore {
         Ok(())
     }
 
+    /// Delete reviews that hold nothing: no reviewed files, comments, edits,
+    /// reference files, view times, AI analysis, change set membership, or
+    /// incremental-review baseline.
+    /// These pile up from `get_or_create` on diffs that were opened but never
+    /// reviewed. Reviews in `keep` (e.g. ones currently open) are never
+    /// deleted. Returns the number removed.
+    pub fn prune_empty(&self, keep: &[DiffId]) -> Result<usize> {
+        // Every table keyed by a review; deleting the review cascades to them
+        const CHILD_TABLES: &[&str] = &[
+            "reviewed_files",
+            "comments",
+            "edits",
+            "reference_files",
+            "file_views",
+            "ai_changeset_summary",
+            "ai_file_analysis",
+            "changeset_diffs",
+        ];
+        let mut conn = lock(&self.conn);
+        let tx = conn.transaction()?;
+        let conditions: Vec<String> = CHILD_TABLES
+            .iter()
+            .map(|table| {
+                format!(
+                    "NOT EXISTS (SELECT 1 FROM {table} t
+                     WHERE t.before_ref = r.before_ref AND t.after_ref = r.after_ref)"
+                )
+            })
+            .collect();
+        // An incremental-review baseline is state worth keeping on its own
+        let query = format!(
+            "SELECT before_ref, after_ref FROM reviews r
+             WHERE r.reviewed_head IS NULL AND {}",
+            conditions.join(" AND ")
+        );
+
+        let empty: Vec<DiffId> = {
+            let mut stmt = tx.prepare(&query)?;
+            let rows = stmt.query_map([], |row| {
+                Ok(DiffId::new(
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                ))
+            })?;
+            rows.collect::<std::result::Result<_, _>>()?
+        };
+
+        let mut removed = 0;
+        for id in empty.iter().filter(|id| !keep.contains(id)) {
+            removed += tx.execute(
+                "DELETE FROM reviews WHERE before_ref = ?1 AND after_ref = ?2",
+                params![&id.before, &id.after],
+            )?;
+        }
+        tx.commit()?;
+        Ok(removed)
+    }
+
     /// Delete an entire review and all associated data.
     pub fn delete(&self, id: &DiffId) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         // Foreign key cascades handle child tables
         conn.execute(
             "DELETE FROM reviews WHERE before_ref = ?1 AND after_ref = ?2",
@@ -537,14 +887,31 @@ impl ReviewStore {
 // Export
 // =============================================================================
 
-/// Export a review as markdown for clipboard.
+/// Options for `export_markdown_with_options`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExportOptions {
+    /// Include comments that haven't been published yet
+    pub include_drafts: bool,
+}
+
+/// Export a review as markdown for clipboard. Only published comments are
+/// included.
 pub fn export_markdown(review: &Review) -> String {
+    export_markdown_with_options(review, &ExportOptions::default())
+}
+
+/// Export a review as markdown, optionally including draft comments.
+pub fn export_markdown_with_options(review: &Review, options: &ExportOptions) -> String {
     let mut md = String::new();
 
     // Group comments by file
     let mut comments_by_file: std::collections::HashMap<&str, Vec<&Comment>> =
         std::collections::HashMap::new();
-    for comment in &review.comments {
+    let visible = review
+        .comments
+        .iter()
+        .filter(|c| options.include_drafts || c.status == CommentStatus::Published);
+    for comment in visible {
         comments_by_file
             .entry(&comment.path)
             .or_default()
@@ -573,11 +940,14 @@ pub fn export_markdown(review: &Review) -> String {
         if let Some(comments) = comments_by_file.get(file) {
             for comment in comments {
                 let span = &comment.span;
-                let location = if span.end == span.start + 1 {
+                let mut location = if span.end == span.start + 1 {
                     format!("Line {}", span.start + 1)
                 } else {
                     format!("Lines {}-{}", span.start + 1, span.end)
                 };
+                if let Some(columns) = comment.column_label() {
+                    location.push_str(&format!(", {columns}"));
+                }
                 md.push_str(&format!("- **{}**: {}\n", location, comment.content));
             }
             md.push('\n');
@@ -585,12 +955,9 @@ pub fn export_markdown(review: &Review) -> String {
 
         if let Some(edits) = edits_by_file.get(file) {
             for edit in edits {
-                md.push_str("**Edit applied:**\n```diff\n");
-                md.push_str(&edit.diff);
-                if !edit.diff.ends_with('\n') {
-                    md.push('\n');
-                }
-                md.push_str("```\n\n");
+                md.push_str("**Edit applied:**\n");
+                md.push_str(&fenced_block("diff", &edit.diff));
+                md.push('\n');
             }
         }
     }
@@ -602,6 +969,60 @@ pub fn export_markdown(review: &Review) -> String {
     md
 }
 
+/// Export a review as markdown to a file. Only published comments are
+/// included.
+pub fn export_markdown_to_file(review: &Review, path: &Path) -> Result<()> {
+    export_markdown_to_file_with_options(review, path, &ExportOptions::default())
+}
+
+/// Like [`export_markdown_to_file`], optionally including draft comments.
+///
+/// Writes to a temp file in the target directory and renames it into place,
+/// so a crash mid-write never leaves a truncated export behind.
+pub fn export_markdown_to_file_with_options(
+    review: &Review,
+    path: &Path,
+    options: &ExportOptions,
+) -> Result<()> {
+    let describe = |action: &str, target: &Path, e: std::io::Error| {
+        if e.kind() == std::io::ErrorKind::PermissionDenied {
+            ReviewError::new(format!("Permission denied {action} {}", target.display()))
+        } else {
+            ReviewError::new(format!("Failed {action} {}: {e}", target.display()))
+        }
+    };
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| ReviewError::new(format!("Not a file path: {}", path.display())))?;
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    std::fs::create_dir_all(&dir).map_err(|e| describe("creating", &dir, e))?;
+
+    let tmp_path = dir.join(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        uuid::Uuid::new_v4()
+    ));
+
+    let write = || -> std::io::Result<()> {
+        let mut file = std::fs::File::create(&tmp_path)?;
+        file.write_all(export_markdown_with_options(review, options).as_bytes())?;
+        file.sync_all()
+    };
+    if let Err(e) = write() {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(describe("writing", &tmp_path, e));
+    }
+
+    std::fs::rename(&tmp_path, path).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp_path);
+        describe("writing", path, e)
+    })
+}
+
 // =============================================================================
 // Tests
 // =============================================================================
@@ -611,6 +1032,22 @@ mod tests {
     use super::*;
     use tempfile::tempdir;
 
+    #[test]
+    fn test_store_usable_after_panic_while_locked() {
+        let dir = tempdir().unwrap();
+        let store = ReviewStore::open(dir.path().join("test.db")).unwrap();
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _conn = lock(&store.conn);
+            panic!("panicked while holding the connection");
+        }));
+        assert!(result.is_err());
+
+        let id = DiffId::new("abc", "def");
+        store.mark_reviewed(&id, "src/main.rs").unwrap();
+        assert_eq!(store.get(&id).unwrap().reviewed, vec!["src/main.rs"]);
+    }
+
     #[test]
     fn test_mark_reviewed() {
         let dir = tempdir().unwrap();
@@ -627,6 +1064,95 @@ mod tests {
         assert!(review.reviewed.is_empty());
     }
 
+    #[test]
+    fn test_review_time_tracking() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let store = ReviewStore::open(db_path).unwrap();
+        let id = DiffId::new("main", "feature");
+
+        store.record_view(&id, "src/main.rs").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        store.mark_reviewed(&id, "src/main.rs").unwrap();
+
+        let review = store.get(&id).unwrap();
+        let time = review.file_time("src/main.rs").unwrap();
+        assert!(time.first_viewed_at.is_some());
+        assert!(time.duration_ms().unwrap() > 0);
+
+        // Re-opening the file keeps the original first view
+        let first = time.first_viewed_at;
+        store.record_view(&id, "src/main.rs").unwrap();
+        let review = store.get(&id).unwrap();
+        assert_eq!(
+            review.file_time("src/main.rs").unwrap().first_viewed_at,
+            first
+        );
+
+        // Viewed but not reviewed has no duration yet
+        store.record_view(&id, "src/lib.rs").unwrap();
+        let review = store.get(&id).unwrap();
+        assert_eq!(review.file_time("src/lib.rs").unwrap().duration_ms(), None);
+    }
+
+    #[test]
+    fn test_compact_reclaims_deleted_rows() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let store = ReviewStore::open(db_path.clone()).unwrap();
+        let id = DiffId::new("main", "feature");
+        let body = "x".repeat(1000);
+
+        for _ in 0..5 {
+            let comments: Vec<_> = (0..200)
+                .map(|i| Comment::new("src/lib.rs", Span::new(i, i + 1), body.clone()))
+                .collect();
+            for comment in &comments {
+                store.add_comment(&id, comment).unwrap();
+            }
+            for comment in &comments {
+                store.delete_comment(&comment.id).unwrap();
+            }
+        }
+
+        let before = std::fs::metadata(&db_path).unwrap().len();
+        let freed = store.compact().unwrap().unwrap();
+        let after = std::fs::metadata(&db_path).unwrap().len();
+        assert!(after < before, "{after} >= {before}");
+        assert_eq!(freed, before - after);
+
+        // Still usable afterwards
+        store.mark_reviewed(&id, "src/lib.rs").unwrap();
+        assert_eq!(store.get(&id).unwrap().reviewed, ["src/lib.rs"]);
+    }
+
+    #[test]
+    fn test_reset_reviewed_keeps_comments() {
+        let dir = tempdir().unwrap();
+        let store = ReviewStore::open(dir.path().join("test.db")).unwrap();
+        let id = DiffId::new("main", "feature");
+        let other = DiffId::new("main", "other");
+
+        store.mark_reviewed(&id, "src/main.rs").unwrap();
+        store.mark_reviewed(&id, "src/lib.rs").unwrap();
+        store.mark_reviewed(&other, "src/main.rs").unwrap();
+        store
+            .add_comment(&id, &Comment::new("src/lib.rs", Span::new(1, 2), "Keep me"))
+            .unwrap();
+
+        assert_eq!(store.reset_reviewed(&id).unwrap(), 2);
+
+        let review = store.get(&id).unwrap();
+        assert!(review.reviewed.is_empty());
+        assert_eq!(review.comments.len(), 1);
+        assert_eq!(review.comments[0].content, "Keep me");
+        // Other reviews are untouched
+        assert_eq!(store.get(&other).unwrap().reviewed, ["src/main.rs"]);
+        // Resetting a review that doesn't exist yet is fine
+        let fresh = DiffId::new("main", "fresh");
+        assert_eq!(store.reset_reviewed(&fresh).unwrap(), 0);
+    }
+
     #[test]
     fn test_comments() {
         let dir = tempdir().unwrap();
@@ -693,6 +1219,74 @@ mod tests {
         assert!(review.comments.is_empty());
     }
 
+    #[test]
+    fn test_prune_empty_keeps_populated_and_open_reviews() {
+        let dir = tempdir().unwrap();
+        let store = ReviewStore::open(dir.path().join("test.db")).unwrap();
+
+        let empty = DiffId::new("a", "b");
+        let populated = DiffId::new("c", "d");
+        let open = DiffId::new("e", "f");
+        let baseline = DiffId::new("g", "h");
+        store.get_or_create(&empty).unwrap();
+        store.get_or_create(&open).unwrap();
+        store.mark_reviewed(&populated, "src/lib.rs").unwrap();
+        store.record_reviewed_head(&baseline, "abc123").unwrap();
+
+        assert_eq!(store.prune_empty(std::slice::from_ref(&open)).unwrap(), 1);
+
+        let conn = store.conn.lock().unwrap();
+        let remaining: Vec<String> = conn
+            .prepare("SELECT before_ref FROM reviews ORDER BY before_ref")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<std::result::Result<_, _>>()
+            .unwrap();
+        assert_eq!(remaining, ["c", "e", "g"]);
+        drop(conn);
+
+        // The populated review and the incremental baseline are untouched
+        assert_eq!(store.get(&populated).unwrap().reviewed, ["src/lib.rs"]);
+        assert_eq!(
+            store.get(&baseline).unwrap().reviewed_head.as_deref(),
+            Some("abc123")
+        );
+    }
+
+    #[test]
+    fn test_column_anchored_comment() {
+        let dir = tempdir().unwrap();
+        let store = ReviewStore::open(dir.path().join("test.db")).unwrap();
+        let id = DiffId::new("main", "feature");
+
+        let comment = Comment::new("src/lib.rs", Span::new(10, 11), "Use a slice here")
+            .with_columns(Some(4), Some(16));
+        store.add_comment(&id, &comment).unwrap();
+        store
+            .add_comment(
+                &id,
+                &Comment::new("src/lib.rs", Span::new(20, 22), "Whole lines"),
+            )
+            .unwrap();
+        store.publish_review(&id).unwrap();
+
+        let review = store.get(&id).unwrap();
+        let loaded = review.comments.iter().find(|c| c.id == comment.id).unwrap();
+        assert_eq!((loaded.col_start, loaded.col_end), (Some(4), Some(16)));
+
+        let md = export_markdown(&review);
+        assert!(md.contains("- **Line 11, cols 5-16**: Use a slice here"));
+        assert!(md.contains("- **Lines 21-22**: Whole lines"));
+    }
+
+    #[test]
+    fn test_review_json_carries_schema_version() {
+        let review = Review::new(DiffId::new("main", "feature"));
+        let json = serde_json::to_value(&review).unwrap();
+        assert_eq!(json["schema_version"], Review::schema_version());
+    }
+
     #[test]
     fn test_export_markdown() {
         let id = DiffId::new("main", "feature");
@@ -707,6 +1301,9 @@ mod tests {
             author: CommentAuthor::User,
             category: None,
             created_at: None,
+            col_start: None,
+            col_end: None,
+            status: CommentStatus::Published,
         });
 
         review.edits.push(Edit {
@@ -721,4 +1318,175 @@ mod tests {
         assert!(md.contains("Fix this"));
         assert!(md.contains("-old"));
     }
+
+    #[test]
+    fn test_drafts_excluded_from_export_until_published() {
+        let dir = tempdir().unwrap();
+        let store = ReviewStore::open(dir.path().join("test.db")).unwrap();
+        let id = DiffId::new("main", "feature");
+        let other = DiffId::new("main", "other");
+
+        store
+            .add_comment(&id, &Comment::new("src/lib.rs", Span::new(0, 1), "First"))
+            .unwrap();
+        store
+            .add_comment(&id, &Comment::new("src/lib.rs", Span::new(4, 5), "Second"))
+            .unwrap();
+        store
+            .add_comment(
+                &other,
+                &Comment::new("src/lib.rs", Span::new(0, 1), "Elsewhere"),
+            )
+            .unwrap();
+
+        let review = store.get(&id).unwrap();
+        assert!(review
+            .comments
+            .iter()
+            .all(|c| c.status == CommentStatus::Draft));
+        assert_eq!(export_markdown(&review), "No comments or edits.\n");
+        let with_drafts = export_markdown_with_options(
+            &review,
+            &ExportOptions {
+                include_drafts: true,
+            },
+        );
+        assert!(with_drafts.contains("First") && with_drafts.contains("Second"));
+
+        assert_eq!(store.publish_review(&id).unwrap(), 2);
+        let md = export_markdown(&store.get(&id).unwrap());
+        assert!(md.contains("First") && md.contains("Second"));
+
+        // Publishing is scoped to one review, and comments added afterwards
+        // start as drafts again
+        assert_eq!(
+            store.get(&other).unwrap().comments[0].status,
+            CommentStatus::Draft
+        );
+        store
+            .add_comment(&id, &Comment::new("src/lib.rs", Span::new(9, 10), "Later"))
+            .unwrap();
+        assert!(!export_markdown(&store.get(&id).unwrap()).contains("Later"));
+        assert_eq!(store.publish_review(&id).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_copy_export_includes_new_comments() {
+        let dir = tempdir().unwrap();
+        let store = ReviewStore::open(dir.path().join("test.db")).unwrap();
+        let id = DiffId::new("main", "feature");
+        store
+            .add_comment(
+                &id,
+                &Comment::new("src/lib.rs", Span::new(2, 3), "Typo here"),
+            )
+            .unwrap();
+
+        // The sidebar's Copy button and file export ask for drafts, so a
+        // fresh review exports what was just written
+        let options = ExportOptions {
+            include_drafts: true,
+        };
+        let review = store.get_or_create(&id).unwrap();
+        let md = export_markdown_with_options(&review, &options);
+        assert_eq!(md, "## src/lib.rs\n\n- **Line 3**: Typo here\n\n");
+
+        let path = dir.path().join("review.md");
+        export_markdown_to_file_with_options(&review, &path, &options).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), md);
+    }
+
+    /// Contents of each fenced code block, per CommonMark's fence rules: a
+    /// block closes on a line (indented up to three spaces) of at least as
+    /// many backticks as opened it, with nothing after them.
+    fn fenced_blocks(md: &str) -> Vec<String> {
+        let mut blocks = Vec::new();
+        let mut open: Option<(usize, String)> = None;
+        for line in md.lines() {
+            let trimmed = line.trim_start_matches(' ');
+            let indent = line.len() - trimmed.len();
+            let ticks = trimmed.len() - trimmed.trim_start_matches('`').len();
+            match open.take() {
+                None if indent <= 3 && ticks >= 3 => open = Some((ticks, String::new())),
+                None => {}
+                Some((len, body))
+                    if indent <= 3 && ticks >= len && trimmed[ticks..].trim().is_empty() =>
+                {
+                    blocks.push(body)
+                }
+                Some((len, mut body)) => {
+                    body.push_str(line);
+                    body.push('\n');
+                    open = Some((len, body));
+                }
+            }
+        }
+        blocks
+    }
+
+    #[test]
+    fn test_export_edit_containing_fence() {
+        let mut review = Review::new(DiffId::new("main", "feature"));
+        let diff = "@@ -1,3 +1,3 @@\n ```\n-old\n+````rust\n```\n";
+        review.edits.push(Edit {
+            id: "e1".into(),
+            path: "README.md".into(),
+            diff: diff.into(),
+        });
+
+        let md = export_markdown(&review);
+        assert_eq!(fenced_blocks(&md), vec![diff.to_string()]);
+        assert!(md.contains("`````diff\n"));
+    }
+
+    #[test]
+    fn test_export_markdown_to_file() {
+        let dir = tempdir().unwrap();
+        let mut review = Review::new(DiffId::new("main", "feature"));
+        let mut comment = Comment::new("src/lib.rs", Span::new(0, 1), "Rename this");
+        comment.status = CommentStatus::Published;
+        review.comments.push(comment);
+
+        let path = dir.path().join("exports/nested/review.md");
+        export_markdown_to_file(&review, &path).unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written, export_markdown(&review));
+        assert!(written.contains("Rename this"));
+
+        let leftovers: Vec<_> = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|name| name.ends_with(".tmp"))
+            .collect();
+        assert!(
+            leftovers.is_empty(),
+            "temp files left behind: {leftovers:?}"
+        );
+    }
+
+    #[test]
+    fn test_previous_reviewed_head() {
+        let dir = tempdir().unwrap();
+        let store = ReviewStore::open(dir.path().join("test.db")).unwrap();
+        let first = DiffId::new("base", "head1");
+        let second = DiffId::new("base", "head2");
+
+        store.mark_reviewed(&first, "a.txt").unwrap();
+        store.record_reviewed_head(&first, "head1").unwrap();
+        assert_eq!(
+            store.get(&first).unwrap().reviewed_head.as_deref(),
+            Some("head1")
+        );
+
+        assert_eq!(
+            store.previous_reviewed_head(&second).unwrap().as_deref(),
+            Some("head1")
+        );
+        // A review isn't its own previous review
+        assert_eq!(store.previous_reviewed_head(&first).unwrap(), None);
+        // Nor is one against a different base
+        let other = DiffId::new("other", "head2");
+        assert_eq!(store.previous_reviewed_head(&other).unwrap(), None);
+    }
 }
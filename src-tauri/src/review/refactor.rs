@@ -0,0 +1,308 @@
+//! Search-and-replace preview across the files in a diff.
+//!
+//! Only computes what a replacement would do; nothing is written.
+
+use std::path::Path;
+
+use regex::Regex;
+use serde::Serialize;
+
+use super::{Result, ReviewError};
+use crate::git::{self, DiffSpec, GitRef};
+
+/// A single match in a file's after-content. Lines are 0-indexed; columns
+/// are byte offsets within the line where the match starts and ends.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplaceMatch {
+    pub line: u32,
+    pub start_column: u32,
+    pub end_line: u32,
+    pub end_column: u32,
+    pub text: String,
+}
+
+/// The effect of a replacement on one changed file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplacePreview {
+    pub path: String,
+    pub matches: Vec<ReplaceMatch>,
+    /// The file's full text after replacement
+    pub new_text: String,
+}
+
+/// Build the matcher. Literal patterns are escaped, and their replacement
+/// is inserted verbatim rather than expanding `$1`-style references.
+fn build_pattern(find: &str, is_regex: bool) -> Result<Regex> {
+    if find.is_empty() {
+        return Err(ReviewError::new("Search pattern is empty"));
+    }
+    let pattern = if is_regex {
+        find.to_string()
+    } else {
+        regex::escape(find)
+    };
+    Regex::new(&pattern).map_err(|e| ReviewError::new(format!("Invalid regex: {e}")))
+}
+
+/// Line and column of byte offsets in `text`, asked for in increasing
+/// order. Each lookup only scans the text since the previous one.
+struct Positions<'t> {
+    text: &'t str,
+    offset: usize,
+    line: u32,
+    line_start: usize,
+}
+
+impl<'t> Positions<'t> {
+    fn new(text: &'t str) -> Self {
+        Self {
+            text,
+            offset: 0,
+            line: 0,
+            line_start: 0,
+        }
+    }
+
+    fn at(&mut self, offset: usize) -> (u32, u32) {
+        let passed = &self.text[self.offset..offset];
+        if let Some(nl) = passed.rfind('\n') {
+            self.line += passed.matches('\n').count() as u32;
+            self.line_start = self.offset + nl + 1;
+        }
+        self.offset = offset;
+        (self.line, (offset - self.line_start) as u32)
+    }
+}
+
+/// Every match of `pattern` in `text`, paired with its replacement.
+///
+/// Both the reported matches and the new text come from this, so they agree
+/// on empty matches such as `^`.
+fn replacements<'t>(
+    text: &'t str,
+    pattern: &Regex,
+    replace: &str,
+    is_regex: bool,
+) -> Vec<(regex::Match<'t>, String)> {
+    pattern
+        .captures_iter(text)
+        .map(|caps| {
+            let m = caps.get(0).expect("group 0 always participates");
+            let replacement = if is_regex {
+                let mut expanded = String::new();
+                caps.expand(replace, &mut expanded);
+                expanded
+            } else {
+                replace.to_string()
+            };
+            (m, replacement)
+        })
+        .collect()
+}
+
+/// Preview replacing `find` in one file's text. Returns None without matches.
+fn preview_text(
+    path: &str,
+    text: &str,
+    pattern: &Regex,
+    replace: &str,
+    is_regex: bool,
+) -> Option<ReplacePreview> {
+    let replacements = replacements(text, pattern, replace, is_regex);
+    if replacements.is_empty() {
+        return None;
+    }
+
+    let mut matches = Vec::with_capacity(replacements.len());
+    let mut new_text = String::with_capacity(text.len());
+    let mut last = 0;
+    let mut positions = Positions::new(text);
+    for (m, replacement) in &replacements {
+        let (line, start_column) = positions.at(m.start());
+        let (end_line, end_column) = positions.at(m.end());
+        matches.push(ReplaceMatch {
+            line,
+            start_column,
+            end_line,
+            end_column,
+            text: m.as_str().to_string(),
+        });
+        new_text.push_str(&text[last..m.start()]);
+        new_text.push_str(replacement);
+        last = m.end();
+    }
+    new_text.push_str(&text[last..]);
+
+    Some(ReplacePreview {
+        path: path.to_string(),
+        matches,
+        new_text,
+    })
+}
+
+/// Preview replacing `find` with `replace` across the after-content of every
+/// changed file in the diff. Deleted, binary and oversized files are skipped;
+/// files without matches are omitted.
+pub fn preview_replace(
+    repo: &Path,
+    spec: &DiffSpec,
+    find: &str,
+    replace: &str,
+    is_regex: bool,
+) -> Result<Vec<ReplacePreview>> {
+    let pattern = build_pattern(find, is_regex)?;
+
+    let mut summaries =
+        git::list_diff_files(repo, spec).map_err(|e| ReviewError::new(e.to_string()))?;
+    summaries.sort_by(|a, b| a.path().cmp(b.path()));
+
+    // Read the after side as-is, so line endings and the final newline survive
+    let head = match &spec.head {
+        GitRef::WorkingTree => git::WORKDIR.to_string(),
+        _ => {
+            git::diff_id(repo, spec)
+                .map_err(|e| ReviewError::new(e.to_string()))?
+                .after
+        }
+    };
+
+    let mut previews = Vec::new();
+    for summary in summaries.iter().filter(|s| s.after.is_some()) {
+        let path = summary.path().to_string_lossy();
+        let text = git::read_text_at_ref(repo, &head, &path)
+            .map_err(|e| ReviewError::new(e.to_string()))?;
+        let Some(text) = text.filter(|t| t.len() as u64 <= git::DEFAULT_MAX_DIFF_FILE_BYTES) else {
+            continue;
+        };
+        if let Some(preview) = preview_text(&path, &text, &pattern, replace, is_regex) {
+            previews.push(preview);
+        }
+    }
+    Ok(previews)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_support::{git, init_test_repo};
+
+    fn repo_with_changes() -> tempfile::TempDir {
+        let (dir, _) = init_test_repo();
+        let repo = dir.path();
+        std::fs::write(repo.join("a.rs"), "fn old_name() {}\n").unwrap();
+        std::fs::write(repo.join("untouched.rs"), "old_name();\n").unwrap();
+        git(repo, &["add", "-A"]);
+        git(repo, &["commit", "-m", "initial"]);
+
+        std::fs::write(
+            repo.join("a.rs"),
+            "fn old_name() {}\nfn caller() { old_name(); }\n",
+        )
+        .unwrap();
+        std::fs::write(repo.join("b.rs"), "use crate::old_name;\n").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_positions_track_lines() {
+        let text = "ab\ncd\n\nef";
+        let mut positions = Positions::new(text);
+        let got: Vec<_> = [0, 1, 3, 5, 6, 7, 9]
+            .into_iter()
+            .map(|offset| positions.at(offset))
+            .collect();
+        assert_eq!(
+            got,
+            [(0, 0), (0, 1), (1, 0), (1, 2), (2, 0), (3, 0), (3, 2)]
+        );
+    }
+
+    #[test]
+    fn test_literal_preview() {
+        let dir = repo_with_changes();
+        let previews = preview_replace(
+            dir.path(),
+            &DiffSpec::uncommitted(),
+            "old_name",
+            "new_$name",
+            false,
+        )
+        .unwrap();
+
+        // untouched.rs isn't part of the diff
+        let paths: Vec<_> = previews.iter().map(|p| p.path.as_str()).collect();
+        assert_eq!(paths, ["a.rs", "b.rs"]);
+
+        assert_eq!(previews[0].matches.len(), 2);
+        assert_eq!(previews[0].matches[1].line, 1);
+        assert_eq!(previews[0].matches[1].start_column, 14);
+        assert_eq!(previews[0].matches[1].end_column, 22);
+        assert_eq!(
+            previews[0].new_text,
+            "fn new_$name() {}\nfn caller() { new_$name(); }\n"
+        );
+        assert_eq!(previews[1].new_text, "use crate::new_$name;\n");
+
+        // Nothing is written
+        let on_disk = std::fs::read_to_string(dir.path().join("b.rs")).unwrap();
+        assert_eq!(on_disk, "use crate::old_name;\n");
+    }
+
+    #[test]
+    fn test_regex_preview() {
+        let dir = repo_with_changes();
+        let previews = preview_replace(
+            dir.path(),
+            &DiffSpec::uncommitted(),
+            r"fn (\w+)\(\)",
+            "fn ${1}_v2()",
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(previews.len(), 1);
+        assert_eq!(
+            previews[0].new_text,
+            "fn old_name_v2() {}\nfn caller_v2() { old_name(); }\n"
+        );
+
+        let err = preview_replace(dir.path(), &DiffSpec::uncommitted(), "(", "", true);
+        assert!(err.unwrap_err().0.starts_with("Invalid regex"));
+    }
+
+    #[test]
+    fn test_preview_keeps_line_endings() {
+        let pattern = build_pattern("old", false).unwrap();
+        let preview = preview_text("a.rs", "a\r\nold\r\n", &pattern, "new", false).unwrap();
+        assert_eq!(preview.new_text, "a\r\nnew\r\n");
+        assert_eq!(
+            preview.matches,
+            [ReplaceMatch {
+                line: 1,
+                start_column: 0,
+                end_line: 1,
+                end_column: 3,
+                text: "old".to_string(),
+            }]
+        );
+
+        // Without a trailing newline nothing is added
+        let preview = preview_text("a.rs", "old", &pattern, "new", false).unwrap();
+        assert_eq!(preview.new_text, "new");
+    }
+
+    #[test]
+    fn test_empty_matches_agree_with_new_text() {
+        let pattern = build_pattern("(?m)^", true).unwrap();
+        let preview = preview_text("a.rs", "x\ny", &pattern, "// ", true).unwrap();
+        assert_eq!(preview.new_text, "// x\n// y");
+        let starts: Vec<_> = preview
+            .matches
+            .iter()
+            .map(|m| (m.line, m.start_column, m.text.as_str()))
+            .collect();
+        assert_eq!(starts, [(0, 0, ""), (1, 0, "")]);
+    }
+}
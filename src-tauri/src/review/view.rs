@@ -0,0 +1,474 @@
+//! Assembled review view: changed files, their diffs, the stored review and
+//! progress, fetched in one round trip.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use super::{Result, Review, ReviewError};
+use crate::git::{self, DiffSpec, FileDiff, FileDiffOptions, FileDiffSummary};
+
+/// Review progress over the files in a diff.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewProgress {
+    pub total_files: usize,
+    /// Reviewed files that are still part of the diff
+    pub reviewed_files: usize,
+    pub comment_count: usize,
+    pub edit_count: usize,
+    /// Sum of view-to-reviewed time over reviewed files still in the diff
+    pub total_review_ms: i64,
+}
+
+impl ReviewProgress {
+    /// Compute progress for a review against the current file list.
+    /// Reviewed paths no longer in the diff don't count.
+    pub fn compute(files: &[FileDiffSummary], review: &Review) -> Self {
+        Self::compute_under(files, review, None)
+    }
+
+    /// Like [`ReviewProgress::compute`], counting only comments and edits
+    /// under `prefix` (a normalized repo-relative directory) when given.
+    pub fn compute_under(files: &[FileDiffSummary], review: &Review, prefix: Option<&str>) -> Self {
+        let in_scope = |path: &str| prefix.map_or(true, |prefix| is_under(path, prefix));
+        let reviewed_files = files.iter().filter(|f| is_reviewed(review, f)).count();
+        let total_review_ms = files.iter().filter_map(|f| review_ms(review, f)).sum();
+        Self {
+            total_files: files.len(),
+            reviewed_files,
+            comment_count: review.comments.iter().filter(|c| in_scope(&c.path)).count(),
+            edit_count: review.edits.iter().filter(|e| in_scope(&e.path)).count(),
+            total_review_ms,
+        }
+    }
+}
+
+/// A changed file in the review view.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewFile {
+    pub summary: FileDiffSummary,
+    /// Full diff content; omitted in summaries-only mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<FileDiff>,
+    pub reviewed: bool,
+    /// Time from first view to marked reviewed, once both are known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub review_ms: Option<i64>,
+    /// Every change is whitespace, line endings or blank lines, so the file
+    /// can be bulk-marked as reviewed; always false in summaries-only mode
+    pub formatting_only: bool,
+}
+
+/// Everything the frontend needs to render a review.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewView {
+    pub files: Vec<ReviewFile>,
+    pub review: Review,
+    pub progress: ReviewProgress,
+}
+
+fn is_reviewed(review: &Review, file: &FileDiffSummary) -> bool {
+    let path = file.path().to_string_lossy();
+    review.reviewed.iter().any(|p| *p == path)
+}
+
+fn review_ms(review: &Review, file: &FileDiffSummary) -> Option<i64> {
+    let path = file.path().to_string_lossy();
+    review.file_time(&path)?.duration_ms()
+}
+
+/// Options for [`build_review_view_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct ReviewViewOptions {
+    /// Skip per-file diffs so the initial render only pays for the file list
+    pub summaries_only: bool,
+    /// Only include files under this directory, given repo-relative or as an
+    /// absolute path inside the repo
+    pub path_prefix: Option<String>,
+}
+
+/// Build the review view for a diff.
+///
+/// With `summaries_only`, per-file diffs are skipped so the initial render
+/// only pays for the file list.
+pub fn build_review_view(
+    repo: &Path,
+    spec: &DiffSpec,
+    review: Review,
+    summaries_only: bool,
+) -> Result<ReviewView> {
+    let options = ReviewViewOptions {
+        summaries_only,
+        ..ReviewViewOptions::default()
+    };
+    build_review_view_with_options(repo, spec, review, &options)
+}
+
+/// Like [`build_review_view`], optionally scoped to a directory subtree.
+///
+/// With a `path_prefix`, only files under it (either side of a rename) are
+/// listed and progress counts only those files and their comments and edits.
+/// The returned `review` is always the full record.
+pub fn build_review_view_with_options(
+    repo: &Path,
+    spec: &DiffSpec,
+    review: Review,
+    options: &ReviewViewOptions,
+) -> Result<ReviewView> {
+    let prefix = match &options.path_prefix {
+        Some(prefix) => {
+            git::normalize_repo_dir(repo, prefix).map_err(|e| ReviewError::new(e.to_string()))?
+        }
+        None => None,
+    };
+
+    let mut summaries =
+        git::list_diff_files(repo, spec).map_err(|e| ReviewError::new(e.to_string()))?;
+    if let Some(prefix) = &prefix {
+        summaries.retain(|f| {
+            [&f.before, &f.after]
+                .into_iter()
+                .flatten()
+                .any(|path| path.starts_with(prefix))
+        });
+    }
+    summaries.sort_by(|a, b| a.path().cmp(b.path()));
+
+    let progress = ReviewProgress::compute_under(&summaries, &review, prefix.as_deref());
+    // Like the per-file diffs, the formatting check is left to the full load
+    let formatting_only = if options.summaries_only {
+        HashSet::new()
+    } else {
+        let paths: Vec<PathBuf> = summaries.iter().map(|f| f.path().to_path_buf()).collect();
+        git::list_formatting_only_files(repo, spec, &paths, FileDiffOptions::default())
+            .map_err(|e| ReviewError::new(e.to_string()))?
+    };
+
+    let files = summaries
+        .into_iter()
+        .map(|summary| {
+            let diff = if options.summaries_only {
+                None
+            } else {
+                Some(
+                    git::get_file_diff(repo, spec, summary.path())
+                        .map_err(|e| ReviewError::new(e.to_string()))?,
+                )
+            };
+            let reviewed = is_reviewed(&review, &summary);
+            let review_ms = review_ms(&review, &summary);
+            let formatting_only = formatting_only.contains(summary.path());
+            Ok(ReviewFile {
+                summary,
+                diff,
+                reviewed,
+                review_ms,
+                formatting_only,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(ReviewView {
+        files,
+        review,
+        progress,
+    })
+}
+
+/// Whether repo-relative `path` is `prefix` or inside it, by whole segments.
+fn is_under(path: &str, prefix: &str) -> bool {
+    Path::new(path).starts_with(prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_support::{git, init_test_repo};
+    use crate::git::Span;
+    use crate::git::{DiffId, GitRef};
+    use crate::review::{Comment, ReviewStore};
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_view_matches_individual_calls() {
+        let (dir, _) = init_test_repo();
+        let repo = dir.path();
+        std::fs::write(repo.join("a.txt"), "one\n").unwrap();
+        std::fs::write(repo.join("b.txt"), "two\n").unwrap();
+        git(repo, &["add", "."]);
+        git(repo, &["commit", "-m", "initial"]);
+
+        std::fs::write(repo.join("a.txt"), "one\nmore\n").unwrap();
+        std::fs::write(repo.join("c.txt"), "new\n").unwrap();
+
+        let db = tempdir().unwrap();
+        let store = ReviewStore::open(db.path().join("review.db")).unwrap();
+        let id = DiffId::new("HEAD", crate::git::WORKDIR);
+        store.mark_reviewed(&id, "a.txt").unwrap();
+        store.mark_reviewed(&id, "gone.txt").unwrap();
+        store
+            .add_comment(&id, &Comment::new("a.txt", Span::new(1, 2), "why?"))
+            .unwrap();
+        let review = store.get(&id).unwrap();
+
+        let spec = DiffSpec::custom(GitRef::Rev("HEAD".to_string()), GitRef::WorkingTree);
+        let view = build_review_view(repo, &spec, review.clone(), false).unwrap();
+
+        let mut expected = git::list_diff_files(repo, &spec).unwrap();
+        expected.sort_by(|a, b| a.path().cmp(b.path()));
+        let paths: Vec<_> = view.files.iter().map(|f| f.summary.clone()).collect();
+        assert_eq!(paths, expected);
+
+        for file in &view.files {
+            let diff = git::get_file_diff(repo, &spec, file.summary.path()).unwrap();
+            assert_eq!(file.diff.as_ref(), Some(&diff));
+        }
+
+        assert!(view.files[0].reviewed); // a.txt
+        assert!(!view.files[1].reviewed); // c.txt
+        assert_eq!(view.review.reviewed, review.reviewed);
+        assert_eq!(
+            view.progress,
+            ReviewProgress {
+                total_files: 2,
+                reviewed_files: 1,
+                comment_count: 1,
+                edit_count: 0,
+                total_review_ms: 0,
+            }
+        );
+
+        let summaries = build_review_view(repo, &spec, review, true).unwrap();
+        assert_eq!(summaries.files.len(), 2);
+        assert!(summaries.files.iter().all(|f| f.diff.is_none()));
+        assert_eq!(summaries.progress, view.progress);
+    }
+
+    #[test]
+    fn test_view_scoped_to_subtree() {
+        let (dir, _) = init_test_repo();
+        let repo = dir.path();
+        let files = [
+            "src/api/users.rs",
+            "src/api/v2/posts.rs",
+            "src/apiary.rs",
+            "docs/api.md",
+        ];
+        for path in files {
+            std::fs::create_dir_all(repo.join(path).parent().unwrap()).unwrap();
+            std::fs::write(repo.join(path), "one\n").unwrap();
+        }
+        git(repo, &["add", "."]);
+        git(repo, &["commit", "-m", "initial"]);
+        for path in files {
+            std::fs::write(repo.join(path), "one\ntwo\n").unwrap();
+        }
+
+        let db = tempdir().unwrap();
+        let store = ReviewStore::open(db.path().join("review.db")).unwrap();
+        let id = DiffId::new("HEAD", crate::git::WORKDIR);
+        store.mark_reviewed(&id, "src/api/users.rs").unwrap();
+        store.mark_reviewed(&id, "docs/api.md").unwrap();
+        store
+            .add_comment(
+                &id,
+                &Comment::new("src/api/v2/posts.rs", Span::new(1, 2), "ok"),
+            )
+            .unwrap();
+        store
+            .add_comment(&id, &Comment::new("docs/api.md", Span::new(1, 2), "typo"))
+            .unwrap();
+        let review = store.get(&id).unwrap();
+
+        let spec = DiffSpec::custom(GitRef::Rev("HEAD".to_string()), GitRef::WorkingTree);
+        let view_under = |prefix: &str| {
+            let options = ReviewViewOptions {
+                summaries_only: true,
+                path_prefix: Some(prefix.to_string()),
+            };
+            build_review_view_with_options(repo, &spec, review.clone(), &options).unwrap()
+        };
+
+        let view = view_under("./src/api/");
+        let paths: Vec<_> = view
+            .files
+            .iter()
+            .map(|f| f.summary.path().clone())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("src/api/users.rs"),
+                PathBuf::from("src/api/v2/posts.rs")
+            ]
+        );
+        assert_eq!(
+            view.progress,
+            ReviewProgress {
+                total_files: 2,
+                reviewed_files: 1,
+                comment_count: 1,
+                edit_count: 0,
+                total_review_ms: 0,
+            }
+        );
+        // The review record itself isn't scoped
+        assert_eq!(view.review.reviewed, review.reviewed);
+        assert_eq!(view.review.comments.len(), 2);
+
+        // Absolute and repo-root prefixes normalize the same way
+        let absolute = view_under(repo.join("src/api").to_str().unwrap());
+        assert_eq!(absolute.progress, view.progress);
+        assert_eq!(view_under(".").progress.total_files, 4);
+        assert!(view_under("src/none").files.is_empty());
+        let options = ReviewViewOptions {
+            summaries_only: true,
+            path_prefix: Some("../elsewhere".to_string()),
+        };
+        assert!(build_review_view_with_options(repo, &spec, review, &options).is_err());
+    }
+
+    #[test]
+    fn test_formatting_only_files() {
+        let (dir, _) = init_test_repo();
+        let repo = dir.path();
+        git(repo, &["config", "core.autocrlf", "false"]);
+        let code = "fn main() {\n  if ok {\n    run();\n  }\n}\n";
+        std::fs::write(repo.join("reindented.rs"), code).unwrap();
+        std::fs::write(repo.join("crlf.rs"), code).unwrap();
+        std::fs::write(repo.join("changed.rs"), code).unwrap();
+        git(repo, &["add", "."]);
+        git(repo, &["commit", "-m", "initial"]);
+
+        std::fs::write(
+            repo.join("reindented.rs"),
+            "fn main() {\n\n    if ok {\n        run();  \n    }\n}\n",
+        )
+        .unwrap();
+        std::fs::write(repo.join("crlf.rs"), code.replace('\n', "\r\n")).unwrap();
+        std::fs::write(
+            repo.join("changed.rs"),
+            "fn main() {\n    if !ok {\n        run();\n    }\n}\n",
+        )
+        .unwrap();
+        std::fs::write(repo.join("added.rs"), "\n").unwrap();
+
+        let spec = DiffSpec::custom(GitRef::Rev("HEAD".to_string()), GitRef::WorkingTree);
+        let review = Review::new(DiffId::new("HEAD", crate::git::WORKDIR));
+        // Skipped for the summaries-only first render
+        let view = build_review_view(repo, &spec, review.clone(), true).unwrap();
+        assert!(view.files.iter().all(|f| !f.formatting_only));
+
+        let view = build_review_view(repo, &spec, review, false).unwrap();
+        let flags: Vec<_> = view
+            .files
+            .iter()
+            .map(|f| {
+                (
+                    f.summary.path().to_string_lossy().to_string(),
+                    f.formatting_only,
+                )
+            })
+            .collect();
+        assert_eq!(
+            flags,
+            vec![
+                ("added.rs".to_string(), false),
+                ("changed.rs".to_string(), false),
+                ("crlf.rs".to_string(), true),
+                ("reindented.rs".to_string(), true),
+            ]
+        );
+
+        // Committed reformatting is detected the same way
+        git(repo, &["add", "."]);
+        git(repo, &["commit", "-m", "reformat"]);
+        let spec = git::commit_spec(repo, "HEAD", None).unwrap();
+        let paths: Vec<PathBuf> = ["changed.rs", "crlf.rs", "reindented.rs"]
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+        let formatting =
+            git::list_formatting_only_files(repo, &spec, &paths, FileDiffOptions::default())
+                .unwrap();
+        assert_eq!(
+            formatting,
+            ["crlf.rs", "reindented.rs"]
+                .into_iter()
+                .map(PathBuf::from)
+                .collect()
+        );
+
+        // Only the given paths are checked
+        let formatting =
+            git::list_formatting_only_files(repo, &spec, &paths[..2], FileDiffOptions::default())
+                .unwrap();
+        assert_eq!(formatting, [PathBuf::from("crlf.rs")].into_iter().collect());
+
+        // Files over the size limit never qualify
+        let options = FileDiffOptions {
+            max_file_bytes: 16,
+            ..Default::default()
+        };
+        let formatting = git::list_formatting_only_files(repo, &spec, &paths, options).unwrap();
+        assert!(formatting.is_empty());
+    }
+
+    #[test]
+    fn test_commit_review_view() {
+        let (dir, _) = init_test_repo();
+        let repo = dir.path();
+        git(repo, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+        std::fs::write(repo.join("a.txt"), "one\n").unwrap();
+        git(repo, &["add", "."]);
+        git(repo, &["commit", "-m", "root"]);
+        std::fs::write(repo.join("a.txt"), "one\ntwo\n").unwrap();
+        std::fs::write(repo.join("b.txt"), "new\n").unwrap();
+        git(repo, &["add", "."]);
+        git(repo, &["commit", "-m", "second"]);
+        // Later changes shouldn't leak into the commit's review
+        std::fs::write(repo.join("c.txt"), "later\n").unwrap();
+        git(repo, &["add", "."]);
+        git(repo, &["commit", "-m", "third"]);
+
+        let paths = |view: &ReviewView| -> Vec<String> {
+            view.files
+                .iter()
+                .map(|f| f.summary.path().to_string_lossy().to_string())
+                .collect()
+        };
+        let view_of = |spec: &DiffSpec| {
+            let review = Review::new(DiffId::new("x", "y"));
+            build_review_view(repo, spec, review, true).unwrap()
+        };
+
+        let spec = git::commit_spec(repo, "HEAD~1", None).unwrap();
+        assert_eq!(paths(&view_of(&spec)), vec!["a.txt", "b.txt"]);
+        assert!(view_of(&spec).files[1].summary.is_added());
+
+        // The root commit diffs against the empty tree
+        let spec = git::commit_spec(repo, "HEAD~2", None).unwrap();
+        assert_eq!(paths(&view_of(&spec)), vec!["a.txt"]);
+        assert_eq!(git::commit_spec(repo, "HEAD~2", Some(1)).unwrap(), spec);
+        assert!(git::commit_spec(repo, "HEAD~2", Some(2)).is_err());
+
+        // Merge commits can be reviewed against either parent
+        git(repo, &["checkout", "-b", "side", "HEAD~1"]);
+        std::fs::write(repo.join("d.txt"), "side\n").unwrap();
+        git(repo, &["add", "."]);
+        git(repo, &["commit", "-m", "side"]);
+        git(repo, &["checkout", "main"]);
+        git(repo, &["merge", "--no-ff", "-m", "merge", "side"]);
+
+        let first = git::commit_spec(repo, "HEAD", None).unwrap();
+        assert_eq!(paths(&view_of(&first)), vec!["d.txt"]);
+        let second = git::commit_spec(repo, "HEAD", Some(2)).unwrap();
+        assert_eq!(paths(&view_of(&second)), vec!["c.txt"]);
+        assert!(git::commit_spec(repo, "HEAD", Some(3)).is_err());
+    }
+}
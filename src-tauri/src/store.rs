@@ -6,9 +6,12 @@
 
 use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Mutex;
 
+use crate::util::{fenced_block, lock};
+
 // =============================================================================
 // Session Types
 // =============================================================================
@@ -23,6 +26,32 @@ pub struct Session {
     pub title: Option<String>,
     pub created_at: i64,
     pub updated_at: i64,
+    /// The session this one was forked from (see `Store::fork_session`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forked_from: Option<String>,
+    /// The agent's own ID for this conversation, used to resume it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acp_session_id: Option<String>,
+}
+
+/// Columns read by [`Session::from_row`], in order.
+const SESSION_COLUMNS: &str =
+    "id, working_dir, agent_id, title, created_at, updated_at, forked_from, acp_session_id";
+
+impl Session {
+    /// Create a Session from a row selected with [`SESSION_COLUMNS`].
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            working_dir: row.get(1)?,
+            agent_id: row.get(2)?,
+            title: row.get(3)?,
+            created_at: row.get(4)?,
+            updated_at: row.get(5)?,
+            forked_from: row.get(6)?,
+            acp_session_id: row.get(7)?,
+        })
+    }
 }
 
 /// A message in a session
@@ -36,10 +65,17 @@ pub struct Message {
     /// For assistant messages: JSON array of ContentSegment
     pub content: String,
     pub created_at: i64,
+    /// Assistant content with code blocks and file references split out of
+    /// its text (see `parse_segments`). Empty for user messages.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub segments: Vec<ContentSegment>,
 }
 
-/// A segment of assistant content (text or tool call), stored in order
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A segment of assistant content (text or tool call), stored in order.
+///
+/// `Code` and `FileRef` never come from the agent; `parse_segments` derives
+/// them from text for `Message::segments`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "camelCase")]
 pub enum ContentSegment {
     Text {
@@ -48,10 +84,56 @@ pub enum ContentSegment {
     ToolCall {
         id: String,
         title: String,
-        status: String,
+        status: ToolCallStatus,
         #[serde(default, skip_serializing_if = "Vec::is_empty")]
         locations: Vec<String>,
     },
+    /// A fenced code block, without its fences
+    Code {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        language: Option<String>,
+        code: String,
+    },
+    /// A repo path mentioned in text, optionally with a line (`src/lib.rs:42`)
+    FileRef {
+        path: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        line: Option<u32>,
+    },
+}
+
+/// Status of a tool call within an assistant turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum ToolCallStatus {
+    #[default]
+    Pending,
+    /// Older rows stored the lowercased Debug name ("inprogress")
+    #[serde(alias = "inprogress", alias = "in_progress")]
+    InProgress,
+    Completed,
+    Failed,
+}
+
+impl ToolCallStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ToolCallStatus::Pending => "pending",
+            ToolCallStatus::InProgress => "inProgress",
+            ToolCallStatus::Completed => "completed",
+            ToolCallStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "pending" => Some(ToolCallStatus::Pending),
+            "inProgress" | "inprogress" | "in_progress" => Some(ToolCallStatus::InProgress),
+            "completed" => Some(ToolCallStatus::Completed),
+            "failed" => Some(ToolCallStatus::Failed),
+            _ => None,
+        }
+    }
 }
 
 /// Message role
@@ -86,6 +168,169 @@ pub struct SessionFull {
     pub messages: Vec<Message>,
 }
 
+impl SessionFull {
+    /// Render the conversation as a markdown document.
+    /// Assistant turns keep their text and list tool calls by title.
+    pub fn to_markdown(&self) -> String {
+        let title = self.session.title.as_deref().unwrap_or("Chat session");
+        let mut out = format!("# {title}\n");
+
+        for message in &self.messages {
+            match message.role {
+                MessageRole::User => {
+                    out.push_str("\n## User\n\n");
+                    out.push_str(message.content.trim());
+                    out.push('\n');
+                }
+                MessageRole::Assistant => {
+                    out.push_str("\n## Assistant\n\n");
+                    let segments: Vec<ContentSegment> = serde_json::from_str(&message.content)
+                        .unwrap_or_else(|_| {
+                            vec![ContentSegment::Text {
+                                text: message.content.clone(),
+                            }]
+                        });
+                    for segment in segments {
+                        match segment {
+                            ContentSegment::Text { text } => {
+                                out.push_str(text.trim());
+                                out.push_str("\n\n");
+                            }
+                            ContentSegment::ToolCall { title, status, .. } => {
+                                out.push_str(&format!("- _{title}_ ({})\n\n", status.as_str()));
+                            }
+                            ContentSegment::Code { language, code } => {
+                                out.push_str(&fenced_block(
+                                    language.as_deref().unwrap_or(""),
+                                    &code,
+                                ));
+                                out.push('\n');
+                            }
+                            ContentSegment::FileRef { path, line } => match line {
+                                Some(line) => out.push_str(&format!("`{path}:{line}`\n\n")),
+                                None => out.push_str(&format!("`{path}`\n\n")),
+                            },
+                        }
+                    }
+                    out.truncate(out.trim_end().len());
+                    out.push('\n');
+                }
+            }
+        }
+
+        out
+    }
+}
+
+/// Split the text segments of an assistant turn into prose, fenced code
+/// blocks and file references, so the frontend can render them without
+/// re-parsing markdown. Other segments pass through unchanged.
+pub fn parse_segments(segments: &[ContentSegment]) -> Vec<ContentSegment> {
+    let mut out = Vec::new();
+    for segment in segments {
+        match segment {
+            ContentSegment::Text { text } => parse_text(text, &mut out),
+            other => out.push(other.clone()),
+        }
+    }
+    out
+}
+
+fn parse_text(text: &str, out: &mut Vec<ContentSegment>) {
+    let mut prose = String::new();
+    let mut lines = text.split_inclusive('\n');
+    while let Some(line) = lines.next() {
+        let Some((fence, info)) = opening_fence(line) else {
+            prose.push_str(line);
+            continue;
+        };
+        push_prose(&std::mem::take(&mut prose), out);
+        // An unterminated fence runs to the end of the text, as in CommonMark
+        let mut code = String::new();
+        for line in lines.by_ref() {
+            if is_closing_fence(line, fence) {
+                break;
+            }
+            code.push_str(line);
+        }
+        out.push(ContentSegment::Code {
+            language: info.split_whitespace().next().map(str::to_string),
+            code,
+        });
+    }
+    push_prose(&prose, out);
+}
+
+/// A line opening a fenced code block: up to three spaces of indent, then at
+/// least three backticks or tildes. Returns the fence and its info string.
+fn opening_fence(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let marker = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = trimmed.len() - trimmed.trim_start_matches(marker).len();
+    if len < 3 {
+        return None;
+    }
+    let (fence, info) = trimmed.split_at(len);
+    // Backticks in a backtick fence's info string make it inline code instead
+    if marker == '`' && info.contains('`') {
+        return None;
+    }
+    Some((fence, info.trim()))
+}
+
+fn is_closing_fence(line: &str, fence: &str) -> bool {
+    let trimmed = line.trim_start_matches(' ');
+    let marker = fence.as_bytes()[0] as char;
+    let rest = trimmed.trim_start_matches(marker);
+    line.len() - trimmed.len() <= 3
+        && trimmed.len() - rest.len() >= fence.len()
+        && rest.trim().is_empty()
+}
+
+fn file_ref_pattern() -> &'static regex::Regex {
+    static PATTERN: OnceLock<regex::Regex> = OnceLock::new();
+    // A relative path with at least one directory and an extension, after
+    // whitespace or an opening bracket, optionally in backticks
+    PATTERN.get_or_init(|| {
+        regex::Regex::new(r"(?:^|[\s(\[])(`?)((?:[\w.-]+/)+[\w.-]+\.[A-Za-z]\w*)(?::(\d+))?(`?)")
+            .unwrap()
+    })
+}
+
+/// Push prose as text, with file references split out.
+fn push_prose(prose: &str, out: &mut Vec<ContentSegment>) {
+    let mut last = 0;
+    for caps in file_ref_pattern().captures_iter(prose) {
+        let path = caps.get(2).unwrap();
+        let end = caps.get(3).map_or(path.end(), |m| m.end());
+        // Swallow the backticks only when they wrap the reference exactly
+        let (start, end) = match (caps.get(1), caps.get(4)) {
+            (Some(open), Some(close)) if !open.is_empty() && !close.is_empty() => {
+                (open.start(), close.end())
+            }
+            _ => (path.start(), end),
+        };
+        if start > last {
+            out.push(ContentSegment::Text {
+                text: prose[last..start].to_string(),
+            });
+        }
+        out.push(ContentSegment::FileRef {
+            path: path.as_str().to_string(),
+            line: caps.get(3).and_then(|m| m.as_str().parse().ok()),
+        });
+        last = end;
+    }
+    if last < prose.len() {
+        out.push(ContentSegment::Text {
+            text: prose[last..].to_string(),
+        });
+    }
+}
+
 // =============================================================================
 // Project Types
 // =============================================================================
@@ -297,6 +542,10 @@ pub struct ProjectAction {
     pub action_type: ActionType,
     pub sort_order: i32,
     pub auto_commit: bool,
+    /// Default environment variables set when the action runs.
+    /// Values may be secrets, so they must never be logged.
+    #[serde(default)]
+    pub env: HashMap<String, String>,
     pub created_at: i64,
     pub updated_at: i64,
 }
@@ -318,6 +567,7 @@ impl ProjectAction {
             action_type,
             sort_order,
             auto_commit: false,
+            env: HashMap::new(),
             created_at: now,
             updated_at: now,
         }
@@ -328,6 +578,11 @@ impl ProjectAction {
         self
     }
 
+    pub fn with_env(mut self, env: HashMap<String, String>) -> Self {
+        self.env = env;
+        self
+    }
+
     /// Create a ProjectAction from a database row.
     fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
         let action_type_str: String = row.get(4)?;
@@ -338,6 +593,7 @@ impl ProjectAction {
                 rusqlite::types::Type::Text,
             )
         })?;
+        let env_json: String = row.get(9)?;
 
         Ok(Self {
             id: row.get(0)?,
@@ -347,6 +603,7 @@ impl ProjectAction {
             action_type,
             sort_order: row.get(5)?,
             auto_commit: row.get::<_, i32>(6)? != 0,
+            env: serde_json::from_str(&env_json).unwrap_or_default(),
             created_at: row.get(7)?,
             updated_at: row.get(8)?,
         })
@@ -375,8 +632,20 @@ pub struct Artifact {
     /// Session ID for viewing the generation conversation
     #[serde(skip_serializing_if = "Option::is_none")]
     pub session_id: Option<String>,
+    /// Who created the artifact ([`ARTIFACT_AUTHOR_USER`], [`ARTIFACT_AUTHOR_AGENT`]).
+    /// `None` for artifacts saved before authorship was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
 }
 
+/// Columns read by [`Artifact::from_row`], in order.
+const ARTIFACT_COLUMNS: &str = "id, project_id, title, data_json, created_at, updated_at, parent_artifact_id, status, error_message, session_id, created_by";
+
+/// `created_by` for artifacts the user saved or created directly.
+pub const ARTIFACT_AUTHOR_USER: &str = "user";
+/// `created_by` for artifacts written by an AI agent.
+pub const ARTIFACT_AUTHOR_AGENT: &str = "agent";
+
 impl Artifact {
     /// Create a new markdown artifact.
     pub fn new_markdown(
@@ -398,6 +667,7 @@ impl Artifact {
             status: ArtifactStatus::Complete,
             error_message: None,
             session_id: None,
+            created_by: None,
         }
     }
 
@@ -417,9 +687,38 @@ impl Artifact {
             status: ArtifactStatus::Generating,
             error_message: None,
             session_id: None,
+            created_by: None,
         }
     }
 
+    /// Record who created the artifact.
+    pub fn with_created_by(mut self, created_by: impl Into<String>) -> Self {
+        self.created_by = Some(created_by.into());
+        self
+    }
+
+    /// Create an Artifact from a row selected with [`ARTIFACT_COLUMNS`].
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        let data_json: String = row.get(3)?;
+        let data: ArtifactData = serde_json::from_str(&data_json).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, Box::new(e))
+        })?;
+        let status_str: String = row.get(7)?;
+        Ok(Self {
+            id: row.get(0)?,
+            project_id: row.get(1)?,
+            title: row.get(2)?,
+            created_at: row.get(4)?,
+            updated_at: row.get(5)?,
+            parent_artifact_id: row.get(6)?,
+            data,
+            status: ArtifactStatus::parse(&status_str),
+            error_message: row.get(8)?,
+            session_id: row.get(9)?,
+            created_by: row.get(10)?,
+        })
+    }
+
     /// Get the artifact type.
     pub fn artifact_type(&self) -> ArtifactType {
         self.data.artifact_type()
@@ -703,6 +1002,23 @@ impl BranchNote {
     }
 }
 
+// =============================================================================
+// Layout Types
+// =============================================================================
+
+/// Per-repo UI layout, saved so switching repos restores where the reviewer
+/// left off. Missing fields fall back to defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LayoutState {
+    /// Path of the selected file
+    pub selected_file: Option<String>,
+    /// Scroll offset of the diff view, in pixels
+    pub scroll_position: f64,
+    /// Pane size ratios keyed by pane name (e.g. "sidebar")
+    pub pane_ratios: HashMap<String, f64>,
+}
+
 // =============================================================================
 // Error type
 // =============================================================================
@@ -742,6 +1058,9 @@ pub type Result<T> = std::result::Result<T, StoreError>;
 // Store
 // =============================================================================
 
+/// Data migrations applied so far, stored as `PRAGMA user_version`.
+const SCHEMA_VERSION: i64 = 1;
+
 /// Unified SQLite-backed storage
 pub struct Store {
     conn: Mutex<Connection>,
@@ -770,7 +1089,7 @@ impl Store {
 
     /// Initialize the database schema
     fn init_schema(&self) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
 
         conn.execute_batch(
             r#"
@@ -904,11 +1223,22 @@ impl Store {
                 sort_order INTEGER NOT NULL,
                 auto_commit INTEGER NOT NULL DEFAULT 0,
                 created_at INTEGER NOT NULL,
-                updated_at INTEGER NOT NULL
+                updated_at INTEGER NOT NULL,
+                env_json TEXT NOT NULL DEFAULT '{}'
             );
 
             CREATE INDEX IF NOT EXISTS idx_project_actions_project ON project_actions(project_id);
             CREATE INDEX IF NOT EXISTS idx_project_actions_type ON project_actions(project_id, action_type);
+
+            -- =================================================================
+            -- UI layout per repo
+            -- =================================================================
+
+            CREATE TABLE IF NOT EXISTS repo_layouts (
+                repo_id TEXT PRIMARY KEY,
+                layout_json TEXT NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
             "#,
         )?;
 
@@ -950,6 +1280,19 @@ impl Store {
             conn.execute("ALTER TABLE artifacts ADD COLUMN session_id TEXT", [])?;
         }
 
+        // Check if created_by column exists on artifacts, add if not
+        let has_created_by: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('artifacts') WHERE name = 'created_by'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap_or(false);
+
+        if !has_created_by {
+            conn.execute("ALTER TABLE artifacts ADD COLUMN created_by TEXT", [])?;
+        }
+
         // Check if ai_session_id column exists on branch_sessions, add if not
         let has_ai_session_id: bool = conn
             .query_row(
@@ -1080,19 +1423,106 @@ impl Store {
             }
         }
 
+        // Check if env_json column exists on project_actions, add if not
+        let has_env_json: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('project_actions') WHERE name = 'env_json'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap_or(false);
+
+        if !has_env_json {
+            conn.execute(
+                "ALTER TABLE project_actions ADD COLUMN env_json TEXT NOT NULL DEFAULT '{}'",
+                [],
+            )?;
+        }
+
+        // Parsed assistant segments (code blocks, file references), stored
+        // alongside the raw content. NULL for rows written before this.
+        let has_segments: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('messages') WHERE name = 'segments'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap_or(false);
+
+        if !has_segments {
+            conn.execute("ALTER TABLE messages ADD COLUMN segments TEXT", [])?;
+        }
+
+        // Check if forked_from column exists on sessions, add if not
+        let has_forked_from: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('sessions') WHERE name = 'forked_from'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap_or(false);
+
+        if !has_forked_from {
+            conn.execute(
+                "ALTER TABLE sessions ADD COLUMN forked_from TEXT REFERENCES sessions(id) ON DELETE SET NULL",
+                [],
+            )?;
+        }
+
+        // Check if acp_session_id column exists on sessions, add if not
+        let has_acp_session_id: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('sessions') WHERE name = 'acp_session_id'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap_or(false);
+
+        if !has_acp_session_id {
+            conn.execute("ALTER TABLE sessions ADD COLUMN acp_session_id TEXT", [])?;
+        }
+
+        // Data migrations run once each, tracked by `PRAGMA user_version`
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        if version < 1 {
+            // Normalize legacy tool-call statuses stored as the lowercased Debug name
+            conn.execute(
+                r#"UPDATE messages
+                   SET content = REPLACE(content, '"status":"inprogress"', '"status":"inProgress"')
+                   WHERE role = 'assistant' AND content LIKE '%"status":"inprogress"%'"#,
+                [],
+            )?;
+        }
+
+        if version < SCHEMA_VERSION {
+            conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        }
+
         Ok(())
     }
 
+    // =========================================================================
+    // Maintenance
+    // =========================================================================
+
+    /// Reclaim space left by deleted rows. Returns bytes freed, or `None` if
+    /// a transaction was in progress and compaction was skipped.
+    pub fn compact(&self) -> Result<Option<u64>> {
+        let conn = lock(&self.conn);
+        Ok(crate::util::sqlite::compact(&conn)?)
+    }
+
     // =========================================================================
     // Session operations
     // =========================================================================
 
     /// Create a new session
     pub fn create_session(&self, session: &Session) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute(
-            "INSERT INTO sessions (id, working_dir, agent_id, title, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            "INSERT INTO sessions (id, working_dir, agent_id, title, created_at, updated_at, forked_from, acp_session_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
             params![
                 &session.id,
                 &session.working_dir,
@@ -1100,28 +1530,37 @@ impl Store {
                 &session.title,
                 session.created_at,
                 session.updated_at,
+                &session.forked_from,
+                &session.acp_session_id,
             ],
         )?;
         Ok(())
     }
 
+    /// Generate a session ID not used by any existing session.
+    pub fn new_session_id(&self) -> Result<String> {
+        let conn = lock(&self.conn);
+        loop {
+            let id = generate_session_id();
+            let taken = conn
+                .query_row("SELECT 1 FROM sessions WHERE id = ?1", params![&id], |_| {
+                    Ok(())
+                })
+                .optional()?
+                .is_some();
+            if !taken {
+                return Ok(id);
+            }
+        }
+    }
+
     /// Get a session by ID
     pub fn get_session(&self, id: &str) -> Result<Option<Session>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.query_row(
-            "SELECT id, working_dir, agent_id, title, created_at, updated_at
-             FROM sessions WHERE id = ?1",
+            &format!("SELECT {SESSION_COLUMNS} FROM sessions WHERE id = ?1"),
             params![id],
-            |row| {
-                Ok(Session {
-                    id: row.get(0)?,
-                    working_dir: row.get(1)?,
-                    agent_id: row.get(2)?,
-                    title: row.get(3)?,
-                    created_at: row.get(4)?,
-                    updated_at: row.get(5)?,
-                })
-            },
+            Session::from_row,
         )
         .optional()
         .map_err(Into::into)
@@ -1129,51 +1568,79 @@ impl Store {
 
     /// List all sessions, ordered by most recently updated
     pub fn list_sessions(&self) -> Result<Vec<Session>> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, working_dir, agent_id, title, created_at, updated_at
-             FROM sessions ORDER BY updated_at DESC",
-        )?;
+        let conn = lock(&self.conn);
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {SESSION_COLUMNS} FROM sessions ORDER BY updated_at DESC"
+        ))?;
         let sessions = stmt
-            .query_map([], |row| {
-                Ok(Session {
-                    id: row.get(0)?,
-                    working_dir: row.get(1)?,
-                    agent_id: row.get(2)?,
-                    title: row.get(3)?,
-                    created_at: row.get(4)?,
-                    updated_at: row.get(5)?,
-                })
-            })?
+            .query_map([], Session::from_row)?
             .collect::<std::result::Result<Vec<_>, _>>()?;
         Ok(sessions)
     }
 
     /// List sessions for a specific working directory
     pub fn list_sessions_for_dir(&self, working_dir: &str) -> Result<Vec<Session>> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, working_dir, agent_id, title, created_at, updated_at
-             FROM sessions WHERE working_dir = ?1 ORDER BY updated_at DESC",
-        )?;
+        let conn = lock(&self.conn);
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {SESSION_COLUMNS} FROM sessions WHERE working_dir = ?1 ORDER BY updated_at DESC"
+        ))?;
         let sessions = stmt
-            .query_map(params![working_dir], |row| {
-                Ok(Session {
-                    id: row.get(0)?,
-                    working_dir: row.get(1)?,
-                    agent_id: row.get(2)?,
-                    title: row.get(3)?,
-                    created_at: row.get(4)?,
-                    updated_at: row.get(5)?,
-                })
-            })?
+            .query_map(params![working_dir], Session::from_row)?
             .collect::<std::result::Result<Vec<_>, _>>()?;
         Ok(sessions)
     }
 
+    /// Fork a session into a new one holding a copy of its messages up to and
+    /// including `up_to_message_id` (tool calls are part of the copied
+    /// assistant turns). The fork keeps the parent's agent, working directory
+    /// and title, and records the parent in `forked_from`. Returns the new
+    /// session's ID.
+    pub fn fork_session(&self, session_id: &str, up_to_message_id: i64) -> Result<String> {
+        let parent = self
+            .get_session(session_id)?
+            .ok_or_else(|| StoreError::new(format!("Session not found: {session_id}")))?;
+        let fork_id = self.new_session_id()?;
+
+        let conn = lock(&self.conn);
+        let in_session: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM messages WHERE id = ?1 AND session_id = ?2",
+            params![up_to_message_id, session_id],
+            |row| row.get(0),
+        )?;
+        if !in_session {
+            return Err(StoreError::new(format!(
+                "Message {up_to_message_id} is not in session {session_id}"
+            )));
+        }
+
+        let now = now_timestamp();
+        let tx = conn.unchecked_transaction()?;
+        tx.execute(
+            "INSERT INTO sessions (id, working_dir, agent_id, title, created_at, updated_at, forked_from)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5, ?6)",
+            params![
+                &fork_id,
+                &parent.working_dir,
+                &parent.agent_id,
+                &parent.title,
+                now,
+                session_id,
+            ],
+        )?;
+        tx.execute(
+            "INSERT INTO messages (session_id, role, content, created_at, segments)
+             SELECT ?1, role, content, created_at, segments FROM messages
+             WHERE session_id = ?2 AND id <= ?3 ORDER BY id ASC",
+            params![&fork_id, session_id, up_to_message_id],
+        )?;
+        tx.commit()?;
+
+        Ok(fork_id)
+    }
+
     /// Update session title
     pub fn update_session_title(&self, id: &str, title: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let now = now_timestamp();
         conn.execute(
             "UPDATE sessions SET title = ?1, updated_at = ?2 WHERE id = ?3",
@@ -1182,9 +1649,23 @@ impl Store {
         Ok(())
     }
 
+    /// Record the agent's session ID for a session, replacing any earlier
+    /// one (e.g. when the old agent session couldn't be loaded)
+    pub fn set_acp_session_id(&self, id: &str, acp_session_id: &str) -> Result<()> {
+        let conn = lock(&self.conn);
+        let updated = conn.execute(
+            "UPDATE sessions SET acp_session_id = ?1 WHERE id = ?2",
+            params![acp_session_id, id],
+        )?;
+        if updated == 0 {
+            return Err(StoreError::new(format!("Session not found: {id}")));
+        }
+        Ok(())
+    }
+
     /// Touch session (update updated_at)
     pub fn touch_session(&self, id: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let now = now_timestamp();
         conn.execute(
             "UPDATE sessions SET updated_at = ?1 WHERE id = ?2",
@@ -1195,7 +1676,7 @@ impl Store {
 
     /// Delete a session and all its messages
     pub fn delete_session(&self, id: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute("DELETE FROM sessions WHERE id = ?1", params![id])?;
         Ok(())
     }
@@ -1206,13 +1687,23 @@ impl Store {
 
     /// Add a message to a session, returns the message ID
     pub fn add_message(&self, session_id: &str, role: MessageRole, content: &str) -> Result<i64> {
-        let conn = self.conn.lock().unwrap();
+        self.insert_message(session_id, role, content, None)
+    }
+
+    fn insert_message(
+        &self,
+        session_id: &str,
+        role: MessageRole,
+        content: &str,
+        segments: Option<&str>,
+    ) -> Result<i64> {
+        let conn = lock(&self.conn);
         let now = now_timestamp();
 
         conn.execute(
-            "INSERT INTO messages (session_id, role, content, created_at)
-             VALUES (?1, ?2, ?3, ?4)",
-            params![session_id, role.as_str(), content, now],
+            "INSERT INTO messages (session_id, role, content, created_at, segments)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![session_id, role.as_str(), content, now, segments],
         )?;
 
         let message_id = conn.last_insert_rowid();
@@ -1228,20 +1719,34 @@ impl Store {
 
     /// Get all messages for a session
     pub fn get_messages(&self, session_id: &str) -> Result<Vec<Message>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let mut stmt = conn.prepare(
-            "SELECT id, session_id, role, content, created_at
+            "SELECT id, session_id, role, content, created_at, segments
              FROM messages WHERE session_id = ?1 ORDER BY id ASC",
         )?;
         let messages = stmt
             .query_map(params![session_id], |row| {
                 let role_str: String = row.get(2)?;
+                let role = MessageRole::parse(&role_str);
+                let content: String = row.get(3)?;
+                let stored: Option<String> = row.get(5)?;
+                let segments = match (role, stored) {
+                    (MessageRole::User, _) => Vec::new(),
+                    (MessageRole::Assistant, Some(json)) => {
+                        serde_json::from_str(&json).unwrap_or_default()
+                    }
+                    // Written before segments were stored
+                    (MessageRole::Assistant, None) => serde_json::from_str::<Vec<_>>(&content)
+                        .map(|raw| parse_segments(&raw))
+                        .unwrap_or_default(),
+                };
                 Ok(Message {
                     id: row.get(0)?,
                     session_id: row.get(1)?,
-                    role: MessageRole::parse(&role_str),
-                    content: row.get(3)?,
+                    role,
+                    content,
                     created_at: row.get(4)?,
+                    segments,
                 })
             })?
             .collect::<std::result::Result<Vec<_>, _>>()?;
@@ -1259,10 +1764,65 @@ impl Store {
         Ok(Some(SessionFull { session, messages }))
     }
 
-    /// Add an assistant turn with ordered segments (text + tool calls interleaved)
+    /// Add an assistant turn with ordered segments (text + tool calls interleaved).
+    /// The parsed form (see `parse_segments`) is stored alongside.
     pub fn add_assistant_turn(&self, session_id: &str, segments: &[ContentSegment]) -> Result<i64> {
         let content = serde_json::to_string(segments)?;
-        self.add_message(session_id, MessageRole::Assistant, &content)
+        let parsed = serde_json::to_string(&parse_segments(segments))?;
+        self.insert_message(session_id, MessageRole::Assistant, &content, Some(&parsed))
+    }
+
+    /// Replace the text of a stored user message, e.g. to fix a typo in a
+    /// prompt before exporting the session. Nothing is resent to the agent.
+    /// Assistant messages are refused; use [`Store::update_assistant_turn`].
+    pub fn update_message_content(&self, id: i64, content: &str) -> Result<()> {
+        self.update_message(id, MessageRole::User, content, None)
+    }
+
+    /// Replace the segments of a stored assistant turn, re-deriving its
+    /// parsed form as [`Store::add_assistant_turn`] does.
+    pub fn update_assistant_turn(&self, id: i64, segments: &[ContentSegment]) -> Result<()> {
+        let content = serde_json::to_string(segments)?;
+        let parsed = serde_json::to_string(&parse_segments(segments))?;
+        self.update_message(id, MessageRole::Assistant, &content, Some(&parsed))
+    }
+
+    /// Overwrite a message of the given role and touch its session.
+    fn update_message(
+        &self,
+        id: i64,
+        role: MessageRole,
+        content: &str,
+        segments: Option<&str>,
+    ) -> Result<()> {
+        let conn = lock(&self.conn);
+        let (session_id, stored_role): (String, String) = conn
+            .query_row(
+                "SELECT session_id, role FROM messages WHERE id = ?1",
+                params![id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?
+            .ok_or_else(|| StoreError::new(format!("Message not found: {id}")))?;
+        if MessageRole::parse(&stored_role) != role {
+            return Err(StoreError::new(format!(
+                "Message {id} is a {stored_role} message, not a {} message",
+                role.as_str()
+            )));
+        }
+
+        let now = now_timestamp();
+        let tx = conn.unchecked_transaction()?;
+        tx.execute(
+            "UPDATE messages SET content = ?1, segments = ?2 WHERE id = ?3",
+            params![content, segments, id],
+        )?;
+        tx.execute(
+            "UPDATE sessions SET updated_at = ?1 WHERE id = ?2",
+            params![now, session_id],
+        )?;
+        tx.commit()?;
+        Ok(())
     }
 
     // =========================================================================
@@ -1271,7 +1831,7 @@ impl Store {
 
     /// Create a new project.
     pub fn create_project(&self, project: &Project) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute(
             "INSERT INTO projects (id, name, created_at, updated_at) VALUES (?1, ?2, ?3, ?4)",
             params![
@@ -1286,7 +1846,7 @@ impl Store {
 
     /// Get a project by ID.
     pub fn get_project(&self, id: &str) -> Result<Option<Project>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.query_row(
             "SELECT id, name, created_at, updated_at FROM projects WHERE id = ?1",
             params![id],
@@ -1305,7 +1865,7 @@ impl Store {
 
     /// List all projects, ordered by most recently updated.
     pub fn list_projects(&self) -> Result<Vec<Project>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let mut stmt = conn.prepare(
             "SELECT id, name, created_at, updated_at FROM projects ORDER BY updated_at DESC",
         )?;
@@ -1324,7 +1884,7 @@ impl Store {
 
     /// Update a project's name.
     pub fn update_project(&self, id: &str, name: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let now = now_timestamp();
         conn.execute(
             "UPDATE projects SET name = ?1, updated_at = ?2 WHERE id = ?3",
@@ -1335,7 +1895,7 @@ impl Store {
 
     /// Delete a project and all its artifacts.
     pub fn delete_project(&self, id: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute("DELETE FROM projects WHERE id = ?1", params![id])?;
         Ok(())
     }
@@ -1346,13 +1906,13 @@ impl Store {
 
     /// Create a new artifact.
     pub fn create_artifact(&self, artifact: &Artifact) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let data_json =
             serde_json::to_string(&artifact.data).map_err(|e| StoreError::new(e.to_string()))?;
 
         conn.execute(
-            "INSERT INTO artifacts (id, project_id, title, artifact_type, data_json, created_at, updated_at, parent_artifact_id, status, error_message, session_id)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            "INSERT INTO artifacts (id, project_id, title, artifact_type, data_json, created_at, updated_at, parent_artifact_id, status, error_message, session_id, created_by)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
             params![
                 &artifact.id,
                 &artifact.project_id,
@@ -1365,6 +1925,7 @@ impl Store {
                 artifact.status.as_str(),
                 &artifact.error_message,
                 &artifact.session_id,
+                &artifact.created_by,
             ],
         )?;
 
@@ -1380,34 +1941,11 @@ impl Store {
 
     /// Get an artifact by ID.
     pub fn get_artifact(&self, id: &str) -> Result<Option<Artifact>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.query_row(
-            "SELECT id, project_id, title, data_json, created_at, updated_at, parent_artifact_id, status, error_message, session_id
-             FROM artifacts WHERE id = ?1",
+            &format!("SELECT {ARTIFACT_COLUMNS} FROM artifacts WHERE id = ?1"),
             params![id],
-            |row| {
-                let data_json: String = row.get(3)?;
-                let data: ArtifactData = serde_json::from_str(&data_json).map_err(|e| {
-                    rusqlite::Error::FromSqlConversionFailure(
-                        3,
-                        rusqlite::types::Type::Text,
-                        Box::new(e),
-                    )
-                })?;
-                let status_str: String = row.get(7)?;
-                Ok(Artifact {
-                    id: row.get(0)?,
-                    project_id: row.get(1)?,
-                    title: row.get(2)?,
-                    created_at: row.get(4)?,
-                    updated_at: row.get(5)?,
-                    parent_artifact_id: row.get(6)?,
-                    data,
-                    status: ArtifactStatus::parse(&status_str),
-                    error_message: row.get(8)?,
-                    session_id: row.get(9)?,
-                })
-            },
+            Artifact::from_row,
         )
         .optional()
         .map_err(Into::into)
@@ -1415,35 +1953,12 @@ impl Store {
 
     /// List artifacts in a project, ordered by most recently updated.
     pub fn list_artifacts(&self, project_id: &str) -> Result<Vec<Artifact>> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, project_id, title, data_json, created_at, updated_at, parent_artifact_id, status, error_message, session_id
-             FROM artifacts WHERE project_id = ?1 ORDER BY updated_at DESC",
-        )?;
+        let conn = lock(&self.conn);
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {ARTIFACT_COLUMNS} FROM artifacts WHERE project_id = ?1 ORDER BY updated_at DESC"
+        ))?;
         let artifacts = stmt
-            .query_map(params![project_id], |row| {
-                let data_json: String = row.get(3)?;
-                let data: ArtifactData = serde_json::from_str(&data_json).map_err(|e| {
-                    rusqlite::Error::FromSqlConversionFailure(
-                        3,
-                        rusqlite::types::Type::Text,
-                        Box::new(e),
-                    )
-                })?;
-                let status_str: String = row.get(7)?;
-                Ok(Artifact {
-                    id: row.get(0)?,
-                    project_id: row.get(1)?,
-                    title: row.get(2)?,
-                    created_at: row.get(4)?,
-                    updated_at: row.get(5)?,
-                    parent_artifact_id: row.get(6)?,
-                    data,
-                    status: ArtifactStatus::parse(&status_str),
-                    error_message: row.get(8)?,
-                    session_id: row.get(9)?,
-                })
-            })?
+            .query_map(params![project_id], Artifact::from_row)?
             .collect::<std::result::Result<Vec<_>, _>>()?;
         Ok(artifacts)
     }
@@ -1455,35 +1970,32 @@ impl Store {
         project_id: &str,
         artifact_type: ArtifactType,
     ) -> Result<Vec<Artifact>> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, project_id, title, data_json, created_at, updated_at, parent_artifact_id, status, error_message, session_id
-             FROM artifacts WHERE project_id = ?1 AND artifact_type = ?2 ORDER BY updated_at DESC",
-        )?;
+        let conn = lock(&self.conn);
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {ARTIFACT_COLUMNS} FROM artifacts WHERE project_id = ?1 AND artifact_type = ?2 ORDER BY updated_at DESC"
+        ))?;
         let artifacts = stmt
-            .query_map(params![project_id, artifact_type.as_str()], |row| {
-                let data_json: String = row.get(3)?;
-                let data: ArtifactData = serde_json::from_str(&data_json).map_err(|e| {
-                    rusqlite::Error::FromSqlConversionFailure(
-                        3,
-                        rusqlite::types::Type::Text,
-                        Box::new(e),
-                    )
-                })?;
-                let status_str: String = row.get(7)?;
-                Ok(Artifact {
-                    id: row.get(0)?,
-                    project_id: row.get(1)?,
-                    title: row.get(2)?,
-                    created_at: row.get(4)?,
-                    updated_at: row.get(5)?,
-                    parent_artifact_id: row.get(6)?,
-                    data,
-                    status: ArtifactStatus::parse(&status_str),
-                    error_message: row.get(8)?,
-                    session_id: row.get(9)?,
-                })
-            })?
+            .query_map(
+                params![project_id, artifact_type.as_str()],
+                Artifact::from_row,
+            )?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(artifacts)
+    }
+
+    /// List artifacts in a project created by `created_by`, ordered by most
+    /// recently updated.
+    pub fn list_artifacts_by_author(
+        &self,
+        project_id: &str,
+        created_by: &str,
+    ) -> Result<Vec<Artifact>> {
+        let conn = lock(&self.conn);
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {ARTIFACT_COLUMNS} FROM artifacts WHERE project_id = ?1 AND created_by = ?2 ORDER BY updated_at DESC"
+        ))?;
+        let artifacts = stmt
+            .query_map(params![project_id, created_by], Artifact::from_row)?
             .collect::<std::result::Result<Vec<_>, _>>()?;
         Ok(artifacts)
     }
@@ -1495,7 +2007,7 @@ impl Store {
         title: Option<&str>,
         data: Option<&ArtifactData>,
     ) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let now = now_timestamp();
 
         match (title, data) {
@@ -1544,7 +2056,7 @@ impl Store {
         title: Option<&str>,
         data: Option<&ArtifactData>,
     ) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let now = now_timestamp();
 
         match (title, data) {
@@ -1583,14 +2095,14 @@ impl Store {
 
     /// Delete an artifact.
     pub fn delete_artifact(&self, id: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute("DELETE FROM artifacts WHERE id = ?1", params![id])?;
         Ok(())
     }
 
     /// Set the session ID for an artifact.
     pub fn set_artifact_session(&self, artifact_id: &str, session_id: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let now = now_timestamp();
         conn.execute(
             "UPDATE artifacts SET session_id = ?1, updated_at = ?2 WHERE id = ?3",
@@ -1599,13 +2111,61 @@ impl Store {
         Ok(())
     }
 
+    /// Export a chat session into a new artifact in the given project.
+    ///
+    /// The artifact content is the markdown rendering of the conversation and
+    /// its `session_id` links back to the chat. Any other artifact in the
+    /// project whose ID is mentioned in the conversation is added as context.
+    pub fn save_chat_as_artifact(
+        &self,
+        session_id: &str,
+        project_id: &str,
+        artifact_type: ArtifactType,
+    ) -> Result<Artifact> {
+        if artifact_type != ArtifactType::Markdown {
+            return Err(StoreError::new(format!(
+                "Cannot save a chat as a {} artifact",
+                artifact_type.as_str()
+            )));
+        }
+
+        let full = self
+            .get_session_full(session_id)?
+            .ok_or_else(|| StoreError::new(format!("Session not found: {session_id}")))?;
+
+        let title = full
+            .session
+            .title
+            .clone()
+            .unwrap_or_else(|| "Chat session".to_string());
+        let mut artifact = Artifact::new_markdown(project_id, title, full.to_markdown())
+            .with_created_by(ARTIFACT_AUTHOR_USER);
+        artifact.session_id = Some(session_id.to_string());
+        self.create_artifact(&artifact)?;
+
+        for existing in self.list_artifacts(project_id)? {
+            if existing.id == artifact.id {
+                continue;
+            }
+            let referenced = full
+                .messages
+                .iter()
+                .any(|m| m.content.contains(&existing.id));
+            if referenced {
+                self.add_context(&artifact.id, &existing.id)?;
+            }
+        }
+
+        Ok(artifact)
+    }
+
     // =========================================================================
     // Context operations
     // =========================================================================
 
     /// Add a context link (artifact X was created using artifact Y as context).
     pub fn add_context(&self, artifact_id: &str, context_artifact_id: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute(
             "INSERT OR IGNORE INTO artifact_context (artifact_id, context_artifact_id) VALUES (?1, ?2)",
             params![artifact_id, context_artifact_id],
@@ -1615,7 +2175,7 @@ impl Store {
 
     /// Get the artifacts that were used as context when creating an artifact.
     pub fn get_context_artifacts(&self, artifact_id: &str) -> Result<Vec<String>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let mut stmt = conn
             .prepare("SELECT context_artifact_id FROM artifact_context WHERE artifact_id = ?1")?;
         let ids = stmt
@@ -1627,7 +2187,7 @@ impl Store {
     /// Get the artifacts that use this artifact as context.
     #[allow(dead_code)]
     pub fn get_dependent_artifacts(&self, context_artifact_id: &str) -> Result<Vec<String>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let mut stmt = conn
             .prepare("SELECT artifact_id FROM artifact_context WHERE context_artifact_id = ?1")?;
         let ids = stmt
@@ -1642,7 +2202,7 @@ impl Store {
 
     /// Create a new branch
     pub fn create_branch(&self, branch: &Branch) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         // Convert pr_number from u64 to i64 for SQLite storage
         let pr_number_i64: Option<i64> = branch.pr_number.map(|n| n as i64);
         conn.execute(
@@ -1665,7 +2225,7 @@ impl Store {
 
     /// Get a branch by ID
     pub fn get_branch(&self, id: &str) -> Result<Option<Branch>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.query_row(
             "SELECT id, project_id, repo_path, branch_name, worktree_path, base_branch, pr_number, created_at, updated_at
              FROM branches WHERE id = ?1",
@@ -1678,7 +2238,7 @@ impl Store {
 
     /// List all branches, ordered by oldest first
     pub fn list_branches(&self) -> Result<Vec<Branch>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let mut stmt = conn.prepare(
             "SELECT id, project_id, repo_path, branch_name, worktree_path, base_branch, pr_number, created_at, updated_at
              FROM branches ORDER BY created_at ASC",
@@ -1691,7 +2251,7 @@ impl Store {
 
     /// List branches for a specific repository
     pub fn list_branches_for_repo(&self, repo_path: &str) -> Result<Vec<Branch>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let mut stmt = conn.prepare(
             "SELECT id, project_id, repo_path, branch_name, worktree_path, base_branch, pr_number, created_at, updated_at
              FROM branches WHERE repo_path = ?1 ORDER BY updated_at DESC",
@@ -1704,7 +2264,7 @@ impl Store {
 
     /// List branches for a specific project
     pub fn list_branches_for_project(&self, project_id: &str) -> Result<Vec<Branch>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let mut stmt = conn.prepare(
             "SELECT id, project_id, repo_path, branch_name, worktree_path, base_branch, pr_number, created_at, updated_at
              FROM branches WHERE project_id = ?1 ORDER BY updated_at DESC",
@@ -1717,14 +2277,14 @@ impl Store {
 
     /// Delete a branch and all its sessions
     pub fn delete_branch(&self, id: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute("DELETE FROM branches WHERE id = ?1", params![id])?;
         Ok(())
     }
 
     /// Touch branch (update updated_at)
     pub fn touch_branch(&self, id: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let now = now_timestamp();
         conn.execute(
             "UPDATE branches SET updated_at = ?1 WHERE id = ?2",
@@ -1735,7 +2295,7 @@ impl Store {
 
     /// Update a branch's base branch
     pub fn update_branch_base(&self, id: &str, base_branch: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let now = now_timestamp();
         conn.execute(
             "UPDATE branches SET base_branch = ?1, updated_at = ?2 WHERE id = ?3",
@@ -1746,7 +2306,7 @@ impl Store {
 
     /// Update a branch's PR number
     pub fn update_branch_pr_number(&self, id: &str, pr_number: Option<u64>) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let now = now_timestamp();
         // Convert pr_number from u64 to i64 for SQLite storage
         let pr_number_i64: Option<i64> = pr_number.map(|n| n as i64);
@@ -1763,7 +2323,7 @@ impl Store {
 
     /// Create a new branch session
     pub fn create_branch_session(&self, session: &BranchSession) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute(
             "INSERT INTO branch_sessions (id, branch_id, ai_session_id, commit_sha, status, prompt, error_message, created_at, updated_at)
              VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
@@ -1792,7 +2352,7 @@ impl Store {
 
     /// Get a branch session by ID
     pub fn get_branch_session(&self, id: &str) -> Result<Option<BranchSession>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.query_row(
             "SELECT id, branch_id, ai_session_id, commit_sha, status, prompt, error_message, created_at, updated_at
              FROM branch_sessions WHERE id = ?1",
@@ -1805,7 +2365,7 @@ impl Store {
 
     /// List all sessions for a branch
     pub fn list_branch_sessions(&self, branch_id: &str) -> Result<Vec<BranchSession>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let mut stmt = conn.prepare(
             "SELECT id, branch_id, ai_session_id, commit_sha, status, prompt, error_message, created_at, updated_at
              FROM branch_sessions WHERE branch_id = ?1 ORDER BY created_at ASC",
@@ -1822,7 +2382,7 @@ impl Store {
         branch_id: &str,
         commit_sha: &str,
     ) -> Result<Option<BranchSession>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.query_row(
             "SELECT id, branch_id, ai_session_id, commit_sha, status, prompt, error_message, created_at, updated_at
              FROM branch_sessions WHERE branch_id = ?1 AND commit_sha = ?2",
@@ -1835,7 +2395,7 @@ impl Store {
 
     /// Get the currently running session for a branch (if any)
     pub fn get_running_session(&self, branch_id: &str) -> Result<Option<BranchSession>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.query_row(
             "SELECT id, branch_id, ai_session_id, commit_sha, status, prompt, error_message, created_at, updated_at
              FROM branch_sessions WHERE branch_id = ?1 AND status = 'running'",
@@ -1851,7 +2411,7 @@ impl Store {
         &self,
         ai_session_id: &str,
     ) -> Result<Option<BranchSession>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.query_row(
             "SELECT id, branch_id, ai_session_id, commit_sha, status, prompt, error_message, created_at, updated_at
              FROM branch_sessions WHERE ai_session_id = ?1",
@@ -1864,7 +2424,7 @@ impl Store {
 
     /// Update a branch session's status and commit SHA
     pub fn update_branch_session_completed(&self, id: &str, commit_sha: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let now = now_timestamp();
         conn.execute(
             "UPDATE branch_sessions SET status = 'completed', commit_sha = ?1, updated_at = ?2 WHERE id = ?3",
@@ -1875,7 +2435,7 @@ impl Store {
 
     /// Update a branch session to error state
     pub fn update_branch_session_error(&self, id: &str, error_message: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let now = now_timestamp();
         conn.execute(
             "UPDATE branch_sessions SET status = 'error', error_message = ?1, updated_at = ?2 WHERE id = ?3",
@@ -1886,7 +2446,7 @@ impl Store {
 
     /// Delete a branch session
     pub fn delete_branch_session(&self, id: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute("DELETE FROM branch_sessions WHERE id = ?1", params![id])?;
         Ok(())
     }
@@ -1897,7 +2457,7 @@ impl Store {
 
     /// Create a new branch note
     pub fn create_branch_note(&self, note: &BranchNote) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute(
             "INSERT INTO branch_notes (id, branch_id, ai_session_id, title, content, status, prompt, error_message, created_at, updated_at)
              VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
@@ -1927,7 +2487,7 @@ impl Store {
 
     /// Get a branch note by ID
     pub fn get_branch_note(&self, id: &str) -> Result<Option<BranchNote>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.query_row(
             "SELECT id, branch_id, ai_session_id, title, content, status, prompt, error_message, created_at, updated_at
              FROM branch_notes WHERE id = ?1",
@@ -1940,7 +2500,7 @@ impl Store {
 
     /// List all notes for a branch, ordered by creation time
     pub fn list_branch_notes(&self, branch_id: &str) -> Result<Vec<BranchNote>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let mut stmt = conn.prepare(
             "SELECT id, branch_id, ai_session_id, title, content, status, prompt, error_message, created_at, updated_at
              FROM branch_notes WHERE branch_id = ?1 ORDER BY created_at ASC",
@@ -1953,7 +2513,7 @@ impl Store {
 
     /// Get a branch note by its AI session ID
     pub fn get_branch_note_by_ai_session(&self, ai_session_id: &str) -> Result<Option<BranchNote>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.query_row(
             "SELECT id, branch_id, ai_session_id, title, content, status, prompt, error_message, created_at, updated_at
              FROM branch_notes WHERE ai_session_id = ?1",
@@ -1966,7 +2526,7 @@ impl Store {
 
     /// Get the currently generating note for a branch (if any)
     pub fn get_generating_note(&self, branch_id: &str) -> Result<Option<BranchNote>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.query_row(
             "SELECT id, branch_id, ai_session_id, title, content, status, prompt, error_message, created_at, updated_at
              FROM branch_notes WHERE branch_id = ?1 AND status = 'generating'",
@@ -1979,7 +2539,7 @@ impl Store {
 
     /// Update a branch note's content and mark as complete
     pub fn update_branch_note_completed(&self, id: &str, content: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let now = now_timestamp();
         conn.execute(
             "UPDATE branch_notes SET status = 'complete', content = ?1, updated_at = ?2 WHERE id = ?3",
@@ -1990,7 +2550,7 @@ impl Store {
 
     /// Update a branch note to error state
     pub fn update_branch_note_error(&self, id: &str, error_message: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let now = now_timestamp();
         conn.execute(
             "UPDATE branch_notes SET status = 'error', error_message = ?1, updated_at = ?2 WHERE id = ?3",
@@ -2001,7 +2561,7 @@ impl Store {
 
     /// Delete a branch note
     pub fn delete_branch_note(&self, id: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute("DELETE FROM branch_notes WHERE id = ?1", params![id])?;
         Ok(())
     }
@@ -2012,7 +2572,7 @@ impl Store {
 
     /// Create a new git project
     pub fn create_git_project(&self, project: &GitProject) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute(
             "INSERT INTO git_projects (id, name, repo_path, subpath, created_at, updated_at)
              VALUES (?1, '', ?2, ?3, ?4, ?5)",
@@ -2029,7 +2589,7 @@ impl Store {
 
     /// Get a git project by ID
     pub fn get_git_project(&self, id: &str) -> Result<Option<GitProject>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.query_row(
             "SELECT id, repo_path, subpath, created_at, updated_at
              FROM git_projects WHERE id = ?1",
@@ -2042,7 +2602,7 @@ impl Store {
 
     /// Get a git project by repo_path
     pub fn get_git_project_by_repo(&self, repo_path: &str) -> Result<Option<GitProject>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.query_row(
             "SELECT id, repo_path, subpath, created_at, updated_at
              FROM git_projects WHERE repo_path = ?1",
@@ -2059,7 +2619,7 @@ impl Store {
         repo_path: &str,
         subpath: Option<&str>,
     ) -> Result<Option<GitProject>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
 
         // Use explicit NULL check or equality depending on subpath value
         // This avoids potential issues with the IS operator and bound parameters
@@ -2083,7 +2643,7 @@ impl Store {
 
     /// List all git projects, ordered by oldest first
     pub fn list_git_projects(&self) -> Result<Vec<GitProject>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let mut stmt = conn.prepare(
             "SELECT id, repo_path, subpath, created_at, updated_at
              FROM git_projects ORDER BY created_at ASC",
@@ -2096,7 +2656,7 @@ impl Store {
 
     /// Update a git project's subpath
     pub fn update_git_project(&self, id: &str, subpath: Option<&str>) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let now = now_timestamp();
         conn.execute(
             "UPDATE git_projects SET subpath = ?1, updated_at = ?2 WHERE id = ?3",
@@ -2108,14 +2668,14 @@ impl Store {
     /// Delete a git project
     /// Note: This does NOT cascade to branches - branches still reference repo_path directly
     pub fn delete_git_project(&self, id: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute("DELETE FROM git_projects WHERE id = ?1", params![id])?;
         Ok(())
     }
 
     /// Touch git project (update updated_at)
     pub fn touch_git_project(&self, id: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let now = now_timestamp();
         conn.execute(
             "UPDATE git_projects SET updated_at = ?1 WHERE id = ?2",
@@ -2130,10 +2690,10 @@ impl Store {
 
     /// Create a new project action
     pub fn create_project_action(&self, action: &ProjectAction) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute(
-            "INSERT INTO project_actions (id, project_id, name, command, action_type, sort_order, auto_commit, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            "INSERT INTO project_actions (id, project_id, name, command, action_type, sort_order, auto_commit, created_at, updated_at, env_json)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
             params![
                 &action.id,
                 &action.project_id,
@@ -2144,6 +2704,7 @@ impl Store {
                 if action.auto_commit { 1 } else { 0 },
                 action.created_at,
                 action.updated_at,
+                serde_json::to_string(&action.env)?,
             ],
         )?;
         Ok(())
@@ -2151,9 +2712,9 @@ impl Store {
 
     /// Get a project action by ID
     pub fn get_project_action(&self, id: &str) -> Result<Option<ProjectAction>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.query_row(
-            "SELECT id, project_id, name, command, action_type, sort_order, auto_commit, created_at, updated_at
+            "SELECT id, project_id, name, command, action_type, sort_order, auto_commit, created_at, updated_at, env_json
              FROM project_actions WHERE id = ?1",
             params![id],
             ProjectAction::from_row,
@@ -2164,9 +2725,9 @@ impl Store {
 
     /// List all actions for a project, ordered by sort_order
     pub fn list_project_actions(&self, project_id: &str) -> Result<Vec<ProjectAction>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let mut stmt = conn.prepare(
-            "SELECT id, project_id, name, command, action_type, sort_order, auto_commit, created_at, updated_at
+            "SELECT id, project_id, name, command, action_type, sort_order, auto_commit, created_at, updated_at, env_json
              FROM project_actions WHERE project_id = ?1 ORDER BY sort_order ASC",
         )?;
         let actions = stmt
@@ -2181,9 +2742,9 @@ impl Store {
         project_id: &str,
         action_type: ActionType,
     ) -> Result<Vec<ProjectAction>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let mut stmt = conn.prepare(
-            "SELECT id, project_id, name, command, action_type, sort_order, auto_commit, created_at, updated_at
+            "SELECT id, project_id, name, command, action_type, sort_order, auto_commit, created_at, updated_at, env_json
              FROM project_actions WHERE project_id = ?1 AND action_type = ?2 ORDER BY sort_order ASC",
         )?;
         let actions = stmt
@@ -2197,12 +2758,12 @@ impl Store {
 
     /// Update a project action
     pub fn update_project_action(&self, action: &ProjectAction) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let now = now_timestamp();
         conn.execute(
             "UPDATE project_actions
-             SET name = ?1, command = ?2, action_type = ?3, sort_order = ?4, auto_commit = ?5, updated_at = ?6
-             WHERE id = ?7",
+             SET name = ?1, command = ?2, action_type = ?3, sort_order = ?4, auto_commit = ?5, updated_at = ?6, env_json = ?7
+             WHERE id = ?8",
             params![
                 &action.name,
                 &action.command,
@@ -2210,6 +2771,7 @@ impl Store {
                 action.sort_order,
                 if action.auto_commit { 1 } else { 0 },
                 now,
+                serde_json::to_string(&action.env)?,
                 &action.id,
             ],
         )?;
@@ -2218,14 +2780,14 @@ impl Store {
 
     /// Delete a project action
     pub fn delete_project_action(&self, id: &str) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         conn.execute("DELETE FROM project_actions WHERE id = ?1", params![id])?;
         Ok(())
     }
 
     /// Reorder project actions by updating their sort_order values
     pub fn reorder_project_actions(&self, action_ids: &[String]) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = lock(&self.conn);
         let tx = conn.unchecked_transaction()?;
 
         for (index, action_id) in action_ids.iter().enumerate() {
@@ -2238,6 +2800,37 @@ impl Store {
         tx.commit()?;
         Ok(())
     }
+
+    // ================================================================================
+    // Repo Layouts
+    // ================================================================================
+
+    /// Save the UI layout for a repo, replacing any previous one
+    pub fn save_layout(&self, repo_id: &str, layout: &LayoutState) -> Result<()> {
+        let conn = lock(&self.conn);
+        conn.execute(
+            "INSERT INTO repo_layouts (repo_id, layout_json, updated_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(repo_id) DO UPDATE SET layout_json = excluded.layout_json, updated_at = excluded.updated_at",
+            params![repo_id, serde_json::to_string(layout)?, now_timestamp()],
+        )?;
+        Ok(())
+    }
+
+    /// Load the UI layout for a repo, or the default if none was saved
+    pub fn load_layout(&self, repo_id: &str) -> Result<LayoutState> {
+        let conn = lock(&self.conn);
+        let json: Option<String> = conn
+            .query_row(
+                "SELECT layout_json FROM repo_layouts WHERE repo_id = ?1",
+                params![repo_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match json {
+            Some(json) => Ok(serde_json::from_str(&json)?),
+            None => Ok(LayoutState::default()),
+        }
+    }
 }
 
 // =============================================================================
@@ -2252,12 +2845,15 @@ pub fn now_timestamp() -> i64 {
         .as_millis() as i64
 }
 
-/// Generate a unique session ID
+/// Generate a session ID: a millisecond timestamp (so IDs sort by creation)
+/// followed by 64 random bits. Unlike a process-local counter, the random part
+/// can't repeat after a restart within the same millisecond.
+///
+/// Prefer `Store::new_session_id`, which also checks against existing sessions.
 pub fn generate_session_id() -> String {
     let timestamp = now_timestamp();
-    static COUNTER: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
-    let count = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-    format!("session_{timestamp:x}_{count:x}")
+    let random = uuid::Uuid::new_v4().as_u64_pair().0;
+    format!("session_{timestamp:x}_{random:016x}")
 }
 
 // =============================================================================
@@ -2322,6 +2918,8 @@ mod tests {
             title: Some("Test Session".to_string()),
             created_at: now,
             updated_at: now,
+            forked_from: None,
+            acp_session_id: None,
         };
 
         store.create_session(&session).unwrap();
@@ -2331,6 +2929,47 @@ mod tests {
         assert_eq!(retrieved.title, Some("Test Session".to_string()));
     }
 
+    #[test]
+    fn test_store_usable_after_panic_while_locked() {
+        let dir = tempdir().unwrap();
+        let store = Store::open(dir.path().join("test.db")).unwrap();
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _conn = lock(&store.conn);
+            panic!("panicked while holding the connection");
+        }));
+        assert!(result.is_err());
+        assert!(store.conn.is_poisoned());
+
+        let now = now_timestamp();
+        let session = Session {
+            id: "after-panic".to_string(),
+            working_dir: "/tmp/repo".to_string(),
+            agent_id: "goose".to_string(),
+            title: None,
+            created_at: now,
+            updated_at: now,
+            forked_from: None,
+            acp_session_id: None,
+        };
+        store.create_session(&session).unwrap();
+        assert!(store.get_session("after-panic").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_session_ids_unique() {
+        let dir = tempdir().unwrap();
+        let store = Store::open(dir.path().join("test.db")).unwrap();
+
+        let ids: std::collections::HashSet<String> =
+            (0..10_000).map(|_| generate_session_id()).collect();
+        assert_eq!(ids.len(), 10_000);
+        assert!(ids.iter().all(|id| id.starts_with("session_")));
+
+        let id = store.new_session_id().unwrap();
+        assert!(store.get_session(&id).unwrap().is_none());
+    }
+
     #[test]
     fn test_add_and_get_messages() {
         let dir = tempdir().unwrap();
@@ -2345,6 +2984,8 @@ mod tests {
             title: None,
             created_at: now,
             updated_at: now,
+            forked_from: None,
+            acp_session_id: None,
         };
         store.create_session(&session).unwrap();
 
@@ -2376,6 +3017,8 @@ mod tests {
             title: None,
             created_at: now,
             updated_at: now,
+            forked_from: None,
+            acp_session_id: None,
         };
         store.create_session(&session).unwrap();
 
@@ -2386,7 +3029,7 @@ mod tests {
             ContentSegment::ToolCall {
                 id: "tc1".to_string(),
                 title: "Read file".to_string(),
-                status: "completed".to_string(),
+                status: ToolCallStatus::Completed,
                 locations: vec!["src/main.rs".to_string()],
             },
             ContentSegment::Text {
@@ -2407,6 +3050,293 @@ mod tests {
         assert_eq!(loaded_segments.len(), 3);
     }
 
+    #[test]
+    fn test_parse_segments_splits_code_and_file_refs() {
+        let text = |t: &str| ContentSegment::Text {
+            text: t.to_string(),
+        };
+        let tool = ContentSegment::ToolCall {
+            id: "tc1".to_string(),
+            title: "Read file".to_string(),
+            status: ToolCallStatus::Completed,
+            locations: vec![],
+        };
+        let parsed = parse_segments(&[
+            text(
+                "The bug is in `src/git/diff.rs:42`, see also src/lib.rs.\n\
+                 ```rust\n\
+                 fn main() {}\n\
+                 ```\n\
+                 Done.\n",
+            ),
+            tool.clone(),
+            text("Unterminated:\n~~~\nraw https://example.com/a.html\n"),
+        ]);
+
+        assert_eq!(
+            parsed,
+            vec![
+                text("The bug is in "),
+                ContentSegment::FileRef {
+                    path: "src/git/diff.rs".to_string(),
+                    line: Some(42),
+                },
+                text(", see also "),
+                ContentSegment::FileRef {
+                    path: "src/lib.rs".to_string(),
+                    line: None,
+                },
+                text(".\n"),
+                ContentSegment::Code {
+                    language: Some("rust".to_string()),
+                    code: "fn main() {}\n".to_string(),
+                },
+                text("Done.\n"),
+                tool,
+                text("Unterminated:\n"),
+                ContentSegment::Code {
+                    language: None,
+                    code: "raw https://example.com/a.html\n".to_string(),
+                },
+            ]
+        );
+
+        // Bare names, URLs and version-like numbers aren't file references
+        let plain = "Edit main.rs per https://example.com/docs/a.html or 1.5/2.5";
+        assert_eq!(parse_segments(&[text(plain)]), vec![text(plain)]);
+    }
+
+    #[test]
+    fn test_session_full_exposes_parsed_segments() {
+        let dir = tempdir().unwrap();
+        let store = Store::open(dir.path().join("test.db")).unwrap();
+        let now = now_timestamp();
+        store
+            .create_session(&Session {
+                id: "s1".to_string(),
+                working_dir: "/tmp/repo".to_string(),
+                agent_id: "goose".to_string(),
+                title: None,
+                created_at: now,
+                updated_at: now,
+                forked_from: None,
+                acp_session_id: None,
+            })
+            .unwrap();
+
+        store
+            .add_message("s1", MessageRole::User, "Fix it")
+            .unwrap();
+        let raw = vec![ContentSegment::Text {
+            text: "Changed src/app.ts:3:\n```ts\nlet x = 1;\n```\n".to_string(),
+        }];
+        store.add_assistant_turn("s1", &raw).unwrap();
+        // A row from before segments were stored is parsed on read
+        {
+            let conn = lock(&store.conn);
+            conn.execute(
+                "UPDATE messages SET segments = NULL WHERE role = 'assistant'",
+                [],
+            )
+            .unwrap();
+        }
+        store.add_assistant_turn("s1", &raw).unwrap();
+
+        let full = store.get_session_full("s1").unwrap().unwrap();
+        assert!(full.messages[0].segments.is_empty());
+        // The raw content is unchanged
+        assert_eq!(
+            serde_json::from_str::<Vec<ContentSegment>>(&full.messages[1].content).unwrap(),
+            raw
+        );
+        let expected = vec![
+            ContentSegment::Text {
+                text: "Changed ".to_string(),
+            },
+            ContentSegment::FileRef {
+                path: "src/app.ts".to_string(),
+                line: Some(3),
+            },
+            ContentSegment::Text {
+                text: ":\n".to_string(),
+            },
+            ContentSegment::Code {
+                language: Some("ts".to_string()),
+                code: "let x = 1;\n".to_string(),
+            },
+        ];
+        assert_eq!(full.messages[1].segments, expected);
+        assert_eq!(full.messages[2].segments, expected);
+    }
+
+    #[test]
+    fn test_update_message_content() {
+        let dir = tempdir().unwrap();
+        let store = Store::open(dir.path().join("test.db")).unwrap();
+        store
+            .create_session(&Session {
+                id: "s1".to_string(),
+                working_dir: "/tmp/repo".to_string(),
+                agent_id: "goose".to_string(),
+                title: None,
+                created_at: 0,
+                updated_at: 0,
+                forked_from: None,
+                acp_session_id: None,
+            })
+            .unwrap();
+        let prompt = store
+            .add_message("s1", MessageRole::User, "Fix teh bug")
+            .unwrap();
+        let reply = store
+            .add_assistant_turn(
+                "s1",
+                &[ContentSegment::Text {
+                    text: "Done.".to_string(),
+                }],
+            )
+            .unwrap();
+        {
+            let conn = lock(&store.conn);
+            conn.execute("UPDATE sessions SET updated_at = 0", [])
+                .unwrap();
+        }
+
+        store.update_message_content(prompt, "Fix the bug").unwrap();
+        // Assistant turns can't be edited as plain text
+        assert!(store.update_message_content(reply, "Edited").is_err());
+        assert!(store.update_message_content(9999, "Missing").is_err());
+
+        let edited = vec![ContentSegment::Text {
+            text: "Fixed in src/lib.rs:7\n```rust\nlet bug = false;\n```\n".to_string(),
+        }];
+        store.update_assistant_turn(reply, &edited).unwrap();
+        assert!(store.update_assistant_turn(prompt, &edited).is_err());
+
+        let full = store.get_session_full("s1").unwrap().unwrap();
+        assert!(full.session.updated_at > 0);
+        assert_eq!(full.messages.len(), 2);
+        assert_eq!(full.messages[0].content, "Fix the bug");
+        assert!(full.messages[0].segments.is_empty());
+        assert_eq!(
+            serde_json::from_str::<Vec<ContentSegment>>(&full.messages[1].content).unwrap(),
+            edited
+        );
+        assert_eq!(full.messages[1].segments, parse_segments(&edited));
+        assert!(full.messages[1]
+            .segments
+            .iter()
+            .any(|s| matches!(s, ContentSegment::Code { .. })));
+    }
+
+    #[test]
+    fn test_fork_session() {
+        let dir = tempdir().unwrap();
+        let store = Store::open(dir.path().join("test.db")).unwrap();
+        store
+            .create_session(&Session {
+                id: "parent".to_string(),
+                working_dir: "/tmp/repo".to_string(),
+                agent_id: "goose".to_string(),
+                title: Some("Debugging".to_string()),
+                created_at: 0,
+                updated_at: 0,
+                forked_from: None,
+                acp_session_id: None,
+            })
+            .unwrap();
+        store
+            .add_message("parent", MessageRole::User, "Why does it crash?")
+            .unwrap();
+        let turn = vec![
+            ContentSegment::ToolCall {
+                id: "tc1".to_string(),
+                title: "Read file".to_string(),
+                status: ToolCallStatus::Completed,
+                locations: vec!["src/main.rs".to_string()],
+            },
+            ContentSegment::Text {
+                text: "An unwrap on line 3.".to_string(),
+            },
+        ];
+        let fork_point = store.add_assistant_turn("parent", &turn).unwrap();
+        store
+            .add_message("parent", MessageRole::User, "Fix it")
+            .unwrap();
+
+        let fork_id = store.fork_session("parent", fork_point).unwrap();
+        assert_ne!(fork_id, "parent");
+
+        let parent = store.get_session_full("parent").unwrap().unwrap();
+        let fork = store.get_session_full(&fork_id).unwrap().unwrap();
+        assert_eq!(fork.session.forked_from.as_deref(), Some("parent"));
+        assert_eq!(fork.session.agent_id, "goose");
+        assert_eq!(fork.session.title.as_deref(), Some("Debugging"));
+        assert_eq!(parent.messages.len(), 3);
+        assert_eq!(fork.messages.len(), 2);
+        for (copied, original) in fork.messages.iter().zip(&parent.messages) {
+            assert_ne!(copied.id, original.id);
+            assert_eq!(copied.role, original.role);
+            assert_eq!(copied.content, original.content);
+            assert_eq!(copied.segments, original.segments);
+        }
+        assert_eq!(
+            serde_json::from_str::<Vec<ContentSegment>>(&fork.messages[1].content).unwrap(),
+            turn
+        );
+
+        // The message must belong to the session being forked
+        let other = store.fork_session(&fork_id, fork_point);
+        assert!(other.is_err());
+        assert!(store.fork_session("missing", fork_point).is_err());
+
+        // Deleting the parent leaves the fork intact
+        store.delete_session("parent").unwrap();
+        let fork = store.get_session_full(&fork_id).unwrap().unwrap();
+        assert_eq!(fork.session.forked_from, None);
+        assert_eq!(fork.messages.len(), 2);
+    }
+
+    #[test]
+    fn test_acp_session_id() {
+        let dir = tempdir().unwrap();
+        let store = Store::open(dir.path().join("test.db")).unwrap();
+        store
+            .create_session(&Session {
+                id: "s1".to_string(),
+                working_dir: "/tmp/repo".to_string(),
+                agent_id: "goose".to_string(),
+                title: None,
+                created_at: 0,
+                updated_at: 0,
+                forked_from: None,
+                acp_session_id: None,
+            })
+            .unwrap();
+        assert_eq!(
+            store.get_session("s1").unwrap().unwrap().acp_session_id,
+            None
+        );
+
+        store.set_acp_session_id("s1", "acp-1").unwrap();
+        let session = store.get_session("s1").unwrap().unwrap();
+        assert_eq!(session.acp_session_id.as_deref(), Some("acp-1"));
+        // Recording the id isn't activity in the chat
+        assert_eq!(session.updated_at, 0);
+
+        // A replacement agent session overwrites the mapping
+        store.set_acp_session_id("s1", "acp-2").unwrap();
+        let sessions = store.list_sessions_for_dir("/tmp/repo").unwrap();
+        assert_eq!(sessions[0].acp_session_id.as_deref(), Some("acp-2"));
+        assert!(store.set_acp_session_id("missing", "acp-3").is_err());
+
+        // Forks start a fresh agent session
+        let message = store.add_message("s1", MessageRole::User, "hi").unwrap();
+        let fork_id = store.fork_session("s1", message).unwrap();
+        let fork = store.get_session(&fork_id).unwrap().unwrap();
+        assert_eq!(fork.acp_session_id, None);
+    }
+
     #[test]
     fn test_delete_session_cascades() {
         let dir = tempdir().unwrap();
@@ -2421,6 +3351,8 @@ mod tests {
             title: None,
             created_at: now,
             updated_at: now,
+            forked_from: None,
+            acp_session_id: None,
         };
         store.create_session(&session).unwrap();
         store
@@ -2474,6 +3406,42 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_artifacts_by_author() {
+        let dir = tempdir().unwrap();
+        let store = Store::open(dir.path().join("test.db")).unwrap();
+
+        let project = Project::new("Test Project");
+        store.create_project(&project).unwrap();
+
+        let mine = Artifact::new_markdown(&project.id, "Notes", "Mine")
+            .with_created_by(ARTIFACT_AUTHOR_USER);
+        let generated =
+            Artifact::new_generating(&project.id, "Plan").with_created_by(ARTIFACT_AUTHOR_AGENT);
+        let legacy = Artifact::new_markdown(&project.id, "Old", "No author");
+        for artifact in [&mine, &generated, &legacy] {
+            store.create_artifact(artifact).unwrap();
+        }
+
+        let stored = store.get_artifact(&generated.id).unwrap().unwrap();
+        assert_eq!(stored.created_by.as_deref(), Some(ARTIFACT_AUTHOR_AGENT));
+        let stored = store.get_artifact(&legacy.id).unwrap().unwrap();
+        assert_eq!(stored.created_by, None);
+
+        let ids = |author| -> Vec<String> {
+            store
+                .list_artifacts_by_author(&project.id, author)
+                .unwrap()
+                .into_iter()
+                .map(|a| a.id)
+                .collect()
+        };
+        assert_eq!(ids(ARTIFACT_AUTHOR_USER), vec![mine.id.clone()]);
+        assert_eq!(ids(ARTIFACT_AUTHOR_AGENT), vec![generated.id.clone()]);
+        assert!(ids("someone-else").is_empty());
+        assert_eq!(store.list_artifacts(&project.id).unwrap().len(), 3);
+    }
+
     #[test]
     fn test_delete_project_cascades() {
         let dir = tempdir().unwrap();
@@ -2515,4 +3483,217 @@ mod tests {
         let dependents = store.get_dependent_artifacts(&a1.id).unwrap();
         assert_eq!(dependents, vec![a2.id.clone()]);
     }
+
+    #[test]
+    fn test_save_chat_as_artifact() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let store = Store::open(db_path).unwrap();
+
+        let project = Project::new("Test Project");
+        store.create_project(&project).unwrap();
+        let research = Artifact::new_markdown(&project.id, "Research", "Findings");
+        let unrelated = Artifact::new_markdown(&project.id, "Other", "Unrelated");
+        store.create_artifact(&research).unwrap();
+        store.create_artifact(&unrelated).unwrap();
+
+        let now = now_timestamp();
+        let session = Session {
+            id: "chat-1".to_string(),
+            working_dir: "/tmp/repo".to_string(),
+            agent_id: "goose".to_string(),
+            title: Some("Auth plan".to_string()),
+            created_at: now,
+            updated_at: now,
+            forked_from: None,
+            acp_session_id: None,
+        };
+        store.create_session(&session).unwrap();
+        store
+            .add_message(
+                "chat-1",
+                MessageRole::User,
+                &format!("Plan the auth flow using {}", research.id),
+            )
+            .unwrap();
+        store
+            .add_assistant_turn(
+                "chat-1",
+                &[ContentSegment::Text {
+                    text: "1. Add a login route".to_string(),
+                }],
+            )
+            .unwrap();
+
+        let artifact = store
+            .save_chat_as_artifact("chat-1", &project.id, ArtifactType::Markdown)
+            .unwrap();
+        let stored = store.get_artifact(&artifact.id).unwrap().unwrap();
+
+        assert_eq!(stored.title, "Auth plan");
+        assert_eq!(stored.session_id.as_deref(), Some("chat-1"));
+        let expected = store
+            .get_session_full("chat-1")
+            .unwrap()
+            .unwrap()
+            .to_markdown();
+        match &stored.data {
+            ArtifactData::Markdown { content } => {
+                assert_eq!(content, &expected);
+                assert!(content.contains("## User"));
+                assert!(content.contains("1. Add a login route"));
+            }
+            other => panic!("expected markdown artifact, got {other:?}"),
+        }
+
+        let context = store.get_context_artifacts(&artifact.id).unwrap();
+        assert_eq!(context, vec![research.id.clone()]);
+
+        assert!(store
+            .save_chat_as_artifact("chat-1", &project.id, ArtifactType::Commit)
+            .is_err());
+    }
+
+    #[test]
+    fn test_chat_markdown_code_containing_fence() {
+        let dir = tempdir().unwrap();
+        let store = Store::open(dir.path().join("test.db")).unwrap();
+        let now = now_timestamp();
+        store
+            .create_session(&Session {
+                id: "s1".to_string(),
+                working_dir: "/tmp/repo".to_string(),
+                agent_id: "goose".to_string(),
+                title: Some("Docs".to_string()),
+                created_at: now,
+                updated_at: now,
+                forked_from: None,
+                acp_session_id: None,
+            })
+            .unwrap();
+        store
+            .add_assistant_turn(
+                "s1",
+                &[ContentSegment::Code {
+                    language: Some("md".to_string()),
+                    code: "```rust\nfn main() {}\n```\n".to_string(),
+                }],
+            )
+            .unwrap();
+
+        let md = store.get_session_full("s1").unwrap().unwrap().to_markdown();
+        assert_eq!(
+            md,
+            "# Docs\n\n## Assistant\n\n````md\n```rust\nfn main() {}\n```\n````\n"
+        );
+    }
+
+    #[test]
+    fn test_tool_call_status_legacy_values() {
+        for (legacy, expected) in [
+            ("pending", ToolCallStatus::Pending),
+            ("inprogress", ToolCallStatus::InProgress),
+            ("in_progress", ToolCallStatus::InProgress),
+            ("inProgress", ToolCallStatus::InProgress),
+            ("completed", ToolCallStatus::Completed),
+            ("failed", ToolCallStatus::Failed),
+        ] {
+            let json =
+                format!(r#"{{"type":"toolCall","id":"t1","title":"Read","status":"{legacy}"}}"#);
+            match serde_json::from_str::<ContentSegment>(&json).unwrap() {
+                ContentSegment::ToolCall { status, .. } => assert_eq!(status, expected),
+                other => panic!("expected tool call, got {other:?}"),
+            }
+            assert_eq!(ToolCallStatus::parse(legacy), Some(expected));
+        }
+
+        let serialized = serde_json::to_string(&ToolCallStatus::InProgress).unwrap();
+        assert_eq!(serialized, r#""inProgress""#);
+    }
+
+    #[test]
+    fn test_migrates_legacy_tool_call_status() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let store = Store::open(db_path.clone()).unwrap();
+
+        let now = now_timestamp();
+        let session = Session {
+            id: "s1".to_string(),
+            working_dir: "/tmp/repo".to_string(),
+            agent_id: "goose".to_string(),
+            title: None,
+            created_at: now,
+            updated_at: now,
+            forked_from: None,
+            acp_session_id: None,
+        };
+        store.create_session(&session).unwrap();
+        store
+            .add_message(
+                "s1",
+                MessageRole::Assistant,
+                r#"[{"type":"toolCall","id":"t1","title":"Read","status":"inprogress"}]"#,
+            )
+            .unwrap();
+        drop(store);
+
+        // Reopening a database from before the migration rewrites the row
+        Connection::open(&db_path)
+            .unwrap()
+            .pragma_update(None, "user_version", 0)
+            .unwrap();
+        let store = Store::open(db_path.clone()).unwrap();
+        let messages = store.get_messages("s1").unwrap();
+        assert_eq!(
+            messages[0].content,
+            r#"[{"type":"toolCall","id":"t1","title":"Read","status":"inProgress"}]"#
+        );
+
+        // Once migrated, later opens leave message content alone
+        store
+            .add_message(
+                "s1",
+                MessageRole::Assistant,
+                r#"[{"type":"toolCall","id":"t2","title":"Read","status":"inprogress"}]"#,
+            )
+            .unwrap();
+        drop(store);
+        let store = Store::open(db_path).unwrap();
+        assert!(store.get_messages("s1").unwrap()[1]
+            .content
+            .contains(r#""status":"inprogress""#));
+    }
+
+    #[test]
+    fn test_layout_round_trip() {
+        let dir = tempdir().unwrap();
+        let store = Store::open(dir.path().join("test.db")).unwrap();
+
+        // Nothing saved yet
+        assert_eq!(
+            store.load_layout("/repos/a").unwrap(),
+            LayoutState::default()
+        );
+
+        let layout = LayoutState {
+            selected_file: Some("src/main.rs".to_string()),
+            scroll_position: 420.5,
+            pane_ratios: HashMap::from([("sidebar".to_string(), 0.25)]),
+        };
+        store.save_layout("/repos/a", &layout).unwrap();
+        assert_eq!(store.load_layout("/repos/a").unwrap(), layout);
+
+        // Saving again replaces; other repos are unaffected
+        let updated = LayoutState {
+            selected_file: None,
+            ..layout
+        };
+        store.save_layout("/repos/a", &updated).unwrap();
+        assert_eq!(store.load_layout("/repos/a").unwrap(), updated);
+        assert_eq!(
+            store.load_layout("/repos/b").unwrap(),
+            LayoutState::default()
+        );
+    }
 }
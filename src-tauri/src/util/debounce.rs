@@ -0,0 +1,89 @@
+//! Coalescing of rapid events.
+
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+/// Coalesces bursts of events, firing only the latest one once no new event
+/// has arrived for the quiet `window`.
+///
+/// A background thread owns the callback. Dropping the debouncer flushes any
+/// pending event and stops the thread.
+pub struct Debouncer<T> {
+    tx: Sender<T>,
+}
+
+impl<T: Send + 'static> Debouncer<T> {
+    pub fn new<F>(window: Duration, on_fire: F) -> Self
+    where
+        F: Fn(T) + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel::<T>();
+
+        thread::spawn(move || {
+            // Block until a burst starts, then wait for it to go quiet
+            while let Ok(mut latest) = rx.recv() {
+                loop {
+                    match rx.recv_timeout(window) {
+                        Ok(event) => latest = event,
+                        Err(RecvTimeoutError::Timeout) => {
+                            on_fire(latest);
+                            break;
+                        }
+                        Err(RecvTimeoutError::Disconnected) => {
+                            on_fire(latest);
+                            return;
+                        }
+                    }
+                }
+            }
+        });
+
+        Self { tx }
+    }
+
+    /// Record an event. It fires after the quiet window unless superseded.
+    pub fn send(&self, event: T) {
+        let _ = self.tx.send(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn test_burst_fires_once_with_latest() {
+        let (fired_tx, fired_rx) = mpsc::channel();
+        let debouncer = Debouncer::new(WINDOW, move |n: u32| {
+            let _ = fired_tx.send(n);
+        });
+
+        for n in 0..10 {
+            debouncer.send(n);
+        }
+
+        assert_eq!(fired_rx.recv_timeout(Duration::from_secs(2)), Ok(9));
+        // Nothing else fires after the window
+        assert!(fired_rx.recv_timeout(WINDOW * 4).is_err());
+    }
+
+    #[test]
+    fn test_separate_bursts_fire_separately() {
+        let (fired_tx, fired_rx) = mpsc::channel();
+        let debouncer = Debouncer::new(WINDOW, move |n: u32| {
+            let _ = fired_tx.send(n);
+        });
+
+        debouncer.send(1);
+        assert_eq!(fired_rx.recv_timeout(Duration::from_secs(2)), Ok(1));
+
+        debouncer.send(2);
+        debouncer.send(3);
+        // Dropping flushes the pending event
+        drop(debouncer);
+        assert_eq!(fired_rx.recv_timeout(Duration::from_secs(2)), Ok(3));
+    }
+}
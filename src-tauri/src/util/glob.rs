@@ -0,0 +1,454 @@
+//! Glob patterns for matching file paths.
+//!
+//! One matcher is shared by diff exclusions and generated-file detection so
+//! they agree on what a pattern means. Matching is done by `globset`; this
+//! module adds anchoring, negation and variables on top. Semantics:
+//!
+//! - Paths and patterns use `/` as the separator. Backslashes in matched
+//!   paths are treated as separators; in patterns `\` escapes the next
+//!   character (`\*` is a literal star).
+//! - `*` matches any run of characters within one path segment, and `?`
+//!   exactly one character; neither crosses a `/`.
+//! - `[abc]`, `[a-z]` and `[!a-z]` (or `[^a-z]`) match one character from,
+//!   or not from, a set. `{a,b}` matches either alternative.
+//! - A `**` segment matches zero or more whole segments, so `a/**/b` matches
+//!   `a/b` and `a/x/y/b`. A trailing `/**` (or a trailing `/`) matches
+//!   everything inside a directory but not the directory itself. `**` inside a
+//!   segment (`a**b`) is just `*`.
+//! - A pattern starting with `/` is anchored to the root. Any other pattern
+//!   matches at any depth, as if it started with `**/`: `*.lock` matches
+//!   `Cargo.lock` and `web/yarn.lock`, and `__snapshots__/**` matches every
+//!   snapshot directory.
+//! - `$REPO` and `$DATA` expand to the paths set in [`GlobVars`], with any
+//!   glob characters in them escaped. For repo-relative paths `$REPO` is the
+//!   empty root, so `$REPO/src/**` is the same as `/src/**`. Using one of
+//!   them where it isn't set is an error. Any other `$NAME` is literal text,
+//!   so `Foo$Bar.class` matches that file name.
+//! - In a [`GlobSet`], a leading `!` negates a pattern: the last pattern that
+//!   matches a path decides, so `!` re-includes paths an earlier pattern
+//!   matched. `\!` is a literal `!`.
+
+use super::display_path;
+use std::path::Path;
+
+/// A pattern that couldn't be compiled.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{pattern}: {reason}")]
+pub struct GlobError {
+    pub pattern: String,
+    pub reason: String,
+}
+
+/// Values for `$REPO` and `$DATA` in patterns.
+#[derive(Debug, Clone, Default)]
+pub struct GlobVars {
+    repo: Option<String>,
+    data: Option<String>,
+}
+
+impl GlobVars {
+    /// No variables set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Variables for matching repo-relative paths: `$REPO` is the root.
+    pub fn repo_relative() -> Self {
+        Self::new().with_repo("")
+    }
+
+    /// Set `$REPO`.
+    pub fn with_repo(mut self, repo: impl AsRef<Path>) -> Self {
+        self.repo = Some(normalize_separators(&display_path(repo.as_ref())));
+        self
+    }
+
+    /// Set `$DATA`, the app's data directory.
+    pub fn with_data(mut self, data: impl AsRef<Path>) -> Self {
+        self.data = Some(normalize_separators(&display_path(data.as_ref())));
+        self
+    }
+
+    fn get(&self, name: &str) -> Option<Option<&str>> {
+        match name {
+            "REPO" => Some(self.repo.as_deref()),
+            "DATA" => Some(self.data.as_deref()),
+            _ => None,
+        }
+    }
+}
+
+/// A compiled glob pattern.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    pattern: String,
+    negated: bool,
+    matcher: globset::GlobMatcher,
+}
+
+impl Glob {
+    /// Compile a pattern with no variables set.
+    pub fn new(pattern: &str) -> Result<Self, GlobError> {
+        Self::with_vars(pattern, &GlobVars::new())
+    }
+
+    /// Compile a pattern, expanding `$REPO` and `$DATA` from `vars`.
+    pub fn with_vars(pattern: &str, vars: &GlobVars) -> Result<Self, GlobError> {
+        let (negated, glob) = compile(pattern, vars)?;
+        Ok(Self {
+            pattern: pattern.to_string(),
+            negated,
+            matcher: glob.compile_matcher(),
+        })
+    }
+
+    /// The pattern as written.
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Whether the pattern started with `!`. [`Glob::is_match`] ignores this;
+    /// [`GlobSet`] uses it to re-include paths.
+    pub fn is_negated(&self) -> bool {
+        self.negated
+    }
+
+    /// Whether the pattern matches `path` itself.
+    pub fn is_match(&self, path: impl AsRef<Path>) -> bool {
+        self.matcher.is_match(normalize_path(path.as_ref()))
+    }
+
+    /// Whether the pattern matches `path` or one of its parent directories,
+    /// so a pattern naming a directory covers everything inside it.
+    pub fn is_match_or_parent(&self, path: impl AsRef<Path>) -> bool {
+        self_and_parents(&normalize_path(path.as_ref())).any(|p| self.matcher.is_match(p))
+    }
+}
+
+/// An ordered list of patterns where the last match wins.
+#[derive(Debug, Clone, Default)]
+pub struct GlobSet {
+    set: globset::GlobSet,
+    /// Whether each pattern, by index in `set`, is negated
+    negated: Vec<bool>,
+}
+
+impl GlobSet {
+    /// Compile patterns in order.
+    pub fn new<I, S>(patterns: I, vars: &GlobVars) -> Result<Self, GlobError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut builder = globset::GlobSetBuilder::new();
+        let mut negated = Vec::new();
+        for pattern in patterns {
+            let (is_negated, glob) = compile(pattern.as_ref(), vars)?;
+            builder.add(glob);
+            negated.push(is_negated);
+        }
+        let set = builder.build().map_err(|e| GlobError {
+            pattern: e.glob().unwrap_or_default().to_string(),
+            reason: e.kind().to_string(),
+        })?;
+        Ok(Self { set, negated })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+
+    /// Whether `path` is matched: the last pattern matching it decides, and a
+    /// negated pattern means it isn't.
+    pub fn is_match(&self, path: impl AsRef<Path>) -> bool {
+        self.decide(self.last_match(&normalize_path(path.as_ref())))
+    }
+
+    /// Like [`GlobSet::is_match`], also matching patterns against `path`'s
+    /// parent directories.
+    pub fn is_match_or_parent(&self, path: impl AsRef<Path>) -> bool {
+        let path = normalize_path(path.as_ref());
+        self.decide(
+            self_and_parents(&path)
+                .filter_map(|p| self.last_match(p))
+                .max(),
+        )
+    }
+
+    fn last_match(&self, path: &str) -> Option<usize> {
+        self.set.matches(path).into_iter().max()
+    }
+
+    fn decide(&self, last_match: Option<usize>) -> bool {
+        last_match.is_some_and(|i| !self.negated[i])
+    }
+}
+
+/// Turn a pattern into a `globset` glob, returning whether it was negated.
+///
+/// Anchoring, `!` and variables are handled here; the rest of the syntax is
+/// `globset`'s, with `*` kept within a segment and `\` always an escape.
+fn compile(pattern: &str, vars: &GlobVars) -> Result<(bool, globset::Glob), GlobError> {
+    let error = |reason: String| GlobError {
+        pattern: pattern.to_string(),
+        reason,
+    };
+
+    let (negated, body) = match pattern.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, pattern),
+    };
+    let (expanded, starts_with_var) = expand_vars(body, vars).map_err(error)?;
+    if expanded.is_empty() {
+        return Err(error("empty pattern".to_string()));
+    }
+
+    // A leading variable is a root of its own, e.g. `C:/...` on Windows.
+    // Paths are matched without a leading `/`, so anchored patterns drop it.
+    let anchored = expanded.starts_with('/') || starts_with_var;
+    let mut glob = expanded.trim_start_matches('/').to_string();
+    if glob.ends_with('/') {
+        glob.push_str("**");
+    }
+    if !anchored && glob != "**" && !glob.starts_with("**/") {
+        glob.insert_str(0, "**/");
+    }
+
+    let glob = globset::GlobBuilder::new(&glob)
+        .literal_separator(true)
+        .backslash_escape(true)
+        .build()
+        .map_err(|e| error(e.kind().to_string()))?;
+    Ok((negated, glob))
+}
+
+fn normalize_separators(path: &str) -> String {
+    path.replace('\\', "/")
+}
+
+/// A path as patterns see it: `/`-separated, without empty or `.` segments.
+fn normalize_path(path: &Path) -> String {
+    normalize_separators(&display_path(path))
+        .split('/')
+        .filter(|p| !p.is_empty() && *p != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// `path` and each of its parent directories, shortest first.
+fn self_and_parents(path: &str) -> impl Iterator<Item = &str> {
+    path.match_indices('/')
+        .map(|(i, _)| &path[..i])
+        .chain([path])
+        .filter(|p| !p.is_empty())
+}
+
+/// Expand `$NAME` variables, escaping glob characters in their values.
+/// Also returns whether the pattern started with a variable.
+fn expand_vars(pattern: &str, vars: &GlobVars) -> Result<(String, bool), String> {
+    let mut starts_with_var = false;
+    let mut out = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                out.push(c);
+                match chars.next() {
+                    Some(escaped) => out.push(escaped),
+                    None => return Err("trailing backslash".to_string()),
+                }
+            }
+            '$' if chars.peek().is_some_and(|n| n.is_ascii_uppercase()) => {
+                let mut name = String::new();
+                while let Some(&n) = chars.peek() {
+                    if !(n.is_ascii_uppercase() || n == '_') {
+                        break;
+                    }
+                    name.push(n);
+                    chars.next();
+                }
+                let value = match vars.get(&name) {
+                    Some(Some(value)) => value,
+                    Some(None) => return Err(format!("${name} is not set here")),
+                    None => {
+                        out.push('$');
+                        out.push_str(&name);
+                        continue;
+                    }
+                };
+                starts_with_var |= out.is_empty();
+                out.push_str(&globset::escape(value));
+            }
+            _ => out.push(c),
+        }
+    }
+    Ok((out, starts_with_var))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob(pattern: &str) -> Glob {
+        Glob::new(pattern).unwrap()
+    }
+
+    #[test]
+    fn test_star_stays_within_a_segment() {
+        let g = glob("src/*.rs");
+        assert!(g.is_match("src/main.rs"));
+        assert!(g.is_match("src/.rs"));
+        assert!(!g.is_match("src/a/main.rs"));
+        assert!(!g.is_match("src/main.rsx"));
+        // Unanchored, so it also matches deeper
+        assert!(g.is_match("crates/core/src/lib.rs"));
+
+        let g = glob("/*.lock");
+        assert!(g.is_match("Cargo.lock"));
+        assert!(!g.is_match("web/yarn.lock"));
+    }
+
+    #[test]
+    fn test_question_mark_and_classes() {
+        let g = glob("/file?.txt");
+        assert!(g.is_match("file1.txt"));
+        assert!(!g.is_match("file.txt"));
+        assert!(!g.is_match("file12.txt"));
+        assert!(!glob("/a?b").is_match("a/b"));
+
+        let g = glob("/v[0-9].[ch]");
+        assert!(g.is_match("v1.c"));
+        assert!(g.is_match("v9.h"));
+        assert!(!g.is_match("vx.c"));
+        let g = glob("/[!._]*");
+        assert!(g.is_match("main.rs"));
+        assert!(!g.is_match(".env"));
+        assert!(!g.is_match("_private"));
+        assert!(glob("/[]x]").is_match("]"));
+        let g = glob("*.{js,ts}");
+        assert!(g.is_match("web/app.ts"));
+        assert!(!g.is_match("web/app.rs"));
+        assert!(Glob::new("[abc").is_err());
+        assert!(Glob::new("[z-a]").is_err());
+    }
+
+    #[test]
+    fn test_double_star_crosses_directories() {
+        let g = glob("/src/**/test_*.py");
+        assert!(g.is_match("src/test_a.py"));
+        assert!(g.is_match("src/pkg/sub/test_b.py"));
+        assert!(!g.is_match("lib/test_a.py"));
+        assert!(!g.is_match("src/pkg/a_test.py"));
+
+        let g = glob("/a/**/b/**/c");
+        assert!(g.is_match("a/b/c"));
+        assert!(g.is_match("a/x/b/y/z/c"));
+        assert!(!g.is_match("a/c"));
+
+        assert!(glob("**").is_match("any/path/at/all"));
+        assert!(glob("/a**b").is_match("axxb"));
+        assert!(!glob("/a**b").is_match("a/b"));
+    }
+
+    #[test]
+    fn test_trailing_double_star_matches_contents_only() {
+        let g = glob("__snapshots__/**");
+        assert!(g.is_match("__snapshots__/a.snap"));
+        assert!(g.is_match("tests/__snapshots__/nested/b.snap"));
+        assert!(!g.is_match("__snapshots__"));
+        assert!(!g.is_match("tests/__snapshots__.rs"));
+
+        let g = glob("dist/");
+        assert!(g.is_match("web/dist/app.js"));
+        assert!(!g.is_match("dist"));
+        assert!(!g.is_match("distance.rs"));
+    }
+
+    #[test]
+    fn test_match_or_parent() {
+        let g = glob("node_modules");
+        assert!(!g.is_match("node_modules/react/index.js"));
+        assert!(g.is_match_or_parent("node_modules/react/index.js"));
+        assert!(g.is_match_or_parent("web/node_modules"));
+        assert!(!g.is_match_or_parent("src/node_modules.rs"));
+    }
+
+    #[test]
+    fn test_escapes_and_paths() {
+        let g = glob("/notes\\*.md");
+        assert!(g.is_match("notes*.md"));
+        assert!(!g.is_match("notes1.md"));
+        assert!(Glob::new("oops\\").is_err());
+        assert!(Glob::new("").is_err());
+
+        // Backslash separators and `./` in paths are normalized
+        let g = glob("/src/*.rs");
+        assert!(g.is_match("src\\main.rs"));
+        assert!(g.is_match("./src/main.rs"));
+    }
+
+    #[test]
+    fn test_negation_in_sets() {
+        let set = GlobSet::new(["*.snap", "!keep/*.snap"], &GlobVars::new()).unwrap();
+        assert!(set.is_match("tests/a.snap"));
+        assert!(!set.is_match("keep/a.snap"));
+        assert!(!set.is_match("src/lib.rs"));
+
+        // Order matters: a later positive pattern wins again
+        let set = GlobSet::new(["!keep/*.snap", "*.snap"], &GlobVars::new()).unwrap();
+        assert!(set.is_match("keep/a.snap"));
+
+        assert!(glob("!*.snap").is_negated());
+        assert!(glob("!*.snap").is_match("a.snap"));
+        assert!(glob("\\!important").is_match("docs/!important"));
+        assert!(GlobSet::default().is_empty());
+        assert!(!GlobSet::default().is_match("anything"));
+    }
+
+    #[test]
+    fn test_variable_expansion() {
+        let vars = GlobVars::new()
+            .with_repo("/home/me/[work]/repo")
+            .with_data("/home/me/.staged");
+
+        let g = Glob::with_vars("$REPO/src/**", &vars).unwrap();
+        assert!(g.is_match("/home/me/[work]/repo/src/main.rs"));
+        assert!(!g.is_match("/home/me/w/repo/src/main.rs"));
+        assert!(!g.is_match("/elsewhere/home/me/[work]/repo/src/main.rs"));
+
+        let g = Glob::with_vars("$DATA/*.db", &vars).unwrap();
+        assert!(g.is_match("/home/me/.staged/review.db"));
+        assert!(!g.is_match("/home/me/.staged/logs/app.db"));
+
+        // Repo-relative matching: $REPO is the root, so it anchors
+        let g = Glob::with_vars("$REPO/docs/*.md", &GlobVars::repo_relative()).unwrap();
+        assert!(g.is_match("docs/readme.md"));
+        assert!(!g.is_match("site/docs/readme.md"));
+
+        let unset = Glob::with_vars("$DATA/**", &GlobVars::repo_relative()).unwrap_err();
+        assert_eq!(unset.reason, "$DATA is not set here");
+        assert!(glob("/\\$HOME").is_match("$HOME"));
+        assert!(glob("/$lower").is_match("$lower"));
+
+        // Other names are literal text
+        assert!(glob("/$HOME/**").is_match("$HOME/.bashrc"));
+        let g = glob("Foo$Bar.class");
+        assert!(g.is_match("build/Foo$Bar.class"));
+        assert!(!g.is_match("build/Foo.class"));
+        assert!(glob("*$Inner*.class").is_match("Outer$Inner$1.class"));
+    }
+
+    #[test]
+    fn test_many_wildcards_match_quickly() {
+        // Naive recursive backtracking is exponential in the number of stars
+        let long = "a".repeat(200);
+        let g = glob(&format!("/{}b", "*a".repeat(20)));
+        assert!(!g.is_match(&long));
+        assert!(g.is_match(format!("{long}b")));
+
+        let path = vec!["a"; 200].join("/");
+        let g = glob(&format!("/{}b", "**/a/".repeat(20)));
+        assert!(!g.is_match(&path));
+        assert!(g.is_match(format!("{path}/b")));
+    }
+}
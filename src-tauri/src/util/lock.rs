@@ -0,0 +1,31 @@
+//! Poison-tolerant mutex locking.
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Lock `mutex`, recovering it if a previous holder panicked.
+///
+/// Used for SQLite connections: a panic mid-operation leaves the connection
+/// usable (an open transaction is rolled back when dropped), so one failed
+/// caller shouldn't make every later caller panic too.
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recovers_poisoned_mutex() {
+        let mutex = Mutex::new(1);
+        let result = std::panic::catch_unwind(|| {
+            let _guard = mutex.lock().unwrap();
+            panic!("holder panicked");
+        });
+        assert!(result.is_err());
+        assert!(mutex.is_poisoned());
+
+        *lock(&mutex) += 1;
+        assert_eq!(*lock(&mutex), 2);
+    }
+}
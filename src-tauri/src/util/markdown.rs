@@ -0,0 +1,47 @@
+//! Markdown generation helpers.
+
+/// Wrap `content` in a fenced code block with the given info string
+/// (e.g. `"diff"`, or `""` for none).
+///
+/// The fence is one backtick longer than the longest run of backticks in
+/// `content`, and at least three, so fences inside the content can't close
+/// the block early (CommonMark's variable-length fence rule). The block ends
+/// with a newline.
+pub fn fenced_block(info: &str, content: &str) -> String {
+    let longest_run = content.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat((longest_run + 1).max(3));
+
+    let mut block = format!("{fence}{info}\n{content}");
+    if !content.ends_with('\n') {
+        block.push('\n');
+    }
+    block.push_str(&fence);
+    block.push('\n');
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_content_uses_three_backticks() {
+        assert_eq!(
+            fenced_block("diff", "-old\n+new"),
+            "```diff\n-old\n+new\n```\n"
+        );
+        assert_eq!(fenced_block("", "x\n"), "```\nx\n```\n");
+    }
+
+    #[test]
+    fn test_fence_outgrows_backticks_in_content() {
+        assert_eq!(
+            fenced_block("md", "```rust\nfn main() {}\n```"),
+            "````md\n```rust\nfn main() {}\n```\n````\n"
+        );
+        assert_eq!(
+            fenced_block("", "inline `code` and ````` five"),
+            "``````\ninline `code` and ````` five\n``````\n"
+        );
+    }
+}
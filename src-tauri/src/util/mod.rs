@@ -0,0 +1,17 @@
+//! Small shared utilities.
+
+mod debounce;
+pub mod glob;
+mod lock;
+mod markdown;
+mod path;
+pub mod sqlite;
+
+pub use debounce::Debouncer;
+pub use glob::{Glob, GlobError, GlobSet, GlobVars};
+pub use lock::lock;
+pub use markdown::fenced_block;
+pub use path::{
+    decode_path, display_path, encode_path, encode_path_bytes, escape_path, escape_path_bytes,
+    path_bytes, raw_path, unescape_path,
+};
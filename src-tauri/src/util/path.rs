@@ -0,0 +1,201 @@
+//! Lossless conversion between filesystem paths and strings.
+//!
+//! Paths cross into the frontend as JSON strings, but on Unix a file name can
+//! be any bytes. A UTF-8 path is sent as is, and that string is both what the
+//! frontend shows and how it names the file. A path that isn't UTF-8 is shown
+//! lossily, and named by its raw form instead: [`RAW_PREFIX`] followed by the
+//! path with `%` written as `%25` and each invalid byte as `%XX`. No real path
+//! contains a NUL, so a raw form can't be mistaken for a UTF-8 path, and
+//! [`decode_path`] maps either string back to the path.
+
+use std::path::{Path, PathBuf};
+
+/// Marks the raw form of a path that isn't UTF-8.
+const RAW_PREFIX: char = '\0';
+
+/// A path for display; lossy if it isn't UTF-8.
+pub fn display_path(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+/// The raw form of a path that isn't UTF-8, or `None` for a UTF-8 path.
+pub fn raw_path(path: &Path) -> Option<String> {
+    match path.to_str() {
+        Some(_) => None,
+        None => Some(encode_os_path(path)),
+    }
+}
+
+/// The string that names `path`: the path itself if it is UTF-8, else its
+/// raw form.
+pub fn encode_path(path: &Path) -> String {
+    raw_path(path).unwrap_or_else(|| display_path(path))
+}
+
+/// Like [`encode_path`], for raw path bytes (e.g. from git's `-z` output).
+pub fn encode_path_bytes(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => s.to_string(),
+        Err(_) => format!("{RAW_PREFIX}{}", escape_path_bytes(bytes)),
+    }
+}
+
+/// Inverse of [`encode_path`]. Anything that isn't a raw form is taken
+/// literally.
+pub fn decode_path(s: &str) -> PathBuf {
+    s.strip_prefix(RAW_PREFIX)
+        .and_then(unescape_bytes)
+        .map(bytes_to_path)
+        .unwrap_or_else(|| PathBuf::from(s))
+}
+
+/// Percent-encode `%` and any bytes that aren't valid UTF-8. Unlike
+/// [`encode_path_bytes`] this escapes every path the same way, so parsers
+/// can carry arbitrary names through `str` and undo it with
+/// [`unescape_path`].
+pub fn escape_path_bytes(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    let mut rest = bytes;
+    loop {
+        match std::str::from_utf8(rest) {
+            Ok(valid) => {
+                out.push_str(&valid.replace('%', "%25"));
+                return out;
+            }
+            Err(e) => {
+                let (valid, after) = rest.split_at(e.valid_up_to());
+                // from_utf8 just validated this prefix
+                out.push_str(&std::str::from_utf8(valid).unwrap().replace('%', "%25"));
+                let invalid_len = e.error_len().unwrap_or(after.len());
+                for byte in &after[..invalid_len] {
+                    out.push_str(&format!("%{byte:02X}"));
+                }
+                rest = &after[invalid_len..];
+            }
+        }
+    }
+}
+
+/// [`escape_path_bytes`] for a path.
+pub fn escape_path(path: &Path) -> String {
+    escape_path_bytes(&path_bytes(path))
+}
+
+/// Inverse of [`escape_path_bytes`]. A malformed escape is taken literally.
+pub fn unescape_path(s: &str) -> PathBuf {
+    unescape_bytes(s)
+        .map(bytes_to_path)
+        .unwrap_or_else(|| PathBuf::from(s))
+}
+
+/// Percent-decode `s`, failing if any `%` doesn't start an escape.
+fn unescape_bytes(s: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut input = s.as_bytes().iter();
+    while let Some(&b) = input.next() {
+        if b == b'%' {
+            let hex = [*input.next()?, *input.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(unix)]
+fn encode_os_path(path: &Path) -> String {
+    encode_path_bytes(&path_bytes(path))
+}
+
+#[cfg(not(unix))]
+fn encode_os_path(path: &Path) -> String {
+    // Windows paths that aren't valid Unicode can't come out of git
+    path.to_string_lossy().into_owned()
+}
+
+/// The bytes of a path, as git stores its name.
+#[cfg(unix)]
+pub fn path_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+/// The bytes of a path, as git stores its name.
+#[cfg(not(unix))]
+pub fn path_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().as_bytes().to_vec()
+}
+
+#[cfg(unix)]
+fn bytes_to_path(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(std::ffi::OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utf8_paths_pass_through() {
+        for s in [
+            "src/main.rs",
+            "docs/100%.md",
+            "50%-off%",
+            "naïve/ü.rs",
+            "my%20notes.md",
+            "caf%E9.txt",
+        ] {
+            assert_eq!(encode_path(Path::new(s)), s);
+            assert_eq!(encode_path_bytes(s.as_bytes()), s);
+            assert_eq!(raw_path(Path::new(s)), None);
+            assert_eq!(decode_path(s), PathBuf::from(s));
+        }
+    }
+
+    #[test]
+    fn test_escape_round_trips() {
+        let cases = [
+            ("caf%E9.txt", "caf%25E9.txt"),
+            ("100%.md%41", "100%25.md%2541"),
+            ("plain.rs", "plain.rs"),
+        ];
+        for (name, escaped) in cases {
+            assert_eq!(escape_path_bytes(name.as_bytes()), escaped);
+            assert_eq!(unescape_path(escaped), PathBuf::from(name));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_paths_round_trip() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let cases: [&[u8]; 4] = [
+            b"latin1/caf\xe9.txt",
+            b"\xff\xfe",
+            b"50%\xa0off",
+            b"trailing\xe2\x82",
+        ];
+        for bytes in cases {
+            let path = Path::new(OsStr::from_bytes(bytes));
+            let encoded = encode_path(path);
+            assert!(encoded.starts_with(RAW_PREFIX));
+            assert_eq!(raw_path(path).as_deref(), Some(encoded.as_str()));
+            assert_eq!(decode_path(&encoded), path, "{encoded:?}");
+        }
+        let path = Path::new(OsStr::from_bytes(b"50%\xa0off"));
+        assert_eq!(encode_path(path), "\x0050%25%A0off");
+        assert_eq!(display_path(path), "50%\u{FFFD}off");
+        // Without the prefix, the same string names a UTF-8 file
+        assert_eq!(decode_path("50%25%A0off"), PathBuf::from("50%25%A0off"));
+    }
+}
@@ -0,0 +1,46 @@
+//! Maintenance for the app's SQLite databases.
+
+use rusqlite::Connection;
+use std::path::Path;
+
+/// Checkpoint and truncate the WAL, then `VACUUM`, returning how many bytes
+/// the database files shrank by.
+///
+/// Returns `None` without touching the database if a transaction is open on
+/// `conn`, since `VACUUM` can't run inside one. Callers hold the store's
+/// connection lock, so no other statement can start while this runs.
+pub fn compact(conn: &Connection) -> rusqlite::Result<Option<u64>> {
+    if !conn.is_autocommit() {
+        return Ok(None);
+    }
+    let before = database_size(conn);
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+    conn.execute_batch("VACUUM")?;
+    Ok(Some(before.saturating_sub(database_size(conn))))
+}
+
+/// Size of the main database file plus its WAL, if any.
+fn database_size(conn: &Connection) -> u64 {
+    let Some(path) = conn.path().filter(|p| !p.is_empty()) else {
+        return 0;
+    };
+    let size = |p: &Path| std::fs::metadata(p).map(|m| m.len()).unwrap_or(0);
+    size(Path::new(path)) + size(Path::new(&format!("{path}-wal")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compact_skips_open_transaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = Connection::open(dir.path().join("t.db")).unwrap();
+        conn.execute_batch("CREATE TABLE t (x TEXT); BEGIN; INSERT INTO t VALUES ('a');")
+            .unwrap();
+        assert_eq!(compact(&conn).unwrap(), None);
+
+        conn.execute_batch("COMMIT").unwrap();
+        assert!(compact(&conn).unwrap().is_some());
+    }
+}
@@ -18,6 +18,9 @@ use std::thread;
 use std::time::Duration;
 use tauri::{AppHandle, Emitter};
 
+use crate::git::{self, GitDirs};
+use crate::logging;
+
 /// Event name for file change notifications sent to frontend.
 const EVENT_FILES_CHANGED: &str = "files-changed";
 
@@ -65,6 +68,7 @@ impl WatcherHandle {
                         if let Some(entry) = watchers.get(&path) {
                             let old_id = entry.watch_id.swap(watch_id, Ordering::SeqCst);
                             log::info!(
+                                target: logging::WATCHER,
                                 "Updated watch_id for {} from {} to {}",
                                 path.display(),
                                 old_id,
@@ -85,6 +89,7 @@ impl WatcherHandle {
                                     },
                                 );
                                 log::info!(
+                                    target: logging::WATCHER,
                                     "Started watching {} (watch_id {}), total watchers: {}",
                                     path.display(),
                                     watch_id,
@@ -93,6 +98,7 @@ impl WatcherHandle {
                             }
                             Err(e) => {
                                 log::error!(
+                                    target: logging::WATCHER,
                                     "Failed to create watcher for {}: {}",
                                     path.display(),
                                     e
@@ -103,6 +109,7 @@ impl WatcherHandle {
                     WatcherCommand::Unwatch { path } => {
                         if watchers.remove(&path).is_some() {
                             log::info!(
+                                target: logging::WATCHER,
                                 "Stopped watching {}, total watchers: {}",
                                 path.display(),
                                 watchers.len()
@@ -135,7 +142,11 @@ fn create_watcher(
     watch_id: Arc<AtomicU64>,
     app_handle: &AppHandle,
 ) -> Result<Debouncer<RecommendedWatcher, RecommendedCache>, String> {
-    let gitignore = build_gitignore(repo_path);
+    let git_dirs = git::git_dirs(repo_path).ok();
+    let gitignore = build_gitignore(repo_path, git_dirs.as_ref());
+    // A linked worktree keeps its index and HEAD outside the checkout
+    let linked_dirs = git_dirs.filter(GitDirs::is_linked_worktree);
+    let linked_dirs_for_filter = linked_dirs.clone();
     let repo_path_for_filter = repo_path.to_path_buf();
     let repo_path_for_log = repo_path.to_path_buf();
     let app_handle = app_handle.clone();
@@ -148,12 +159,18 @@ fn create_watcher(
                 let relevant_paths: Vec<_> = events
                     .iter()
                     .flat_map(|e| e.paths.iter())
-                    .filter(|p| should_trigger_refresh(p, &repo_path_for_filter, &gitignore))
+                    .filter(|p| {
+                        should_trigger_refresh(p, &repo_path_for_filter, &gitignore)
+                            || linked_dirs_for_filter
+                                .as_ref()
+                                .is_some_and(|dirs| is_worktree_state_change(p, dirs))
+                    })
                     .collect();
 
                 if !relevant_paths.is_empty() {
                     let current_watch_id = watch_id.load(Ordering::SeqCst);
                     log::debug!(
+                        target: logging::WATCHER,
                         "Emitting files-changed for {} (watch_id {}), {} relevant paths",
                         repo_path_for_log.display(),
                         current_watch_id,
@@ -169,7 +186,7 @@ fn create_watcher(
             }
             Err(errors) => {
                 for e in errors {
-                    log::warn!("Watcher error: {e}");
+                    log::warn!(target: logging::WATCHER, "Watcher error: {e}");
                 }
             }
         },
@@ -180,11 +197,20 @@ fn create_watcher(
         .watch(repo_path, RecursiveMode::Recursive)
         .map_err(|e| e.to_string())?;
 
+    if let Some(dirs) = &linked_dirs {
+        debouncer
+            .watch(&dirs.git_dir, RecursiveMode::NonRecursive)
+            .map_err(|e| e.to_string())?;
+        debouncer
+            .watch(dirs.common_dir.join("refs"), RecursiveMode::Recursive)
+            .map_err(|e| e.to_string())?;
+    }
+
     Ok(debouncer)
 }
 
 /// Build a Gitignore matcher for the repository.
-fn build_gitignore(repo_path: &Path) -> Arc<Gitignore> {
+fn build_gitignore(repo_path: &Path, git_dirs: Option<&GitDirs>) -> Arc<Gitignore> {
     let mut builder = GitignoreBuilder::new(repo_path);
 
     // Add .gitignore in repo root
@@ -193,8 +219,11 @@ fn build_gitignore(repo_path: &Path) -> Arc<Gitignore> {
         let _ = builder.add(&gitignore_path);
     }
 
-    // Add .git/info/exclude
-    let exclude_path = repo_path.join(".git/info/exclude");
+    // Add info/exclude (shared by all worktrees, so it lives in the common dir)
+    let exclude_path = match git_dirs {
+        Some(dirs) => dirs.common_dir.join("info/exclude"),
+        None => repo_path.join(".git/info/exclude"),
+    };
     if exclude_path.exists() {
         let _ = builder.add(&exclude_path);
     }
@@ -267,6 +296,15 @@ fn should_trigger_refresh(path: &Path, repo_root: &Path, gitignore: &Gitignore)
     true
 }
 
+/// Determine if a change in a linked worktree's git directories (outside the
+/// checkout) touches its index, HEAD, or refs.
+fn is_worktree_state_change(path: &Path, dirs: &GitDirs) -> bool {
+    if let Ok(relative) = path.strip_prefix(&dirs.git_dir) {
+        return relative == Path::new("index") || relative == Path::new("HEAD");
+    }
+    path.starts_with(dirs.common_dir.join("refs"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -316,6 +354,36 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_linked_worktree_state_filtering() {
+        let dirs = GitDirs {
+            git_dir: PathBuf::from("/main/.git/worktrees/wt"),
+            common_dir: PathBuf::from("/main/.git"),
+        };
+
+        assert!(is_worktree_state_change(
+            Path::new("/main/.git/worktrees/wt/index"),
+            &dirs
+        ));
+        assert!(is_worktree_state_change(
+            Path::new("/main/.git/worktrees/wt/HEAD"),
+            &dirs
+        ));
+        assert!(is_worktree_state_change(
+            Path::new("/main/.git/refs/heads/feature"),
+            &dirs
+        ));
+
+        assert!(!is_worktree_state_change(
+            Path::new("/main/.git/worktrees/wt/logs/HEAD"),
+            &dirs
+        ));
+        assert!(!is_worktree_state_change(
+            Path::new("/main/.git/objects/ab/cdef123"),
+            &dirs
+        ));
+    }
+
     #[test]
     fn test_gitignore_filtering() {
         let repo = Path::new("/repo");
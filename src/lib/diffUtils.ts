@@ -23,7 +23,8 @@ export function getDisplayPath(diff: FileDiff): string {
  * Get the primary path for a diff (prefers after, falls back to before).
  */
 export function getFilePath(diff: FileDiff): string | null {
-  return diff.after?.path ?? diff.before?.path ?? null;
+  const file = diff.after ?? diff.before;
+  return file ? (file.raw_path ?? file.path) : null;
 }
 
 /**
@@ -66,14 +67,24 @@ export function getLineBoundary(
 }
 
 /**
- * Detect language from diff paths (prefers after path).
+ * First line of a diff's file content (prefers after), for shebang detection.
+ */
+export function getFirstLine(diff: FileDiff): string | undefined {
+  const file = diff.after ?? diff.before;
+  return file?.content.type === 'Text' ? file.content.lines[0] : undefined;
+}
+
+/**
+ * Detect language from diff paths (prefers after path), falling back to
+ * the file's first line when the path alone is inconclusive.
  */
 export function getLanguageFromDiff<T>(
   diff: FileDiff,
-  detectLanguage: (path: string) => T | null
+  detectLanguage: (path: string, firstLine?: string) => T | null
 ): T | null {
-  if (diff.after?.path) return detectLanguage(diff.after.path);
-  if (diff.before?.path) return detectLanguage(diff.before.path);
+  const firstLine = getFirstLine(diff);
+  if (diff.after?.path) return detectLanguage(diff.after.path, firstLine);
+  if (diff.before?.path) return detectLanguage(diff.before.path, firstLine);
   return null;
 }
 
@@ -87,11 +98,18 @@ export function isBinaryDiff(diff: FileDiff): boolean {
 }
 
 /**
- * Get text lines from a file, or empty array if binary/null.
+ * Check if a diff was skipped because a file exceeded the size limit.
+ */
+export function isTooLargeDiff(diff: FileDiff): boolean {
+  return diff.too_large === true;
+}
+
+/**
+ * Get text lines from a file, or empty array if binary/too large/null.
  */
 export function getTextLines(diff: FileDiff, side: 'before' | 'after'): string[] {
   const file = side === 'before' ? diff.before : diff.after;
-  if (!file || file.content.type === 'Binary') return [];
+  if (!file || file.content.type !== 'Text') return [];
   return file.content.lines;
 }
 
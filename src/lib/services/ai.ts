@@ -21,6 +21,10 @@ export interface Session {
   title: string | null;
   createdAt: number;
   updatedAt: number;
+  /** The session this one was forked from */
+  forkedFrom?: string;
+  /** The agent's own session ID, used to resume the conversation */
+  acpSessionId?: string;
 }
 
 /** Message role */
@@ -34,12 +38,29 @@ export interface Message {
   /** For user: plain text. For assistant: JSON array of ContentSegment */
   content: string;
   createdAt: number;
+  /** Assistant content with code blocks and file references split out of its text */
+  segments?: MessageSegment[];
 }
 
+/** Status of a stored tool call */
+export type ToolCallStatus = 'pending' | 'inProgress' | 'completed' | 'failed';
+
 /** A segment of assistant content (text or tool call), stored in order */
 export type ContentSegment =
   | { type: 'text'; text: string }
-  | { type: 'toolCall'; id: string; title: string; status: string; locations?: string[] };
+  | {
+      type: 'toolCall';
+      id: string;
+      title: string;
+      status: ToolCallStatus;
+      locations?: string[];
+    };
+
+/** A parsed segment of an assistant message (see `Message.segments`) */
+export type MessageSegment =
+  | ContentSegment
+  | { type: 'code'; language?: string; code: string }
+  | { type: 'fileRef'; path: string; line?: number };
 
 /** Full session with all messages */
 export interface SessionFull {
@@ -64,12 +85,43 @@ export type SessionStatus =
   | { status: 'error'; message: string }
   | { status: 'cancelled' };
 
+/** A slash command an agent accepts */
+export interface AgentCommand {
+  name: string;
+  description: string;
+}
+
+/** A mode an agent can operate in */
+export interface AgentMode {
+  id: string;
+  name: string;
+  description: string | null;
+}
+
+/** What an agent advertised when a session connected */
+export interface AgentCapabilities {
+  agentName: string | null;
+  agentVersion: string | null;
+  loadSession: boolean;
+  promptImages: boolean;
+  promptAudio: boolean;
+  promptEmbeddedContext: boolean;
+  commands: AgentCommand[];
+  modes: AgentMode[];
+  currentMode: string | null;
+}
+
 /** Session status event payload */
 export interface SessionStatusEvent {
   sessionId: string;
   status: SessionStatus;
 }
 
+/** A live session shut down to stay under the live session cap */
+export interface SessionEvictedEvent {
+  sessionId: string;
+}
+
 // =============================================================================
 // Types - ACP SDK (streaming events)
 // =============================================================================
@@ -109,10 +161,16 @@ export interface SessionNotification {
 // Types - Legacy (for backward compatibility)
 // =============================================================================
 
-/** Available ACP provider info */
+/** Known ACP provider info */
 export interface AcpProviderInfo {
   id: string;
   label: string;
+  /** Whether the agent was found and verified */
+  available: boolean;
+  /** Resolved path to the agent binary */
+  path: string | null;
+  /** Version reported by `--version` */
+  version: string | null;
 }
 
 /** Response from legacy send_agent_prompt */
@@ -176,6 +234,14 @@ export async function getSessionStatus(sessionId: string): Promise<SessionStatus
   return invoke<SessionStatus>('get_session_status', { sessionId });
 }
 
+/**
+ * Get the commands, modes and prompt capabilities a session's agent reported.
+ * Returns null until the session is live and has connected.
+ */
+export async function getSessionCapabilities(sessionId: string): Promise<AgentCapabilities | null> {
+  return invoke<AgentCapabilities | null>('get_session_capabilities', { sessionId });
+}
+
 /**
  * Send a prompt to a session.
  * Streams response via events, persists on completion.
@@ -199,6 +265,23 @@ export async function updateSessionTitle(sessionId: string, title: string): Prom
   return invoke<void>('update_session_title', { sessionId, title });
 }
 
+/**
+ * Fork a session into a new one holding its messages up to and including
+ * `upToMessageId`. The fork's agent starts fresh, seeded with that history.
+ * Returns the new session's ID.
+ */
+export async function forkSession(sessionId: string, upToMessageId: number): Promise<string> {
+  return invoke<string>('fork_session', { sessionId, upToMessageId });
+}
+
+/**
+ * Edit the text of a stored user message (e.g. to fix a typo before export).
+ * The prompt is not resent; assistant messages can't be edited.
+ */
+export async function updateMessageContent(messageId: number, content: string): Promise<void> {
+  return invoke<void>('update_message_content', { messageId, content });
+}
+
 // =============================================================================
 // Legacy AI Analysis Commands
 // =============================================================================
@@ -210,11 +293,19 @@ export async function checkAiAvailable(): Promise<string> {
   return invoke<string>('check_ai_available');
 }
 
+/**
+ * List all known ACP providers, including ones that weren't found.
+ */
+export async function listAcpProviders(): Promise<AcpProviderInfo[]> {
+  return invoke<AcpProviderInfo[]>('discover_acp_providers');
+}
+
 /**
  * Discover available ACP providers on the system.
  */
 export async function discoverAcpProviders(): Promise<AcpProviderInfo[]> {
-  return invoke<AcpProviderInfo[]>('discover_acp_providers');
+  const providers = await listAcpProviders();
+  return providers.filter((p) => p.available);
 }
 
 /**
@@ -329,6 +420,21 @@ export async function saveAiComments(
   return invoke<Comment[]>('save_ai_comments', { repoPath, spec, annotations });
 }
 
+/** A tool permission the agent needs the user to decide on */
+export interface PermissionRequestEvent {
+  requestId: string;
+  sessionId: string;
+  toolTitle: string;
+  options: { optionId: string; name: string; kind: string }[];
+}
+
+/**
+ * Answer a permission request. Pass null to reject it.
+ */
+export async function respondPermission(requestId: string, optionId: string | null): Promise<void> {
+  return invoke('respond_permission', { requestId, optionId });
+}
+
 // =============================================================================
 // Event Listeners
 // =============================================================================
@@ -354,3 +460,25 @@ export async function listenToSessionStatus(
     callback(event.payload);
   });
 }
+
+/**
+ * Listen for live sessions being shut down to make room for new ones.
+ */
+export async function listenToSessionEvictions(
+  callback: (event: SessionEvictedEvent) => void
+): Promise<UnlistenFn> {
+  return listen<SessionEvictedEvent>('session-evicted', (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for tool permission requests that aren't auto-approved.
+ */
+export async function listenToPermissionRequests(
+  callback: (event: PermissionRequestEvent) => void
+): Promise<UnlistenFn> {
+  return listen<PermissionRequestEvent>('permission-request', (event) => {
+    callback(event.payload);
+  });
+}
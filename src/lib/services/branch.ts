@@ -56,11 +56,52 @@ export interface ActionStatusEvent {
   completedAt: number | null;
 }
 
+/** How escape codes in action output are delivered */
+export type AnsiMode = 'raw' | 'strip' | 'spans';
+
+/** A terminal color: a 256-color palette index (0-15 are the basic colors) or 24-bit RGB */
+export type AnsiColor = { indexed: number } | { rgb: [number, number, number] };
+
+/** Text attributes from SGR escape codes; unset attributes are omitted */
+export interface AnsiStyle {
+  fg?: AnsiColor;
+  bg?: AnsiColor;
+  bold?: boolean;
+  dim?: boolean;
+  italic?: boolean;
+  underline?: boolean;
+  inverse?: boolean;
+}
+
+/** A run of action output in a single style */
+export interface StyledSpan {
+  text: string;
+  style?: AnsiStyle;
+}
+
 /** Event for action output */
 export interface ActionOutputEvent {
   executionId: string;
   chunk: string;
   stream: 'stdout' | 'stderr';
+  /** The chunk split into styled spans, when run in 'spans' mode */
+  spans?: StyledSpan[];
+}
+
+/** A compiler/linter diagnostic recognized in action output */
+export interface ActionDiagnostic {
+  file: string;
+  line: number;
+  column: number | null;
+  severity: 'error' | 'warning';
+  message: string;
+}
+
+/** Event for a diagnostic recognized in action output */
+export interface ActionDiagnosticEvent {
+  executionId: string;
+  stream: 'stdout' | 'stderr';
+  diagnostic: ActionDiagnostic;
 }
 
 /** Buffered output chunk */
@@ -792,9 +833,43 @@ export async function detectProjectActions(projectId: string): Promise<Suggested
   return invoke<SuggestedAction[]>('detect_project_actions', { projectId });
 }
 
-/** Run an action on a branch */
-export async function runBranchAction(branchId: string, actionId: string): Promise<string> {
-  return invoke<string>('run_branch_action', { branchId, actionId });
+/** Cancel a running action detection; resolves false if none was running */
+export async function cancelDetectProjectActions(projectId: string): Promise<boolean> {
+  return invoke<boolean>('cancel_detect_project_actions', { projectId });
+}
+
+/**
+ * Run an action on a branch. An action configured to auto-commit only does so
+ * when `autoCommit` is true.
+ */
+export async function runBranchAction(
+  branchId: string,
+  actionId: string,
+  ansi?: AnsiMode,
+  autoCommit = false
+): Promise<string> {
+  return invoke<string>('run_branch_action', {
+    branchId,
+    actionId,
+    ansi: ansi ?? null,
+    autoCommit,
+  });
+}
+
+/**
+ * Confirm that a detected action may run. Fails if no action by that name
+ * was detected for the project.
+ */
+export async function confirmDetectedAction(projectId: string, actionName: string): Promise<void> {
+  return invoke<void>('confirm_detected_action', { projectId, actionName });
+}
+
+/**
+ * Run a detected action by name on a branch. The action must have been
+ * confirmed with `confirmDetectedAction`. Resolves to the execution id.
+ */
+export async function runDetectedAction(branchId: string, actionName: string): Promise<string> {
+  return invoke<string>('run_detected_action', { branchId, actionName });
 }
 
 /** Run prerun actions for a branch */
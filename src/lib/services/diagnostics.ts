@@ -0,0 +1,52 @@
+import { invoke } from '@tauri-apps/api/core';
+import type { AcpProviderInfo } from './ai';
+
+// =============================================================================
+// Types
+// =============================================================================
+
+/** A directory the app reads from or writes to */
+export interface DirDiagnostics {
+  path: string;
+  exists: boolean;
+  writable: boolean;
+}
+
+/** One of the app's SQLite stores */
+export interface StoreDiagnostics {
+  name: string;
+  path: string;
+  exists: boolean;
+  sizeBytes: number | null;
+  /** PRAGMA user_version */
+  schemaVersion: number | null;
+  tables: string[];
+  /** Why the store couldn't be inspected */
+  error: string | null;
+}
+
+/** Environment readiness report */
+export interface Diagnostics {
+  appVersion: string;
+  os: string;
+  providers: AcpProviderInfo[];
+  git: {
+    /** Output of `git --version`, or null if git couldn't be run */
+    cliVersion: string | null;
+    libgit2Version: string;
+  };
+  dataDir: DirDiagnostics;
+  configDir: DirDiagnostics | null;
+  stores: StoreDiagnostics[];
+}
+
+// =============================================================================
+// Commands
+// =============================================================================
+
+/**
+ * Collect environment diagnostics for support requests.
+ */
+export async function getDiagnostics(): Promise<Diagnostics> {
+  return invoke<Diagnostics>('diagnostics');
+}
@@ -74,6 +74,12 @@ export async function findRecentRepos(hoursAgo?: number, limit?: number): Promis
 // File Browsing API
 // =============================================================================
 
+/** File search results; `truncated` means only part of a very large repo was searched */
+export interface FileSearchResult {
+  paths: string[];
+  truncated: boolean;
+}
+
 /**
  * Search for files matching a query in the repository.
  *
@@ -85,8 +91,8 @@ export async function searchFiles(
   query: string,
   limit?: number,
   repoPath?: string
-): Promise<string[]> {
-  return invoke<string[]>('search_files', {
+): Promise<FileSearchResult> {
+  return invoke<FileSearchResult>('search_files', {
     repoPath: repoPath ?? null,
     refName,
     query,
@@ -97,7 +103,8 @@ export async function searchFiles(
 /**
  * Get the content of a file at a specific ref.
  *
- * For WORKDIR, reads from the working directory.
+ * For WORKDIR (or '@'), reads from the working directory.
+ * For ':index', reads the staged version.
  * For other refs, reads from the git tree.
  */
 export async function getFileAtRef(
@@ -1,8 +1,10 @@
 import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import type {
   DiffSpec,
   FileDiffSummary,
   FileDiff,
+  DiffRow,
   PullRequest,
   Issue,
   GitHubAuthStatus,
@@ -53,13 +55,50 @@ export async function getMergeBase(ref1: string, ref2: string, repoPath?: string
   });
 }
 
+/**
+ * Export a diff to `destPath` as a unified patch that applies with `git apply`.
+ * Working tree diffs include untracked files.
+ */
+export async function exportPatch(
+  spec: DiffSpec,
+  destPath: string,
+  repoPath?: string
+): Promise<void> {
+  return invoke<void>('export_patch', { repoPath: repoPath ?? null, spec, destPath });
+}
+
+/** Progress of a long-running git operation */
+export interface GitProgress {
+  /** Current step, e.g. "diff", "renames", "files" or "status" */
+  phase: string;
+  /** Completion of the current phase, 0 to 1 */
+  fraction: number;
+}
+
+/**
+ * Listen for progress while large diffs are listed.
+ */
+export async function listenToGitProgress(
+  callback: (progress: GitProgress) => void
+): Promise<UnlistenFn> {
+  return listen<GitProgress>('git-progress', (event) => {
+    callback(event.payload);
+  });
+}
+
 /**
  * List files changed in a diff (for sidebar).
+ * Paths matching any of `excludeGlobs` (e.g. `__snapshots__/**`) are left out.
  */
-export async function listDiffFiles(spec: DiffSpec, repoPath?: string): Promise<FileDiffSummary[]> {
+export async function listDiffFiles(
+  spec: DiffSpec,
+  repoPath?: string,
+  excludeGlobs?: string[]
+): Promise<FileDiffSummary[]> {
   return invoke<FileDiffSummary[]>('list_diff_files', {
     repoPath: repoPath ?? null,
     spec,
+    excludeGlobs: excludeGlobs ?? null,
   });
 }
 
@@ -78,15 +117,62 @@ export async function getFileDiff(
   });
 }
 
+/**
+ * Flatten a file diff into unified rows (removed lines before added ones).
+ * With `whitespaceMarkers`, added rows are flagged for trailing whitespace and tabs.
+ */
+export async function renderUnifiedDiff(
+  diff: FileDiff,
+  whitespaceMarkers?: boolean
+): Promise<DiffRow[]> {
+  return invoke<DiffRow[]>('render_unified_diff', { diff, whitespaceMarkers });
+}
+
+/**
+ * A patch containing only one changed region of a file diff, for copying
+ * or applying a single hunk with `git apply`.
+ */
+export async function hunkToPatch(diff: FileDiff, hunkIndex: number): Promise<string> {
+  return invoke<string>('hunk_to_patch', { diff, hunkIndex });
+}
+
+/**
+ * Trailers appended to a commit message.
+ */
+export interface CommitTrailers {
+  /** Co-authors as `Name <email>` */
+  coAuthors?: string[];
+  /** Add a Signed-off-by trailer for the committer */
+  signOff?: boolean;
+}
+
+/**
+ * Identity recorded on a commit. Unset fields fall back to the repo's
+ * configured user.name / user.email.
+ */
+export interface CommitIdentity {
+  authorName?: string;
+  authorEmail?: string;
+  committerName?: string;
+  committerEmail?: string;
+}
+
 /**
  * Create a commit with the specified files.
  * Returns the short SHA of the new commit.
  */
-export async function commit(paths: string[], message: string, repoPath?: string): Promise<string> {
+export async function commit(
+  paths: string[],
+  message: string,
+  repoPath?: string,
+  trailers?: CommitTrailers,
+  identity?: CommitIdentity
+): Promise<string> {
   return invoke<string>('commit', {
     repoPath: repoPath ?? null,
     paths,
     message,
+    options: { ...trailers, ...identity },
   });
 }
 
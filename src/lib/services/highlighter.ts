@@ -723,7 +723,68 @@ const FILENAME_MAP: Record<string, BundledLanguage> = {
   'go.sum': 'go',
 };
 
-export function detectLanguage(filePath: string): BundledLanguage | null {
+/**
+ * Interpreters named in a shebang line, mapped to languages.
+ * Version suffixes are stripped first (python3.12 -> python).
+ */
+const INTERPRETER_MAP: Record<string, BundledLanguage> = {
+  python: 'python',
+  ruby: 'ruby',
+  perl: 'perl',
+  php: 'php',
+  lua: 'lua',
+  node: 'javascript',
+  deno: 'typescript',
+  bun: 'typescript',
+  'ts-node': 'typescript',
+  tsx: 'typescript',
+  sh: 'bash',
+  bash: 'bash',
+  dash: 'bash',
+  ksh: 'bash',
+  zsh: 'bash',
+  elixir: 'elixir',
+  escript: 'erlang',
+  runghc: 'haskell',
+  runhaskell: 'haskell',
+  scala: 'scala',
+  groovy: 'groovy',
+  kotlin: 'kotlin',
+  swift: 'swift',
+  dart: 'dart',
+};
+
+/**
+ * Detect language from the first line of a file, for files whose name
+ * doesn't give it away (e.g. an extensionless `deploy` script).
+ * Looks at shebangs (`#!/bin/bash`, `#!/usr/bin/env python3`) and a few
+ * unambiguous openers (`<?php`, `<?xml`).
+ */
+export function detectLanguageFromContent(firstLine: string): BundledLanguage | null {
+  const line = firstLine.trim();
+
+  if (line.startsWith('#!')) {
+    const parts = line.slice(2).trim().split(/\s+/);
+    let interpreter = parts[0]?.split('/').pop() ?? '';
+    if (interpreter === 'env') {
+      // Skip env's own flags, e.g. `#!/usr/bin/env -S deno run`
+      interpreter = parts.slice(1).find((p) => !p.startsWith('-') && !p.includes('=')) ?? '';
+    }
+    interpreter = interpreter.replace(/[\d.]+$/, '');
+    return INTERPRETER_MAP[interpreter] ?? null;
+  }
+
+  if (line.startsWith('<?php')) return 'php';
+  if (line.startsWith('<?xml')) return 'xml';
+
+  return null;
+}
+
+/**
+ * Detect language from a file path, falling back to the file's first line
+ * (shebang) when the name and extension are unknown.
+ */
+export function detectLanguage(filePath: string, firstLine?: string): BundledLanguage | null {
   // Get the filename (last path component)
   const filename = filePath.split('/').pop() || '';
   const filenameLower = filename.toLowerCase();
@@ -739,8 +800,13 @@ export function detectLanguage(filePath: string): BundledLanguage | null {
   }
 
   // Fall back to extension-based detection
-  const ext = filePath.split('.').pop()?.toLowerCase() || '';
-  return EXTENSION_MAP[ext] || null;
+  const ext = filename.includes('.') ? filename.split('.').pop()?.toLowerCase() || '' : '';
+  if (EXTENSION_MAP[ext]) {
+    return EXTENSION_MAP[ext];
+  }
+
+  // Finally, look at the content
+  return firstLine !== undefined ? detectLanguageFromContent(firstLine) : null;
 }
 
 /**
@@ -820,8 +886,8 @@ export function highlightLine(code: string, lang: BundledLanguage | null): Token
  * Call this when a file is selected to ensure its language is loaded.
  * Returns true if language is ready.
  */
-export async function prepareLanguage(filePath: string): Promise<boolean> {
-  const lang = detectLanguage(filePath);
+export async function prepareLanguage(filePath: string, firstLine?: string): Promise<boolean> {
+  const lang = detectLanguage(filePath, firstLine);
   if (!lang) return false;
   return ensureLanguageLoaded(lang);
 }
@@ -1,5 +1,16 @@
 import { invoke } from '@tauri-apps/api/core';
-import type { DiffSpec, Review, Comment, Edit, NewComment, NewEdit } from '../types';
+import type {
+  DiffId,
+  DiffSpec,
+  FileDiff,
+  Review,
+  Comment,
+  Edit,
+  NewComment,
+  NewEdit,
+  ChangeSet,
+  ChangeSetView,
+} from '../types';
 
 /**
  * Get or create a review for a diff.
@@ -33,6 +44,14 @@ export async function deleteComment(commentId: string): Promise<void> {
   return invoke('delete_comment', { commentId });
 }
 
+/**
+ * Submit a review: publish its draft comments so exports include them.
+ * Returns how many comments were published.
+ */
+export async function publishReview(spec: DiffSpec, repoPath?: string): Promise<number> {
+  return invoke<number>('publish_review', { repoPath: repoPath ?? null, spec });
+}
+
 /**
  * Mark a file as reviewed.
  */
@@ -40,6 +59,34 @@ export async function markReviewed(spec: DiffSpec, path: string, repoPath?: stri
   return invoke('mark_reviewed', { repoPath: repoPath ?? null, spec, path });
 }
 
+/**
+ * Diffs of the files that changed since a review's head was last reviewed.
+ * Without `previousHead`, the most recent earlier review against the same
+ * base is used.
+ */
+export async function getIncrementalDiff(
+  diffId: DiffId,
+  previousHead?: string,
+  repoPath?: string
+): Promise<FileDiff[]> {
+  return invoke<FileDiff[]>('get_incremental_diff', {
+    repoPath: repoPath ?? null,
+    diffId,
+    previousHead: previousHead ?? null,
+  });
+}
+
+/**
+ * Record that a file was opened, for per-file review time.
+ */
+export async function recordFileView(
+  spec: DiffSpec,
+  path: string,
+  repoPath?: string
+): Promise<void> {
+  return invoke('record_file_view', { repoPath: repoPath ?? null, spec, path });
+}
+
 /**
  * Unmark a file as reviewed.
  */
@@ -51,6 +98,14 @@ export async function unmarkReviewed(
   return invoke('unmark_reviewed', { repoPath: repoPath ?? null, spec, path });
 }
 
+/**
+ * Start a review over: clear every reviewed file but keep comments and edits.
+ * Returns how many files were unmarked.
+ */
+export async function resetReviewed(spec: DiffSpec, repoPath?: string): Promise<number> {
+  return invoke<number>('reset_reviewed', { repoPath: repoPath ?? null, spec });
+}
+
 /**
  * Record an edit made during review.
  */
@@ -61,8 +116,16 @@ export async function recordEdit(spec: DiffSpec, edit: NewEdit, repoPath?: strin
 /**
  * Export review as markdown for clipboard.
  */
-export async function exportReviewMarkdown(spec: DiffSpec, repoPath?: string): Promise<string> {
-  return invoke<string>('export_review_markdown', { repoPath: repoPath ?? null, spec });
+export async function exportReviewMarkdown(
+  spec: DiffSpec,
+  repoPath?: string,
+  includeDrafts?: boolean
+): Promise<string> {
+  return invoke<string>('export_review_markdown', {
+    repoPath: repoPath ?? null,
+    spec,
+    includeDrafts: includeDrafts ?? null,
+  });
 }
 
 /**
@@ -72,6 +135,64 @@ export async function clearReview(spec: DiffSpec, repoPath?: string): Promise<vo
   return invoke('clear_review', { repoPath: repoPath ?? null, spec });
 }
 
+/**
+ * Delete stored reviews that hold nothing (no comments, reviewed files, edits, etc.).
+ * Reviews for the diffs in `keep` are left alone. Returns how many were removed.
+ */
+export async function pruneEmptyReviews(keep: DiffSpec[], repoPath?: string): Promise<number> {
+  return invoke<number>('prune_empty_reviews', { repoPath: repoPath ?? null, keep });
+}
+
+/**
+ * Create an empty change set for reviewing diffs across repos together.
+ */
+export async function createChangeset(name: string): Promise<ChangeSet> {
+  return invoke<ChangeSet>('create_changeset', { name });
+}
+
+/**
+ * Get a change set with its member reviews and aggregate progress.
+ */
+export async function getChangeset(changesetId: string): Promise<ChangeSetView> {
+  return invoke<ChangeSetView>('get_changeset', { changesetId });
+}
+
+/**
+ * Add a repo's diff to a change set.
+ */
+export async function addChangesetDiff(
+  changesetId: string,
+  repoPath: string,
+  spec: DiffSpec
+): Promise<void> {
+  return invoke('add_changeset_diff', { changesetId, repoPath, spec });
+}
+
+/**
+ * Remove a diff from a change set (its review is kept).
+ */
+export async function removeChangesetDiff(
+  changesetId: string,
+  repoPath: string,
+  spec: DiffSpec
+): Promise<void> {
+  return invoke('remove_changeset_diff', { changesetId, repoPath, spec });
+}
+
+/**
+ * Delete a change set (member reviews are kept).
+ */
+export async function deleteChangeset(changesetId: string): Promise<void> {
+  return invoke('delete_changeset', { changesetId });
+}
+
+/**
+ * Export a change set as markdown, sectioned per repo.
+ */
+export async function exportChangesetMarkdown(changesetId: string): Promise<string> {
+  return invoke<string>('export_changeset_markdown', { changesetId });
+}
+
 /**
  * Add a reference file path to a review.
  */
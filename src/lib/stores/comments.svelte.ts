@@ -17,6 +17,7 @@ import {
   markReviewed as apiMarkReviewed,
   unmarkReviewed as apiUnmarkReviewed,
   exportReviewMarkdown,
+  publishReview,
 } from '../services/review';
 import { writeText } from '@tauri-apps/plugin-clipboard-manager';
 
@@ -304,7 +305,30 @@ export async function deleteAllComments(): Promise<boolean> {
 }
 
 /**
- * Export all comments as markdown and copy to clipboard.
+ * Publish the review: draft comments become published, so exports and
+ * GitHub sync include them. Returns how many comments were published.
+ */
+export async function publishComments(): Promise<number> {
+  if (!commentsState.currentSpec) {
+    console.error('Cannot publish: no diff selected');
+    return 0;
+  }
+
+  try {
+    const repoPath = commentsState.currentRepoPath ?? undefined;
+    const published = await publishReview(commentsState.currentSpec, repoPath);
+    commentsState.comments = commentsState.comments.map(
+      (c): Comment => ({ ...c, status: 'published' })
+    );
+    return published;
+  } catch (e) {
+    console.error('Failed to publish review:', e);
+    return 0;
+  }
+}
+
+/**
+ * Export all comments, drafts included, as markdown and copy to clipboard.
  */
 export async function copyCommentsToClipboard(): Promise<boolean> {
   if (!commentsState.currentSpec) {
@@ -314,7 +338,7 @@ export async function copyCommentsToClipboard(): Promise<boolean> {
 
   try {
     const repoPath = commentsState.currentRepoPath ?? undefined;
-    const markdown = await exportReviewMarkdown(commentsState.currentSpec, repoPath);
+    const markdown = await exportReviewMarkdown(commentsState.currentSpec, repoPath, true);
     await writeText(markdown);
     return true;
   } catch (e) {
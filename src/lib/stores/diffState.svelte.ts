@@ -13,6 +13,7 @@
  */
 
 import { listDiffFiles, getFileDiff } from '../services/git';
+import { recordFileView } from '../services/review';
 import type { DiffSpec, FileDiffSummary, FileDiff } from '../types';
 
 // =============================================================================
@@ -85,7 +86,7 @@ export const diffState = $state(createDiffState());
 
 /** Get the primary path for a file summary */
 function getFilePath(summary: FileDiffSummary): string {
-  return summary.after ?? summary.before ?? '';
+  return summary.after_raw ?? summary.after ?? summary.before_raw ?? summary.before ?? '';
 }
 
 /**
@@ -150,6 +151,9 @@ export async function loadFiles(spec: DiffSpec, repoPath?: string): Promise<void
   try {
     diffState.files = await listDiffFiles(spec, repoPath);
     const pathToLoad = updateSelection();
+    if (diffState.selectedFile) {
+      recordView(diffState.selectedFile);
+    }
     // Load the diff for the auto-selected file
     if (pathToLoad) {
       await loadFileDiff(pathToLoad);
@@ -175,7 +179,10 @@ export async function refreshFiles(spec: DiffSpec, repoPath?: string): Promise<v
     diffState.currentRepoPath = repoPath ?? null;
 
     // updateSelection() handles auto-select and checks if selected file still exists
-    updateSelection();
+    const newSelection = updateSelection();
+    if (newSelection) {
+      recordView(newSelection);
+    }
 
     // Reload the selected file's diff if it exists
     // Don't clear cache first - fetch new diff, then swap atomically to avoid flicker
@@ -239,6 +246,18 @@ export function clearScrollTargetCommentId(): void {
   diffState.scrollTargetCommentId = null;
 }
 
+/**
+ * Record the first time a changed file is opened, for per-file review time.
+ * Reference files aren't part of the diff and are skipped.
+ */
+function recordView(path: string): void {
+  const spec = diffState.currentSpec;
+  if (!spec || !diffState.files.some((f) => getFilePath(f) === path)) return;
+  recordFileView(spec, path, diffState.currentRepoPath ?? undefined).catch((e) => {
+    console.error(`Failed to record view of ${path}:`, e);
+  });
+}
+
 /** Counter to track the current selection and ignore stale async results */
 let selectionId = 0;
 
@@ -256,6 +275,9 @@ export async function selectFile(
   diffState.selectedFile = path;
   diffState.scrollTargetLine = scrollToLine ?? null;
   diffState.scrollTargetCommentId = commentId ?? null;
+  if (path) {
+    recordView(path);
+  }
   if (path && !diffState.diffCache.has(path)) {
     await loadFileDiff(path);
     // If user selected a different file while we were loading, don't update
@@ -108,7 +108,7 @@ function clearSearch(): void {
  * Get the primary path for a file summary.
  */
 function getFilePath(file: FileDiffSummary): string {
-  return file.after ?? file.before ?? '';
+  return file.after_raw ?? file.after ?? file.before_raw ?? file.before ?? '';
 }
 
 /**
@@ -10,6 +10,7 @@ import type {
   FinalizedMessage,
   SessionUpdate,
   ContentBlock,
+  ToolCallStatus,
 } from '../services/ai';
 import {
   onSessionUpdate,
@@ -20,8 +21,6 @@ import {
 
 // Types used locally by this store
 
-export type ToolCallStatus = 'pending' | 'in_progress' | 'completed' | 'failed';
-
 export interface ToolCallLocation {
   path: string;
   line?: number;
@@ -64,13 +63,21 @@ interface SessionUpdateToolCallUpdate {
   sessionUpdate: 'tool_call_update';
   toolCallId: string;
   fields: {
-    status?: ToolCallStatus;
+    status?: string;
     title?: string;
     content?: Array<{ type: string; [key: string]: unknown }>;
     locations?: ToolCallLocation[];
   };
 }
 
+/**
+ * Convert an ACP tool call status ("in_progress") to the form the backend
+ * stores and serializes ("inProgress").
+ */
+function toToolCallStatus(status: string | undefined): ToolCallStatus | undefined {
+  return status === 'in_progress' ? 'inProgress' : (status as ToolCallStatus | undefined);
+}
+
 class LiveSessionStore {
   sessions = $state<Map<string, LiveSession>>(new Map());
   /** The most recently created/updated session ID (for finding active streams) */
@@ -145,7 +152,7 @@ class LiveSessionStore {
           session.toolCalls.set(toolCallId, {
             id: toolCallId,
             title: tc.title ?? 'Tool Call',
-            status: (tc.status as ToolCallStatus) ?? 'pending',
+            status: toToolCallStatus(tc.status) ?? 'pending',
             kind: tc.kind ?? 'other',
             locations: tc.locations?.map((l: ToolCallLocation) => l.path) ?? [],
           });
@@ -163,7 +170,7 @@ class LiveSessionStore {
         // fields might be undefined or named differently in SDK
         const fields = tcu.fields ?? (tcu as unknown as Record<string, unknown>);
         if (tc && fields) {
-          const status = (fields as { status?: ToolCallStatus }).status;
+          const status = toToolCallStatus((fields as { status?: string }).status);
           const title = (fields as { title?: string }).title;
           const locations = (fields as { locations?: ToolCallLocation[] }).locations;
           const content = (fields as { content?: Array<{ type: string; [key: string]: unknown }> })
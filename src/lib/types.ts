@@ -124,19 +124,40 @@ export interface Span {
   end: number;
 }
 
-/** Content of a file - either text lines or binary marker */
-export type FileContent = { type: 'Text'; lines: string[] } | { type: 'Binary' };
+/** Content of a file - text lines, a binary marker, or a too-large marker */
+export type FileContent =
+  | {
+      type: 'Text';
+      lines: string[];
+      /** 0-indexed lines that ended in CRLF (omitted when none) */
+      crlf_lines?: number[];
+      /** The last line has no newline (omitted when it does) */
+      missing_final_newline?: boolean;
+    }
+  | { type: 'Binary' }
+  | { type: 'TooLarge'; size: number };
 
 /** A file with its path and content */
 export interface File {
+  /** Path for display (lossy if the name isn't UTF-8) */
   path: string;
+  /** Name to send back to the backend when the path isn't UTF-8 */
+  raw_path?: string;
   content: FileContent;
+  /** True if invalid UTF-8 was replaced with U+FFFD for display */
+  had_invalid_utf8?: boolean;
 }
 
 /** Summary of a file in the diff (for sidebar) */
 export interface FileDiffSummary {
+  /** Paths for display (lossy if a name isn't UTF-8) */
   before: string | null;
   after: string | null;
+  /** Names to send back to the backend when a path isn't UTF-8 */
+  before_raw?: string;
+  after_raw?: string;
+  /** Generated or vendored file (lockfile, build output, codegen) */
+  generated?: boolean;
 }
 
 /** Maps a region in the before file to a region in the after file */
@@ -149,12 +170,36 @@ export interface Alignment {
 
 /** Full diff content for rendering a single file */
 export interface FileDiff {
+  /** Bumped when the shape of this JSON changes incompatibly */
+  schema_version: number;
   /** File before the change (null if added) */
   before: File | null;
   /** File after the change (null if deleted) */
   after: File | null;
   /** Alignments mapping regions between before/after */
   alignments: Alignment[];
+  /** True if either side exceeded the size limit and wasn't diffed */
+  too_large?: boolean;
+  /** Generated or vendored file (lockfile, build output, codegen) */
+  generated?: boolean;
+  /** True if the after side contains merge conflict markers */
+  has_conflicts?: boolean;
+  /** True if either side had invalid UTF-8 replaced for display */
+  had_invalid_utf8?: boolean;
+}
+
+/** One row of a unified (single-column) diff; line numbers are 0-indexed */
+export interface DiffRow {
+  kind: 'context' | 'removed' | 'added';
+  /** Line in before (null for added rows) */
+  before_line: number | null;
+  /** Line in after (null for removed rows) */
+  after_line: number | null;
+  content: string;
+  /** Added row ends in spaces or tabs (only with whitespace markers on) */
+  has_trailing_whitespace?: boolean;
+  /** Added row contains a hard tab (only with whitespace markers on) */
+  has_tabs?: boolean;
 }
 
 // =============================================================================
@@ -217,8 +262,16 @@ export interface Comment {
   content: string;
   /** When the comment was created (ISO timestamp) */
   created_at?: string;
+  /** Column where the comment starts on the span's first line (0-indexed) */
+  col_start?: number;
+  /** Column where the comment ends on the span's last line (exclusive) */
+  col_end?: number;
+  /** Drafts are left out of exports until the review is published */
+  status: CommentStatus;
 }
 
+export type CommentStatus = 'draft' | 'published';
+
 /** An edit made during review, stored as a unified diff */
 export interface Edit {
   id: string;
@@ -228,11 +281,46 @@ export interface Edit {
 
 /** A review attached to a specific diff */
 export interface Review {
+  /** Bumped when the shape of this JSON changes incompatibly */
+  schema_version: number;
   id: DiffId;
   reviewed: string[];
   comments: Comment[];
   edits: Edit[];
   reference_files: string[];
+  file_times?: FileReviewTime[];
+  /** Commit the head was at when a file was last marked reviewed */
+  reviewed_head?: string | null;
+}
+
+/** Review timing for a file (unix ms timestamps) */
+export interface FileReviewTime {
+  path: string;
+  first_viewed_at: number | null;
+  reviewed_at: number | null;
+}
+
+/** A group of diffs (possibly across repos) reviewed together */
+export interface ChangeSet {
+  id: string;
+  name: string;
+  diffs: { repoPath: string; id: DiffId }[];
+  createdAt: string;
+}
+
+/** Totals across every review in a change set */
+export interface ChangeSetProgress {
+  diffCount: number;
+  reviewedFiles: number;
+  commentCount: number;
+  editCount: number;
+}
+
+/** A change set with its member reviews and aggregate progress */
+export interface ChangeSetView {
+  changeset: ChangeSet;
+  reviews: { repoPath: string; review: Review }[];
+  progress: ChangeSetProgress;
 }
 
 /** Input for creating a new comment */
@@ -240,6 +328,9 @@ export interface NewComment {
   path: string;
   span: Span;
   content: string;
+  /** Optional column range within the span (see Comment) */
+  col_start?: number;
+  col_end?: number;
 }
 
 /** Input for recording a new edit */